@@ -1,226 +1,444 @@
 use std::{
-    sync::{Arc, OnceLock},
-    thread,
-    time::Duration,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use eframe::egui::{self, mutex::Mutex};
-use portable_pty::{Child, CommandBuilder, PtyPair, PtySize, native_pty_system};
-
-use crate::{explorer_widget::ExplorerWidget, logging, terminal_widget::TerminalWidget};
-
-pub static INPUT_BUFFER: OnceLock<Arc<Mutex<Vec<u8>>>> = OnceLock::new();
-pub static OUTPUT_BUFFER: OnceLock<Arc<Mutex<Vec<u8>>>> = OnceLock::new();
+use eframe::egui;
+
+use crate::{
+    Args, CONFIG,
+    app_window::AppWindow,
+    asciicast::Player,
+    config::{Config, UiTheme, watcher::ConfigWatcher},
+    dropdown::Dropdown,
+    settings_window::{SettingsAction, SettingsWindow},
+    state::{self, State},
+    system_theme::SystemTheme,
+    terminal_session,
+    toast::Toasts,
+};
 
 pub struct App {
-    pub terminal_widget: TerminalWidget,
-    explorer_widget: ExplorerWidget,
-    pub pty_pair: Option<PtyPair>,
-    pub child: Option<Box<dyn Child + Send + Sync>>,
-    output_buffer: Arc<Mutex<Vec<u8>>>,
-    input_buffer: Arc<Mutex<Vec<u8>>>,
-    is_running: bool,
-    last_size: (u16, u16),
-    pid: Option<u32>,
-}
-
-impl Default for App {
-    fn default() -> Self {
-        Self {
-            terminal_widget: TerminalWidget::new(80, 24),
-            explorer_widget: ExplorerWidget::new(),
-            pty_pair: None,
-            child: None,
-            is_running: false,
-            output_buffer: OUTPUT_BUFFER
-                .get_or_init(|| Arc::new(Mutex::new(Vec::new())))
-                .clone(),
-            input_buffer: INPUT_BUFFER
-                .get_or_init(|| Arc::new(Mutex::new(Vec::new())))
-                .clone(),
-            last_size: (0, 0),
-            pid: None,
-        }
-    }
+    /// The window eframe created, shown in the root viewport
+    root: AppWindow,
+    /// Further windows opened with Ctrl+Shift+N, each in its own viewport
+    windows: Vec<(egui::ViewportId, AppWindow)>,
+    /// Used to give every new window a distinct viewport id
+    window_count: u64,
+    /// The root window was closed while others were still open; it is hidden until they close
+    root_hidden: bool,
+    /// The root window slides in and out, in drop-down mode
+    dropdown: Option<Dropdown>,
+    /// Reloads the configuration file when it is edited
+    config_watcher: Option<ConfigWatcher>,
+    /// The command line, applied again over a reloaded configuration
+    args: Args,
+    /// Errors of reloading the configuration
+    toasts: Toasts,
+    /// The Settings window, while it is open
+    settings: Option<SettingsWindow>,
+    /// The desktop's color scheme, watched once `ui_theme = "system"` is used
+    system_theme: Option<SystemTheme>,
+    /// The theme the panels were last given
+    ui_theme: Option<egui::Theme>,
+    /// Settings of the configuration file that were ignored, shown until dismissed
+    config_problems: Vec<String>,
+    /// The state written last, compared with the current one every `state::SAVE_INTERVAL`
+    saved_state: Option<State>,
+    state_checked_at: Instant,
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        let mut app = Self::default();
-
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        state: Option<State>,
+        config_path: Option<String>,
+        args: Args,
+        player: Option<Player>,
+    ) -> Self {
         egui_extras::install_image_loaders(&cc.egui_ctx);
 
         crate::utils::load_system_font(&cc.egui_ctx).expect("Failed to load system font");
-        app.start_pty();
 
-        app
-    }
+        let state = state.unwrap_or_default();
+        // An explicitly requested directory wins over the restored one
+        let working_directory = if CONFIG
+            .get()
+            .is_some_and(|config| config.working_directory.is_some())
+        {
+            None
+        } else {
+            state.cwd.clone().filter(|cwd| cwd.is_dir())
+        };
 
-    fn start_pty(&mut self) {
-        let pty_system = native_pty_system();
-        let pty_pair = pty_system
-            .openpty(PtySize {
-                rows: 24,
-                cols: 80,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .expect("Failed to create PTY");
-
-        // Spawn a shell in the PTY
-        let cmd = CommandBuilder::new("bash");
-        let child = pty_pair
-            .slave
-            .spawn_command(cmd)
-            .expect("Failed to spawn shell");
-        self.pid = child.process_id();
-
-        self.pty_pair = Some(pty_pair);
-        self.child = Some(child);
-        self.is_running = true;
-
-        // Initialize output thread
-        let output_buffer = self.output_buffer.clone();
-        if let Some(ref pty_pair) = self.pty_pair {
-            let mut reader = pty_pair
-                .master
-                .try_clone_reader()
-                .expect("Failed to clone reader");
-
-            thread::spawn(move || {
-                let mut buffer = [0u8; 4096];
-                loop {
-                    match reader.read(&mut buffer) {
-                        Ok(0) => break, // EOF
-                        Ok(n) => {
-                            let mut output = output_buffer.lock();
-                            output.extend_from_slice(&buffer[..n]);
-                        }
-                        Err(e) => {
-                            error!("Error reading from PTY: {e}");
-                            break;
-                        }
-                    }
-                    thread::sleep(Duration::from_millis(10));
-                }
+        let mut root = match player {
+            Some(player) => AppWindow::with_player(&cc.egui_ctx, player),
+            None => AppWindow::new(&cc.egui_ctx, working_directory),
+        };
+        root.restore(&cc.egui_ctx, &state);
+
+        let dropdown = CONFIG
+            .get()
+            .is_some_and(|config| config.dropdown.enabled)
+            .then(|| Dropdown::new(&cc.egui_ctx))
+            .and_then(|dropdown| {
+                // The window would stay hidden without a way to summon it
+                dropdown
+                    .inspect_err(|e| {
+                        error!("Drop-down mode is off: {e}");
+                        cc.egui_ctx
+                            .send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                    })
+                    .ok()
             });
+        if dropdown.is_some() {
+            // The drop-down geometry isn't the one to restore in a normal window
+            root.track_geometry = false;
+        }
+        if let Some(path) = &args.record
+            && let Err(e) = root.active().start_recording(PathBuf::from(path))
+        {
+            error!("Failed to start recording: {e}");
         }
 
-        // Initialize input thread
-        let input_buffer = self.input_buffer.clone();
-        if let Some(ref pty_pair) = self.pty_pair {
-            let mut writer = pty_pair
-                .master
-                .take_writer()
-                .expect("Failed to take writer");
-
-            thread::spawn(move || {
-                loop {
-                    let data_to_write = {
-                        let mut input = input_buffer.lock();
-                        if input.is_empty() {
-                            None
-                        } else {
-                            let data = input.clone();
-                            input.clear();
-                            Some(data)
-                        }
-                    };
-
-                    if let Some(data) = data_to_write
-                        && let Err(e) = writer.write_all(&data)
-                    {
-                        error!("Error writing to PTY: {e}");
-                        break;
-                    }
-
-                    thread::sleep(Duration::from_millis(10));
-                }
-            });
+        // Without a configuration file, one created where it is looked for first is picked up
+        let config_path = PathBuf::from(config_path.unwrap_or_else(Config::default_path));
+        let config_watcher = ConfigWatcher::new(&cc.egui_ctx, config_path)
+            .inspect_err(|e| warn!("Not watching the configuration file for changes: {e}"))
+            .ok();
+
+        Self {
+            root,
+            windows: Vec::new(),
+            window_count: 0,
+            root_hidden: false,
+            dropdown,
+            config_watcher,
+            args,
+            toasts: Toasts::default(),
+            settings: None,
+            system_theme: None,
+            ui_theme: None,
+            config_problems: CONFIG
+                .get()
+                .map(|config| config.problems.clone())
+                .unwrap_or_default(),
+            saved_state: None,
+            state_checked_at: Instant::now(),
         }
     }
 
-    fn handle_pty_output(&mut self, ctx: &egui::Context) {
-        let data = {
-            let mut output = self.output_buffer.lock();
-            if output.is_empty() {
+    /// Read the configuration file again once it was edited. A file that doesn't load keeps
+    /// the previous configuration in effect
+    fn reload_config(&mut self, ctx: &egui::Context) {
+        let Some(watcher) = &mut self.config_watcher else {
+            return;
+        };
+        if !watcher.poll(ctx) {
+            return;
+        }
+
+        let path = watcher.path().to_string_lossy().into_owned();
+        let mut config = match Config::load(&path) {
+            Ok(config) => config,
+            Err(e) => {
+                self.toasts.error(format!(
+                    "{path} not reloaded, the previous settings stay: {e}"
+                ));
                 return;
             }
-            let data = output.clone();
-            output.clear();
-            data
         };
+        self.args.apply(&mut config);
+        info!("Reloaded {path}");
+        self.config_problems = config.problems.clone();
+        self.set_config(ctx, config);
+    }
 
-        self.terminal_widget.process_output(ctx, &data);
+    /// Put `config` in effect in every window
+    fn set_config(&mut self, ctx: &egui::Context, config: Config) {
+        let previous = CONFIG.get();
+        let config = Arc::new(config);
+        CONFIG.set(Arc::clone(&config));
+
+        let fonts_changed = previous.as_deref().is_none_or(|previous| {
+            previous.ui_font_family != config.ui_font_family
+                || previous.terminal_font_family != config.terminal_font_family
+                || previous.terminal_fallback_font_families
+                    != config.terminal_fallback_font_families
+                || previous.fonts != config.fonts
+        });
+        if fonts_changed {
+            let result = crate::utils::load_system_font(ctx);
+            if let Some(settings) = &mut self.settings {
+                settings.font_error = result
+                    .as_ref()
+                    .err()
+                    .map(|e| format!("Failed to load the fonts: {e}"));
+            } else if let Err(e) = result {
+                self.toasts.error(format!("Failed to load the fonts: {e}"));
+            }
+        }
+        self.root.apply_config(&config, previous.as_deref());
+        for (_, window) in &mut self.windows {
+            window.apply_config(&config, previous.as_deref());
+        }
+    }
 
-        logging::log_output_data(&data);
+    /// Open the Settings window, or bring it to the front
+    fn open_settings(&mut self, ctx: &egui::Context) {
+        if self.settings.is_some() {
+            ctx.send_viewport_cmd_to(settings_viewport(), egui::ViewportCommand::Focus);
+            return;
+        }
+        let config = CONFIG
+            .get()
+            .map(|config| (*config).clone())
+            .unwrap_or_default();
+        let path = self
+            .config_watcher
+            .as_ref()
+            .map(|watcher| watcher.path().to_path_buf())
+            .unwrap_or_else(|| PathBuf::from(Config::default_path()));
+        self.settings = Some(SettingsWindow::new(&config, path));
     }
 
-    fn send_input_to_pty(&mut self, data: Vec<u8>) {
-        if !data.is_empty() {
-            let mut input = self.input_buffer.lock();
-            input.extend_from_slice(&data);
+    fn show_settings(&mut self, ctx: &egui::Context) {
+        let Some(settings) = &mut self.settings else {
+            return;
+        };
+
+        let builder = egui::ViewportBuilder::default()
+            .with_title("Settings")
+            .with_inner_size([560.0, 480.0]);
+        let action = ctx.show_viewport_immediate(settings_viewport(), builder, |ctx, _class| {
+            let closed = ctx.input(|i| i.viewport().close_requested());
+            let action = egui::CentralPanel::default()
+                .show(ctx, |ui| settings.show(ui))
+                .inner;
+            if closed {
+                Some(SettingsAction::Close)
+            } else {
+                action
+            }
+        });
+
+        match action {
+            Some(SettingsAction::Apply(config)) => self.set_config(ctx, *config),
+            Some(SettingsAction::Close) => {
+                // What wasn't saved is undone
+                let saved = settings.saved().clone();
+                self.settings = None;
+                self.set_config(ctx, saved);
+            }
+            None => {}
         }
+    }
 
-        logging::log_input_data(&data);
+    /// Give the panels, menus and dialogs the configured theme; the terminal keeps its palette
+    fn apply_ui_theme(&mut self, ctx: &egui::Context) {
+        let ui_theme = CONFIG
+            .get()
+            .and_then(|config| config.ui_theme)
+            .unwrap_or_default();
+        let theme = match ui_theme {
+            UiTheme::Dark => egui::Theme::Dark,
+            UiTheme::Light => egui::Theme::Light,
+            UiTheme::System => {
+                let system_theme = self
+                    .system_theme
+                    .get_or_insert_with(|| SystemTheme::new(ctx));
+                system_theme
+                    .theme()
+                    .or_else(|| ctx.system_theme())
+                    .unwrap_or(egui::Theme::Dark)
+            }
+        };
+        if self.ui_theme != Some(theme) {
+            ctx.set_theme(theme);
+            self.ui_theme = Some(theme);
+        }
     }
 
-    fn resize_pty(&mut self, cols: u16, rows: u16) {
-        if let Some(ref pty_pair) = self.pty_pair {
-            let new_size = PtySize {
-                rows,
-                cols,
-                pixel_width: 0,
-                pixel_height: 0,
-            };
-            if let Err(e) = pty_pair.master.resize(new_size) {
-                error!("Failed to resize PTY: {e}");
+    /// Remember where the user left off, read back by `main` on the next start
+    fn save_state(&mut self) {
+        if self.root_hidden
+            || !CONFIG
+                .get()
+                .is_none_or(|config| config.restore_session.unwrap_or(true))
+        {
+            return;
+        }
+
+        let state = self.root.state();
+        if self.saved_state.as_ref() == Some(&state) {
+            return;
+        }
+        match state.save() {
+            Ok(()) => self.saved_state = Some(state),
+            Err(e) => error!("Failed to save session state: {e}"),
+        }
+    }
+
+    /// Save the state once in a while when it changed, not only on exit
+    fn save_state_if_due(&mut self) {
+        if self.state_checked_at.elapsed() < state::SAVE_INTERVAL {
+            return;
+        }
+        self.state_checked_at = Instant::now();
+        self.save_state();
+    }
+
+    /// List the ignored settings of the configuration file above the root window until they
+    /// are dismissed or the file is fixed
+    fn show_config_problems(&mut self, ctx: &egui::Context) {
+        if self.config_problems.is_empty() {
+            return;
+        }
+
+        let mut dismissed = false;
+        egui::Area::new(egui::Id::new("config_problems"))
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 12.0])
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(600.0);
+                    let path = self
+                        .config_watcher
+                        .as_ref()
+                        .map_or_else(Config::default_path, |watcher| {
+                            watcher.path().to_string_lossy().into_owned()
+                        });
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Some settings of {path} were ignored and left at their defaults:"
+                        ))
+                        .strong(),
+                    );
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for problem in &self.config_problems {
+                                ui.colored_label(ui.visuals().warn_fg_color, problem);
+                            }
+                        });
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                        dismissed = ui.button("Dismiss").clicked();
+                    });
+                });
+            });
+        if dismissed {
+            self.config_problems.clear();
+        }
+    }
+
+    /// Open a new window starting in `working_directory`
+    fn open_window(&mut self, ctx: &egui::Context, working_directory: Option<PathBuf>) {
+        self.window_count += 1;
+        let id = egui::ViewportId::from_hash_of(("window", self.window_count));
+        self.windows
+            .push((id, AppWindow::new(ctx, working_directory)));
+    }
+
+    fn show_windows(&mut self, ctx: &egui::Context) {
+        let mut new_window_dirs = Vec::new();
+
+        for (id, window) in &mut self.windows {
+            let builder = egui::ViewportBuilder::default()
+                .with_title("explotty")
+                .with_inner_size([800.0, 600.0])
+                .with_transparent(true);
+            ctx.show_viewport_immediate(*id, builder, |ctx, _class| {
+                if window.show(ctx) {
+                    window.stop();
+                }
+            });
+
+            if window.new_window_requested {
+                window.new_window_requested = false;
+                new_window_dirs.push(window.current_dir());
             }
         }
+
+        // Closed windows only tear down their own sessions
+        self.windows.retain(|(_, window)| !window.closed);
+
+        for working_directory in new_window_dirs {
+            self.open_window(ctx, working_directory);
+        }
     }
 }
 
+fn settings_viewport() -> egui::ViewportId {
+    egui::ViewportId::from_hash_of("settings")
+}
+
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Start the PTY processing
-        self.handle_pty_output(ctx);
+        self.reload_config(ctx);
+        self.apply_ui_theme(ctx);
+        self.toasts.show(ctx);
+        if !self.root_hidden {
+            self.show_config_problems(ctx);
+        }
 
-        // Repainting requests for continuous updating | ~60fps
-        ctx.request_repaint_after(Duration::from_millis(16));
+        if let Some(dropdown) = &mut self.dropdown {
+            let settings = CONFIG
+                .get()
+                .map(|config| config.dropdown.clone())
+                .unwrap_or_default();
+            dropdown.update(ctx, &settings, self.settings.is_some());
+        }
 
-        egui::TopBottomPanel::bottom("explorer")
-            .resizable(true)
-            .default_height(200.0)
-            .show(ctx, |ui| {
-                self.explorer_widget.show(ui, self.pid);
-            });
+        if !self.root_hidden && self.root.show(ctx) && !self.windows.is_empty() {
+            // Closing the last window exits the app; while others are open the root window
+            // only disappears
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+            self.save_state();
+            self.root.stop();
+            self.root_hidden = true;
+        }
+        if self.root.new_window_requested {
+            self.root.new_window_requested = false;
+            let working_directory = self.root.current_dir();
+            self.open_window(ctx, working_directory);
+        }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let response = self.terminal_widget.show(ui);
+        self.show_windows(ctx);
 
-            let cols = self.terminal_widget.buffer.width as u16;
-            let rows = self.terminal_widget.buffer.height as u16;
+        let mut settings_requested = std::mem::take(&mut self.root.settings_requested);
+        for (_, window) in &mut self.windows {
+            settings_requested |= std::mem::take(&mut window.settings_requested);
+        }
+        if settings_requested {
+            self.open_settings(ctx);
+        }
+        self.show_settings(ctx);
 
-            if self.last_size != (cols, rows) {
-                self.resize_pty(cols, rows);
-                self.last_size = (cols, rows);
-            }
+        self.save_state_if_due();
 
-            // Always focus terminal widget
-            ui.memory_mut(|mem| mem.request_focus(response.id));
+        if self.root_hidden && self.windows.is_empty() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+        }
 
-            // If it has focus, handle input
-            if response.has_focus() || ui.memory(|mem| mem.has_focus(response.id)) {
-                let input_data = self.terminal_widget.handle_input(ctx);
-                self.send_input_to_pty(input_data);
-            }
-        });
+        // Output wakes the UI up by itself; this only catches changes without output,
+        // such as the shell's directory being changed from elsewhere
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+
+    /// Windows are transparent, so the terminal's background can be; the panels cover the rest
+    fn clear_color(&self, _visuals: &egui::Visuals) -> [f32; 4] {
+        egui::Rgba::TRANSPARENT.to_array()
     }
 
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if let Some(mut child) = self.child.take() {
-            let _ = child.kill();
-            let _ = child.wait();
+        self.save_state();
+
+        self.root.stop();
+        for (_, window) in &mut self.windows {
+            window.stop();
         }
+        terminal_session::join_reapers();
     }
 }
@@ -0,0 +1,1145 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use eframe::egui;
+
+use crate::{
+    CONFIG,
+    asciicast::Player,
+    command_palette::{CommandPalette, PaletteAction, PaletteEvent},
+    config::{BACKGROUND_OPACITY_RANGE, Config, ExitBehavior},
+    explorer_widget::ExplorerWidget,
+    keybindings::{Action, ExplorerAction, TerminalAction},
+    state::State,
+    term::{color::Palette, terminal_cell::TerminalCell},
+    terminal_session::{TabActivity, TerminalSession, describe_exit_status},
+    terminal_widget::DEFAULT_FONT_SIZE,
+    toast::Toasts,
+};
+
+const DEFAULT_EXPLORER_HEIGHT: f32 = 200.0;
+const DEFAULT_TITLE_TEMPLATE: &str = "{cwd} — {command}";
+/// How much the opacity actions change the background's opacity
+const OPACITY_STEP: f32 = 0.1;
+
+/// What the close confirmation dialog is asking about
+#[derive(Clone, Copy)]
+enum PendingClose {
+    Window,
+    /// The tab of the session with this id
+    Tab(u64),
+}
+
+/// The Save as PDF dialog: the cells to save, taken when it was opened, and the path typed
+struct PdfExport {
+    path: String,
+    lines: Vec<Vec<TerminalCell>>,
+    palette: Palette,
+    /// The height of the screen, where pages break
+    rows_per_page: usize,
+    /// Whether the path field got the keyboard
+    focused: bool,
+}
+
+/// The contents of one top-level window: its tabs and its explorer
+pub struct AppWindow {
+    explorer_widget: ExplorerWidget,
+    /// The right pane while the explorer is split
+    second_explorer: Option<ExplorerWidget>,
+    /// The open terminal tabs, there is always at least one
+    sessions: Vec<TerminalSession>,
+    active_session: usize,
+    window_title: String,
+    /// Set while the close confirmation dialog is shown
+    pending_close: Option<PendingClose>,
+    /// The user agreed to close the window although jobs are running
+    close_confirmed: bool,
+    /// Geometry tracked every frame, saved on exit because it's unavailable by then
+    window_size: Option<egui::Vec2>,
+    window_position: Option<egui::Pos2>,
+    /// Whether the geometry is tracked; otherwise the restored one is saved again
+    pub track_geometry: bool,
+    explorer_height: f32,
+    /// Whether the explorer panel is shown (Ctrl+Shift+B)
+    explorer_visible: bool,
+    /// Whether the window stays above the others (Ctrl+Shift+O)
+    always_on_top: bool,
+    /// The level last sent to the viewport, `None` before the first frame
+    window_level: Option<bool>,
+    /// Opacity of the terminal's background (Ctrl+Alt+ArrowUp/ArrowDown)
+    background_opacity: f32,
+    /// The command palette while it's open, with whether the second explorer pane had the
+    /// keyboard before
+    command_palette: Option<(bool, CommandPalette)>,
+    /// The Save as PDF dialog while it's open
+    pdf_export: Option<PdfExport>,
+    /// Errors of the actions of the window, e.g. saving a PDF
+    toasts: Toasts,
+    /// The sequence inspector while it's open, with the tab it inspects
+    #[cfg(feature = "inspector")]
+    inspector: Option<(usize, crate::inspector::Inspector)>,
+    /// The window was closed and its sessions are stopped
+    pub closed: bool,
+    /// Ctrl+Shift+N was pressed, the app opens the window
+    pub new_window_requested: bool,
+    /// Ctrl+, was pressed, the app opens the Settings window
+    pub settings_requested: bool,
+}
+
+impl AppWindow {
+    /// A window with a single tab, started in `working_directory` if given
+    pub fn new(ctx: &egui::Context, working_directory: Option<PathBuf>) -> Self {
+        Self::with_session(ctx, TerminalSession::new(ctx, working_directory))
+    }
+
+    /// A window playing a recording back in its tab (`--play`)
+    pub fn with_player(ctx: &egui::Context, player: Player) -> Self {
+        Self::with_session(ctx, TerminalSession::play(ctx, player))
+    }
+
+    fn with_session(ctx: &egui::Context, session: TerminalSession) -> Self {
+        let config = CONFIG.get();
+        let explorer_visible = config
+            .as_ref()
+            .and_then(|config| config.explorer_visible)
+            .unwrap_or(true);
+        let mut explorer_widget = ExplorerWidget::new(ctx, egui::Id::new("explorer"));
+        if !explorer_visible {
+            explorer_widget.suspend();
+        }
+
+        Self {
+            explorer_widget,
+            second_explorer: None,
+            sessions: vec![session],
+            active_session: 0,
+            window_title: String::new(),
+            pending_close: None,
+            close_confirmed: false,
+            window_size: None,
+            window_position: None,
+            track_geometry: true,
+            explorer_height: DEFAULT_EXPLORER_HEIGHT,
+            explorer_visible,
+            always_on_top: config
+                .as_ref()
+                .and_then(|config| config.start_on_top)
+                .unwrap_or(false),
+            window_level: None,
+            background_opacity: config
+                .as_ref()
+                .and_then(|config| config.background_opacity)
+                .unwrap_or(1.0),
+            command_palette: None,
+            pdf_export: None,
+            toasts: Toasts::default(),
+            #[cfg(feature = "inspector")]
+            inspector: None,
+            closed: false,
+            new_window_requested: false,
+            settings_requested: false,
+        }
+    }
+
+    /// Apply what was saved from the previous run
+    pub fn restore(&mut self, ctx: &egui::Context, state: &State) {
+        self.window_size = state.window_size.map(egui::Vec2::from);
+        self.window_position = state.window_position.map(egui::Pos2::from);
+        if let Some(font_size) = state.font_size {
+            self.active().terminal_widget.set_font_size(font_size);
+        }
+        if let Some(always_on_top) = state.always_on_top {
+            self.always_on_top = always_on_top;
+        }
+        if let Some(explorer_height) = state.explorer_height {
+            self.explorer_height = explorer_height;
+        }
+        if let Some(show_hidden) = state.show_hidden {
+            self.explorer_widget.set_show_hidden(show_hidden);
+        }
+        if let Some(columns) = &state.explorer_columns {
+            self.explorer_widget.set_columns(columns);
+        }
+        if let Some(widths) = &state.explorer_column_widths {
+            self.explorer_widget.set_column_widths(widths);
+        }
+        if state.explorer_split == Some(true) {
+            let directory = state
+                .explorer_second_directory
+                .clone()
+                .or_else(|| state.cwd.clone())
+                .filter(|directory| directory.is_dir());
+            self.open_second_explorer(ctx, directory);
+        }
+    }
+
+    /// What to remember of this window for the next run
+    pub fn state(&mut self) -> State {
+        State {
+            version: crate::state::VERSION,
+            cwd: self.active().current_dir(),
+            window_size: self.window_size.map(|size| [size.x, size.y]),
+            window_position: self.window_position.map(|pos| [pos.x, pos.y]),
+            explorer_height: Some(self.explorer_height),
+            font_size: self.changed_font_size(),
+            show_hidden: Some(self.explorer_widget.show_hidden()),
+            explorer_columns: Some(self.explorer_widget.columns().to_vec()),
+            explorer_split: Some(self.second_explorer.is_some()),
+            explorer_second_directory: self
+                .second_explorer
+                .as_ref()
+                .and_then(|explorer| explorer.current_directory())
+                .map(Path::to_path_buf),
+            explorer_column_widths: Some(self.explorer_widget.column_widths().clone()),
+            always_on_top: Some(self.always_on_top),
+        }
+    }
+
+    /// Split the explorer, showing `directory` in the new pane. It starts with the settings of
+    /// the first pane and doesn't follow the terminal
+    fn open_second_explorer(&mut self, ctx: &egui::Context, directory: Option<PathBuf>) {
+        let mut explorer = ExplorerWidget::new(ctx, egui::Id::new("explorer_2"));
+        explorer.set_follow_terminal(false);
+        explorer.set_show_hidden(self.explorer_widget.show_hidden());
+        explorer.set_columns(self.explorer_widget.columns());
+        explorer.set_column_widths(self.explorer_widget.column_widths());
+        if let Some(directory) = directory {
+            explorer.navigate_to(directory);
+        }
+        if !self.explorer_visible {
+            explorer.suspend();
+        }
+        self.second_explorer = Some(explorer);
+    }
+
+    /// The explorer panes, the first one and the second while split
+    fn explorers(&mut self) -> impl Iterator<Item = &mut ExplorerWidget> {
+        std::iter::once(&mut self.explorer_widget).chain(self.second_explorer.as_mut())
+    }
+
+    /// F6 gives the keyboard to the other pane, or to the first one from the terminal
+    fn switch_explorer_focus(&mut self) {
+        let Some(second) = &mut self.second_explorer else {
+            return;
+        };
+        if self.explorer_widget.wants_keyboard() {
+            self.explorer_widget.release_keyboard();
+            second.focus();
+        } else {
+            second.release_keyboard();
+            self.explorer_widget.focus();
+        }
+    }
+
+    /// The font size if it isn't the configured one, so a new `font_size` in the config
+    /// applies on the next run
+    fn changed_font_size(&mut self) -> Option<f32> {
+        let configured = CONFIG
+            .get()
+            .and_then(|config| config.font_size)
+            .unwrap_or(DEFAULT_FONT_SIZE);
+        let font_size = self.active().terminal_widget.font_size;
+        (font_size != configured).then_some(font_size)
+    }
+
+    pub fn active(&mut self) -> &mut TerminalSession {
+        &mut self.sessions[self.active_session]
+    }
+
+    /// The working directory of the active tab
+    pub fn current_dir(&mut self) -> Option<PathBuf> {
+        self.active().current_dir()
+    }
+
+    /// Run a command line in the terminal, in `cwd`: typed into the active tab (changing into
+    /// `cwd` first when needed) or, with `run_in_new_tab`, in a tab of its own
+    pub fn run_in_terminal(&mut self, ctx: &egui::Context, command: &str, cwd: &Path) {
+        if CONFIG
+            .get()
+            .and_then(|config| config.run_in_new_tab)
+            .unwrap_or(false)
+        {
+            let session = TerminalSession::new(ctx, Some(cwd.to_path_buf()));
+            session.run_command(command);
+            self.sessions.push(session);
+            self.active_session = self.sessions.len() - 1;
+            return;
+        }
+
+        let session = self.active();
+        if session.current_dir().as_deref() == Some(cwd) {
+            session.run_command(command);
+        } else {
+            session.run_command(&format!(
+                "cd -- {} && {command}",
+                crate::utils::shell_quote(&cwd.to_string_lossy())
+            ));
+        }
+    }
+
+    /// Whether a job is running in any tab
+    pub fn has_running_job(&self) -> bool {
+        self.sessions
+            .iter()
+            .any(|session| session.has_running_job())
+    }
+
+    /// Take over the settings of a reloaded `config` in every tab
+    pub fn apply_config(&mut self, config: &Config, previous: Option<&Config>) {
+        for session in &mut self.sessions {
+            session.apply_config(config, previous);
+        }
+        let explorer_visible = config.explorer_visible.unwrap_or(true);
+        if previous
+            .is_none_or(|previous| previous.explorer_visible.unwrap_or(true) != explorer_visible)
+        {
+            self.set_explorer_visible(explorer_visible);
+        }
+        let start_on_top = config.start_on_top.unwrap_or(false);
+        if previous.is_none_or(|previous| previous.start_on_top.unwrap_or(false) != start_on_top) {
+            self.always_on_top = start_on_top;
+        }
+        if previous.is_none_or(|previous| previous.background_opacity != config.background_opacity)
+        {
+            self.background_opacity = config.background_opacity.unwrap_or(1.0);
+        }
+    }
+
+    /// Show or hide the explorer panel. Hidden, the explorers stop watching their directories
+    /// and the terminal gets the whole height
+    fn set_explorer_visible(&mut self, visible: bool) {
+        if visible == self.explorer_visible {
+            return;
+        }
+        self.explorer_visible = visible;
+        if visible {
+            self.explorers().for_each(ExplorerWidget::resume);
+        } else {
+            self.explorers().for_each(ExplorerWidget::suspend);
+        }
+    }
+
+    /// Stop the shells of all tabs
+    pub fn stop(&mut self) {
+        for session in &mut self.sessions {
+            session.stop();
+        }
+        self.closed = true;
+    }
+
+    /// Open a new tab starting in the directory of the active one
+    fn open_tab(&mut self, ctx: &egui::Context) {
+        let working_directory = self.active().current_dir();
+        self.sessions
+            .push(TerminalSession::new(ctx, working_directory));
+        self.active_session = self.sessions.len() - 1;
+    }
+
+    /// Close a tab and its shell. Closing the last tab closes the window.
+    fn close_tab(&mut self, ctx: &egui::Context, index: usize) {
+        if self.sessions.len() == 1 {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+            return;
+        }
+
+        let mut session = self.sessions.remove(index);
+        session.stop();
+
+        if self.active_session > index || self.active_session >= self.sessions.len() {
+            self.active_session = self.active_session.saturating_sub(1);
+        }
+    }
+
+    fn confirm_close() -> bool {
+        CONFIG
+            .get()
+            .and_then(|config| config.confirm_close)
+            .unwrap_or(true)
+    }
+
+    /// Close a tab on the user's request, asking first if a job is running in it
+    fn request_close_tab(&mut self, ctx: &egui::Context, index: usize) {
+        if Self::confirm_close() && self.sessions[index].has_running_job() {
+            self.pending_close = Some(PendingClose::Tab(self.sessions[index].id));
+        } else {
+            self.close_tab(ctx, index);
+        }
+    }
+
+    /// Intercept closing the window while jobs are running to ask the user first.
+    /// Returns whether the window is closing.
+    fn handle_close_request(&mut self, ctx: &egui::Context) -> bool {
+        if !ctx.input(|i| i.viewport().close_requested()) {
+            return false;
+        }
+
+        if !self.close_confirmed && Self::confirm_close() && self.has_running_job() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+            self.pending_close = Some(PendingClose::Window);
+            return false;
+        }
+
+        true
+    }
+
+    fn show_close_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(pending_close) = self.pending_close else {
+            return;
+        };
+
+        let mut close = false;
+        let mut cancel = false;
+
+        let modal = egui::Modal::new(egui::Id::new("close_confirmation")).show(ctx, |ui| {
+            ui.heading("Close?");
+            ui.label(match pending_close {
+                PendingClose::Window => "A job is still running. Closing the window ends it.",
+                PendingClose::Tab(_) => "A job is still running. Closing the tab ends it.",
+            });
+            ui.horizontal(|ui| {
+                close = ui.button("Close anyway").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+        cancel |= modal.should_close();
+
+        if close {
+            self.pending_close = None;
+            match pending_close {
+                PendingClose::Window => {
+                    self.close_confirmed = true;
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+                PendingClose::Tab(id) => {
+                    // The tab may have moved or gone away while the dialog was shown
+                    if let Some(index) = self.sessions.iter().position(|session| session.id == id) {
+                        // Closing the last tab closes the window, which was confirmed just now
+                        self.close_confirmed = self.sessions.len() == 1;
+                        self.close_tab(ctx, index);
+                    }
+                }
+            }
+        } else if cancel {
+            self.pending_close = None;
+        }
+    }
+
+    /// Switch to the tab `offset` positions away from the active one, wrapping around
+    fn switch_tab(&mut self, offset: isize) {
+        let count = self.sessions.len() as isize;
+        self.active_session = (self.active_session as isize + offset).rem_euclid(count) as usize;
+    }
+
+    /// The window's and the terminal's shortcuts of `[keybindings.terminal]`. While the
+    /// explorer has the keyboard, a chord it binds itself goes to the explorer instead
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let config = CONFIG.get();
+        let keybindings = config
+            .as_deref()
+            .map(|config| config.keybindings.clone())
+            .unwrap_or_default();
+        let explorer_focused = self.explorer_widget.wants_keyboard()
+            || self
+                .second_explorer
+                .as_ref()
+                .is_some_and(|second| second.wants_keyboard());
+        let has_second_pane = self.second_explorer.is_some();
+
+        let pressed = |i: &mut egui::InputState, action: TerminalAction| {
+            let Some(chord) = keybindings.chord(action) else {
+                return false;
+            };
+            if explorer_focused && keybindings.action_for::<ExplorerAction>(chord).is_some() {
+                return false;
+            }
+            chord.consume(i)
+        };
+        let actions: Vec<TerminalAction> = ctx.input_mut(|i| {
+            TerminalAction::registry()
+                .iter()
+                .map(|info| info.action)
+                // Without a second pane the key goes to the terminal
+                .filter(|&action| action != TerminalAction::SwitchPane || has_second_pane)
+                .filter(|&action| pressed(i, action))
+                .collect()
+        });
+        for action in actions {
+            self.run_action(ctx, action);
+        }
+    }
+
+    /// Do what `action` stands for, pressed or picked in the command palette
+    fn run_action(&mut self, ctx: &egui::Context, action: TerminalAction) {
+        match action {
+            TerminalAction::NewWindow => self.new_window_requested = true,
+            TerminalAction::NewTab => self.open_tab(ctx),
+            TerminalAction::CloseTab => self.request_close_tab(ctx, self.active_session),
+            TerminalAction::PreviousTab => self.switch_tab(-1),
+            TerminalAction::NextTab => self.switch_tab(1),
+            TerminalAction::SwitchPane => self.switch_explorer_focus(),
+            TerminalAction::ToggleExplorer => self.set_explorer_visible(!self.explorer_visible),
+            TerminalAction::OpenSettings => self.settings_requested = true,
+            TerminalAction::CommandPalette => self.toggle_command_palette(),
+            TerminalAction::ToggleRecording => self.toggle_recording(),
+            TerminalAction::SaveAsPdf => self.open_pdf_export(),
+            TerminalAction::ToggleAlwaysOnTop => self.always_on_top = !self.always_on_top,
+            TerminalAction::IncreaseOpacity => self.change_opacity(OPACITY_STEP),
+            TerminalAction::DecreaseOpacity => self.change_opacity(-OPACITY_STEP),
+            #[cfg(feature = "inspector")]
+            TerminalAction::ToggleInspector => self.toggle_inspector(),
+        }
+    }
+
+    /// Record the active tab to `explotty-<date>-<time>.cast` in the home directory, or stop
+    /// recording it
+    fn toggle_recording(&mut self) {
+        let session = self.active();
+        if session.stop_recording().is_some() {
+            return;
+        }
+        let path = gio::glib::home_dir().join(format!(
+            "explotty-{}.cast",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        if let Err(e) = session.start_recording(path) {
+            error!("Failed to start recording: {e}");
+        }
+    }
+
+    /// Open the Save as PDF dialog for the selection of the active tab, or its screen, with
+    /// `explotty-<date>-<time>.pdf` in the home directory as the path
+    fn open_pdf_export(&mut self) {
+        let widget = &self.active().terminal_widget;
+        let path = gio::glib::home_dir().join(format!(
+            "explotty-{}.pdf",
+            chrono::Local::now().format("%Y%m%d-%H%M%S")
+        ));
+        let export = PdfExport {
+            path: path.to_string_lossy().into_owned(),
+            lines: widget
+                .export_lines()
+                .into_iter()
+                .map(<[TerminalCell]>::to_vec)
+                .collect(),
+            palette: widget.term.palette().clone(),
+            rows_per_page: widget.term.buffer.height,
+            focused: false,
+        };
+        self.pdf_export = Some(export);
+    }
+
+    fn show_pdf_export(&mut self, ctx: &egui::Context) {
+        let Some(export) = &mut self.pdf_export else {
+            return;
+        };
+
+        let mut save = false;
+        let mut cancel = false;
+        let modal = egui::Modal::new(egui::Id::new("pdf_export")).show(ctx, |ui| {
+            ui.heading("Save as PDF");
+            let response =
+                ui.add(egui::TextEdit::singleline(&mut export.path).desired_width(400.0));
+            if !export.focused {
+                response.request_focus();
+                export.focused = true;
+            }
+            save = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            ui.horizontal(|ui| {
+                save |= ui.button("Save").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+        cancel |= modal.should_close();
+
+        if save {
+            let Some(export) = self.pdf_export.take() else {
+                return;
+            };
+            let path = crate::utils::expand_home(export.path.trim());
+            let lines: Vec<&[TerminalCell]> = export.lines.iter().map(Vec::as_slice).collect();
+            let saved =
+                crate::fonts::terminal_font_styles(CONFIG.get().as_deref()).and_then(|fonts| {
+                    crate::pdf::save(&path, &lines, &export.palette, export.rows_per_page, fonts)
+                });
+            match saved {
+                Ok(()) => info!("Saved the terminal to {}", path.display()),
+                Err(e) => self.toasts.error(format!("Failed to save as PDF: {e}")),
+            }
+        } else if cancel {
+            self.pdf_export = None;
+        }
+    }
+
+    /// Make the terminal's background more opaque by `step`, more transparent when negative
+    fn change_opacity(&mut self, step: f32) {
+        let opacity = (self.background_opacity + step).clamp(
+            *BACKGROUND_OPACITY_RANGE.start(),
+            *BACKGROUND_OPACITY_RANGE.end(),
+        );
+        // Rounded, so the steps keep landing on tenths
+        self.background_opacity = (opacity * 10.0).round() / 10.0;
+    }
+
+    /// Keep the viewport's level in line with `always_on_top`; only a command to the window,
+    /// which stays as it is with its tabs
+    fn update_window_level(&mut self, ctx: &egui::Context) {
+        if self.window_level == Some(self.always_on_top) {
+            return;
+        }
+        let level = match self.always_on_top {
+            true => egui::WindowLevel::AlwaysOnTop,
+            false => egui::WindowLevel::Normal,
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(level));
+        self.window_level = Some(self.always_on_top);
+    }
+
+    /// Open the command palette, or close it when it's open. The pane that had the keyboard
+    /// gets the explorer's actions picked there
+    fn toggle_command_palette(&mut self) {
+        if self.command_palette.take().is_some() {
+            return;
+        }
+        let second_pane = self
+            .second_explorer
+            .as_ref()
+            .is_some_and(|second| second.wants_keyboard());
+        // The palette's text box has the keyboard now
+        self.explorers().for_each(ExplorerWidget::release_keyboard);
+        self.command_palette = Some((second_pane, CommandPalette::default()));
+    }
+
+    fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let Some((second_pane, palette)) = &mut self.command_palette else {
+            return;
+        };
+        let keybindings = CONFIG
+            .get()
+            .map(|config| config.keybindings.clone())
+            .unwrap_or_default();
+        let second_pane = *second_pane;
+        let Some(event) = palette.show(ctx, &keybindings, self.explorer_visible) else {
+            return;
+        };
+
+        self.command_palette = None;
+        match event {
+            PaletteEvent::Run(PaletteAction::Terminal(action)) => self.run_action(ctx, action),
+            PaletteEvent::Run(PaletteAction::Explorer(action)) => {
+                match (&mut self.second_explorer, second_pane) {
+                    (Some(second), true) => second.request_action(action),
+                    _ => self.explorer_widget.request_action(action),
+                }
+            }
+            PaletteEvent::Close => {}
+        }
+    }
+
+    #[cfg(feature = "inspector")]
+    fn toggle_inspector(&mut self) {
+        if self.inspector.take().is_some() {
+            self.clear_highlighted_rows();
+        } else {
+            self.inspector = Some((self.active_session, Default::default()));
+        }
+    }
+
+    #[cfg(feature = "inspector")]
+    fn clear_highlighted_rows(&mut self) {
+        for session in &mut self.sessions {
+            session.terminal_widget.highlighted_rows = None;
+        }
+    }
+
+    /// Show the inspector for the active tab, starting over when another tab became active
+    #[cfg(feature = "inspector")]
+    fn show_inspector(&mut self, ctx: &egui::Context) {
+        let active = self.active_session;
+        if self
+            .inspector
+            .as_ref()
+            .is_some_and(|(inspected, _)| *inspected != active)
+        {
+            self.clear_highlighted_rows();
+            self.inspector = Some((active, Default::default()));
+        }
+        if let Some((_, inspector)) = &mut self.inspector {
+            inspector.show(ctx, &mut self.sessions[active].terminal_widget);
+        }
+    }
+
+    /// Check whether any shell has exited and apply the configured exit behavior
+    fn check_child_exits(&mut self, ctx: &egui::Context) {
+        let exit_behavior = CONFIG
+            .get()
+            .and_then(|config| config.exit_behavior)
+            .unwrap_or_default();
+
+        let mut index = 0;
+        while index < self.sessions.len() {
+            self.sessions[index].restart_if_due();
+            if let Some(status) = self.sessions[index].poll_exit() {
+                match exit_behavior {
+                    ExitBehavior::Close => {
+                        self.close_tab(ctx, index);
+                        continue;
+                    }
+                    ExitBehavior::Restart => self.sessions[index].schedule_restart(),
+                    ExitBehavior::Hold => self.sessions[index].exit_status = Some(status),
+                }
+            }
+            index += 1;
+        }
+    }
+
+    /// Overlay shown over the terminal after the shell exited (exit_behavior = "hold")
+    fn show_exit_overlay(&mut self, ctx: &egui::Context, rect: egui::Rect) {
+        let Some(status) = &self.active().exit_status else {
+            return;
+        };
+        let message = describe_exit_status(status);
+
+        let mut restart = false;
+        let mut close = false;
+
+        egui::Area::new(egui::Id::new("exit_overlay"))
+            .fixed_pos(rect.center())
+            .pivot(egui::Align2::CENTER_CENTER)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(message);
+                    ui.horizontal(|ui| {
+                        restart = ui.button("Restart shell (Enter)").clicked();
+                        close = ui.button("Close tab (Esc)").clicked();
+                    });
+                });
+            });
+
+        ctx.input(|i| {
+            restart |= i.key_pressed(egui::Key::Enter);
+            close |= i.key_pressed(egui::Key::Escape);
+        });
+
+        if restart {
+            self.active().restart();
+        } else if close {
+            self.close_tab(ctx, self.active_session);
+        }
+    }
+
+    fn show_tab_bar(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
+        let mut close = None;
+        let mut explorer_visible = self.explorer_visible;
+
+        let config = CONFIG.get();
+        let show_output = config
+            .as_ref()
+            .and_then(|config| config.tab_activity)
+            .unwrap_or(true);
+        let silence_after = config
+            .as_ref()
+            .and_then(|config| config.tab_silence_seconds)
+            .filter(|&seconds| seconds > 0)
+            .map(Duration::from_secs);
+        // Only the other tabs collect activity
+        self.sessions[self.active_session].mark_seen();
+
+        ui.horizontal(|ui| {
+            for (index, session) in self.sessions.iter().enumerate() {
+                let mut response =
+                    ui.selectable_label(index == self.active_session, session.label());
+                match session.activity(silence_after) {
+                    Some(TabActivity::Output) if show_output => {
+                        let center = response.rect.right_top() + egui::vec2(-4.0, 4.0);
+                        ui.painter()
+                            .circle_filled(center, 2.5, ui.visuals().selection.bg_fill);
+                        response = response.on_hover_text("New output");
+                    }
+                    Some(TabActivity::Silence) => {
+                        let center = response.rect.right_top() + egui::vec2(-4.0, 4.0);
+                        ui.painter().circle_stroke(
+                            center,
+                            2.5,
+                            egui::Stroke::new(1.5, ui.visuals().warn_fg_color),
+                        );
+                        response = response.on_hover_text("Output stopped");
+                    }
+                    _ => {}
+                }
+                if response.clicked() {
+                    self.active_session = index;
+                }
+                if response.middle_clicked() {
+                    close = Some(index);
+                }
+            }
+
+            if ui
+                .button("+")
+                .on_hover_text(format!("New tab{}", shortcut_hint(TerminalAction::NewTab)))
+                .clicked()
+            {
+                self.open_tab(ctx);
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.menu_button("View", |ui| {
+                    ui.checkbox(
+                        &mut explorer_visible,
+                        format!("Explorer{}", shortcut_hint(TerminalAction::ToggleExplorer)),
+                    );
+                });
+            });
+        });
+
+        if let Some(index) = close {
+            self.request_close_tab(ctx, index);
+        }
+        self.set_explorer_visible(explorer_visible);
+    }
+
+    /// Tell the user about long commands that finished while they were looking elsewhere
+    fn notify_finished_commands(&mut self, ctx: &egui::Context) {
+        let notify_after = CONFIG
+            .get()
+            .and_then(|config| config.notify_after_seconds)
+            .unwrap_or(10);
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+
+        for session in &mut self.sessions {
+            for finished in session.take_finished_commands() {
+                if notify_after == 0 || focused || finished.duration.as_secs() < notify_after {
+                    continue;
+                }
+
+                let command = if finished.command.is_empty() {
+                    "Command"
+                } else {
+                    &finished.command
+                };
+                let mut summary = format!("{command} finished");
+                if let Some(exit_code) = finished.exit_code {
+                    summary.push_str(&format!(", exit {exit_code}"));
+                }
+                summary.push_str(&format!(
+                    ", {}",
+                    crate::utils::to_human_readable_duration(finished.duration)
+                ));
+
+                crate::utils::send_desktop_notification("explotty", &summary);
+                ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                    egui::UserAttentionType::Informational,
+                ));
+            }
+        }
+    }
+
+    /// Keep the window title in sync with the title of the active tab
+    fn update_window_title(&mut self, ctx: &egui::Context) {
+        let config = CONFIG.get();
+        let config = config.as_deref();
+        let fixed_title = config.and_then(|config| config.title.as_deref());
+        let template = config
+            .and_then(|config| config.title_template.as_deref())
+            .unwrap_or(DEFAULT_TITLE_TEMPLATE);
+
+        let title = if config.is_some_and(|config| config.fixed_title) {
+            fixed_title.unwrap_or("explotty").to_string()
+        } else if let Some(title) = self.active().title() {
+            // A title set by the application wins, unless the template places it itself
+            if template.contains("{title}") {
+                self.active().format_title(template)
+            } else {
+                title.to_string()
+            }
+        } else if let Some(title) = fixed_title {
+            title.to_string()
+        } else {
+            self.active().format_title(template)
+        };
+        let title = if title.trim().is_empty() {
+            "explotty".to_string()
+        } else {
+            title
+        };
+        let title = match self.active().title_prefix() {
+            Some(prefix) => format!("{prefix}{title}"),
+            None => title,
+        };
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
+
+    /// Type the shell-quoted paths of explorer rows dropped onto the terminal
+    fn handle_dropped_paths(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        if response.dnd_hover_payload::<Vec<PathBuf>>().is_some() {
+            ui.painter().rect_stroke(
+                response.rect,
+                0.0,
+                ui.visuals().selection.stroke,
+                egui::StrokeKind::Inside,
+            );
+        }
+
+        if let Some(paths) = response.dnd_release_payload::<Vec<PathBuf>>() {
+            self.type_paths(&paths);
+            self.explorers().for_each(ExplorerWidget::release_keyboard);
+        }
+    }
+
+    /// Type `paths` shell-quoted at the prompt, followed by a space for the next argument
+    fn type_paths(&mut self, paths: &[PathBuf]) {
+        let quoted: Vec<String> = paths
+            .iter()
+            .map(|path| crate::utils::shell_quote(&path.to_string_lossy()))
+            .collect();
+        self.active()
+            .send_input(format!("{} ", quoted.join(" ")).as_bytes());
+    }
+
+    /// Files dragged in from other applications: dropped onto the terminal their paths are
+    /// typed, dropped onto an explorer pane they are copied into its directory
+    fn handle_dropped_files(&mut self, ctx: &egui::Context, terminal_rect: egui::Rect) {
+        let (hovering, dropped, pointer) = ctx.input(|i| {
+            (
+                !i.raw.hovered_files.is_empty(),
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|file| file.path.clone())
+                    .collect::<Vec<_>>(),
+                i.pointer.latest_pos(),
+            )
+        });
+        // A hidden explorer keeps the rect it had when last shown
+        let explorer_visible = self.explorer_visible;
+        let onto_explorer = pointer.filter(|_| explorer_visible).and_then(|pos| {
+            self.explorers()
+                .position(|explorer| explorer.rect().contains(pos))
+        });
+
+        if hovering {
+            let (rect, action) = if let Some(index) = onto_explorer {
+                let explorer = self.explorers().nth(index).expect("pane under the pointer");
+                (explorer.rect(), "Copy here")
+            } else {
+                (terminal_rect, "Insert path")
+            };
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("file_drop"),
+            ));
+            let visuals = ctx.style().visuals.clone();
+            painter.rect_filled(rect, 0.0, visuals.selection.bg_fill.gamma_multiply(0.3));
+            painter.rect_stroke(
+                rect,
+                0.0,
+                visuals.selection.stroke,
+                egui::StrokeKind::Inside,
+            );
+            painter.text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                action,
+                egui::FontId::proportional(18.0),
+                visuals.strong_text_color(),
+            );
+        }
+
+        if dropped.is_empty() {
+            return;
+        }
+        if let Some(index) = onto_explorer {
+            let explorer = self.explorers().nth(index).expect("pane under the pointer");
+            explorer.copy_here(ctx, dropped);
+        } else {
+            self.type_paths(&dropped);
+        }
+    }
+
+    /// Draw the window into the viewport of `ctx`. Returns `true` when the window is closing;
+    /// the caller decides whether it really goes away.
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        // Start the PTY processing, background tabs keep running too
+        for session in &mut self.sessions {
+            session.handle_pty_output();
+            session.update_profile();
+        }
+        self.check_child_exits(ctx);
+        self.notify_finished_commands(ctx);
+        let closing = self.handle_close_request(ctx);
+        if self.pending_close.is_none() {
+            self.handle_shortcuts(ctx);
+        }
+        self.update_window_title(ctx);
+        self.update_window_level(ctx);
+
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            self.show_tab_bar(ctx, ui);
+        });
+
+        ctx.input(|i| {
+            if !self.track_geometry {
+                return;
+            }
+            let viewport = i.viewport();
+            if let Some(inner_rect) = viewport.inner_rect {
+                self.window_size = Some(inner_rect.size());
+            }
+            if let Some(outer_rect) = viewport.outer_rect {
+                self.window_position = Some(outer_rect.min);
+            }
+        });
+
+        // Hidden, the panel is left out and the terminal's PTY is resized to the full height
+        let explorer_panel = self.explorer_visible.then(|| {
+            egui::TopBottomPanel::bottom("explorer")
+                .resizable(true)
+                .default_height(self.explorer_height)
+                .show(ctx, |ui| {
+                    let session = &self.sessions[self.active_session];
+                    match &mut self.second_explorer {
+                        Some(second) => {
+                            // Each pane can copy and move to the directory of the other
+                            let first_directory = self
+                                .explorer_widget
+                                .current_directory()
+                                .map(Path::to_path_buf);
+                            let second_directory =
+                                second.current_directory().map(Path::to_path_buf);
+                            self.explorer_widget.set_other_pane(second_directory);
+                            second.set_other_pane(first_directory);
+
+                            let second_had_keyboard = second.wants_keyboard();
+                            ui.columns(2, |columns| {
+                                self.explorer_widget.show(&mut columns[0], session);
+                                second.show(&mut columns[1], session);
+                            });
+                            // Only the pane clicked last keeps the keyboard
+                            if self.explorer_widget.wants_keyboard() && second.wants_keyboard() {
+                                if second_had_keyboard {
+                                    second.release_keyboard();
+                                } else {
+                                    self.explorer_widget.release_keyboard();
+                                }
+                            }
+                        }
+                        None => {
+                            self.explorer_widget.set_other_pane(None);
+                            self.explorer_widget.show(ui, session);
+                        }
+                    }
+                })
+        });
+
+        let mut split_toggled = false;
+        let mut commands = Vec::new();
+        let mut typed_paths = Vec::new();
+        for explorer in self.explorers() {
+            split_toggled |= explorer.take_split_toggle();
+            commands.extend(explorer.take_commands());
+            typed_paths.extend(explorer.take_typed_paths());
+        }
+        if split_toggled && self.second_explorer.take().is_none() {
+            let directory = self
+                .explorer_widget
+                .current_directory()
+                .map(Path::to_path_buf);
+            self.open_second_explorer(ctx, directory);
+        }
+        for command in commands {
+            self.run_in_terminal(ctx, &command.command, &command.cwd);
+        }
+        if !typed_paths.is_empty() {
+            self.type_paths(&typed_paths);
+            self.explorers().for_each(ExplorerWidget::release_keyboard);
+        }
+        if let Some(explorer_panel) = explorer_panel {
+            self.explorer_height = explorer_panel.response.rect.height();
+        }
+
+        #[cfg(feature = "inspector")]
+        self.show_inspector(ctx);
+        self.show_command_palette(ctx);
+        self.show_pdf_export(ctx);
+
+        // The margin around the terminal is part of its background
+        let panel_fill = ctx.style().visuals.panel_fill;
+        let background_opacity = self.background_opacity;
+        let terminal_rect = egui::CentralPanel::default()
+            .frame(
+                egui::Frame::central_panel(&ctx.style())
+                    .fill(panel_fill.gamma_multiply(background_opacity)),
+            )
+            .show(ctx, |ui| {
+                self.active().terminal_widget.background_opacity = background_opacity;
+                let response = self.active().show(ui);
+                self.handle_dropped_paths(ui, &response);
+
+                // Always focus terminal widget, unless the explorer or the command palette has
+                // the keyboard
+                let keyboard_elsewhere = self.command_palette.is_some()
+                    || self.pdf_export.is_some()
+                    || self.explorers().any(|explorer| explorer.wants_keyboard());
+                if !keyboard_elsewhere {
+                    ui.memory_mut(|mem| mem.request_focus(response.id));
+                }
+
+                // If it has focus, handle input
+                if self.pending_close.is_some() {
+                    self.show_close_confirmation(ctx);
+                } else if self.active().exit_status.is_some() {
+                    self.show_exit_overlay(ctx, response.rect);
+                } else if !keyboard_elsewhere
+                    && (response.has_focus() || ui.memory(|mem| mem.has_focus(response.id)))
+                {
+                    self.active().handle_input(ctx);
+                }
+
+                response.rect
+            })
+            .inner;
+        self.handle_dropped_files(ctx, terminal_rect);
+        self.reveal_listing_click();
+        if self.active().terminal_widget.take_pdf_request() {
+            self.open_pdf_export();
+        }
+        self.toasts.show(ctx);
+
+        closing
+    }
+
+    /// Select the file clicked in a listing in the terminal in the explorer, if it is shown.
+    /// The keyboard stays with the terminal
+    fn reveal_listing_click(&mut self) {
+        let Some(click) = self.active().terminal_widget.take_listing_click() else {
+            return;
+        };
+        if !self.explorer_visible {
+            return;
+        }
+        let Some(path) = self.active().resolve_listing_click(&click) else {
+            debug!(
+                "No file for the names clicked in a listing: {:?}",
+                click.names
+            );
+            return;
+        };
+        self.explorer_widget.reveal(&path);
+        self.explorer_widget.flash(&path);
+        self.explorer_widget.release_keyboard();
+    }
+}
+
+/// ` (Ctrl+Shift+T)` for the chord of `action`, to put after its name
+fn shortcut_hint(action: TerminalAction) -> String {
+    CONFIG
+        .get()
+        .and_then(|config| config.keybindings.chord(action))
+        .map(|chord| format!(" ({chord})"))
+        .unwrap_or_default()
+}
@@ -0,0 +1,223 @@
+use std::{
+    fs,
+    io::{self, Read, Seek},
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Archive formats that are read without external tools
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+/// Extensions of the archive kinds, longer ones first
+const EXTENSIONS: [(&str, ArchiveKind); 6] = [
+    (".tar.gz", ArchiveKind::TarGz),
+    (".tar.zst", ArchiveKind::TarZst),
+    (".tgz", ArchiveKind::TarGz),
+    (".tzst", ArchiveKind::TarZst),
+    (".tar", ArchiveKind::Tar),
+    (".zip", ArchiveKind::Zip),
+];
+
+impl ArchiveKind {
+    /// The kind of the archive named `name`, and the name without its extension
+    pub fn split(name: &str) -> Option<(Self, &str)> {
+        let lowercase = name.to_lowercase();
+        EXTENSIONS.iter().find_map(|(extension, kind)| {
+            let stem = name.get(..name.len().checked_sub(extension.len())?)?;
+            (lowercase.ends_with(extension) && !stem.is_empty()).then_some((*kind, stem))
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryKind {
+    File,
+    Directory,
+    /// Links and special files, which aren't extracted
+    Other,
+}
+
+/// An entry of an archive, as listed in it
+pub struct ArchiveEntry {
+    /// The name as written in the archive
+    pub name: String,
+    /// Where it goes, relative to the extraction directory. `None` if it would end up outside
+    pub path: Option<PathBuf>,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    pub kind: EntryKind,
+    /// Unix permission bits, if the archive has them
+    pub mode: Option<u32>,
+}
+
+/// `name` as a path inside the extraction directory. Absolute paths and `..` are rejected
+fn enclosed_path(name: &Path) -> Option<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+/// A reader adding up the bytes read through it, for the progress of the archive file
+struct Counted<'a, R> {
+    inner: R,
+    read: &'a AtomicU64,
+}
+
+impl<R: Read> Read for Counted<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for Counted<'_, R> {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Go through the entries of the archive at `path` in order, handing each with its contents
+/// to `visit` until it returns `false`. `read` counts the bytes read from the archive file
+pub fn read_entries(
+    path: &Path,
+    kind: ArchiveKind,
+    read: &AtomicU64,
+    mut visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> bool,
+) -> anyhow::Result<()> {
+    let file = Counted {
+        inner: io::BufReader::new(fs::File::open(path)?),
+        read,
+    };
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(file)?;
+            for index in 0..archive.len() {
+                let mut file = archive.by_index(index)?;
+                let name = file.name()?.into_owned();
+                let entry = ArchiveEntry {
+                    path: enclosed_path(Path::new(&name)),
+                    name,
+                    size: file.size(),
+                    kind: if file.is_dir() {
+                        EntryKind::Directory
+                    } else if file.is_symlink() {
+                        EntryKind::Other
+                    } else {
+                        EntryKind::File
+                    },
+                    mode: file.unix_mode(),
+                };
+                if !visit(entry, &mut file) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        ArchiveKind::Tar => read_tar(file, visit),
+        ArchiveKind::TarGz => read_tar(flate2::read::GzDecoder::new(file), visit),
+        ArchiveKind::TarZst => read_tar(zstd::stream::read::Decoder::new(file)?, visit),
+    }
+}
+
+fn read_tar(
+    reader: impl Read,
+    mut visit: impl FnMut(ArchiveEntry, &mut dyn Read) -> bool,
+) -> anyhow::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let header = entry.header();
+        let kind = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+            tar::EntryType::Directory => EntryKind::Directory,
+            _ => EntryKind::Other,
+        };
+        let entry_info = ArchiveEntry {
+            path: enclosed_path(&name),
+            name: name.to_string_lossy().into_owned(),
+            size: header.size().unwrap_or(0),
+            kind,
+            mode: header.mode().ok(),
+        };
+        if !visit(entry_info, &mut entry) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The first `limit` entries of the archive at `path`, and whether there are more
+pub fn peek(
+    path: &Path,
+    kind: ArchiveKind,
+    limit: usize,
+) -> anyhow::Result<(Vec<ArchiveEntry>, bool)> {
+    let mut entries = Vec::new();
+    let mut more = false;
+    read_entries(path, kind, &AtomicU64::new(0), |entry, _| {
+        if entries.len() >= limit {
+            more = true;
+            return false;
+        }
+        entries.push(entry);
+        true
+    })?;
+    Ok((entries, more))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enclosed_paths_stay_inside_the_destination() {
+        let cases = [
+            ("a", Some("a")),
+            ("./a", Some("a")),
+            ("a/./b/", Some("a/b")),
+            // The archive's root
+            ("./", Some("")),
+            ("../x", None),
+            ("a/../../x", None),
+            // Even when it would come back inside
+            ("a/../b", None),
+            ("/etc/passwd", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                enclosed_path(Path::new(name)),
+                expected.map(PathBuf::from),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn archive_names_split_off_their_extension() {
+        assert_eq!(
+            ArchiveKind::split("backup.TAR.GZ"),
+            Some((ArchiveKind::TarGz, "backup"))
+        );
+        assert_eq!(
+            ArchiveKind::split("a.tar.zst"),
+            Some((ArchiveKind::TarZst, "a"))
+        );
+        assert_eq!(ArchiveKind::split("a.zip"), Some((ArchiveKind::Zip, "a")));
+        assert_eq!(ArchiveKind::split(".tar"), None);
+        assert_eq!(ArchiveKind::split("a.gz"), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! Sessions in the asciicast v2 format of asciinema: a JSON header line, then one
+//! `[time, code, data]` line per event. `Recorder` writes the output and resizes of a PTY,
+//! `Player` reads them back at the recorded pace for `--play`.
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// How often buffered events are written out
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The speeds the player offers
+pub const SPEEDS: [f64; 5] = [0.5, 1.0, 2.0, 4.0, 8.0];
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    width: u16,
+    height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<i64>,
+}
+
+/// Output and resizes of a terminal, recorded to an asciicast file
+pub struct Recorder {
+    pub path: PathBuf,
+    output: BufWriter<File>,
+    started: Instant,
+    last_flush: Instant,
+    /// The start of a UTF-8 sequence split between two chunks of output
+    partial: Vec<u8>,
+}
+
+impl Recorder {
+    /// Create `path` for a terminal of `width`×`height` cells, replacing an existing file
+    pub fn create(path: PathBuf, width: u16, height: u16) -> anyhow::Result<Self> {
+        let file = File::create(&path)
+            .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", path.display()))?;
+        let mut output = BufWriter::new(file);
+        let header = Header {
+            version: 2,
+            width,
+            height,
+            timestamp: Some(chrono::Local::now().timestamp()),
+        };
+        writeln!(output, "{}", serde_json::to_string(&header)?)?;
+        output.flush()?;
+
+        Ok(Self {
+            path,
+            output,
+            started: Instant::now(),
+            last_flush: Instant::now(),
+            partial: Vec::new(),
+        })
+    }
+
+    /// Record a chunk of output of the PTY
+    pub fn output(&mut self, data: &[u8]) {
+        let mut bytes = std::mem::take(&mut self.partial);
+        bytes.extend_from_slice(data);
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => text.to_string(),
+            Err(e) if e.error_len().is_none() => {
+                // Cut in the middle of a character, the rest comes with the next chunk
+                self.partial = bytes[e.valid_up_to()..].to_vec();
+                String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned()
+            }
+            Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+        };
+        if !text.is_empty() {
+            self.event("o", &text);
+        }
+    }
+
+    /// Record that the terminal is now `width`×`height` cells
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.event("r", &format!("{width}x{height}"));
+    }
+
+    fn event(&mut self, code: &str, data: &str) {
+        let time = self.started.elapsed().as_secs_f64();
+        let written = serde_json::to_string(&(time, code, data))
+            .map_err(std::io::Error::other)
+            .and_then(|line| writeln!(self.output, "{line}"));
+        let written = written.and_then(|()| {
+            if self.last_flush.elapsed() >= FLUSH_INTERVAL {
+                self.last_flush = Instant::now();
+                self.output.flush()?;
+            }
+            Ok(())
+        });
+        if let Err(e) = written {
+            error!("Failed to write recording {}: {e}", self.path.display());
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.output.flush();
+    }
+}
+
+/// An event of a recording, as the player hands it out
+#[derive(Clone)]
+pub enum PlayerEvent {
+    Output(String),
+    Resize(usize, usize),
+}
+
+/// A recording played back at its pace, scaled by `speed`
+pub struct Player {
+    pub path: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    /// The events with their times in seconds, output and resizes only
+    events: Vec<(f64, PlayerEvent)>,
+    /// Index of the next event to hand out
+    next: usize,
+    /// Seconds of the recording played so far
+    position: f64,
+    /// When `position` was last advanced, `None` while paused
+    resumed_at: Option<Instant>,
+    pub speed: f64,
+}
+
+impl Player {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .map_err(|e| anyhow::anyhow!("failed to open {}: {e}", path.display()))?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header = lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{} is empty", path.display()))??;
+        let header: Header = serde_json::from_str(&header)
+            .map_err(|e| anyhow::anyhow!("{} has no asciicast header: {e}", path.display()))?;
+        if header.version != 2 {
+            anyhow::bail!(
+                "{} is asciicast version {}, only version 2 is supported",
+                path.display(),
+                header.version
+            );
+        }
+
+        let mut events = Vec::new();
+        for (number, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (time, code, data): (f64, String, String) =
+                serde_json::from_str(&line).map_err(|e| {
+                    anyhow::anyhow!(
+                        "malformed event on line {} of {}: {e}",
+                        number + 2,
+                        path.display()
+                    )
+                })?;
+            let event = match code.as_str() {
+                "o" => PlayerEvent::Output(data),
+                "r" => match data
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                {
+                    Some((width, height)) => PlayerEvent::Resize(width, height),
+                    None => {
+                        warn!("Ignoring malformed resize event {data:?}");
+                        continue;
+                    }
+                },
+                // Input and markers don't change the screen
+                _ => continue,
+            };
+            events.push((time, event));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            width: header.width.into(),
+            height: header.height.into(),
+            events,
+            next: 0,
+            position: 0.0,
+            resumed_at: Some(Instant::now()),
+            speed: 1.0,
+        })
+    }
+
+    /// The events that are due since the last call
+    pub fn due_events(&mut self) -> Vec<PlayerEvent> {
+        if let Some(resumed_at) = self.resumed_at {
+            self.position += resumed_at.elapsed().as_secs_f64() * self.speed;
+            self.resumed_at = Some(Instant::now());
+        }
+        let start = self.next;
+        while self
+            .events
+            .get(self.next)
+            .is_some_and(|(time, _)| *time <= self.position)
+        {
+            self.next += 1;
+        }
+        self.events[start..self.next]
+            .iter()
+            .map(|(_, event)| event.clone())
+            .collect()
+    }
+
+    /// How long until the next event is due, `None` when paused or at the end
+    pub fn until_next(&self) -> Option<Duration> {
+        self.resumed_at?;
+        let (time, _) = self.events.get(self.next)?;
+        Some(Duration::from_secs_f64(
+            ((time - self.position) / self.speed).max(0.0),
+        ))
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.resumed_at.is_none()
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused && let Some(resumed_at) = self.resumed_at.take() {
+            self.position += resumed_at.elapsed().as_secs_f64() * self.speed;
+        } else if !paused && self.resumed_at.is_none() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        // The time played at the old speed counts with it
+        if let Some(resumed_at) = self.resumed_at {
+            self.position += resumed_at.elapsed().as_secs_f64() * self.speed;
+            self.resumed_at = Some(Instant::now());
+        }
+        self.speed = speed;
+    }
+
+    /// Go back to the start; the terminal has to be reset as well
+    pub fn rewind(&mut self) {
+        self.next = 0;
+        self.position = 0.0;
+        if self.resumed_at.is_some() {
+            self.resumed_at = Some(Instant::now());
+        }
+    }
+
+    /// Seconds played and the length of the recording
+    pub fn progress(&self) -> (f64, f64) {
+        let length = self.events.last().map_or(0.0, |(time, _)| *time);
+        (self.position.min(length), length)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+}
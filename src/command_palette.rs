@@ -0,0 +1,168 @@
+//! The command palette (Ctrl+Shift+P): the actions of the keybinding registry, picked by typing
+//! a part of their names
+
+use eframe::egui;
+
+use crate::keybindings::{Action, ExplorerAction, Keybindings, TerminalAction};
+
+/// An action offered by the palette, of either scope
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteAction {
+    Terminal(TerminalAction),
+    Explorer(ExplorerAction),
+}
+
+/// What happened in the palette this frame
+pub enum PaletteEvent {
+    Run(PaletteAction),
+    Close,
+}
+
+#[derive(Default)]
+pub struct CommandPalette {
+    query: String,
+    /// Index of the highlighted entry among the matching ones
+    highlighted: usize,
+}
+
+impl CommandPalette {
+    /// Show the palette over the window. The explorer's actions are only offered while it is
+    /// shown
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        keybindings: &Keybindings,
+        with_explorer: bool,
+    ) -> Option<PaletteEvent> {
+        let terminal_entries = TerminalAction::registry()
+            .iter()
+            .filter(|info| info.action != TerminalAction::CommandPalette)
+            .map(|info| {
+                (
+                    PaletteAction::Terminal(info.action),
+                    info.description.to_string(),
+                    shortcut(keybindings, info.action),
+                )
+            });
+        let explorer_entries = ExplorerAction::registry()
+            .iter()
+            .filter(|_| with_explorer)
+            .map(|info| {
+                (
+                    PaletteAction::Explorer(info.action),
+                    format!("Explorer: {}", info.description),
+                    shortcut(keybindings, info.action),
+                )
+            });
+        let mut entries: Vec<(i32, PaletteAction, String, String)> = terminal_entries
+            .chain(explorer_entries)
+            .filter_map(|(action, label, chord)| {
+                fuzzy_score(&self.query, &label).map(|score| (score, action, label, chord))
+            })
+            .collect();
+        // Best matches first, in registry order among equals
+        entries.sort_by_key(|(score, ..)| -score);
+        self.highlighted = self.highlighted.min(entries.len().saturating_sub(1));
+
+        let mut event = None;
+        egui::Modal::new(egui::Id::new("command_palette")).show(ctx, |ui| {
+            ui.set_width(420.0);
+            // Taken before the text box sees them
+            let (up, down, enter, escape) = ui.input_mut(|i| {
+                (
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::Escape),
+                )
+            });
+            if up {
+                self.highlighted = self.highlighted.saturating_sub(1);
+            }
+            if down && self.highlighted + 1 < entries.len() {
+                self.highlighted += 1;
+            }
+
+            let query = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Type a command")
+                    .desired_width(f32::INFINITY),
+            );
+            query.request_focus();
+            if query.changed() {
+                self.highlighted = 0;
+            }
+
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for (index, (_, action, label, chord)) in entries.iter().enumerate() {
+                        let highlighted = index == self.highlighted;
+                        let response = ui
+                            .horizontal(|ui| {
+                                let response = ui.selectable_label(highlighted, label.as_str());
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.weak(chord);
+                                    },
+                                );
+                                response
+                            })
+                            .inner;
+                        if highlighted && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            event = Some(PaletteEvent::Run(*action));
+                        }
+                    }
+                    if entries.is_empty() {
+                        ui.weak("No matching command");
+                    }
+                });
+
+            if enter && let Some((_, action, ..)) = entries.get(self.highlighted) {
+                event = Some(PaletteEvent::Run(*action));
+            }
+            if escape {
+                event = Some(PaletteEvent::Close);
+            }
+        });
+        event
+    }
+}
+
+/// The chord of `action` as the palette shows it, empty when unbound
+fn shortcut<A: Action>(keybindings: &Keybindings, action: A) -> String {
+    keybindings
+        .chord(action)
+        .map(|chord| chord.to_string())
+        .unwrap_or_default()
+}
+
+/// How well `text` matches `query`: none unless the characters of the query appear in it in
+/// order, ignoring case. Characters following each other and at the start of words count more
+fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous = None;
+    for wanted in query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|ch| !ch.is_whitespace())
+    {
+        let found = position + text[position..].iter().position(|&ch| ch == wanted)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 3;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
@@ -1,38 +1,1084 @@
-use std::path::Path;
+mod validate;
+pub mod watcher;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use gio::glib::home_dir;
+use regex::Regex;
 use serde::Deserialize;
 
-#[derive(Deserialize, Default, Debug)]
+use crate::{
+    explorer_widget::columns::Column,
+    keybindings::Keybindings,
+    term::{
+        CursorStyle,
+        color::{Palette, Rgba},
+    },
+};
+
+/// A configuration file with every setting commented out, written by `--init-config`
+pub const DEFAULT_CONFIG: &str = include_str!("config/default.toml");
+
+/// The range `font_size` has to be in
+const FONT_SIZE_RANGE: std::ops::RangeInclusive<f32> = 6.0..=72.0;
+/// The range `tab_width` has to be in
+pub const TAB_WIDTH_RANGE: std::ops::RangeInclusive<u8> = 1..=16;
+/// The range `background_opacity` has to be in; fully transparent would lose the text
+pub const BACKGROUND_OPACITY_RANGE: std::ops::RangeInclusive<f32> = 0.1..=1.0;
+
+/// What to do when the shell exits
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExitBehavior {
+    /// Close the window
+    Close,
+    /// Start a new shell
+    Restart,
+    /// Keep the terminal open and ask the user
+    #[default]
+    Hold,
+}
+
+/// What opening a directory in the explorer does
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpenDirectory {
+    /// Type `cd` into the shell, the explorer follows it
+    #[default]
+    Shell,
+    /// Show the directory in the explorer only
+    Explorer,
+}
+
+/// Light or dark look of the panels, menus and dialogs around the terminal
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UiTheme {
+    #[default]
+    Dark,
+    Light,
+    /// The desktop's preference
+    System,
+}
+
+/// What the bell (BEL) of an application does
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BellMode {
+    /// Nothing
+    #[default]
+    None,
+    /// Flash the terminal
+    Visual,
+    /// Ask the window manager to draw attention to the window, when it isn't focused
+    Urgent,
+}
+
+/// Where the terminal goes when output arrives while it is scrolled back
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScrollbackOnOutput {
+    /// Keep showing the history
+    #[default]
+    Stay,
+    /// Jump to the bottom
+    Bottom,
+}
+
+/// The `[behavior]` section
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Behavior {
+    pub bell_mode: BellMode,
+    /// Ask before pasting several lines to an application that doesn't use bracketed paste
+    pub paste_protection: bool,
+    /// Copy the selection to the clipboard as soon as it is made
+    pub copy_on_select: bool,
+    pub scrollback_on_output: ScrollbackOnOutput,
+    /// Report the mouse to applications that ask for it (xterm modes 1000, 1002, 1003, 1006).
+    /// Shift still selects text
+    pub mouse_reporting_enabled: bool,
+    /// Characters besides letters and digits that belong to a word selected by double-click
+    pub word_chars: String,
+    /// Publish the visible lines and the cursor to screen readers
+    pub accessibility: bool,
+}
+
+impl Default for Behavior {
+    fn default() -> Self {
+        Self {
+            bell_mode: BellMode::None,
+            paste_protection: false,
+            copy_on_select: false,
+            scrollback_on_output: ScrollbackOnOutput::Stay,
+            mouse_reporting_enabled: false,
+            word_chars: DEFAULT_WORD_CHARS.to_string(),
+            accessibility: true,
+        }
+    }
+}
+
+const DEFAULT_WORD_CHARS: &str = "-_./~";
+
+/// The range the drop-down window's `width` and `height` have to be in, percent of the screen
+pub const DROPDOWN_SIZE_RANGE: std::ops::RangeInclusive<f32> = 10.0..=100.0;
+
+/// The `[dropdown]` section
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Dropdown {
+    /// Start hidden and slide down from the top of the screen when summoned (`--dropdown`)
+    pub enabled: bool,
+    /// Size of the window in percent of the screen
+    pub width: f32,
+    pub height: f32,
+    /// Slide up again when another window gets the focus
+    pub hide_on_focus_loss: bool,
+}
+
+impl Default for Dropdown {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: 100.0,
+            height: 40.0,
+            hide_on_focus_loss: true,
+        }
+    }
+}
+
+/// The range `size_adjust` of a font has to be in
+pub const SIZE_ADJUST_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+
+/// One font of the `[fonts]` section
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FontSpec {
+    /// Family name as fontconfig knows it
+    pub family: Option<String>,
+    /// A font file, used instead of looking `family` up
+    pub path: Option<PathBuf>,
+    /// Scale of the glyphs, for a font that looks bigger or smaller than the others at the
+    /// same size. Defaults to 1
+    pub size_adjust: Option<f32>,
+}
+
+impl FontSpec {
+    fn family(family: &str) -> Self {
+        Self {
+            family: Some(family.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// The file or the family, for the log and the font's name in egui
+    pub fn label(&self) -> String {
+        match (&self.path, &self.family) {
+            (Some(path), _) => path.display().to_string(),
+            (None, Some(family)) => family.clone(),
+            (None, None) => "system default".to_string(),
+        }
+    }
+}
+
+/// The `[fonts]` section, taking precedence over `ui_font_family`, `terminal_font_family` and
+/// `terminal_fallback_font_families`
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fonts {
+    pub ui_font: Option<FontSpec>,
+    pub terminal_font: Option<FontSpec>,
+    /// Fonts tried in order for characters the terminal font doesn't have
+    pub fallbacks: Option<Vec<FontSpec>>,
+}
+
+/// When a `[[profile]]` applies to a tab: every condition given has to hold
+#[derive(Debug, Clone)]
+pub struct ProfileMatch {
+    /// Glob (`*`, `?`) the shell's directory has to match, `~` expanded
+    cwd: Option<Vec<char>>,
+    /// Regular expression searched in the command line of the foreground program
+    command: Option<Regex>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileMatchTable {
+    cwd: Option<String>,
+    command: Option<String>,
+}
+
+impl TryFrom<ProfileMatchTable> for ProfileMatch {
+    type Error = String;
+
+    fn try_from(table: ProfileMatchTable) -> Result<Self, Self::Error> {
+        if table.cwd.is_none() && table.command.is_none() {
+            return Err("match needs cwd or command".to_string());
+        }
+        let command = table
+            .command
+            .map(|command| Regex::new(&command))
+            .transpose()
+            .map_err(|e| format!("invalid command regex: {e}"))?;
+        Ok(Self {
+            cwd: table.cwd.map(|cwd| {
+                crate::utils::expand_home(&cwd)
+                    .to_string_lossy()
+                    .chars()
+                    .collect()
+            }),
+            command,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ProfileMatch {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ProfileMatchTable::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl ProfileMatch {
+    fn matches(&self, cwd: Option<&Path>, command_line: Option<&str>) -> bool {
+        let cwd_matches = self.cwd.as_ref().is_none_or(|pattern| {
+            cwd.is_some_and(|cwd| {
+                let cwd: Vec<char> = cwd.to_string_lossy().chars().collect();
+                crate::utils::glob_matches(pattern, &cwd)
+            })
+        });
+        let command_matches = self.command.as_ref().is_none_or(|regex| {
+            command_line.is_some_and(|command_line| regex.is_match(command_line))
+        });
+        cwd_matches && command_matches
+    }
+}
+
+/// A `[[profile]]`: a different look for tabs in certain directories or running certain
+/// programs
+#[derive(Deserialize, Debug, Clone)]
+pub struct Profile {
+    /// Shown in the log when the profile starts or stops applying
+    pub name: Option<String>,
+    #[serde(rename = "match")]
+    pub condition: ProfileMatch,
+    /// Of several matching profiles the highest priority wins, then the first one
+    #[serde(default)]
+    pub priority: i32,
+    /// Theme instead of the configured one
+    pub theme: Option<String>,
+    /// Colors replacing those of the theme, like `[colors]`
+    pub colors: Option<Colors>,
+    /// Put in front of the window title
+    pub title_prefix: Option<String>,
+    /// The resolved colors, set by `Config::update_palette`
+    #[serde(skip)]
+    palette: Palette,
+}
+
+impl Profile {
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// The name for the log, else the position in the file
+    pub fn label(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("#{}", index + 1))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HighlightTable {
+    regex: String,
+    fg: Option<String>,
+    bg: Option<String>,
+    #[serde(default)]
+    bold: bool,
+    #[serde(default)]
+    underline: bool,
+    action: Option<RuleAction>,
+    message: Option<String>,
+    cooldown_seconds: Option<u64>,
+}
+
+/// What a `[[highlight]]` rule does besides drawing, when its regex matches new output
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Send a desktop notification with the rule's message
+    Notify,
+    /// Ring the bell as `bell_mode` says, flashing with `"none"`
+    Bell,
+    /// Mark the line at the left edge of the terminal
+    Mark,
+}
+
+/// A `[[highlight]]`: output matching a regular expression drawn over the program's colors
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "HighlightTable")]
+pub struct HighlightRule {
+    pub regex: Regex,
+    pub fg: Option<Rgba>,
+    pub bg: Option<Rgba>,
+    pub bold: bool,
+    pub underline: bool,
+    pub action: Option<RuleAction>,
+    /// The text of a notification, with `${0}` for the match and `${1}`… for its groups
+    pub message: String,
+    /// Time the action waits after firing before it fires again
+    pub cooldown: Duration,
+}
+
+impl HighlightRule {
+    /// Whether the rule changes how the text looks, rather than only acting on it
+    pub fn draws(&self) -> bool {
+        self.fg.is_some() || self.bg.is_some() || self.bold || self.underline
+    }
+}
+
+impl TryFrom<HighlightTable> for HighlightRule {
+    type Error = String;
+
+    fn try_from(table: HighlightTable) -> Result<Self, Self::Error> {
+        let regex = Regex::new(&table.regex).map_err(|e| format!("invalid regex: {e}"))?;
+        let color = |key: &str, value: Option<String>| {
+            value
+                .map(|value| {
+                    parse_hex_color(&value).ok_or_else(|| {
+                        format!("{key} must be a color like \"#rrggbb\", got \"{value}\"")
+                    })
+                })
+                .transpose()
+        };
+        Ok(Self {
+            regex,
+            fg: color("fg", table.fg)?,
+            bg: color("bg", table.bg)?,
+            bold: table.bold,
+            underline: table.underline,
+            action: table.action,
+            message: table.message.unwrap_or_else(|| "${0}".to_string()),
+            cooldown: Duration::from_secs(table.cooldown_seconds.unwrap_or(10)),
+        })
+    }
+}
+
+impl PartialEq for HighlightRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+            && self.fg == other.fg
+            && self.bg == other.bg
+            && self.bold == other.bold
+            && self.underline == other.underline
+            && self.action == other.action
+            && self.message == other.message
+            && self.cooldown == other.cooldown
+    }
+}
+
+/// Names of colors 0–15 in the `[colors]` section
+pub const ANSI_COLOR_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright_black",
+    "bright_red",
+    "bright_green",
+    "bright_yellow",
+    "bright_blue",
+    "bright_magenta",
+    "bright_cyan",
+    "bright_white",
+];
+
+/// The `[colors]` section: colors as `"#rrggbb"`, each replacing the one of the theme
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(try_from = "BTreeMap<String, toml::Value>")]
+pub struct Colors {
+    /// The colors set, by their key
+    colors: Vec<(String, Rgba)>,
+    bold_is_bright: Option<bool>,
+    /// Keys that aren't colors, reported once the config is loaded
+    pub unknown_keys: Vec<String>,
+}
+
+impl Colors {
+    /// The color set for `key`, if any
+    pub fn get(&self, key: &str) -> Option<Rgba> {
+        self.colors
+            .iter()
+            .find(|(set, _)| set == key)
+            .map(|(_, color)| *color)
+    }
+
+    /// Set the color of `key`, one the section knows
+    pub fn set(&mut self, key: &str, color: Rgba) {
+        match self.colors.iter_mut().find(|(set, _)| set == key) {
+            Some((_, set)) => *set = color,
+            None => self.colors.push((key.to_string(), color)),
+        }
+    }
+
+    /// Go back to the colors of the theme
+    pub fn clear(&mut self) {
+        self.colors.clear();
+    }
+
+    pub fn bold_is_bright(&self) -> Option<bool> {
+        self.bold_is_bright
+    }
+
+    pub fn set_bold_is_bright(&mut self, bold_is_bright: Option<bool>) {
+        self.bold_is_bright = bold_is_bright;
+    }
+
+    /// Replace the colors of `palette` that are set
+    pub fn apply(&self, palette: &mut Palette) {
+        for (key, color) in &self.colors {
+            if let Some(slot) = color_slot(palette, key) {
+                *slot = *color;
+            }
+        }
+        if let Some(bold_is_bright) = self.bold_is_bright {
+            palette.bold_is_bright = bold_is_bright;
+        }
+    }
+}
+
+/// The color of `palette` the `[colors]` key `key` sets
+fn color_slot<'a>(palette: &'a mut Palette, key: &str) -> Option<&'a mut Rgba> {
+    if let Some(index) = ANSI_COLOR_NAMES.iter().position(|name| *name == key) {
+        return Some(&mut palette.ansi[index]);
+    }
+    match key {
+        "foreground" => Some(&mut palette.foreground),
+        "background" => Some(&mut palette.background),
+        "cursor" => Some(&mut palette.cursor),
+        "selection_background" => Some(&mut palette.selection_background),
+        "selection_foreground" => Some(palette.selection_foreground.insert(Rgba::TRANSPARENT)),
+        _ => None,
+    }
+}
+
+impl TryFrom<BTreeMap<String, toml::Value>> for Colors {
+    type Error = String;
+
+    fn try_from(table: BTreeMap<String, toml::Value>) -> Result<Self, Self::Error> {
+        let mut colors = Self::default();
+        for (key, value) in table {
+            if key == "bold_is_bright" {
+                colors.bold_is_bright =
+                    Some(value.as_bool().ok_or_else(|| {
+                        format!("colors.{key} must be true or false, got {value}")
+                    })?);
+            } else if color_slot(&mut Palette::default(), &key).is_some() {
+                let color = value.as_str().and_then(parse_hex_color).ok_or_else(|| {
+                    format!("colors.{key} must be a color like \"#rrggbb\", got {value}")
+                })?;
+                colors.colors.push((key, color));
+            } else {
+                colors.unknown_keys.push(key);
+            }
+        }
+        Ok(colors)
+    }
+}
+
+/// Parse `#rrggbb`
+fn parse_hex_color(text: &str) -> Option<Rgba> {
+    let hex = text.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some(Rgba::from_rgb(
+        channel(0..2)?,
+        channel(2..4)?,
+        channel(4..6)?,
+    ))
+}
+
+/// The configuration in effect, replaced as a whole when the file is reloaded
+pub struct SharedConfig(RwLock<Option<Arc<Config>>>);
+
+impl SharedConfig {
+    pub const fn new() -> Self {
+        Self(RwLock::new(None))
+    }
+
+    /// The current configuration, `None` before it was loaded
+    pub fn get(&self) -> Option<Arc<Config>> {
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn set(&self, config: Arc<Config>) {
+        *self
+            .0
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(config);
+    }
+}
+
+#[derive(Deserialize, Default, Debug, Clone)]
 pub struct Config {
     pub ui_font_family: Option<String>,
+    /// Theme of everything but the terminal. Defaults to `"dark"`
+    pub ui_theme: Option<UiTheme>,
+    /// Whether windows start above the others. Defaults to `false`
+    pub start_on_top: Option<bool>,
+    /// Opacity of the terminal's background, 0.1 to 1. Defaults to 1
+    pub background_opacity: Option<f32>,
     pub terminal_font_family: Option<String>,
     pub terminal_fallback_font_families: Option<Vec<String>>,
+    /// Size of the terminal font in points, 6 to 72. Defaults to 14
+    pub font_size: Option<f32>,
+    /// Lines kept in the scrollback of each tab. Defaults to 1000
+    pub scrollback_lines: Option<usize>,
+    /// `"block"`, `"underline"` or `"bar"`. Defaults to `"block"`
+    pub cursor_style: Option<CursorStyle>,
+    /// Whether the cursor blinks. Defaults to `false`
+    pub cursor_blink: Option<bool>,
+    /// Columns between the default tab stops, 1 to 16. Defaults to 8
+    pub tab_width: Option<u8>,
+    /// TAB writes spaces up to the next stop instead of moving the cursor. Defaults to `false`
+    pub tab_insert_spaces: Option<bool>,
+    /// Sent to applications that send ENQ, control characters left out. Defaults to nothing
+    pub answerback: Option<String>,
+    pub shell: Option<String>,
+    pub shell_args: Option<Vec<String>>,
+    /// Value of `TERM` for the shell. Defaults to `xterm-256color`
+    pub term: Option<String>,
+    pub working_directory: Option<String>,
+    pub exit_behavior: Option<ExitBehavior>,
+    /// Ask before closing a window or tab while a job is running. Defaults to `true`
+    pub confirm_close: Option<bool>,
+    /// How long jobs get to exit after SIGHUP before they are killed. Defaults to 1000
+    pub close_grace_period_ms: Option<u64>,
+    /// Record everything the shell prints to this file (`script` typescript format)
+    pub log_output: Option<String>,
+    /// Write `scriptreplay` timing data for `log_output` to this file
+    pub log_timing: Option<String>,
+    /// Rotate the output log to `<file>.old` once it would grow past this size
+    pub log_max_bytes: Option<u64>,
+    /// Notify when a command that ran at least this long finishes while the window is
+    /// unfocused. Needs shell integration (OSC 133). Defaults to 10, 0 disables
+    pub notify_after_seconds: Option<u64>,
+    /// Mark tabs other than the active one that printed something. Defaults to `true`
+    pub tab_activity: Option<bool>,
+    /// Mark tabs other than the active one whose output stopped this long ago. Defaults to 0,
+    /// which disables it
+    pub tab_silence_seconds: Option<u64>,
+    /// Restore the working directory, window geometry, explorer height, font size, hidden
+    /// files toggle and explorer columns and their widths of the previous run. Defaults to `true`
+    pub restore_session: Option<bool>,
+    /// Window title when the application didn't set one. `{cwd}`, `{command}` and `{title}`
+    /// are replaced. Defaults to `{cwd} — {command}`
+    pub title_template: Option<String>,
+    /// Run commands from the explorer ("Run in terminal", "Extract here") in a new tab instead
+    /// of the active one. Defaults to `false`
+    pub run_in_new_tab: Option<bool>,
+    /// Whether windows start with the explorer shown. Defaults to `true`
+    pub explorer_visible: Option<bool>,
+    /// Whether opening a directory in the explorer changes the shell's directory (`"shell"`)
+    /// or only the explorer's (`"explorer"`). Defaults to `"shell"`
+    pub open_directory: Option<OpenDirectory>,
+    /// Run executables in the terminal when they are opened from the explorer, instead of
+    /// opening them with their default application. Defaults to `false`
+    pub double_click_runs_executables: Option<bool>,
+    /// Columns of the explorer's list, e.g. `["size", "type", "modified", "permissions",
+    /// "owner"]`. The name is always shown. Defaults to size, type and modified
+    pub explorer_columns: Option<Vec<Column>>,
+    /// Mark changed, untracked and ignored items in git work trees in the explorer. Defaults
+    /// to `true`
+    pub git_status: Option<bool>,
+    /// Variables set for the shell (`[env]` table). `${VAR}` in values is replaced with the
+    /// variable from explotty's own environment
+    pub env: Option<HashMap<String, String>>,
+    /// Variables removed from the shell's environment, e.g. `TMUX` leaking from a parent
+    pub env_remove: Option<Vec<String>>,
+    /// Built-in color theme, one of `color::THEMES`. Defaults to `"default-dark"`
+    pub theme: Option<String>,
+    /// Colors of the terminal replacing those of the theme (`[colors]` table)
+    pub colors: Option<Colors>,
+    /// Fonts with files and size adjustments (`[fonts]` table)
+    #[serde(default)]
+    pub fonts: Fonts,
+    /// Chords of the terminal's and the explorer's actions (`[keybindings]` table)
+    #[serde(default)]
+    pub keybindings: Keybindings,
+    /// Bell, paste, selection, scrolling and mouse settings (`[behavior]` table)
+    #[serde(default)]
+    pub behavior: Behavior,
+    /// The drop-down mode (`[dropdown]` table), read at the start
+    #[serde(default)]
+    pub dropdown: Dropdown,
+    /// Looks applied to tabs depending on their directory and program (`[[profile]]` tables)
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<Profile>,
+    /// Output drawn in other colors where it matches (`[[highlight]]` tables)
+    #[serde(default, rename = "highlight")]
+    pub highlights: Vec<HighlightRule>,
+
+    // Set from the command line only
+    /// Command to run instead of the shell (`-e`)
+    #[serde(skip)]
+    pub command: Option<Vec<String>>,
+    /// Initial window title (`--title`)
+    #[serde(skip)]
+    pub title: Option<String>,
+    /// Ignore titles set by applications (`--fixed-title`)
+    #[serde(skip)]
+    pub fixed_title: bool,
+
+    /// The theme with the colors of `[colors]` on top, resolved by `load`
+    #[serde(skip)]
+    palette: Palette,
+    /// Settings of the file that were ignored and why, found by `load`
+    #[serde(skip)]
+    pub problems: Vec<String>,
 }
 
 impl Config {
+    /// The font of the interface: `fonts.ui_font`, else `ui_font_family`
+    pub fn ui_font(&self) -> Option<FontSpec> {
+        self.fonts
+            .ui_font
+            .clone()
+            .or_else(|| self.ui_font_family.as_deref().map(FontSpec::family))
+    }
+
+    /// The font of the terminal: `fonts.terminal_font`, else `terminal_font_family`
+    pub fn terminal_font(&self) -> Option<FontSpec> {
+        self.fonts
+            .terminal_font
+            .clone()
+            .or_else(|| self.terminal_font_family.as_deref().map(FontSpec::family))
+    }
+
+    /// The fallbacks of the terminal font in order: `fonts.fallbacks`, else
+    /// `terminal_fallback_font_families`
+    pub fn fallback_fonts(&self) -> Vec<FontSpec> {
+        match &self.fonts.fallbacks {
+            Some(fallbacks) => fallbacks.clone(),
+            None => self
+                .terminal_fallback_font_families
+                .iter()
+                .flatten()
+                .map(|family| FontSpec::family(family))
+                .collect(),
+        }
+    }
+
+    /// Read the configuration file at `path`. Settings that are wrong are left at their
+    /// defaults and listed in `problems`; only a file that can't be read or isn't TOML fails
     pub fn load(path: &str) -> anyhow::Result<Self> {
         let toml_str = std::fs::read_to_string(path)?;
-        let config: Self = toml::de::from_str(&toml_str)?;
+        let mut config = validate::parse(&toml_str)?;
+        for problem in &config.problems {
+            warn!("{path}: {problem}");
+        }
+        config.update_palette();
         Ok(config)
     }
 
+    /// Resolve the palette again after `theme` or `colors` changed: the chosen theme with the
+    /// `[colors]` overrides applied
+    pub fn update_palette(&mut self) {
+        let mut palette = self
+            .theme
+            .as_deref()
+            .and_then(Palette::theme)
+            .unwrap_or_default();
+        if let Some(colors) = &self.colors {
+            colors.apply(&mut palette);
+        }
+
+        // A profile's colors go over its own theme, or over the configured colors
+        for profile in &mut self.profiles {
+            let mut profile_palette = match profile.theme.as_deref().and_then(Palette::theme) {
+                Some(theme) => theme,
+                None => palette.clone(),
+            };
+            if let Some(colors) = &profile.colors {
+                colors.apply(&mut profile_palette);
+            }
+            profile.palette = profile_palette;
+        }
+
+        self.palette = palette;
+    }
+
+    /// Index of the profile for a tab in `cwd` running `command_line` in the foreground
+    pub fn profile_for(&self, cwd: Option<&Path>, command_line: Option<&str>) -> Option<usize> {
+        let mut best: Option<(usize, i32)> = None;
+        for (index, profile) in self.profiles.iter().enumerate() {
+            if profile.condition.matches(cwd, command_line)
+                && best.is_none_or(|(_, priority)| profile.priority > priority)
+            {
+                best = Some((index, profile.priority));
+            }
+        }
+        best.map(|(index, _)| index)
+    }
+
+    /// The terminal colors: the theme with the `[colors]` overrides
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Where a configuration file is looked for, in order: `explotty/config.toml` in
+    /// `$XDG_CONFIG_HOME` and in `~/.config`, then the older `~/.config/explotty.toml` and
+    /// `~/.explotty.toml`
     fn generate_config_path() -> Vec<String> {
-        let home_dir = home_dir();
-        let config_paths = vec![
+        Self::search_paths(&home_dir(), std::env::var_os("XDG_CONFIG_HOME"))
+    }
+
+    /// `generate_config_path` for the given home directory and `$XDG_CONFIG_HOME`
+    fn search_paths(home_dir: &Path, xdg_config_home: Option<std::ffi::OsString>) -> Vec<String> {
+        let xdg_config_home = xdg_config_home
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute());
+        let mut config_paths: Vec<PathBuf> = xdg_config_home
+            .into_iter()
+            .map(|config_home| config_home.join("explotty/config.toml"))
+            .collect();
+        config_paths.extend([
+            home_dir.join(".config/explotty/config.toml"),
             home_dir.join(".config/explotty.toml"),
             home_dir.join(".explotty.toml"),
-        ];
+        ]);
+        config_paths.dedup();
         config_paths
             .into_iter()
             .map(|path| path.to_string_lossy().into_owned())
             .collect()
     }
 
+    /// Where a configuration file is looked for first
+    pub fn default_path() -> String {
+        Self::generate_config_path().remove(0)
+    }
+
+    /// Write `DEFAULT_CONFIG` to `default_path`, unless there already is a configuration file.
+    /// Returns the path written
+    pub fn init() -> anyhow::Result<String> {
+        if let Some(existing) = Self::get_first_existing_path() {
+            anyhow::bail!("{existing} already exists");
+        }
+        let path = Self::default_path();
+        if let Some(directory) = Path::new(&path).parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&path, DEFAULT_CONFIG)?;
+        Ok(path)
+    }
+
     pub fn get_first_existing_path() -> Option<String> {
-        let config_paths = Self::generate_config_path();
+        Self::first_existing(Self::generate_config_path())
+    }
+
+    fn first_existing(config_paths: Vec<String>) -> Option<String> {
         config_paths
             .into_iter()
             .find(|path| Path::new(&path).exists())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory under the system's temporary directory, removed again on drop
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("explotty-config-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn create(&self, relative: &str) {
+            let path = self.0.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn search_order() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            Config::search_paths(home, Some("/xdg".into())),
+            [
+                "/xdg/explotty/config.toml",
+                "/home/user/.config/explotty/config.toml",
+                "/home/user/.config/explotty.toml",
+                "/home/user/.explotty.toml",
+            ]
+        );
+        // A relative $XDG_CONFIG_HOME is invalid and ignored, and the default one isn't
+        // looked in twice
+        for xdg_config_home in [None, Some("xdg".into()), Some("/home/user/.config".into())] {
+            assert_eq!(
+                Config::search_paths(home, xdg_config_home),
+                [
+                    "/home/user/.config/explotty/config.toml",
+                    "/home/user/.config/explotty.toml",
+                    "/home/user/.explotty.toml",
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn the_first_existing_file_wins() {
+        let dir = TempDir::new("search-order");
+        let home = dir.0.join("home");
+        let search = || {
+            Config::first_existing(Config::search_paths(&home, Some(dir.0.join("xdg").into()))).map(
+                |path| {
+                    path.strip_prefix(&*dir.0.to_string_lossy())
+                        .unwrap()
+                        .to_string()
+                },
+            )
+        };
+
+        assert_eq!(search(), None);
+        let expected = [
+            "home/.explotty.toml",
+            "home/.config/explotty.toml",
+            "home/.config/explotty/config.toml",
+            "xdg/explotty/config.toml",
+        ];
+        // Each file takes precedence over those created before it
+        for relative in expected {
+            dir.create(relative);
+            assert_eq!(search(), Some(format!("/{relative}")));
+        }
+    }
+
+    /// `DEFAULT_CONFIG` with every setting it shows uncommented
+    fn default_config_uncommented() -> String {
+        let setting =
+            Regex::new(r#"^# ?(\[\[?[a-z_.]+\]\]?\s*|[a-z_"][a-z_."+-]* = .*)$"#).unwrap();
+        DEFAULT_CONFIG
+            .lines()
+            .filter(|line| cfg!(feature = "inspector") || !line.contains("toggle_inspector"))
+            .filter_map(|line| setting.captures(line))
+            .map(|captures| format!("{}\n", &captures[1]))
+            .collect()
+    }
+
+    #[test]
+    fn the_default_config_sets_every_key_without_problems() {
+        let text = default_config_uncommented();
+        let config = validate::parse(&text).unwrap();
+        assert_eq!(config.problems, Vec::<String>::new());
+
+        assert_eq!(config.scrollback_lines, Some(1000));
+        assert_eq!(config.exit_behavior, Some(ExitBehavior::Hold));
+        assert_eq!(config.profiles.len(), 1);
+        assert_eq!(config.highlights.len(), 2);
+        assert_eq!(config.fallback_fonts().len(), 1);
+    }
+
+    #[test]
+    fn behavior_sets_every_key() {
+        let config = validate::parse(
+            r#"
+            [behavior]
+            bell_mode = "urgent"
+            paste_protection = true
+            copy_on_select = true
+            scrollback_on_output = "bottom"
+            mouse_reporting_enabled = true
+            word_chars = "-"
+            accessibility = false
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.problems, Vec::<String>::new());
+        assert_eq!(
+            config.behavior,
+            Behavior {
+                bell_mode: BellMode::Urgent,
+                paste_protection: true,
+                copy_on_select: true,
+                scrollback_on_output: ScrollbackOnOutput::Bottom,
+                mouse_reporting_enabled: true,
+                word_chars: "-".to_string(),
+                accessibility: false,
+            }
+        );
+
+        // Left out, every key keeps the behavior from before the section existed
+        let config = validate::parse("[behavior]\n").unwrap();
+        assert_eq!(config.behavior, Behavior::default());
+    }
+
+    #[test]
+    fn a_config_with_errors_is_partly_applied() {
+        let config = validate::parse(
+            r##"
+terminal_font_famly = "Iosevka"
+font_size = 200.0
+scrollback_lines = 5000
+cursor_style = "triangle"
+theme = "nord"
+tab_width = 4
+
+[colors]
+foreground = "#12345"
+background = "#101010"
+pink = "#ff00ff"
+
+[behavior]
+bell_mode = "visual"
+copy_on_select = "yes"
+
+[[highlight]]
+regex = "("
+
+[[highlight]]
+regex = "WARN"
+"##,
+        )
+        .unwrap();
+
+        let expected_problems = [
+            "line 2, column 1: unknown setting `terminal_font_famly`, did you mean `terminal_font_family`?",
+            "line 3, column 1: font_size must be",
+            "line 5, column 1: cursor_style:",
+            "line 6, column 1: unknown theme \"nord\"",
+            "line 10, column 1: colors.foreground:",
+            "line 12, column 1: unknown color `colors.pink`",
+            "line 16, column 1: behavior.copy_on_select:",
+            "line 18, column 1: highlight #1: invalid regex",
+        ];
+        let mut problems = config.problems.clone();
+        problems.sort_by_key(|problem| {
+            expected_problems
+                .iter()
+                .position(|expected| problem.starts_with(expected))
+        });
+        assert_eq!(problems.len(), expected_problems.len(), "{problems:#?}");
+        for (problem, expected) in problems.iter().zip(expected_problems) {
+            assert!(problem.starts_with(expected), "{problem} / {expected}");
+        }
+
+        // The settings that are fine take effect, the others keep their defaults
+        assert_eq!(config.terminal_font_family, None);
+        assert_eq!(config.font_size, None);
+        assert_eq!(config.scrollback_lines, Some(5000));
+        assert_eq!(config.cursor_style, None);
+        assert_eq!(config.theme, None);
+        assert_eq!(config.tab_width, Some(4));
+        assert_eq!(
+            config.colors.as_ref().unwrap().colors,
+            [("background".to_string(), Rgba::from_rgb(0x10, 0x10, 0x10))]
+        );
+        assert_eq!(config.behavior.bell_mode, BellMode::Visual);
+        assert!(!config.behavior.copy_on_select);
+        assert_eq!(config.highlights.len(), 1);
+        assert_eq!(config.highlights[0].regex.as_str(), "WARN");
+    }
+
+    #[test]
+    fn font_specs_round_trip() {
+        let config = validate::parse(
+            r#"
+            [fonts]
+            ui_font = { family = "Cantarell" }
+            terminal_font = { path = "/fonts/Mono.ttf", size_adjust = 1.1 }
+            fallbacks = [{ family = "Noto Sans CJK JP", size_adjust = 0.9 }, { family = "Symbola" }]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.problems, Vec::<String>::new());
+        assert_eq!(config.ui_font(), Some(FontSpec::family("Cantarell")));
+        assert_eq!(
+            config.terminal_font(),
+            Some(FontSpec {
+                path: Some("/fonts/Mono.ttf".into()),
+                size_adjust: Some(1.1),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            config.fallback_fonts(),
+            [
+                FontSpec {
+                    size_adjust: Some(0.9),
+                    ..FontSpec::family("Noto Sans CJK JP")
+                },
+                FontSpec::family("Symbola"),
+            ]
+        );
+
+        // Out of range adjustments are reported and that font left out
+        let config =
+            validate::parse("[fonts]\nterminal_font = { family = \"Mono\", size_adjust = 9.0 }\n")
+                .unwrap();
+        assert_eq!(config.problems.len(), 1, "{:?}", config.problems);
+        assert_eq!(config.terminal_font(), None);
+    }
+
+    #[test]
+    fn old_font_settings_migrate_to_font_specs() {
+        let config = validate::parse(
+            r#"
+            ui_font_family = "Cantarell"
+            terminal_font_family = "Iosevka"
+            terminal_fallback_font_families = ["Noto Sans CJK JP", "Symbola"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.ui_font(), Some(FontSpec::family("Cantarell")));
+        assert_eq!(config.terminal_font(), Some(FontSpec::family("Iosevka")));
+        assert_eq!(
+            config.fallback_fonts(),
+            [
+                FontSpec::family("Noto Sans CJK JP"),
+                FontSpec::family("Symbola")
+            ]
+        );
+
+        // The [fonts] section takes precedence
+        let config = validate::parse(
+            r#"
+            terminal_font_family = "Iosevka"
+            terminal_fallback_font_families = ["Symbola"]
+            [fonts]
+            terminal_font = { family = "Hack" }
+            fallbacks = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.terminal_font(), Some(FontSpec::family("Hack")));
+        assert_eq!(config.fallback_fonts(), []);
+    }
+}
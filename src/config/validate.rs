@@ -0,0 +1,405 @@
+//! Loading a configuration file setting by setting: a setting that doesn't parse or makes no
+//! sense is reported with its location and left at its default, the rest takes effect
+
+use serde::de::{self, Visitor};
+use toml_edit::{Document, DocumentMut, Item, Table, TableLike};
+
+use crate::{
+    config::{
+        BACKGROUND_OPACITY_RANGE, Config, DROPDOWN_SIZE_RANGE, Dropdown, FONT_SIZE_RANGE, FontSpec,
+        SIZE_ADJUST_RANGE, TAB_WIDTH_RANGE,
+    },
+    keybindings::{Action, ExplorerAction, TerminalAction},
+    term::color::{Palette, THEMES},
+};
+
+/// Parse `text` into a configuration, collecting the problems of single settings in
+/// `Config::problems`. Fails only when the file isn't TOML at all
+pub fn parse(text: &str) -> anyhow::Result<Config> {
+    let document = Document::parse(text.to_string())?;
+    let mut problems = Vec::new();
+
+    let mut kept = DocumentMut::new();
+    for (key, item) in document.as_table().iter() {
+        // The original key, whose span locates the problems `check_values` finds
+        let (formatted_key, _) = document.as_table().get_key_value(key).unwrap();
+        let at = Location::of_key(text, document.as_table(), key);
+        if !known_keys().contains(&key) {
+            let suggestion = known_keys()
+                .iter()
+                .filter(|known| edit_distance(key, known) <= 2)
+                .min_by_key(|known| edit_distance(key, known))
+                .map(|known| format!(", did you mean `{known}`?"))
+                .unwrap_or_default();
+            problems.push(at.problem(format!("unknown setting `{key}`{suggestion}")));
+            continue;
+        }
+
+        match test_setting(&[key], item) {
+            Ok(()) => {
+                kept.insert_formatted(formatted_key, item.clone());
+            }
+            Err(message) => {
+                // Of a table or array only the entries that fail are dropped, as long as the
+                // rest is fine by itself
+                let mut entry_problems = Vec::new();
+                match keep_valid_entries(text, &[key], item, &mut entry_problems)
+                    .filter(|item| test_setting(&[key], item).is_ok())
+                {
+                    Some(item) => {
+                        kept.insert_formatted(formatted_key, item);
+                        problems.append(&mut entry_problems);
+                    }
+                    None => problems.push(at.problem(format!("{key}: {message}"))),
+                }
+            }
+        }
+    }
+
+    let mut config: Config = toml::from_str(&kept.to_string())?;
+    check_values(text, kept.as_table(), &mut config, &mut problems);
+    config.problems = problems;
+    Ok(config)
+}
+
+/// Whether `item` alone deserializes as the setting at `path`, e.g. `["keybindings", "explorer"]`
+fn test_setting(path: &[&str], item: &Item) -> Result<(), String> {
+    let mut item = item.clone();
+    for key in path[1..].iter().rev() {
+        let mut table = Table::new();
+        table.insert(key, item);
+        item = Item::Table(table);
+    }
+    let mut document = DocumentMut::new();
+    document.insert(path[0], item);
+    toml::from_str::<Config>(&document.to_string())
+        .map(drop)
+        .map_err(|e| e.message().trim_end().to_string())
+}
+
+/// The entries of the table or array `item` at `path` that deserialize, reporting the
+/// others. Tables in tables are sorted out the same way. `None` when `item` has no entries
+/// to tell apart
+fn keep_valid_entries(
+    text: &str,
+    path: &[&str],
+    item: &Item,
+    problems: &mut Vec<String>,
+) -> Option<Item> {
+    let key = path.join(".");
+    if let Some(table) = item.as_table_like() {
+        let mut kept = Table::new();
+        for (entry, value) in table.iter() {
+            // Kept with its span, for problems found in the entry later
+            let (formatted_entry, _) = table.get_key_value(entry).unwrap();
+            let mut single = Table::new();
+            single.insert(entry, value.clone());
+            let message = match test_setting(path, &Item::Table(single)) {
+                Ok(()) => {
+                    kept.insert_formatted(formatted_entry, value.clone());
+                    continue;
+                }
+                Err(message) => message,
+            };
+
+            let entry_path = [path, &[entry]].concat();
+            let mut entry_problems = Vec::new();
+            match value
+                .is_table_like()
+                .then(|| keep_valid_entries(text, &entry_path, value, &mut entry_problems))
+                .flatten()
+                .filter(|value| test_setting(&entry_path, value).is_ok())
+            {
+                Some(value) => {
+                    kept.insert_formatted(formatted_entry, value);
+                    problems.append(&mut entry_problems);
+                }
+                None => {
+                    let at = Location::of_key(text, table, entry);
+                    problems.push(at.problem(format!("{key}.{entry}: {message}")));
+                }
+            }
+        }
+        return Some(Item::Table(kept));
+    }
+
+    if let Some(tables) = item.as_array_of_tables() {
+        let mut kept = toml_edit::ArrayOfTables::new();
+        for (index, table) in tables.iter().enumerate() {
+            let mut single = toml_edit::ArrayOfTables::new();
+            single.push(table.clone());
+            match test_setting(path, &Item::ArrayOfTables(single)) {
+                Ok(()) => kept.push(table.clone()),
+                Err(message) => {
+                    let at = Location::of_span(text, table.span());
+                    problems.push(at.problem(format!("{key} #{}: {message}", index + 1)));
+                }
+            }
+        }
+        return Some(Item::ArrayOfTables(kept));
+    }
+
+    if let Some(array) = item.as_array() {
+        let mut kept = toml_edit::Array::new();
+        for (index, value) in array.iter().enumerate() {
+            let mut single = toml_edit::Array::new();
+            single.push_formatted(value.clone());
+            match test_setting(path, &toml_edit::value(single)) {
+                Ok(()) => kept.push_formatted(value.clone()),
+                Err(message) => {
+                    let at = Location::of_span(text, value.span());
+                    problems.push(at.problem(format!("{key} #{}: {message}", index + 1)));
+                }
+            }
+        }
+        return Some(toml_edit::value(kept));
+    }
+
+    None
+}
+
+/// Check what deserializes but can't be used, resetting it to the default
+fn check_values(text: &str, root: &Table, config: &mut Config, problems: &mut Vec<String>) {
+    let at = |key: &str| Location::of_key(text, root, key);
+
+    if let Some(font_size) = config.font_size
+        && !FONT_SIZE_RANGE.contains(&font_size)
+    {
+        problems.push(at("font_size").problem(format!(
+            "font_size must be between {} and {}, got {font_size}",
+            FONT_SIZE_RANGE.start(),
+            FONT_SIZE_RANGE.end()
+        )));
+        config.font_size = None;
+    }
+    if let Some(tab_width) = config.tab_width
+        && !TAB_WIDTH_RANGE.contains(&tab_width)
+    {
+        problems.push(at("tab_width").problem(format!(
+            "tab_width must be between {} and {}, got {tab_width}",
+            TAB_WIDTH_RANGE.start(),
+            TAB_WIDTH_RANGE.end()
+        )));
+        config.tab_width = None;
+    }
+    if let Some(opacity) = config.background_opacity
+        && !BACKGROUND_OPACITY_RANGE.contains(&opacity)
+    {
+        problems.push(at("background_opacity").problem(format!(
+            "background_opacity must be between {} and {}, got {opacity}",
+            BACKGROUND_OPACITY_RANGE.start(),
+            BACKGROUND_OPACITY_RANGE.end()
+        )));
+        config.background_opacity = None;
+    }
+    let dropdown_table = root.get("dropdown").and_then(Item::as_table_like);
+    let defaults = Dropdown::default();
+    for (key, size, default) in [
+        ("width", &mut config.dropdown.width, defaults.width),
+        ("height", &mut config.dropdown.height, defaults.height),
+    ] {
+        if !DROPDOWN_SIZE_RANGE.contains(size) {
+            let at = dropdown_table.map_or(Location::default(), |table| {
+                Location::of_key(text, table, key)
+            });
+            problems.push(at.problem(format!(
+                "dropdown.{key} must be between {} and {}, got {size}",
+                DROPDOWN_SIZE_RANGE.start(),
+                DROPDOWN_SIZE_RANGE.end()
+            )));
+            *size = default;
+        }
+    }
+    if let Some(name) = &config.theme
+        && Palette::theme(name).is_none()
+    {
+        problems.push(at("theme").problem(format!(
+            "unknown theme \"{name}\", using \"default-dark\". Themes: {}",
+            THEMES.join(", ")
+        )));
+        config.theme = None;
+    }
+    if let Some(colors) = &mut config.colors {
+        let table = root.get("colors").and_then(Item::as_table_like);
+        for key in std::mem::take(&mut colors.unknown_keys) {
+            let at = table.map_or(Location::default(), |table| {
+                Location::of_key(text, table, &key)
+            });
+            problems.push(at.problem(format!("unknown color `colors.{key}`")));
+        }
+    }
+    let fonts_table = root.get("fonts").and_then(Item::as_table_like);
+    let fonts_at = |key: &str| {
+        fonts_table.map_or(Location::default(), |table| {
+            Location::of_key(text, table, key)
+        })
+    };
+    for (key, spec) in [
+        ("ui_font", &mut config.fonts.ui_font),
+        ("terminal_font", &mut config.fonts.terminal_font),
+    ] {
+        if let Some(message) = spec.as_ref().and_then(check_font) {
+            problems.push(fonts_at(key).problem(format!("fonts.{key}: {message}")));
+            *spec = None;
+        }
+    }
+    if let Some(fallbacks) = &mut config.fonts.fallbacks {
+        let mut index = 0;
+        fallbacks.retain(|fallback| {
+            index += 1;
+            let message = check_font(fallback);
+            if let Some(message) = &message {
+                problems.push(
+                    fonts_at("fallbacks").problem(format!("fonts.fallbacks #{index}: {message}")),
+                );
+            }
+            message.is_none()
+        });
+    }
+
+    let keybindings_table = root.get("keybindings").and_then(Item::as_table_like);
+    report_conflicts::<TerminalAction>(text, keybindings_table, config, problems);
+    report_conflicts::<ExplorerAction>(text, keybindings_table, config, problems);
+
+    let profile_tables = root.get("profile").and_then(Item::as_array_of_tables);
+    for (index, profile) in config.profiles.iter_mut().enumerate() {
+        if let Some(name) = &profile.theme
+            && Palette::theme(name).is_none()
+        {
+            let at = profile_tables
+                .and_then(|tables| tables.get(index))
+                .map_or(Location::default(), |table| {
+                    Location::of_key(text, table, "theme")
+                });
+            problems.push(at.problem(format!(
+                "unknown theme \"{name}\" in profile {}, keeping the configured colors",
+                profile.label(index)
+            )));
+            profile.theme = None;
+        }
+    }
+}
+
+/// Unbind the actions of a scope of `[keybindings]` that collide with another one
+fn report_conflicts<A: Action>(
+    text: &str,
+    keybindings_table: Option<&dyn TableLike>,
+    config: &mut Config,
+    problems: &mut Vec<String>,
+) {
+    let scope_table = keybindings_table
+        .and_then(|table| table.get(A::SCOPE))
+        .and_then(Item::as_table_like);
+    for (unbound, kept, chord) in config.keybindings.resolve_conflicts::<A>() {
+        let (unbound, kept) = (unbound.info().name, kept.info().name);
+        // Where the later of the two is bound, or else the table
+        let at = scope_table.map_or(Location::default(), |table| {
+            match Location::of_key(text, table, unbound) {
+                Location(None) => Location::of_key(text, table, kept),
+                at => at,
+            }
+        });
+        problems.push(at.problem(format!(
+            "keybindings.{}: {chord} is bound to both `{kept}` and `{unbound}`, `{unbound}` is left unbound",
+            A::SCOPE
+        )));
+    }
+}
+
+/// What is wrong with a font of `[fonts]`, if anything
+fn check_font(spec: &FontSpec) -> Option<String> {
+    if spec.family.is_none() && spec.path.is_none() {
+        return Some("needs a `family` or a `path`".to_string());
+    }
+    if let Some(size_adjust) = spec.size_adjust
+        && !SIZE_ADJUST_RANGE.contains(&size_adjust)
+    {
+        return Some(format!(
+            "size_adjust must be between {} and {}, got {size_adjust}",
+            SIZE_ADJUST_RANGE.start(),
+            SIZE_ADJUST_RANGE.end()
+        ));
+    }
+    None
+}
+
+/// Where in the file a problem is, if known
+#[derive(Default)]
+struct Location(Option<(usize, usize)>);
+
+impl Location {
+    fn of_span(text: &str, span: Option<std::ops::Range<usize>>) -> Self {
+        Self(span.map(|span| {
+            let before = &text[..span.start.min(text.len())];
+            let line = before.matches('\n').count() + 1;
+            let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+            (line, column)
+        }))
+    }
+
+    fn of_key(text: &str, table: &dyn TableLike, key: &str) -> Self {
+        Self::of_span(
+            text,
+            table.get_key_value(key).and_then(|(key, _)| key.span()),
+        )
+    }
+
+    fn problem(&self, message: String) -> String {
+        match self.0 {
+            Some((line, column)) => format!("line {line}, column {column}: {message}"),
+            None => message,
+        }
+    }
+}
+
+/// The settings `Config` reads, as serde knows them
+fn known_keys() -> &'static [&'static str] {
+    static KEYS: std::sync::OnceLock<&'static [&'static str]> = std::sync::OnceLock::new();
+    KEYS.get_or_init(|| {
+        let mut fields: &'static [&'static str] = &[];
+        let _ = <Config as serde::Deserialize>::deserialize(FieldNames(&mut fields));
+        fields
+    })
+}
+
+/// A deserializer that only records the field names of the struct asking for them
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only the field names were asked for"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// Levenshtein distance, for suggesting the setting a typo meant
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,87 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Quiet time after the last change before the file is read, editors often write in steps
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches the configuration file, so edits apply without a restart
+pub struct ConfigWatcher {
+    path: PathBuf,
+    /// Kept alive for as long as the file is watched
+    _watcher: notify::RecommendedWatcher,
+    receiver: mpsc::Receiver<Instant>,
+    /// When the last change not yet reloaded for arrived
+    changed_at: Option<Instant>,
+}
+
+impl ConfigWatcher {
+    /// Watch the file at `path`, which doesn't have to exist yet. Its directory is watched
+    /// rather than the file, as editors replace the file when saving
+    pub fn new(ctx: &egui::Context, path: PathBuf) -> anyhow::Result<Self> {
+        let directory = path
+            .parent()
+            .ok_or_else(|| anyhow::anyhow!("{} has no directory", path.display()))?
+            .to_path_buf();
+
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let file_name = path.file_name().map(ToOwned::to_owned);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let touches_file = event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref());
+                if touches_file
+                    && matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_)
+                    )
+                {
+                    let _ = sender.send(Instant::now());
+                    ctx.request_repaint_after(DEBOUNCE);
+                }
+            })?;
+        watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            path,
+            _watcher: watcher,
+            receiver,
+            changed_at: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file changed and has been quiet for long enough to read it again
+    pub fn poll(&mut self, ctx: &egui::Context) -> bool {
+        while let Ok(changed_at) = self.receiver.try_recv() {
+            self.changed_at = Some(changed_at);
+        }
+
+        match self.changed_at {
+            Some(changed_at) if changed_at.elapsed() >= DEBOUNCE => {
+                self.changed_at = None;
+                // Removing the file leaves the configuration as it is
+                self.path.exists()
+            }
+            Some(changed_at) => {
+                ctx.request_repaint_after(DEBOUNCE - changed_at.elapsed());
+                false
+            }
+            None => false,
+        }
+    }
+}
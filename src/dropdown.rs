@@ -0,0 +1,341 @@
+//! The drop-down mode (`--dropdown`): the root window starts hidden and slides down from the
+//! top of the screen when summoned, and back up when summoned again or when it loses the
+//! focus. Summoning is left to the desktop, which binds a global shortcut to
+//! `explotty --toggle`; that writes to a socket in `$XDG_RUNTIME_DIR` the running instance
+//! listens on. The tabs keep running while the window is hidden.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+
+use crate::config;
+
+/// How long sliding in or out takes
+const SLIDE_DURATION: Duration = Duration::from_millis(150);
+
+/// How long a client gets to send its command
+const READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What a client asks of the drop-down window, one per line on the socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Toggle,
+    Show,
+    Hide,
+}
+
+impl Command {
+    fn parse(line: &str) -> Option<Self> {
+        match line.trim() {
+            "toggle" => Some(Self::Toggle),
+            "show" => Some(Self::Show),
+            "hide" => Some(Self::Hide),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Toggle => "toggle",
+            Self::Show => "show",
+            Self::Hide => "hide",
+        }
+    }
+}
+
+/// `explotty-dropdown.sock` in `$XDG_RUNTIME_DIR`, or without it in `explotty-<uid>` in the
+/// temporary directory, which only the user may enter
+pub fn socket_path() -> anyhow::Result<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+    {
+        Some(runtime_dir) => Ok(runtime_dir.join("explotty-dropdown.sock")),
+        None => {
+            Ok(private_dir(&std::env::temp_dir(), current_uid())?.join("explotty-dropdown.sock"))
+        }
+    }
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid can't fail and has no preconditions
+    unsafe { libc::getuid() }
+}
+
+/// `explotty-<uid>` in `parent`, created with mode 0700 if needed. The temporary directory is
+/// shared, so one that is there already is only used if it is a directory of `uid` that no one
+/// else can enter
+fn private_dir(parent: &Path, uid: u32) -> anyhow::Result<PathBuf> {
+    let dir = parent.join(format!("explotty-{uid}"));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => anyhow::bail!("failed to create {}: {e}", dir.display()),
+    }
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        anyhow::bail!(
+            "{} isn't a directory only this user can enter",
+            dir.display()
+        );
+    }
+    Ok(dir)
+}
+
+/// Fail if the file at `socket` belongs to another user, who could listen there or have it
+/// removed
+fn check_owner(socket: &Path) -> anyhow::Result<()> {
+    match fs::symlink_metadata(socket) {
+        Ok(metadata) if metadata.uid() != current_uid() => {
+            anyhow::bail!("{} belongs to another user", socket.display())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Hand `command` to the drop-down instance that is running
+pub fn send(command: Command) -> anyhow::Result<()> {
+    let path = socket_path()?;
+    check_owner(&path)?;
+    let mut stream = UnixStream::connect(&path).map_err(|e| {
+        anyhow::anyhow!(
+            "no drop-down explotty is listening on {}: {e}",
+            path.display()
+        )
+    })?;
+    writeln!(stream, "{}", command.name())?;
+    Ok(())
+}
+
+/// The root window in drop-down mode, with the listener for commands
+pub struct Dropdown {
+    socket: PathBuf,
+    receiver: mpsc::Receiver<Command>,
+    /// Whether the window is shown, or sliding in
+    shown: bool,
+    /// When the window started sliding in or out
+    slide: Option<Instant>,
+    /// Whether the window had the focus in the last frame
+    focused: bool,
+}
+
+impl Dropdown {
+    /// Listen for commands on the socket, which fails when another instance listens there
+    pub fn new(ctx: &egui::Context) -> anyhow::Result<Self> {
+        let socket = socket_path()?;
+        check_owner(&socket)?;
+        let listener = match UnixListener::bind(&socket) {
+            Ok(listener) => listener,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                if UnixStream::connect(&socket).is_ok() {
+                    anyhow::bail!("another explotty listens on {}", socket.display());
+                }
+                // Left behind by an instance that didn't exit cleanly
+                std::fs::remove_file(&socket)?;
+                UnixListener::bind(&socket)?
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "failed to listen on {}: {e}",
+                    socket.display()
+                ));
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::Builder::new()
+            .name("dropdown-listener".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+                    for line in BufReader::new(stream).lines() {
+                        let Ok(line) = line else {
+                            break;
+                        };
+                        let Some(command) = Command::parse(&line) else {
+                            debug!("Ignoring unknown drop-down command {line:?}");
+                            continue;
+                        };
+                        if sender.send(command).is_err() {
+                            return;
+                        }
+                        ctx.request_repaint();
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            socket,
+            receiver,
+            shown: false,
+            slide: None,
+            focused: false,
+        })
+    }
+
+    /// Carry out the commands that arrived and slide the window. With `keep_shown` (e.g.
+    /// while the Settings window has the focus) losing the focus doesn't hide it
+    pub fn update(&mut self, ctx: &egui::Context, settings: &config::Dropdown, keep_shown: bool) {
+        while let Ok(command) = self.receiver.try_recv() {
+            let shown = match command {
+                Command::Toggle => !self.shown,
+                Command::Show => true,
+                Command::Hide => false,
+            };
+            self.set_shown(ctx, shown);
+        }
+
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(false));
+        if settings.hide_on_focus_loss
+            && self.shown
+            && self.focused
+            && !focused
+            && !keep_shown
+            && self.slide.is_none()
+        {
+            self.set_shown(ctx, false);
+        }
+        self.focused = focused;
+
+        self.slide(ctx, settings);
+    }
+
+    fn set_shown(&mut self, ctx: &egui::Context, shown: bool) {
+        if shown == self.shown {
+            return;
+        }
+        self.shown = shown;
+        self.slide = Some(Instant::now());
+        if shown {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+        }
+    }
+
+    /// Move the window a step further in or out, hiding it at the end of sliding out
+    fn slide(&mut self, ctx: &egui::Context, settings: &config::Dropdown) {
+        let Some(started) = self.slide else {
+            return;
+        };
+        let progress = (started.elapsed().as_secs_f32() / SLIDE_DURATION.as_secs_f32()).min(1.0);
+
+        // Without the monitor's size the window only appears and disappears
+        if let Some(monitor) = ctx.input(|i| i.viewport().monitor_size) {
+            let size = egui::vec2(
+                monitor.x * settings.width / 100.0,
+                monitor.y * settings.height / 100.0,
+            );
+            let visible = match self.shown {
+                true => progress,
+                false => 1.0 - progress,
+            };
+            // Easing out, fast at first and slowing down at the end
+            let visible = 1.0 - (1.0 - visible).powi(2);
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+            ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(egui::pos2(
+                (monitor.x - size.x) / 2.0,
+                -size.y * (1.0 - visible),
+            )));
+        }
+
+        if progress < 1.0 {
+            ctx.request_repaint();
+            return;
+        }
+        self.slide = None;
+        if !self.shown {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+        }
+    }
+}
+
+impl Drop for Dropdown {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    /// A fresh directory for one test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("explotty-dropdown-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn the_private_directory_is_created_for_the_user_alone() {
+        let parent = TempDir::new("create");
+        let uid = current_uid();
+        let dir = private_dir(&parent.0, uid).unwrap();
+        let metadata = fs::metadata(&dir).unwrap();
+        assert_eq!(metadata.mode() & 0o777, 0o700);
+        assert_eq!(metadata.uid(), uid);
+        // And reused
+        assert_eq!(private_dir(&parent.0, uid).unwrap(), dir);
+    }
+
+    #[test]
+    fn directories_others_can_enter_or_own_are_refused() {
+        let parent = TempDir::new("refuse");
+        let uid = current_uid();
+        let dir = parent.0.join(format!("explotty-{uid}"));
+
+        fs::create_dir(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(private_dir(&parent.0, uid).is_err());
+
+        // Someone else's
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).unwrap();
+        assert!(private_dir(&parent.0, uid + 1).is_err());
+
+        // A link to a directory elsewhere
+        fs::remove_dir(&dir).unwrap();
+        let elsewhere = parent.0.join("elsewhere");
+        fs::create_dir(&elsewhere).unwrap();
+        fs::set_permissions(&elsewhere, fs::Permissions::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, &dir).unwrap();
+        assert!(private_dir(&parent.0, uid).is_err());
+    }
+
+    #[test]
+    fn sockets_of_this_user_pass_the_owner_check() {
+        let dir = TempDir::new("owner");
+        let socket = dir.0.join("socket");
+        // Nothing there yet
+        check_owner(&socket).unwrap();
+        let _listener = UnixListener::bind(&socket).unwrap();
+        check_owner(&socket).unwrap();
+    }
+}
@@ -1,20 +1,60 @@
+mod archive_peek;
+pub mod columns;
+mod disk_usage;
+mod file_types;
+mod git_status;
+mod listing;
+mod open_with;
+mod places;
+mod properties;
+mod search;
+mod size_scan;
+mod watcher;
+
 use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
     fs,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use chrono::{DateTime, Local};
 use eframe::egui::{self, RichText};
 use egui_extras::{Size, StripBuilder};
 
-use crate::utils::{
-    get_desc_from_mime_type, get_formatted_icon_path, get_mime_type_from_path,
-    to_human_readable_size,
+use crate::{
+    CONFIG,
+    archive::ArchiveKind,
+    config::OpenDirectory,
+    explorer_widget::{
+        archive_peek::ArchivePeek,
+        columns::Column,
+        disk_usage::DiskUsage,
+        file_types::{FileTypes, Resolved},
+        git_status::{GitStatus, GitStatuses},
+        listing::{Listing, Update},
+        open_with::OpenWith,
+        places::Places,
+        properties::PropertiesDialog,
+        search::{Search, SearchAction},
+        watcher::DirectoryWatcher,
+    },
+    file_operation::{ConflictChoice, FileOperation, OperationKind, unique_name},
+    keybindings::ExplorerAction,
+    terminal_session::TerminalSession,
+    toast::Toasts,
+    utils::{to_display_path, to_human_readable_size},
 };
 
-const COLUMN_SIZES: [f32; 4] = [100.0, 80.0, 80.0, 120.0];
 const HEADER_HEIGHT: f32 = 28.0;
 const ROW_HEIGHT: f32 = 24.0;
+/// How long a refresh may take before its progress is shown
+const LOADING_DELAY: Duration = Duration::from_millis(300);
+/// How often the shell's working directory is looked up
+const SHELL_DIRECTORY_INTERVAL: Duration = Duration::from_millis(250);
+/// Pause after which typing starts a new type-ahead search
+const TYPE_AHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long a revealed row stays lit up
+const FLASH_DURATION: Duration = Duration::from_millis(800);
 
 /// The main widget for exploring files and directories
 pub struct ExplorerWidget {
@@ -22,8 +62,151 @@ pub struct ExplorerWidget {
     files: Vec<FileItem>,
     /// The current directory being explored
     current_directory: Option<PathBuf>,
-    /// The index of the currently selected file or directory
-    selected_index: Option<usize>,
+    /// The shell's working directory when it was last looked at
+    shell_directory: Option<PathBuf>,
+    /// When the shell's working directory was last looked up
+    shell_directory_checked_at: Option<Instant>,
+    /// The explorer was navigated away from the shell's directory and stopped following it
+    detached: bool,
+    /// Whether the explorer shows the shell's directory when it changes ("Follow terminal")
+    follow_terminal: bool,
+    /// Tells the panels of this explorer apart from those of the other pane
+    id: egui::Id,
+    /// Where the explorer was drawn last
+    rect: egui::Rect,
+    /// The directory of the other pane, while the explorer is split
+    other_pane: Option<PathBuf>,
+    /// The split button was clicked, taken by the window with `take_split_toggle`
+    split_toggled: bool,
+    /// Text and error of the editable path bar, `None` while the breadcrumbs are shown
+    path_edit: Option<(String, Option<String>)>,
+    /// Indices of the selected files and directories
+    selected: BTreeSet<usize>,
+    /// The row selected last by clicking or the keyboard, where Shift+click ranges start
+    anchor: Option<usize>,
+    /// Commands to run in the terminal, taken by the window with `take_commands`
+    commands: Vec<TerminalCommand>,
+    /// Paths to type at the prompt, taken by the window with `take_typed_paths`
+    typed_paths: Vec<PathBuf>,
+    /// The row being renamed inline
+    renaming: Option<Rename>,
+    /// Whether keys go to the explorer instead of the terminal, since a row was clicked
+    focused: bool,
+    /// An action picked in the command palette, done like a key press in the next frame
+    requested_action: Option<ExplorerAction>,
+    /// The row of a file clicked in a listing in the terminal, lit up for a moment
+    flash: Option<(String, Instant)>,
+    /// Names waiting for the confirmation of a permanent delete
+    pending_delete: Option<Vec<String>>,
+    properties: Option<PropertiesDialog>,
+    archive_peek: Option<ArchivePeek>,
+    /// The recursive search shown instead of the listing
+    search: Option<Search>,
+    /// The disk usage view shown instead of the listing
+    disk_usage: Option<DiskUsage>,
+    open_with: OpenWith,
+    /// Errors of file operations
+    toasts: Toasts,
+    /// Items marked with Copy or Cut, for Paste
+    clipboard: Option<(Vec<PathBuf>, OperationKind)>,
+    /// The running paste
+    operation: Option<FileOperation>,
+    /// Characters typed to jump to a file, and when the last one was typed
+    type_ahead: (String, Instant),
+    /// Text of the filter box, `None` while it is closed
+    filter: Option<String>,
+    /// The filter box should take the focus
+    focus_filter: bool,
+    /// The filter box had the focus in the last frame
+    filter_has_focus: bool,
+    /// Scroll the selected row into view in the next frame
+    scroll_to_selected: bool,
+    /// Whether dotfiles are listed
+    show_hidden: bool,
+    /// The columns of the list, in their order
+    columns: Vec<Column>,
+    /// Widths of the columns that were resized, the others have their default width
+    column_widths: BTreeMap<Column, f32>,
+    /// Available and total bytes of the current directory's filesystem, as of the last listing
+    disk_space: Option<(u64, u64)>,
+    /// How many rows fit into the list, for PageUp/PageDown
+    page_rows: usize,
+    /// Refreshes the listing when the directory changes
+    watcher: DirectoryWatcher,
+    /// Fills in the types and icons of the listed files
+    file_types: FileTypes,
+    /// What git says about the listed items
+    git_status: GitStatuses,
+    /// The directory being read
+    loading: Option<Loading>,
+    /// The directory that couldn't be read and why, until a listing succeeds
+    listing_error: Option<(PathBuf, String)>,
+    ctx: egui::Context,
+    places: Places,
+    /// Whether the places sidebar is shown
+    show_places: bool,
+}
+
+/// State of an inline rename
+struct Rename {
+    /// Index of the row in `files`
+    index: usize,
+    /// The name being edited
+    text: String,
+    /// Why the last attempt failed, shown under the row
+    error: Option<String>,
+    /// Set until the text field got focus and its initial selection
+    just_started: bool,
+    /// The row is a provisional one for an item that is created on commit
+    new_item: Option<NewItem>,
+}
+
+/// A directory being read into `files`
+struct Loading {
+    listing: Listing,
+    /// Whether items are shown as they arrive, or `held` until the listing is complete
+    streamed: bool,
+    held: Vec<FileItem>,
+    after_load: Option<AfterLoad>,
+    /// What was shown before changing directories, shown again if the new one can't be read
+    previous: Option<Previous>,
+}
+
+/// The listing of the directory left for the one being read
+struct Previous {
+    directory: PathBuf,
+    files: Vec<FileItem>,
+    disk_space: Option<(u64, u64)>,
+}
+
+/// What to select once a refresh is done
+enum AfterLoad {
+    /// The item with this name
+    Select(String),
+    /// The item at this index, if nothing stayed selected
+    SelectNear(usize),
+}
+
+/// Kind of item created from the explorer
+#[derive(Clone, Copy)]
+enum NewItem {
+    File,
+    Folder,
+}
+
+impl NewItem {
+    fn default_name(self) -> &'static str {
+        match self {
+            NewItem::File => "New File",
+            NewItem::Folder => "New Folder",
+        }
+    }
+}
+
+/// A command line the explorer wants to run in the terminal, in `cwd`
+pub struct TerminalCommand {
+    pub command: String,
+    pub cwd: PathBuf,
 }
 
 /// This structure containing file information to be displayed in the UI
@@ -32,7 +215,9 @@ struct FileItem {
     name: String,
     /// The size of the file or directory. Human readable format
     size: String,
-    /// The type description of the file or directory
+    /// The size of a file in bytes
+    bytes: Option<u64>,
+    /// The type description of the file or directory, empty until it is looked up
     file_type: String,
     /// The last modified date and time of the file or directory
     modified_at: String,
@@ -40,133 +225,1938 @@ struct FileItem {
     is_directory: bool,
     /// Whether the item is hidden (starts with a dot)
     is_hidden: bool,
-    /// The URI path to the icon (starts with file:///)
-    icon_path: String,
+    /// Whether the item is a file that can be run: executable or a script
+    is_executable: bool,
+    /// Whether the item is a symbolic link. The other fields describe its target
+    is_symlink: bool,
+    /// Where the link points, as written in it
+    link_target: Option<PathBuf>,
+    /// Whether the item is a link whose target doesn't exist
+    is_broken_link: bool,
+    /// Type and mode like `ls -l` shows them, e.g. `drwxr-xr-x`
+    permissions: String,
+    /// Names of the owning user and group, `user:group`
+    owner: String,
+    /// Why its metadata couldn't be read
+    error: Option<String>,
+    /// A themed icon, or a bundled one while the type is looked up
+    icon: egui::ImageSource<'static>,
 }
 
 impl ExplorerWidget {
-    pub fn new() -> Self {
+    pub fn new(ctx: &egui::Context, id: egui::Id) -> Self {
         Self {
+            id,
+            rect: egui::Rect::NOTHING,
+            other_pane: None,
+            split_toggled: false,
             files: Vec::new(),
             current_directory: None,
-            selected_index: None,
+            shell_directory: None,
+            shell_directory_checked_at: None,
+            detached: false,
+            follow_terminal: true,
+            path_edit: None,
+            selected: BTreeSet::new(),
+            anchor: None,
+            commands: Vec::new(),
+            typed_paths: Vec::new(),
+            renaming: None,
+            focused: false,
+            requested_action: None,
+            flash: None,
+            pending_delete: None,
+            properties: None,
+            archive_peek: None,
+            search: None,
+            disk_usage: None,
+            open_with: OpenWith::default(),
+            toasts: Toasts::default(),
+            clipboard: None,
+            operation: None,
+            type_ahead: (String::new(), Instant::now()),
+            filter: None,
+            focus_filter: false,
+            filter_has_focus: false,
+            scroll_to_selected: false,
+            show_hidden: true,
+            disk_space: None,
+            columns: CONFIG
+                .get()
+                .and_then(|config| config.explorer_columns.clone())
+                .map_or_else(Column::defaults, |columns| Column::ordered(&columns)),
+            column_widths: BTreeMap::new(),
+            page_rows: 1,
+            watcher: DirectoryWatcher::new(ctx),
+            file_types: FileTypes::new(ctx),
+            git_status: GitStatuses::default(),
+            loading: None,
+            listing_error: None,
+            ctx: ctx.clone(),
+            places: Places::new(),
+            show_places: true,
+        }
+    }
+
+    /// Give the keyboard back to the terminal
+    pub fn release_keyboard(&mut self) {
+        self.focused = false;
+    }
+
+    /// Give the keyboard to the explorer, like clicking it does
+    pub fn focus(&mut self) {
+        self.focused = true;
+    }
+
+    /// Give the keyboard to the explorer and do `action` in the next frame, as if its chord was
+    /// pressed
+    pub fn request_action(&mut self, action: ExplorerAction) {
+        self.focused = true;
+        self.requested_action = Some(action);
+    }
+
+    /// Light up the row of `path` for a moment, e.g. after revealing it
+    pub fn flash(&mut self, path: &Path) {
+        if let Some(name) = path.file_name() {
+            self.flash = Some((name.to_string_lossy().to_string(), Instant::now()));
+        }
+    }
+
+    /// Stop watching the directory while the explorer is hidden. Not being shown, it doesn't
+    /// look up the shell's directory either
+    pub fn suspend(&mut self) {
+        self.watcher.watch(self.current_directory.as_deref(), None);
+        self.focused = false;
+    }
+
+    /// Watch the directory again after `suspend`, catching up with the shell's directory and
+    /// the changes missed meanwhile
+    pub fn resume(&mut self) {
+        self.watcher.watch(None, self.current_directory.as_deref());
+        self.shell_directory_checked_at = None;
+        self.refresh();
+    }
+
+    pub fn current_directory(&self) -> Option<&Path> {
+        self.current_directory.as_deref()
+    }
+
+    /// Where the explorer was drawn in the last frame
+    pub fn rect(&self) -> egui::Rect {
+        self.rect
+    }
+
+    pub fn set_follow_terminal(&mut self, follow_terminal: bool) {
+        self.follow_terminal = follow_terminal;
+    }
+
+    /// The directory of the other pane, which copies and moves can go to. `None` while the
+    /// explorer isn't split
+    pub fn set_other_pane(&mut self, directory: Option<PathBuf>) {
+        self.other_pane = directory;
+    }
+
+    /// Whether the split button was clicked since the last call
+    pub fn take_split_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.split_toggled)
+    }
+
+    /// Whether the explorer has the keyboard, because it is focused or editing text
+    pub fn wants_keyboard(&self) -> bool {
+        self.focused
+            || self.filter_has_focus
+            || self.path_edit.is_some()
+            || self.renaming.is_some()
+            || self.pending_delete.is_some()
+            || self.properties.is_some()
+            || self.archive_peek.is_some()
+            || self.open_with.is_choosing()
+    }
+
+    /// The columns shown, in their order
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Show these columns, in their usual order. The name is always shown
+    pub fn set_columns(&mut self, columns: &[Column]) {
+        self.columns = Column::ordered(columns);
+    }
+
+    /// Widths of the columns that were resized
+    pub fn column_widths(&self) -> &BTreeMap<Column, f32> {
+        &self.column_widths
+    }
+
+    pub fn set_column_widths(&mut self, widths: &BTreeMap<Column, f32>) {
+        self.column_widths = widths
+            .iter()
+            .filter(|(column, _)| **column != Column::Name)
+            .map(|(&column, &width)| (column, width.max(Column::MIN_WIDTH)))
+            .collect();
+    }
+
+    fn column_width(&self, column: Column) -> f32 {
+        self.column_widths
+            .get(&column)
+            .copied()
+            .unwrap_or_else(|| column.width())
+    }
+
+    /// Move the boundary after the `boundary`th column by `delta`. The columns on both sides
+    /// change, the name column through the space it is left with
+    fn resize_column(&mut self, boundary: usize, delta: f32, name_width: f32) {
+        let (Some(&left), Some(&right)) =
+            (self.columns.get(boundary), self.columns.get(boundary + 1))
+        else {
+            return;
+        };
+        let right_width = self.column_width(right);
+        if left == Column::Name {
+            let widest = right_width + (name_width - Column::Name.width()).max(0.0);
+            let width = (right_width - delta).min(widest).max(Column::MIN_WIDTH);
+            self.column_widths.insert(right, width);
+        } else {
+            let left_width = self.column_width(left);
+            let delta = delta
+                .min(right_width - Column::MIN_WIDTH)
+                .max(Column::MIN_WIDTH - left_width);
+            self.column_widths.insert(left, left_width + delta);
+            self.column_widths.insert(right, right_width - delta);
+        }
+    }
+
+    /// Make `column` as wide as its title and the widest text of the listed rows
+    fn fit_column(&mut self, ui: &egui::Ui, column: Column, visible_indices: &[usize]) {
+        let font_id = egui::TextStyle::Body.resolve(ui.style());
+        let widest = ui.fonts(|fonts| {
+            visible_indices
+                .iter()
+                .map(|&index| column.text(&self.files[index]))
+                .chain([column.title()])
+                .map(|text| {
+                    fonts
+                        .layout_no_wrap(text.to_string(), font_id.clone(), egui::Color32::WHITE)
+                        .size()
+                        .x
+                })
+                .fold(0.0, f32::max)
+        });
+        let padding = ui.spacing().item_spacing.x * 2.0;
+        self.column_widths
+            .insert(column, (widest + padding).max(Column::MIN_WIDTH));
+    }
+
+    fn toggle_column(&mut self, column: Column) {
+        let mut columns = self.columns.clone();
+        if let Some(position) = columns.iter().position(|shown| *shown == column) {
+            columns.remove(position);
+        } else {
+            columns.push(column);
+        }
+        self.set_columns(&columns);
+    }
+
+    pub fn show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    /// Show or hide dotfiles. Selected dotfiles that disappear are unselected; if nothing
+    /// stays selected, the next row still shown is
+    pub fn set_show_hidden(&mut self, show_hidden: bool) {
+        self.show_hidden = show_hidden;
+
+        let visible = self.visible_indices();
+        let had_selection = !self.selected.is_empty();
+        self.selected.retain(|index| visible.contains(index));
+        if had_selection && self.selected.is_empty() {
+            let anchor = self.anchor.unwrap_or_default();
+            if let Some(&index) = visible
+                .iter()
+                .find(|&&index| index > anchor)
+                .or(visible.last())
+            {
+                self.select_only(index);
+            }
+        }
+        if self.anchor.is_some_and(|anchor| !visible.contains(&anchor)) {
+            self.anchor = self.selected.first().copied();
+        }
+        self.scroll_to_selected = true;
+    }
+
+    /// Select the single row at `index`
+    fn select_only(&mut self, index: usize) {
+        self.selected = BTreeSet::from([index]);
+        self.anchor = Some(index);
+    }
+
+    /// The row actions on a single item apply to: the anchor, or the first selected row
+    fn primary_selection(&self) -> Option<usize> {
+        self.anchor
+            .filter(|anchor| self.selected.contains(anchor))
+            .or_else(|| self.selected.first().copied())
+    }
+
+    /// Update the selection for a click on the row at `index`: Ctrl toggles it, Shift selects
+    /// the range from the anchor in the order shown
+    fn click_row(&mut self, index: usize, modifiers: egui::Modifiers) {
+        if modifiers.shift
+            && let Some(anchor) = self.anchor
+        {
+            let visible = self.visible_indices();
+            let (Some(from), Some(to)) = (
+                visible.iter().position(|&i| i == anchor),
+                visible.iter().position(|&i| i == index),
+            ) else {
+                self.select_only(index);
+                return;
+            };
+            if !modifiers.command {
+                self.selected.clear();
+            }
+            self.selected.extend(&visible[from.min(to)..=from.max(to)]);
+        } else if modifiers.command {
+            if !self.selected.remove(&index) {
+                self.selected.insert(index);
+            }
+            self.anchor = Some(index);
+        } else {
+            self.select_only(index);
+        }
+    }
+
+    /// Arrows, Home/End and PageUp/PageDown move the selection (Shift adds to it),
+    /// Backspace and the `parent` shortcut go to the parent directory, Tab gives the keyboard
+    /// back to the terminal
+    fn handle_navigation_keys(&mut self, ui: &egui::Ui, session: &TerminalSession, parent: bool) {
+        let visible = self.visible_indices();
+        let current = self
+            .anchor
+            .and_then(|anchor| visible.iter().position(|&index| index == anchor));
+        let last = visible.len().saturating_sub(1);
+
+        let mut target = None;
+        let mut extend = false;
+        let mut parent = parent;
+        let mut leave = false;
+        ui.input_mut(|i| {
+            parent |= i.consume_key(egui::Modifiers::NONE, egui::Key::Backspace);
+            for modifiers in [egui::Modifiers::SHIFT, egui::Modifiers::NONE] {
+                let moves = [
+                    (
+                        egui::Key::ArrowUp,
+                        current.map_or(last, |pos| pos.saturating_sub(1)),
+                    ),
+                    (
+                        egui::Key::ArrowDown,
+                        current.map_or(0, |pos| (pos + 1).min(last)),
+                    ),
+                    (egui::Key::Home, 0),
+                    (egui::Key::End, last),
+                    (
+                        egui::Key::PageUp,
+                        current.map_or(0, |pos| pos.saturating_sub(self.page_rows)),
+                    ),
+                    (
+                        egui::Key::PageDown,
+                        current.map_or(0, |pos| (pos + self.page_rows).min(last)),
+                    ),
+                ];
+                for (key, pos) in moves {
+                    if i.consume_key(modifiers, key) {
+                        target = Some(pos);
+                        extend = modifiers.shift;
+                    }
+                }
+            }
+            leave = i.consume_key(egui::Modifiers::NONE, egui::Key::Tab);
+        });
+
+        if let Some(&index) = target.and_then(|pos| visible.get(pos)) {
+            if extend {
+                self.selected.insert(index);
+                self.anchor = Some(index);
+            } else {
+                self.select_only(index);
+            }
+            self.scroll_to_selected = true;
+        }
+        if parent && let Some(index) = self.files.iter().position(|file| file.name == "..") {
+            self.open(index, session);
+        }
+        if leave {
+            self.focused = false;
+        }
+    }
+
+    /// Select every row shown except ".."
+    fn select_all(&mut self) {
+        self.selected = self
+            .visible_indices()
+            .into_iter()
+            .filter(|&index| self.files[index].name != "..")
+            .collect();
+    }
+
+    /// Indices of the rows shown: the ones matching the filter, without dotfiles if they are
+    /// hidden, and the one being edited
+    fn visible_indices(&self) -> Vec<usize> {
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|filter| filter.to_lowercase())
+            .filter(|filter| !filter.is_empty());
+
+        self.files
+            .iter()
+            .enumerate()
+            .filter(|(index, file)| {
+                let shown = (self.show_hidden || !file.is_hidden)
+                    && filter.as_ref().is_none_or(|filter| {
+                        file.name != ".." && file.name.to_lowercase().contains(filter)
+                    });
+                shown
+                    || self
+                        .renaming
+                        .as_ref()
+                        .is_some_and(|rename| rename.index == *index)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Jump to the first visible row starting with the characters typed in a quick succession
+    fn type_ahead(&mut self, text: &str) {
+        let (typed, last_typed) = &mut self.type_ahead;
+        if last_typed.elapsed() > TYPE_AHEAD_TIMEOUT {
+            typed.clear();
+        }
+        typed.push_str(&text.to_lowercase());
+        *last_typed = Instant::now();
+
+        let typed = typed.clone();
+        if let Some(index) = self
+            .visible_indices()
+            .into_iter()
+            .find(|&index| self.files[index].name.to_lowercase().starts_with(&typed))
+        {
+            self.select_only(index);
+            self.scroll_to_selected = true;
+        }
+    }
+
+    /// The filter box above the header, opened with Ctrl+F
+    fn show_filter_bar(&mut self, ui: &mut egui::Ui) {
+        let match_count = self
+            .visible_indices()
+            .iter()
+            .filter(|&&index| self.files[index].name != "..")
+            .count();
+        let Some(filter) = &mut self.filter else {
+            self.filter_has_focus = false;
+            return;
+        };
+
+        let mut close = false;
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            let response = ui.add(
+                egui::TextEdit::singleline(filter)
+                    .hint_text("Part of the name")
+                    .desired_width(200.0),
+            );
+            if std::mem::take(&mut self.focus_filter) {
+                response.request_focus();
+            }
+            ui.label(format!(
+                "{match_count} match{}",
+                if match_count == 1 { "" } else { "es" }
+            ));
+            if ui.button("✖").on_hover_text("Clear the filter").clicked() {
+                close = true;
+            }
+
+            self.filter_has_focus = response.has_focus();
+            if (response.has_focus() || response.lost_focus())
+                && ui.input(|i| i.key_pressed(egui::Key::Escape))
+            {
+                close = true;
+            }
+        });
+
+        if close {
+            self.filter = None;
+            self.filter_has_focus = false;
+            self.focused = true;
+            self.scroll_to_selected = true;
+        }
+    }
+
+    fn start_rename(&mut self, index: usize) {
+        let Some(file) = self.files.get(index) else {
+            return;
+        };
+        // The parent directory entry can't be renamed
+        if file.name == ".." {
+            return;
         }
+
+        self.renaming = Some(Rename {
+            index,
+            text: file.name.clone(),
+            error: None,
+            just_started: true,
+            new_item: None,
+        });
+    }
+
+    /// Insert a provisional row for a new item and start editing its name
+    fn start_new_item(&mut self, kind: NewItem) {
+        let Some(current_dir) = &self.current_directory else {
+            return;
+        };
+
+        let name = unique_name(current_dir, kind.default_name());
+        let (file_type, mime_type) = match kind {
+            NewItem::File => ("Empty file", "text/plain"),
+            NewItem::Folder => ("Directory", "inode/directory"),
+        };
+        let icon = self.file_types.icon(mime_type);
+        // Right below "..", where it is visible without scrolling
+        let index = usize::from(self.files.first().is_some_and(|file| file.name == ".."));
+        self.files.insert(
+            index,
+            FileItem {
+                name: name.clone(),
+                size: "--".to_string(),
+                bytes: None,
+                file_type: file_type.to_string(),
+                modified_at: "--".to_string(),
+                is_directory: matches!(kind, NewItem::Folder),
+                is_hidden: false,
+                is_executable: false,
+                is_symlink: false,
+                link_target: None,
+                is_broken_link: false,
+                permissions: "--".to_string(),
+                owner: "--".to_string(),
+                error: None,
+                icon,
+            },
+        );
+        self.select_only(index);
+        self.renaming = Some(Rename {
+            index,
+            text: name,
+            error: None,
+            just_started: true,
+            new_item: Some(kind),
+        });
     }
 
-    pub fn show(&mut self, ui: &mut egui::Ui, pid: Option<u32>) {
-        let new_directory = crate::utils::get_current_dir_from_pty(pid.unwrap_or(0));
-        if new_directory != self.current_directory {
-            self.current_directory = new_directory;
-            if let Err(e) = self.refresh_files() {
-                ui.label(format!("Error refreshing files: {e}"));
+    fn cancel_rename(&mut self) {
+        if let Some(rename) = self.renaming.take()
+            && rename.new_item.is_some()
+        {
+            self.files.remove(rename.index);
+            self.selected.clear();
+            self.anchor = None;
+        }
+    }
+
+    /// Rename the file to the edited name. On failure the rename stays open with the error.
+    fn commit_rename(&mut self) {
+        let Some(rename) = &mut self.renaming else {
+            return;
+        };
+        let (Some(file), Some(current_dir)) =
+            (self.files.get(rename.index), &self.current_directory)
+        else {
+            self.renaming = None;
+            return;
+        };
+
+        let mut new_name = rename.text.trim().to_string();
+        if new_name == file.name && rename.new_item.is_none() {
+            self.renaming = None;
+            return;
+        }
+        if new_name.is_empty() || new_name == "." || new_name == ".." || new_name.contains('/') {
+            rename.error = Some(format!("\"{new_name}\" is not a valid name"));
+            return;
+        }
+
+        let result = match rename.new_item {
+            Some(kind) => {
+                // New items never replace anything, they get a numbered name instead
+                new_name = unique_name(current_dir, &new_name);
+                let target = current_dir.join(&new_name);
+                match kind {
+                    NewItem::File => fs::File::create_new(&target).map(|_| ()),
+                    NewItem::Folder => fs::create_dir(&target),
+                }
+                .map_err(|e| format!("Failed to create \"{new_name}\": {e}"))
+            }
+            None => {
+                let target = current_dir.join(&new_name);
+                // symlink_metadata so that a dangling symlink counts as existing too
+                if target.symlink_metadata().is_ok() {
+                    Err(format!("\"{new_name}\" already exists"))
+                } else {
+                    fs::rename(current_dir.join(&file.name), &target)
+                        .map_err(|e| format!("Failed to rename: {e}"))
+                }
             }
+        };
+        if let Err(e) = result {
+            rename.error = Some(e);
+            return;
+        }
+
+        self.renaming = None;
+        // Select the renamed or created item at its new place
+        self.start_listing(false, Some(AfterLoad::Select(new_name)));
+    }
+
+    /// Names of the selected items that can be deleted
+    fn selected_names(&self) -> Vec<String> {
+        self.selected
+            .iter()
+            .filter_map(|&index| self.files.get(index))
+            .filter(|file| file.name != "..")
+            .map(|file| file.name.clone())
+            .collect()
+    }
+
+    /// Mark the selected items for a later paste, and put them on the system clipboard for
+    /// other file managers
+    fn mark_selected(&mut self, kind: OperationKind) {
+        let Some(current_dir) = &self.current_directory else {
+            return;
+        };
+        let paths: Vec<PathBuf> = self
+            .selected_names()
+            .iter()
+            .map(|name| current_dir.join(name))
+            .collect();
+        if !paths.is_empty() {
+            crate::utils::copy_file_uris_to_clipboard(&paths);
+            self.clipboard = Some((paths, kind));
+        }
+    }
+
+    /// Copy or move the marked items, or files copied in another application, into the current
+    /// directory
+    fn paste(&mut self, ctx: &egui::Context) {
+        // What is on the system clipboard is newer unless it is what we put there
+        let (sources, kind) = match (
+            crate::utils::read_file_uris_from_clipboard(),
+            &self.clipboard,
+        ) {
+            (Some(paths), Some((marked, kind))) if *marked == paths => (marked.clone(), *kind),
+            (Some(paths), _) => (paths, OperationKind::Copy),
+            (None, Some((marked, kind))) => (marked.clone(), *kind),
+            (None, None) => return,
+        };
+        // Cut items can only be moved once
+        if self.start_operation(ctx, kind, sources) && kind == OperationKind::Move {
+            self.clipboard = None;
+        }
+    }
+
+    /// Copy files dropped from other applications into the current directory
+    pub fn copy_here(&mut self, ctx: &egui::Context, paths: Vec<PathBuf>) {
+        self.start_operation(ctx, OperationKind::Copy, paths);
+    }
+
+    /// Start copying or moving `sources` into the current directory. Only one operation runs
+    /// at a time; returns whether it was started
+    fn start_operation(
+        &mut self,
+        ctx: &egui::Context,
+        kind: OperationKind,
+        sources: Vec<PathBuf>,
+    ) -> bool {
+        let Some(destination) = self.current_directory.clone() else {
+            return false;
+        };
+        self.start_operation_into(ctx, kind, sources, destination)
+    }
+
+    fn start_operation_into(
+        &mut self,
+        ctx: &egui::Context,
+        kind: OperationKind,
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+    ) -> bool {
+        if self.operation.is_some() {
+            self.toasts
+                .error("Wait for the running operation to finish");
+            return false;
+        }
+
+        self.operation = Some(FileOperation::start(ctx, kind, sources, destination));
+        true
+    }
+
+    /// Extract `archive` into the current directory, or into its subdirectory `directory`
+    fn start_extract(&mut self, ctx: &egui::Context, archive: PathBuf, directory: Option<String>) {
+        let Some(current_dir) = &self.current_directory else {
+            return;
+        };
+        if self.operation.is_some() {
+            self.toasts
+                .error("Wait for the running operation to finish");
+            return;
         }
 
-        ui.label(format!(
-            "Current Directory: {}",
-            self.current_directory
-                .clone()
-                .map_or("N/A".to_string(), |path| path.to_string_lossy().to_string())
+        let destination = match directory {
+            Some(directory) => current_dir.join(directory),
+            None => current_dir.clone(),
+        };
+        self.operation = Some(FileOperation::start(
+            ctx,
+            OperationKind::Extract,
+            vec![archive],
+            destination,
         ));
+    }
+
+    /// Item count, the selection's size and the free space, at the bottom of the pane
+    fn show_status_bar(&self, ui: &mut egui::Ui) {
+        let item_count = self
+            .visible_indices()
+            .iter()
+            .filter(|&&index| self.files[index].name != "..")
+            .count();
+        let selected: Vec<&FileItem> = self
+            .selected
+            .iter()
+            .filter_map(|&index| self.files.get(index))
+            .filter(|file| file.name != "..")
+            .collect();
+
+        egui::TopBottomPanel::bottom(self.id.with("status")).show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{item_count} item{}",
+                    if item_count == 1 { "" } else { "s" }
+                ));
+                if !selected.is_empty() {
+                    let bytes: u64 = selected.iter().filter_map(|file| file.bytes).sum();
+                    let mut summary = format!(
+                        "{} selected ({}",
+                        selected.len(),
+                        to_human_readable_size(bytes)
+                    );
+                    // Directories would have to be added up
+                    if selected.iter().any(|file| file.is_directory) {
+                        summary.push_str(" in files");
+                    }
+                    summary.push(')');
+                    ui.label(summary);
+                }
+
+                if let Some((available, total)) = self.disk_space {
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        ui.label(format!(
+                            "{} free of {}",
+                            to_human_readable_size(available),
+                            to_human_readable_size(total)
+                        ));
+                    });
+                }
+            });
+        });
+    }
+
+    /// The progress strip of a running paste at the bottom of the pane, and its conflict dialog
+    fn show_file_operation(&mut self, ui: &mut egui::Ui) {
+        let Some(operation) = &mut self.operation else {
+            return;
+        };
+
+        if let Some(errors) = operation.poll() {
+            self.operation = None;
+            for error in errors {
+                self.toasts.error(error);
+            }
+            self.refresh();
+            return;
+        }
+
+        egui::TopBottomPanel::bottom(self.id.with("file_operation")).show_inside(ui, |ui| {
+            let progress = operation.progress.clone();
+            ui.horizontal(|ui| {
+                ui.label(match operation.kind {
+                    OperationKind::Copy => {
+                        format!("Copying {}/{}", progress.files_done, progress.files_total)
+                    }
+                    OperationKind::Move => {
+                        format!("Moving {}/{}", progress.files_done, progress.files_total)
+                    }
+                    OperationKind::Extract => format!("Extracting {}", progress.files_done),
+                });
+                if ui.button("Cancel").clicked() {
+                    operation.cancel();
+                }
+                ui.add(
+                    egui::ProgressBar::new(if progress.bytes_total == 0 {
+                        0.0
+                    } else {
+                        progress.bytes_done as f32 / progress.bytes_total as f32
+                    })
+                    .text(format!(
+                        "{} / {}",
+                        to_human_readable_size(progress.bytes_done),
+                        to_human_readable_size(progress.bytes_total)
+                    )),
+                );
+            });
+        });
+
+        let Some(conflict) = operation.conflict.clone() else {
+            return;
+        };
+        let mut choice = None;
+        egui::Modal::new(egui::Id::new("file_operation_conflict")).show(ui.ctx(), |ui| {
+            ui.heading("Item already exists");
+            ui.label(format!(
+                "\"{}\" already exists in the destination.",
+                conflict.to_string_lossy()
+            ));
+            ui.checkbox(&mut operation.apply_to_all, "Apply to all");
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Skip").clicked() {
+                    choice = Some(ConflictChoice::Skip);
+                }
+                if ui.button("Overwrite").clicked() {
+                    choice = Some(ConflictChoice::Overwrite);
+                }
+                if ui.button("Rename").clicked() {
+                    choice = Some(ConflictChoice::Rename);
+                }
+                if ui.button("Cancel").clicked() {
+                    operation.cancel();
+                }
+            });
+        });
+        if let Some(choice) = choice {
+            operation.resolve_conflict(choice);
+        }
+    }
+
+    /// Move `names` to the trash, directories with everything in them
+    fn trash(&mut self, names: &[String]) {
+        let Some(current_dir) = self.current_directory.clone() else {
+            return;
+        };
+
+        for name in names {
+            let file = gio::File::for_path(current_dir.join(name));
+            if let Err(e) = gio::prelude::FileExt::trash(&file, None::<&gio::Cancellable>) {
+                self.toasts
+                    .error(format!("Failed to move \"{name}\" to the trash: {e}"));
+            }
+        }
+        self.refresh_after_delete();
+    }
+
+    fn delete_permanently(&mut self, names: &[String]) {
+        let Some(current_dir) = self.current_directory.clone() else {
+            return;
+        };
+
+        for name in names {
+            let path = current_dir.join(name);
+            // Symlinks to directories are removed themselves, not what they point to
+            let result = match path.symlink_metadata() {
+                Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&path),
+                Ok(_) => fs::remove_file(&path),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                self.toasts
+                    .error(format!("Failed to delete \"{name}\": {e}"));
+            }
+        }
+        self.refresh_after_delete();
+    }
+
+    /// Refresh the listing, selecting the row that took the place of the deleted ones
+    fn refresh_after_delete(&mut self) {
+        let first_selected = self.selected.first().copied();
+        self.start_listing(false, first_selected.map(AfterLoad::SelectNear));
+    }
+
+    fn show_delete_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(names) = &self.pending_delete else {
+            return;
+        };
+
+        let mut confirmed = None;
+        egui::Modal::new(egui::Id::new("delete_confirmation")).show(ctx, |ui| {
+            ui.heading("Delete permanently?");
+            ui.label("These items will be deleted and can't be restored:");
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for name in names {
+                        ui.label(format!("• {name}"));
+                    }
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Delete").clicked() {
+                    confirmed = Some(true);
+                }
+                if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    confirmed = Some(false);
+                }
+            });
+        });
+
+        match confirmed {
+            Some(true) => {
+                if let Some(names) = self.pending_delete.take() {
+                    self.delete_permanently(&names);
+                }
+            }
+            Some(false) => self.pending_delete = None,
+            None => {}
+        }
+    }
+
+    /// Open the properties of the selection, or of the row at `index` when it isn't selected
+    fn show_properties(&mut self, ctx: &egui::Context, index: usize) {
+        let Some(current_dir) = &self.current_directory else {
+            return;
+        };
+
+        let indices: Vec<usize> = if self.selected.contains(&index) {
+            self.selected.iter().copied().collect()
+        } else {
+            vec![index]
+        };
+        let paths = indices
+            .into_iter()
+            .filter_map(|index| self.files.get(index))
+            .map(|file| match file.name.as_str() {
+                ".." => current_dir.parent().unwrap_or(current_dir).to_path_buf(),
+                name => current_dir.join(name),
+            })
+            .collect();
+        self.properties = Some(PropertiesDialog::new(ctx, paths, &mut self.file_types));
+    }
+
+    /// Show `path` without changing the shell's directory. The explorer stops following the
+    /// shell until it gets back to the shell's directory
+    pub fn navigate_to(&mut self, path: PathBuf) {
+        self.detached = self.shell_directory.as_ref() != Some(&path);
+        self.set_directory(Some(path));
+    }
+
+    fn set_directory(&mut self, directory: Option<PathBuf>) {
+        if directory == self.current_directory {
+            return;
+        }
+        self.watcher
+            .watch(self.current_directory.as_deref(), directory.as_deref());
+        // While a directory is still read, the one before it is the one to go back to
+        let previous = match self.loading.take() {
+            Some(loading) if loading.streamed => loading.previous,
+            _ => self.current_directory.take().map(|directory| Previous {
+                directory,
+                files: std::mem::take(&mut self.files),
+                disk_space: self.disk_space,
+            }),
+        };
+        self.current_directory = directory;
+        self.filter = None;
+        self.selected.clear();
+        self.anchor = None;
+        self.start_listing(true, None);
+        if let Some(loading) = &mut self.loading {
+            loading.previous = previous;
+        }
+    }
+
+    /// Show the listing of the directory left again, after the new one couldn't be read
+    fn restore_previous(&mut self, previous: Previous) {
+        self.watcher.watch(
+            self.current_directory.as_deref(),
+            Some(previous.directory.as_path()),
+        );
+        self.detached = self.shell_directory.as_ref() != Some(&previous.directory);
+        if CONFIG
+            .get()
+            .and_then(|config| config.git_status)
+            .unwrap_or(true)
+        {
+            self.git_status.update(&self.ctx, &previous.directory);
+        }
+        self.current_directory = Some(previous.directory);
+        self.disk_space = previous.disk_space;
+        self.replace_files(previous.files);
+    }
+
+    /// Read the directory again, keeping the selection
+    fn refresh(&mut self) {
+        self.start_listing(false, None);
+    }
+
+    /// Look up the shell's working directory, at most every `SHELL_DIRECTORY_INTERVAL`, and
+    /// follow it there. The selected name stays selected if the new directory has it too
+    fn check_shell_directory(&mut self, ctx: &egui::Context, session: &TerminalSession) {
+        if let Some(checked_at) = self.shell_directory_checked_at {
+            let elapsed = checked_at.elapsed();
+            if elapsed < SHELL_DIRECTORY_INTERVAL {
+                ctx.request_repaint_after(SHELL_DIRECTORY_INTERVAL - elapsed);
+                return;
+            }
+        }
+        self.shell_directory_checked_at = Some(Instant::now());
+
+        let shell_directory = session.current_dir();
+        if shell_directory == self.shell_directory {
+            return;
+        }
+        self.shell_directory = shell_directory;
+        if self.shell_directory == self.current_directory {
+            self.detached = false;
+        } else if self.follow_terminal && !self.detached {
+            let selected = self
+                .anchor
+                .or_else(|| self.selected.first().copied())
+                .and_then(|index| self.files.get(index))
+                .map(|file| file.name.clone())
+                .filter(|name| name != "..");
+            self.set_directory(self.shell_directory.clone());
+            if let Some(loading) = &mut self.loading {
+                loading.after_load = selected.map(AfterLoad::Select);
+            }
+        }
+    }
+
+    /// Go back to showing the shell's directory
+    fn follow_shell(&mut self) {
+        self.detached = false;
+        self.set_directory(self.shell_directory.clone());
+    }
+
+    /// The path bar: the ancestors of the current directory as buttons, or a text field for
+    /// typing a path (Ctrl+L or clicking next to the buttons)
+    fn show_path_bar(&mut self, ui: &mut egui::Ui) {
+        if let Some((text, error)) = &mut self.path_edit {
+            let response = ui.add(
+                egui::TextEdit::singleline(text)
+                    .id(ui.make_persistent_id("path_edit"))
+                    .desired_width(f32::INFINITY),
+            );
+            if !response.has_focus() && !response.lost_focus() {
+                response.request_focus();
+            }
+            if let Some(error) = error {
+                ui.colored_label(egui::Color32::RED, error.as_str());
+            }
+
+            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.path_edit = None;
+            } else if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                let path = crate::utils::expand_home(text.trim());
+                if !path.is_absolute() {
+                    *error = Some("Enter an absolute path".to_string());
+                } else if !path.is_dir() {
+                    *error = Some(format!("\"{}\" is not a directory", path.display()));
+                } else {
+                    self.path_edit = None;
+                    self.navigate_to(path);
+                }
+            } else if response.lost_focus() {
+                self.path_edit = None;
+            }
+            return;
+        }
+
+        let mut target = None;
+        let mut edit = false;
+        let mut search = false;
+        ui.horizontal(|ui| {
+            ui.toggle_value(&mut self.show_places, "☰")
+                .on_hover_text("Places");
+            if let Some(current_dir) = &self.current_directory {
+                for (i, ancestor) in current_dir
+                    .ancestors()
+                    .collect::<Vec<_>>()
+                    .iter()
+                    .rev()
+                    .enumerate()
+                {
+                    if i > 1 {
+                        ui.label("›");
+                    }
+                    let name = ancestor
+                        .file_name()
+                        .map_or("/".to_string(), |name| name.to_string_lossy().into_owned());
+                    if ui.button(name).clicked() {
+                        target = Some(ancestor.to_path_buf());
+                    }
+                }
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("⟳").on_hover_text("Refresh (F5)").clicked() {
+                    self.refresh();
+                }
+                if ui
+                    .button("🔍")
+                    .on_hover_text("Search below this directory (Ctrl+Shift+F)")
+                    .clicked()
+                {
+                    search = true;
+                }
+                if let Some(error) = &self.watcher.error {
+                    ui.colored_label(ui.visuals().warn_fg_color, "⚠")
+                        .on_hover_text(format!(
+                            "Changes aren't picked up automatically, refresh with F5: {error}"
+                        ));
+                }
+                let mut show_hidden = self.show_hidden;
+                if ui
+                    .checkbox(&mut show_hidden, "Hidden files")
+                    .on_hover_text("Show dotfiles (Ctrl+H)")
+                    .changed()
+                {
+                    self.set_show_hidden(show_hidden);
+                }
+                let split = self.other_pane.is_some();
+                if ui
+                    .selectable_label(split, "◫")
+                    .on_hover_text(if split {
+                        "Close the second pane"
+                    } else {
+                        "Split into two panes (F6 switches between them)"
+                    })
+                    .clicked()
+                {
+                    self.split_toggled = true;
+                }
+                let mut follow_terminal = self.follow_terminal;
+                if ui
+                    .checkbox(&mut follow_terminal, "Follow terminal")
+                    .on_hover_text("Show the shell's directory whenever it changes")
+                    .changed()
+                {
+                    self.follow_terminal = follow_terminal;
+                    if follow_terminal {
+                        self.follow_shell();
+                    }
+                }
+                if !self.follow_terminal {
+                    if let Some(shell_directory) = self.shell_directory.clone()
+                        && self.current_directory.as_ref() != Some(&shell_directory)
+                    {
+                        if ui
+                            .button("Go to shell directory")
+                            .on_hover_text(shell_directory.to_string_lossy())
+                            .clicked()
+                        {
+                            target = Some(shell_directory.clone());
+                        }
+                        ui.weak(format!("Shell: {}", to_display_path(&shell_directory)));
+                    }
+                } else if self.detached {
+                    if ui
+                        .button("Follow shell")
+                        .on_hover_text("Show the shell's directory again")
+                        .clicked()
+                    {
+                        self.follow_shell();
+                    }
+                    ui.colored_label(ui.visuals().warn_fg_color, "Not the shell's directory");
+                }
+
+                // The empty space between the buttons and the toggles
+                if ui
+                    .allocate_response(ui.available_size(), egui::Sense::click())
+                    .on_hover_cursor(egui::CursorIcon::Text)
+                    .clicked()
+                {
+                    edit = true;
+                }
+            });
+        });
+
+        if let Some(target) = target {
+            self.navigate_to(target);
+        }
+        if edit {
+            self.start_path_edit();
+        }
+        if search {
+            self.start_search();
+        }
+    }
+
+    fn start_path_edit(&mut self) {
+        let text = self
+            .current_directory
+            .as_ref()
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.path_edit = Some((text, None));
+    }
+
+    pub fn take_commands(&mut self) -> Vec<TerminalCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    pub fn take_typed_paths(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.typed_paths)
+    }
+
+    /// Show the directory of `session`'s shell, unless navigated elsewhere; opening a directory
+    /// changes into it in that shell
+    pub fn show(&mut self, ui: &mut egui::Ui, session: &TerminalSession) {
+        self.rect = ui.max_rect();
+        self.check_shell_directory(ui.ctx(), session);
+
+        if self.show_places {
+            let current = self.current_directory.clone();
+            let result = egui::SidePanel::left(self.id.with("places"))
+                .resizable(true)
+                .default_width(140.0)
+                .show_inside(ui, |ui| self.places.show(ui, current.as_deref()))
+                .inner;
+            match result {
+                Ok(Some(target)) => self.navigate_to(target),
+                Ok(None) => {}
+                Err(e) => self
+                    .toasts
+                    .error(format!("Failed to save the bookmarks: {e}")),
+            }
+        }
+
+        // Refreshing would end a rename, the changes wait until it is done
+        if self.renaming.is_none() && self.watcher.poll(ui.ctx()) {
+            self.refresh();
+        }
+        self.poll_listing();
+        self.apply_file_types();
+        self.git_status
+            .poll(ui.ctx(), self.current_directory.as_deref());
+
+        self.show_path_bar(ui);
+        self.show_listing_error(ui);
         ui.separator();
 
-        egui::ScrollArea::vertical()
+        // The keyboard belongs to the area clicked last, the explorer or the terminal
+        let explorer_rect = ui.max_rect();
+        if let Some(pos) = ui.input(|i| {
+            i.pointer
+                .any_pressed()
+                .then(|| i.pointer.interact_pos())
+                .flatten()
+        }) {
+            self.focused = explorer_rect.contains(pos);
+        }
+
+        if let Some(disk_usage) = &mut self.disk_usage {
+            if !disk_usage.show(ui, self.focused) {
+                self.disk_usage = None;
+            }
+            self.show_dialogs(ui.ctx());
+            return;
+        }
+
+        if let Some(search) = &mut self.search {
+            match search.show(ui, self.focused) {
+                Some(SearchAction::Reveal(path)) => {
+                    self.search = None;
+                    self.reveal(&path);
+                }
+                Some(SearchAction::Close) => self.search = None,
+                None => {}
+            }
+            self.show_dialogs(ui.ctx());
+            return;
+        }
+
+        if self.focused
+            && !self.filter_has_focus
+            && self.path_edit.is_none()
+            && self.renaming.is_none()
+            && self.pending_delete.is_none()
+        {
+            let config = CONFIG.get();
+            let keybindings = config
+                .as_deref()
+                .map(|config| config.keybindings.clone())
+                .unwrap_or_default();
+            let requested = self.requested_action.take();
+            let pressed = |action| {
+                requested == Some(action) || ui.input_mut(|i| keybindings.pressed(i, action))
+            };
+
+            if pressed(ExplorerAction::Refresh) {
+                self.refresh();
+            }
+            if pressed(ExplorerAction::EditPath) {
+                self.start_path_edit();
+            }
+            if pressed(ExplorerAction::ToggleHidden) {
+                self.set_show_hidden(!self.show_hidden);
+            }
+            if pressed(ExplorerAction::Search) {
+                self.start_search();
+            }
+            if pressed(ExplorerAction::Filter) {
+                self.filter.get_or_insert_default();
+                self.focus_filter = true;
+            }
+            if let Some(index) = self.primary_selection()
+                && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Enter))
+            {
+                self.open(index, session);
+            }
+            // Before the plain keys, which would match with any Shift or Alt too
+            let parent = pressed(ExplorerAction::Parent);
+            self.handle_navigation_keys(ui, session, parent);
+            if pressed(ExplorerAction::SelectAll) {
+                self.select_all();
+            }
+            if ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Escape)) {
+                self.selected.clear();
+            }
+            let typed: String = ui.input(|i| {
+                i.events
+                    .iter()
+                    .filter_map(|event| match event {
+                        egui::Event::Text(text) => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            });
+            if !typed.is_empty() {
+                self.type_ahead(&typed);
+            }
+            if let Some(index) = self.primary_selection()
+                && pressed(ExplorerAction::Rename)
+            {
+                self.start_rename(index);
+            }
+            if pressed(ExplorerAction::Delete) {
+                let names = self.selected_names();
+                if !names.is_empty() {
+                    self.pending_delete = Some(names);
+                }
+            } else if pressed(ExplorerAction::Trash) {
+                let names = self.selected_names();
+                self.trash(&names);
+            }
+
+            // The clipboard shortcuts arrive as events rather than keys
+            let (copy, cut, paste) = ui.input(|i| {
+                i.events
+                    .iter()
+                    .fold((false, false, false), |(copy, cut, paste), event| {
+                        (
+                            copy || matches!(event, egui::Event::Copy),
+                            cut || matches!(event, egui::Event::Cut),
+                            paste || matches!(event, egui::Event::Paste(_)),
+                        )
+                    })
+            });
+            if copy {
+                self.mark_selected(OperationKind::Copy);
+            } else if cut {
+                self.mark_selected(OperationKind::Move);
+            } else if paste {
+                self.paste(ui.ctx());
+            }
+        }
+
+        self.show_status_bar(ui);
+        self.show_file_operation(ui);
+        self.show_filter_bar(ui);
+        self.show_loading(ui);
+
+        // Selection, renaming etc. keep working on indices into `files`
+        let visible_indices = self.visible_indices();
+        let mut rename_requested = None;
+        let mut delete_requested = None;
+        let mut row_clicked = None;
+        let mut new_item_requested = None;
+        let mut properties_requested = None;
+        let mut directory_properties_requested = false;
+        let mut clipboard_requested = None;
+        let mut finish_rename = None;
+        let mut open_requested = None;
+        let mut bookmark_requested = false;
+        let mut column_toggled = None;
+        // The archive, and the directory to create for its contents
+        let mut extract_requested = None;
+        let mut transfer_requested = None;
+        // Boundary dragged with the distance and the name column's width, or column to fit
+        let mut column_resized = None;
+        let mut column_fitted = None;
+
+        let list = egui::ScrollArea::vertical()
             .auto_shrink([false, false])
             .show(ui, |ui| {
-                StripBuilder::new(ui)
-                    .size(Size::exact(HEADER_HEIGHT))
-                    .sizes(Size::exact(ROW_HEIGHT), self.files.len())
-                    .vertical(|mut strip| {
-                        strip.cell(|ui| {
-                            StripBuilder::new(ui)
-                                .size(Size::remainder().at_least(COLUMN_SIZES[0]))
-                                .size(Size::exact(COLUMN_SIZES[1]))
-                                .size(Size::exact(COLUMN_SIZES[2]))
-                                .size(Size::exact(COLUMN_SIZES[3]))
-                                .horizontal(|mut strip| {
-                                    let contents = ["Name", "Size", "Type", "Modified"];
-                                    for title in contents {
-                                        Self::render_cell(&mut strip, |ui| ui.label(title));
-                                    }
-                                });
+                let mut builder = StripBuilder::new(ui).size(Size::exact(HEADER_HEIGHT));
+                for &index in &visible_indices {
+                    // A failed rename shows its error in a second line under the row
+                    let has_error = self
+                        .renaming
+                        .as_ref()
+                        .is_some_and(|rename| rename.index == index && rename.error.is_some());
+                    builder = builder.size(Size::exact(if has_error {
+                        ROW_HEIGHT * 2.0
+                    } else {
+                        ROW_HEIGHT
+                    }));
+                }
+                builder.vertical(|mut strip| {
+                    strip.cell(|ui| {
+                        let header = ui.interact(
+                            ui.max_rect(),
+                            ui.id().with("header"),
+                            egui::Sense::click(),
+                        );
+                        let mut cells = Vec::new();
+                        Self::column_strip(ui, &self.columns, &self.column_widths).horizontal(
+                            |mut strip| {
+                                for column in &self.columns {
+                                    strip.cell(|ui| {
+                                        cells.push(ui.max_rect());
+                                        ui.add(egui::Label::new(column.title()).truncate());
+                                    });
+                                }
+                            },
+                        );
+
+                        // Dragging the separators resizes the columns, double-clicking fits one
+                        // to its contents
+                        for (boundary, pair) in cells.windows(2).enumerate() {
+                            let x = (pair[0].right() + pair[1].left()) / 2.0;
+                            let rect = egui::Rect::from_x_y_ranges(
+                                x - 3.0..=x + 3.0,
+                                header.rect.y_range(),
+                            );
+                            let separator = ui
+                                .interact(
+                                    rect,
+                                    header.id.with(("separator", boundary)),
+                                    egui::Sense::click_and_drag(),
+                                )
+                                .on_hover_cursor(egui::CursorIcon::ResizeColumn);
+                            let stroke = if separator.hovered() || separator.dragged() {
+                                ui.visuals().widgets.active.fg_stroke
+                            } else {
+                                ui.visuals().widgets.noninteractive.bg_stroke
+                            };
+                            ui.painter().vline(x, rect.y_range(), stroke);
+
+                            if separator.dragged() {
+                                column_resized =
+                                    Some((boundary, separator.drag_delta().x, cells[0].width()));
+                            }
+                            if separator.double_clicked() {
+                                // The name column takes the remaining space, fit its neighbor
+                                column_fitted = self.columns[boundary..=boundary + 1]
+                                    .iter()
+                                    .copied()
+                                    .find(|column| *column != Column::Name);
+                            }
+                        }
+                        header.context_menu(|ui| {
+                            for column in Column::OPTIONAL {
+                                let mut shown = self.columns.contains(&column);
+                                if ui.checkbox(&mut shown, column.title()).changed() {
+                                    column_toggled = Some(column);
+                                }
+                            }
                         });
+                    });
 
-                        for (index, file) in self.files.iter().enumerate() {
-                            strip.cell(|ui| {
-                                let is_selected = self.selected_index == Some(index);
+                    for (row, &index) in visible_indices.iter().enumerate() {
+                        let file = &self.files[index];
+                        strip.cell(|ui| {
+                            let is_selected = self.selected.contains(&index);
+                            if is_selected && self.scroll_to_selected {
+                                ui.scroll_to_rect(ui.max_rect(), None);
+                                self.scroll_to_selected = false;
+                            }
 
-                                let bg_color = if is_selected {
-                                    ui.style().visuals.selection.bg_fill
-                                } else if index % 2 == 1 {
-                                    ui.style().visuals.faint_bg_color
-                                } else {
-                                    egui::Color32::TRANSPARENT
-                                };
+                            let bg_color = if is_selected {
+                                ui.style().visuals.selection.bg_fill
+                            } else if row % 2 == 1 {
+                                // faint_bg_color is barely different from the panel in the
+                                // light theme, so the stripe is mixed from the text color
+                                let visuals = ui.visuals();
+                                visuals.panel_fill.lerp_to_gamma(visuals.text_color(), 0.06)
+                            } else {
+                                egui::Color32::TRANSPARENT
+                            };
 
-                                if bg_color != egui::Color32::TRANSPARENT {
-                                    ui.painter().rect_filled(
+                            if bg_color != egui::Color32::TRANSPARENT {
+                                ui.painter().rect_filled(
+                                    ui.available_rect_before_wrap(),
+                                    0.0,
+                                    bg_color,
+                                );
+                            }
+                            if let Some((name, started)) = &self.flash
+                                && *name == file.name
+                            {
+                                let elapsed = started.elapsed();
+                                if elapsed < FLASH_DURATION {
+                                    let fade =
+                                        1.0 - elapsed.as_secs_f32() / FLASH_DURATION.as_secs_f32();
+                                    ui.painter().rect_stroke(
                                         ui.available_rect_before_wrap(),
                                         0.0,
-                                        bg_color,
+                                        egui::Stroke::new(
+                                            2.0,
+                                            ui.visuals()
+                                                .selection
+                                                .stroke
+                                                .color
+                                                .gamma_multiply(fade),
+                                        ),
+                                        egui::StrokeKind::Inside,
                                     );
+                                    ui.ctx().request_repaint();
                                 }
+                            }
+
+                            let rect = ui.max_rect();
+                            let id = ui.make_persistent_id(index);
+                            let response = ui.interact(rect, id, egui::Sense::click_and_drag());
+                            // Screen readers get the row as a selectable item, not a bare rect
+                            response.widget_info(|| {
+                                let label = [&file.name, &file.size, &file.file_type]
+                                    .into_iter()
+                                    .filter(|part| !part.is_empty())
+                                    .map(String::as_str)
+                                    .collect::<Vec<_>>()
+                                    .join(", ");
+                                egui::WidgetInfo::selected(
+                                    egui::WidgetType::SelectableLabel,
+                                    true,
+                                    is_selected,
+                                    label,
+                                )
+                            });
+                            if response.clicked() {
+                                row_clicked = Some((index, ui.input(|i| i.modifiers)));
+                            } else if response.secondary_clicked() && !is_selected {
+                                // The context menu acts on the selection, or on this row alone
+                                row_clicked = Some((index, egui::Modifiers::NONE));
+                            }
 
-                                let rect = ui.max_rect();
-                                let id = ui.make_persistent_id(index);
-                                let response = ui.interact(rect, id, egui::Sense::click());
-                                if response.clicked() {
-                                    self.selected_index = Some(index);
+                            // Dragging and "Open with…" take the selection along when this row
+                            // is part of it
+                            let paths: Vec<PathBuf> = match &self.current_directory {
+                                Some(current_dir) if file.name != ".." => {
+                                    if is_selected {
+                                        self.selected
+                                            .iter()
+                                            .map(|&index| &self.files[index].name)
+                                            .filter(|name| *name != "..")
+                                            .map(|name| current_dir.join(name))
+                                            .collect()
+                                    } else {
+                                        vec![current_dir.join(&file.name)]
+                                    }
                                 }
+                                _ => Vec::new(),
+                            };
 
-                                if response.double_clicked() {
-                                    Self::open_file(file, self.current_directory.clone());
+                            // Rows can be dropped onto the terminal to type their paths
+                            if !paths.is_empty() {
+                                response.dnd_set_drag_payload(paths.clone());
+                                if response.dragged() {
+                                    ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
                                 }
+                            }
 
-                                response.context_menu(|ui| {
-                                    if ui.button("Open").clicked() {
-                                        Self::open_file(file, self.current_directory.clone());
+                            if response.double_clicked() {
+                                open_requested = Some(index);
+                            }
+
+                            response.context_menu(|ui| {
+                                if ui.button("Open").clicked() {
+                                    open_requested = Some(index);
+                                }
+                                if !paths.is_empty() {
+                                    ui.menu_button("Open with…", |ui| {
+                                        if let Err(e) = self.open_with.menu(ui, &paths) {
+                                            self.toasts.error(e);
+                                        }
+                                    });
+                                }
+                                if file.is_executable {
+                                    if ui.button("Run in terminal").clicked() {
+                                        self.commands.extend(Self::terminal_command(
+                                            format!("./{}", file.name),
+                                            self.current_directory.clone(),
+                                            |path| path,
+                                        ));
+                                    }
+                                    if let Some(path) = paths.first()
+                                        && ui.button("Run").clicked()
+                                        && let Err(e) = Self::run_detached(path)
+                                    {
+                                        self.toasts
+                                            .error(format!("Failed to run \"{}\": {e}", file.name));
+                                    }
+                                }
+                                if let Some((kind, stem)) = ArchiveKind::split(&file.name)
+                                    && let Some(current_dir) = &self.current_directory
+                                {
+                                    ui.separator();
+                                    let archive = current_dir.join(&file.name);
+                                    if ui.button("Peek Contents").clicked() {
+                                        self.archive_peek =
+                                            Some(ArchivePeek::new(ui.ctx(), archive.clone(), kind));
                                     }
+                                    if ui.button("Extract Here").clicked() {
+                                        extract_requested = Some((archive.clone(), None));
+                                    }
+                                    if ui.button(format!("Extract to \"{stem}/\"")).clicked() {
+                                        extract_requested = Some((archive, Some(stem.to_string())));
+                                    }
+                                    ui.separator();
+                                } else if let Some(extract) = Self::extract_command(&file.name)
+                                    && ui.button("Extract here").clicked()
+                                {
+                                    self.commands.extend(Self::terminal_command(
+                                        format!("./{}", file.name),
+                                        self.current_directory.clone(),
+                                        |path| format!("{extract} {path}"),
+                                    ));
+                                }
+                                if file.name != ".." {
+                                    if ui.button("Rename").clicked() {
+                                        rename_requested = Some(index);
+                                    }
+                                    if ui.button("Move to Trash").clicked() {
+                                        delete_requested = Some(false);
+                                    }
+                                    if ui.button("Delete Permanently…").clicked() {
+                                        delete_requested = Some(true);
+                                    }
+                                }
+                                ui.separator();
+                                Self::new_item_buttons(ui, &mut new_item_requested);
+                                ui.separator();
+                                if file.name != ".." {
                                     if ui.button("Copy").clicked() {
-                                        crate::utils::copy_file_uri_to_clipboard(
-                                            &Self::get_absolute_path_string(
-                                                self.current_directory.clone(),
-                                                &file.name,
-                                            )
-                                            .unwrap_or_default(),
-                                        );
+                                        clipboard_requested = Some(Some(OperationKind::Copy));
                                     }
-                                });
-
-                                StripBuilder::new(ui)
-                                    .size(Size::remainder().at_least(COLUMN_SIZES[0]))
-                                    .size(Size::exact(COLUMN_SIZES[1]))
-                                    .size(Size::exact(COLUMN_SIZES[2]))
-                                    .size(Size::exact(COLUMN_SIZES[3]))
-                                    .horizontal(|mut strip| {
-                                        Self::render_cell(&mut strip, |ui| {
-                                            ui.image(&file.icon_path);
-                                            ui.label(if file.is_hidden {
-                                                RichText::new(&file.name)
-                                                    .color(egui::Color32::DARK_GRAY)
-                                            } else {
-                                                RichText::new(&file.name)
-                                            });
-                                        });
+                                    if ui.button("Cut").clicked() {
+                                        clipboard_requested = Some(Some(OperationKind::Move));
+                                    }
+                                }
+                                if ui.button("Paste").clicked() {
+                                    clipboard_requested = Some(None);
+                                }
+                                if self.other_pane.is_some() && !paths.is_empty() {
+                                    if ui.button("Copy to Other Pane").clicked() {
+                                        transfer_requested =
+                                            Some((OperationKind::Copy, paths.clone()));
+                                    }
+                                    if ui.button("Move to Other Pane").clicked() {
+                                        transfer_requested =
+                                            Some((OperationKind::Move, paths.clone()));
+                                    }
+                                }
+                                if !paths.is_empty() {
+                                    ui.separator();
+                                    Self::copy_text_buttons(ui, &paths);
+                                    if ui.button("Paste Path into Terminal").clicked() {
+                                        self.typed_paths.extend(paths.iter().cloned());
+                                    }
+                                }
+                                ui.separator();
+                                if file.is_directory
+                                    && file.name != ".."
+                                    && let Some(path) = paths.first()
+                                    && ui.button("Analyze Disk Usage").clicked()
+                                {
+                                    self.disk_usage = Some(DiskUsage::new(ui.ctx(), path.clone()));
+                                }
+                                if ui.button("Properties").clicked() {
+                                    properties_requested = Some(index);
+                                }
+                            });
 
-                                        let contents = [
-                                            file.size.clone(),
-                                            file.file_type.clone(),
-                                            file.modified_at.clone(),
-                                        ];
+                            let mut rename = self
+                                .renaming
+                                .as_mut()
+                                .filter(|rename| rename.index == index);
+                            let error = rename.as_ref().and_then(|rename| rename.error.clone());
 
-                                        for content in contents {
-                                            Self::render_cell(&mut strip, |ui| ui.label(content));
+                            StripBuilder::new(ui)
+                                .size(Size::exact(ROW_HEIGHT))
+                                .size(Size::remainder())
+                                .vertical(|mut strip| {
+                                    strip.cell(|ui| {
+                                        Self::column_strip(ui, &self.columns, &self.column_widths)
+                                            .horizontal(|mut strip| {
+                                                for &column in &self.columns {
+                                                    Self::render_cell(&mut strip, |ui| {
+                                                        if column != Column::Name {
+                                                            ui.label(column.text(file));
+                                                            return;
+                                                        }
+                                                        Self::show_icon(ui, file);
+                                                        if let Some(rename) = rename.as_mut() {
+                                                            finish_rename = Self::show_rename_edit(
+                                                                ui, rename, file,
+                                                            );
+                                                        } else {
+                                                            Self::show_name(
+                                                                ui,
+                                                                file,
+                                                                self.git_status.get(&file.name),
+                                                            );
+                                                        }
+                                                    });
+                                                }
+                                            });
+                                    });
+                                    strip.cell(|ui| {
+                                        if let Some(error) = error {
+                                            ui.colored_label(egui::Color32::RED, error);
                                         }
                                     });
-                            })
-                        }
-                    });
+                                });
+                        })
+                    }
+                });
+
+                // The empty space below the rows
+                let background = ui.allocate_response(
+                    ui.available_size().max(egui::vec2(0.0, ROW_HEIGHT)),
+                    egui::Sense::click(),
+                );
+                background.context_menu(|ui| {
+                    Self::new_item_buttons(ui, &mut new_item_requested);
+                    ui.separator();
+                    if ui.button("Paste").clicked() {
+                        clipboard_requested = Some(None);
+                    }
+                    ui.separator();
+                    if ui.button("Add Bookmark").clicked() {
+                        bookmark_requested = true;
+                    }
+                    if ui.button("Properties").clicked() {
+                        directory_properties_requested = true;
+                    }
+                });
             });
+        self.page_rows =
+            ((list.inner_rect.height() - HEADER_HEIGHT) / ROW_HEIGHT).max(1.0) as usize;
+
+        if let Some(column) = column_toggled {
+            self.toggle_column(column);
+        }
+        if let Some((archive, directory)) = extract_requested {
+            self.start_extract(ui.ctx(), archive, directory);
+        }
+        if let Some((kind, sources)) = transfer_requested
+            && let Some(other_pane) = self.other_pane.clone()
+        {
+            self.start_operation_into(ui.ctx(), kind, sources, other_pane);
+        }
+        if let Some((boundary, delta, name_width)) = column_resized {
+            self.resize_column(boundary, delta, name_width);
+        }
+        if let Some(column) = column_fitted {
+            self.fit_column(ui, column, &visible_indices);
+        }
+
+        if bookmark_requested
+            && let Some(current_dir) = self.current_directory.clone()
+            && let Err(e) = self.places.add_bookmark(current_dir)
+        {
+            self.toasts
+                .error(format!("Failed to save the bookmarks: {e}"));
+        }
+
+        if let Some(index) = properties_requested {
+            self.show_properties(ui.ctx(), index);
+        }
+        if directory_properties_requested && let Some(current_dir) = self.current_directory.clone()
+        {
+            self.properties = Some(PropertiesDialog::new(
+                ui.ctx(),
+                vec![current_dir],
+                &mut self.file_types,
+            ));
+        }
+
+        if let Some((index, modifiers)) = row_clicked {
+            self.click_row(index, modifiers);
+            self.focused = true;
+        }
+        if let Some(index) = open_requested {
+            self.open(index, session);
+        }
+        if let Some(index) = rename_requested {
+            self.start_rename(index);
+        }
+        match finish_rename {
+            Some(true) => self.commit_rename(),
+            Some(false) => self.cancel_rename(),
+            None => {}
+        }
+        match clipboard_requested {
+            Some(Some(kind)) => self.mark_selected(kind),
+            Some(None) => self.paste(ui.ctx()),
+            None => {}
+        }
+        if let Some(kind) = new_item_requested {
+            self.cancel_rename();
+            self.start_new_item(kind);
+        }
+        if let Some(permanently) = delete_requested {
+            let names = self.selected_names();
+            if permanently {
+                self.pending_delete = Some(names);
+            } else {
+                self.trash(&names);
+            }
+        }
+
+        self.show_dialogs(ui.ctx());
+    }
+
+    /// The dialogs and messages drawn over the window
+    fn show_dialogs(&mut self, ctx: &egui::Context) {
+        self.show_delete_confirmation(ctx);
+        if let Some(properties) = &mut self.properties
+            && !properties.show(ctx)
+        {
+            self.properties = None;
+        }
+        if let Some(peek) = &mut self.archive_peek
+            && !peek.show(ctx)
+        {
+            self.archive_peek = None;
+        }
+        if let Err(e) = self.open_with.show_chooser(ctx) {
+            self.toasts.error(e);
+        }
+        self.toasts.show(ctx);
+    }
+
+    /// Search the current directory and below, in place of the listing
+    fn start_search(&mut self) {
+        if let Some(current_dir) = self.current_directory.clone() {
+            self.search = Some(Search::new(current_dir));
+        }
+    }
+
+    /// Show `path` in its directory, selected
+    pub fn reveal(&mut self, path: &Path) {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return;
+        };
+        let name = name.to_string_lossy().to_string();
+        if name.starts_with('.') {
+            self.set_show_hidden(true);
+        }
+
+        if self.current_directory.as_deref() != Some(parent) {
+            self.navigate_to(parent.to_path_buf());
+        }
+        match &mut self.loading {
+            Some(loading) => loading.after_load = Some(AfterLoad::Select(name)),
+            None => {
+                if let Some(index) = self.files.iter().position(|file| file.name == name) {
+                    self.select_only(index);
+                    self.scroll_to_selected = true;
+                }
+            }
+        }
+        self.focused = true;
+    }
+
+    fn new_item_buttons(ui: &mut egui::Ui, requested: &mut Option<NewItem>) {
+        if ui.button("New Folder").clicked() {
+            *requested = Some(NewItem::Folder);
+        }
+        if ui.button("New File").clicked() {
+            *requested = Some(NewItem::File);
+        }
+    }
+
+    /// The text field of an inline rename. Returns `Some(true)` to commit, `Some(false)` to
+    /// cancel.
+    fn show_rename_edit(ui: &mut egui::Ui, rename: &mut Rename, file: &FileItem) -> Option<bool> {
+        let id = ui.make_persistent_id(("rename", &file.name));
+        let mut output = egui::TextEdit::singleline(&mut rename.text)
+            .id(id)
+            .desired_width(f32::INFINITY)
+            .show(ui);
+
+        if rename.just_started {
+            rename.just_started = false;
+            output.response.request_focus();
+
+            // Select the name without the extension, so typing keeps the file type
+            let stem_len = if file.is_directory {
+                rename.text.chars().count()
+            } else {
+                Path::new(&rename.text)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().chars().count())
+                    .unwrap_or_else(|| rename.text.chars().count())
+            };
+            output
+                .state
+                .cursor
+                .set_char_range(Some(egui::text::CCursorRange::two(
+                    egui::text::CCursor::new(0),
+                    egui::text::CCursor::new(stem_len),
+                )));
+            output.state.store(ui.ctx(), id);
+            return None;
+        }
+
+        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            Some(false)
+        } else if output.response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            Some(true)
+        } else if output.response.lost_focus() {
+            // Clicking elsewhere cancels like in file managers
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// A strip with a cell for each of `columns`
+    fn column_strip<'a>(
+        ui: &'a mut egui::Ui,
+        columns: &[Column],
+        widths: &BTreeMap<Column, f32>,
+    ) -> StripBuilder<'a> {
+        columns
+            .iter()
+            .fold(StripBuilder::new(ui), |builder, column| {
+                builder.size(match column {
+                    Column::Name => Size::remainder().at_least(column.width()),
+                    _ => Size::exact(widths.get(column).copied().unwrap_or(column.width())),
+                })
+            })
     }
 
     fn render_cell<R>(strip: &mut egui_extras::Strip<'_, '_>, f: impl FnOnce(&mut egui::Ui) -> R) {
@@ -183,104 +2173,445 @@ impl ExplorerWidget {
         })
     }
 
-    fn open_file(file: &FileItem, current_directory: Option<PathBuf>) {
-        if file.is_directory {
-            if let Some(input) = crate::app::INPUT_BUFFER.get() {
-                let cd_command = format!("cd {}", file.name.replace(" ", "\\ "));
-                let b = format!("\x15{cd_command}/\r");
+    /// Open the item at `index`: directories are changed into in the shell, or only in the
+    /// explorer while it doesn't follow the shell; files are opened with their application
+    fn open(&mut self, index: usize, session: &TerminalSession) {
+        let (Some(file), Some(current_dir)) = (self.files.get(index), &self.current_directory)
+        else {
+            return;
+        };
 
-                let mut input = input.lock();
-                input.extend_from_slice(b.as_bytes());
-            }
-        } else {
-            let current_dir = current_directory.clone().unwrap_or_default();
-            let file_path = Path::new(&current_dir).join(&file.name);
-            if let Err(e) = open::that(file_path) {
-                log::error!("Failed to open file: {e}");
+        let open_in_explorer = self.detached
+            || !self.follow_terminal
+            || CONFIG
+                .get()
+                .and_then(|config| config.open_directory)
+                .unwrap_or_default()
+                == OpenDirectory::Explorer;
+        if file.is_broken_link {
+            let target = file
+                .link_target
+                .as_ref()
+                .map_or("?".into(), |target| target.to_string_lossy());
+            self.toasts.error(format!(
+                "\"{}\" is a broken link, \"{target}\" doesn't exist",
+                file.name
+            ));
+        } else if file.is_directory && open_in_explorer {
+            let path = if file.name == ".." {
+                current_dir.parent().map(Path::to_path_buf)
+            } else {
+                Some(current_dir.join(&file.name))
+            };
+            if let Some(path) = path {
+                self.navigate_to(path);
             }
+        } else if file.is_directory {
+            // The full path, so the shell follows even when it is elsewhere by now
+            session.run_command(&format!(
+                "cd -- {}",
+                crate::utils::shell_quote(&current_dir.join(&file.name).to_string_lossy())
+            ));
+        } else if file.is_executable
+            && CONFIG
+                .get()
+                .and_then(|config| config.double_click_runs_executables)
+                .unwrap_or(false)
+        {
+            self.commands.extend(Self::terminal_command(
+                format!("./{}", file.name),
+                Some(current_dir.clone()),
+                |path| path,
+            ));
+        } else if let Err(e) = open::that(current_dir.join(&file.name)) {
+            log::error!("Failed to open file: {e}");
         }
     }
 
-    pub fn refresh_files(&mut self) -> anyhow::Result<()> {
-        self.files.clear();
-        self.selected_index = None;
+    /// Start the program at `path` in its directory, apart from the terminal and the window
+    fn run_detached(path: &Path) -> std::io::Result<()> {
+        use std::os::unix::process::CommandExt;
 
-        if let Some(current_dir) = &self.current_directory {
-            let path = Path::new(current_dir);
-            if path.parent().is_some() {
-                self.files.push(FileItem {
-                    name: "..".to_string(),
-                    size: "--".to_string(),
-                    file_type: "Directory".to_string(),
-                    modified_at: "--".to_string(),
-                    is_directory: true,
-                    is_hidden: false,
-                    icon_path: get_formatted_icon_path("inode/directory", 48),
-                });
-            }
+        let mut command = std::process::Command::new(path);
+        if let Some(directory) = path.parent() {
+            command.current_dir(directory);
         }
+        let mut child = command
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            // Not to get the signals of the window's process group
+            .process_group(0)
+            .spawn()?;
+        // Reaped when it exits, not to leave a zombie
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
 
-        for entry in
-            fs::read_dir(self.current_directory.clone().unwrap_or_default())?.filter_map(Result::ok)
-        {
-            let path = entry.path();
-            if path.is_dir() {
-                self.files.push(FileItem {
-                    name: entry.file_name().to_string_lossy().to_string(),
-                    size: "--".to_string(),
-                    file_type: "Directory".to_string(),
-                    modified_at: "--".to_string(),
-                    is_directory: true,
-                    is_hidden: entry.file_name().to_string_lossy().starts_with('.'),
-                    icon_path: get_formatted_icon_path("inode/directory", 48),
-                });
+    /// The icon of a row, with an arrow in the corner for links
+    fn show_icon(ui: &mut egui::Ui, file: &FileItem) {
+        let icon = ui.image(file.icon.clone());
+        if file.is_executable {
+            ui.painter().text(
+                icon.rect.right_bottom(),
+                egui::Align2::RIGHT_BOTTOM,
+                "⚙",
+                egui::FontId::proportional(10.0),
+                ui.visuals().strong_text_color(),
+            );
+        }
+        if file.is_symlink {
+            let color = if file.is_broken_link {
+                ui.visuals().error_fg_color
             } else {
-                let mime_type = get_mime_type_from_path(&path);
-                let metadata = entry.metadata()?;
-                let file_type = get_desc_from_mime_type(&mime_type);
-                let size = to_human_readable_size(metadata.len());
-                let modified: DateTime<Local> = metadata.modified()?.into();
-                let formatted_modified = modified.format("%Y-%m-%d %H:%M").to_string();
-
-                self.files.push(FileItem {
-                    name: entry.file_name().to_string_lossy().to_string(),
-                    size,
-                    file_type,
-                    modified_at: formatted_modified,
-                    is_directory: false,
-                    is_hidden: entry.file_name().to_string_lossy().starts_with('.'),
-                    icon_path: get_formatted_icon_path(&mime_type, 48),
-                });
+                ui.visuals().strong_text_color()
+            };
+            ui.painter().text(
+                icon.rect.left_bottom(),
+                egui::Align2::LEFT_BOTTOM,
+                "↗",
+                egui::FontId::proportional(10.0),
+                color,
+            );
+        }
+    }
+
+    /// The name of a row: dimmed when hidden or ignored by git, in italics with the target as
+    /// tooltip for links. Other git statuses get a colored dot
+    fn show_name(ui: &mut egui::Ui, file: &FileItem, git_status: Option<GitStatus>) {
+        if let Some(status) = git_status.filter(|status| *status != GitStatus::Ignored) {
+            let (rect, response) =
+                ui.allocate_exact_size(egui::vec2(8.0, 8.0), egui::Sense::hover());
+            ui.painter()
+                .circle_filled(rect.center(), 3.5, status.color());
+            response.on_hover_text(status.description());
+        }
+
+        let mut name = RichText::new(&file.name);
+        if file.is_hidden || git_status == Some(GitStatus::Ignored) {
+            name = name.color(egui::Color32::DARK_GRAY);
+        }
+        if file.is_symlink {
+            name = name.italics();
+        }
+
+        let label = ui.label(name);
+        if let Some(target) = &file.link_target {
+            let broken = if file.is_broken_link { " (broken)" } else { "" };
+            label.on_hover_text(format!("→ {}{broken}", target.display()));
+        }
+        if let Some(error) = &file.error {
+            ui.colored_label(ui.visuals().warn_fg_color, "⚠")
+                .on_hover_text(format!("Couldn't read its details: {error}"));
+        }
+    }
+
+    /// "Copy Path" and "Copy Name", putting one line per item on the clipboard as plain text
+    fn copy_text_buttons(ui: &mut egui::Ui, paths: &[PathBuf]) {
+        let lines = |text: fn(&Path) -> String| {
+            paths
+                .iter()
+                .map(|path| text(path))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        if ui.button("Copy Path").clicked() {
+            ui.ctx()
+                .copy_text(lines(|path| path.to_string_lossy().into_owned()));
+        }
+        if ui.button("Copy Name").clicked() {
+            ui.ctx().copy_text(lines(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            }));
+        }
+    }
+
+    /// A command for the file at `path` (relative to `current_directory`), quoted and passed
+    /// through `build`
+    fn terminal_command(
+        path: String,
+        current_directory: Option<PathBuf>,
+        build: impl FnOnce(String) -> String,
+    ) -> Option<TerminalCommand> {
+        Some(TerminalCommand {
+            command: build(crate::utils::shell_quote(&path)),
+            cwd: current_directory?,
+        })
+    }
+
+    /// The command extracting an archive with this name, for the kinds `ArchiveKind` doesn't
+    /// read
+    fn extract_command(name: &str) -> Option<&'static str> {
+        let name = name.to_lowercase();
+        [".tar.bz2", ".tbz2", ".tar.xz", ".txz"]
+            .iter()
+            .any(|extension| name.ends_with(extension))
+            .then_some("tar -xf")
+    }
+
+    /// Fill in the types and icons the worker found
+    fn apply_file_types(&mut self) {
+        let resolved = self.file_types.poll();
+        if resolved.is_empty() {
+            return;
+        }
+
+        let indices: HashMap<&str, usize> = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (file.name.as_str(), index))
+            .collect();
+        let updates: Vec<(usize, Resolved)> = resolved
+            .into_iter()
+            .filter_map(|resolved| Some((*indices.get(resolved.name.as_str())?, resolved)))
+            .collect();
+        for (index, resolved) in updates {
+            self.files[index].file_type = resolved.description;
+            self.files[index].icon = resolved.icon;
+        }
+    }
+
+    /// Take the items read so far, selecting what the refresh was asked to when it is done
+    fn poll_listing(&mut self) {
+        let Some(mut loading) = self.loading.take() else {
+            return;
+        };
+
+        let mut items = Vec::new();
+        let mut finished = false;
+        for update in loading.listing.poll() {
+            match update {
+                Update::Batch(batch) => items.extend(batch),
+                Update::Finished { errors, disk_space } => {
+                    finished = true;
+                    self.disk_space = disk_space;
+                    self.listing_error = None;
+                    if let Some(first) = errors.first() {
+                        self.toasts.error(format!(
+                            "Failed to read {} item(s), like {first}",
+                            errors.len()
+                        ));
+                    }
+                }
+                Update::Failed(e) => {
+                    let message = if e.kind() == std::io::ErrorKind::PermissionDenied {
+                        "Permission denied".to_string()
+                    } else {
+                        e.to_string()
+                    };
+                    self.listing_error = self
+                        .current_directory
+                        .clone()
+                        .map(|directory| (directory, message));
+                    // A refresh keeps what it would have replaced, a new directory shows the
+                    // one left again
+                    if let Some(previous) = loading.previous.take() {
+                        self.restore_previous(previous);
+                    }
+                    return;
+                }
+            }
+        }
+
+        if loading.streamed {
+            // Shown as they arrive, in a new directory there's nothing to wait for
+            if !items.is_empty() {
+                self.prepare_items(&mut items);
+                let mut files = std::mem::take(&mut self.files);
+                files.append(&mut items);
+                self.replace_files(files);
+            }
+        } else {
+            // A refresh keeps the old listing until the new one is complete, not to flicker
+            loading.held.append(&mut items);
+            if finished {
+                let mut files = std::mem::take(&mut loading.held);
+                self.file_types.start_listing();
+                self.prepare_items(&mut files);
+                self.replace_files(files);
             }
         }
 
-        self.files.sort_by(|a, b| {
-            if !a.is_hidden && b.is_hidden {
-                std::cmp::Ordering::Less
-            } else if a.is_hidden && !b.is_hidden {
-                std::cmp::Ordering::Greater
-            } else if a.is_directory && !b.is_directory {
-                std::cmp::Ordering::Less
-            } else if !a.is_directory && b.is_directory {
-                std::cmp::Ordering::Greater
+        if !finished {
+            self.loading = Some(loading);
+            return;
+        }
+        match loading.after_load {
+            Some(AfterLoad::Select(name)) => {
+                if let Some(index) = self.files.iter().position(|file| file.name == name) {
+                    self.select_only(index);
+                    self.scroll_to_selected = true;
+                }
+            }
+            // Items that couldn't be deleted stay selected
+            Some(AfterLoad::SelectNear(index)) => {
+                if self.selected.is_empty()
+                    && let Some(last) = self.files.len().checked_sub(1)
+                {
+                    self.select_only(index.min(last));
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Give read items their icons, and look up the types not known from the old listing
+    fn prepare_items(&mut self, items: &mut [FileItem]) {
+        // A link may point somewhere else now
+        let known: HashMap<&str, &FileItem> = self
+            .files
+            .iter()
+            .filter(|file| !file.file_type.is_empty() && !file.is_symlink)
+            .map(|file| (file.name.as_str(), file))
+            .collect();
+
+        let directory_icon = self.file_types.icon("inode/directory");
+        let broken_link_icon = self.file_types.icon("inode/symlink");
+        let mut requested = Vec::new();
+        for item in items.iter_mut() {
+            if item.is_directory {
+                item.icon = directory_icon.clone();
+            } else if item.is_broken_link {
+                item.icon = broken_link_icon.clone();
+            } else if let Some(old) = known.get(item.name.as_str()) {
+                item.file_type = old.file_type.clone();
+                item.icon = old.icon.clone();
             } else {
-                a.name.cmp(&b.name)
+                requested.push(item.name.clone());
             }
+        }
+        for name in requested {
+            self.file_types.request(name);
+        }
+    }
+
+    /// Replace the listing, keeping the selection and the row being renamed by name
+    fn replace_files(&mut self, mut files: Vec<FileItem>) {
+        let name_of = |index: usize| self.files.get(index).map(|file| file.name.clone());
+        let selected_names: Vec<String> =
+            self.selected.iter().filter_map(|&i| name_of(i)).collect();
+        let anchor_name = self.anchor.and_then(name_of);
+        let renaming_name = self
+            .renaming
+            .as_ref()
+            .and_then(|rename| name_of(rename.index));
+
+        files.sort_by(|a, b| {
+            // Visible before hidden, directories before files, then by name
+            a.is_hidden
+                .cmp(&b.is_hidden)
+                .then(b.is_directory.cmp(&a.is_directory))
+                .then_with(|| a.name.cmp(&b.name))
         });
+        self.files = files;
 
-        Ok(())
+        let position = |name: &String| self.files.iter().position(|file| file.name == *name);
+        self.selected = selected_names.iter().filter_map(position).collect();
+        self.anchor = anchor_name.as_ref().and_then(position);
+        match renaming_name.as_ref().and_then(position) {
+            Some(index) => {
+                if let Some(rename) = &mut self.renaming {
+                    rename.index = index;
+                }
+            }
+            None => self.renaming = None,
+        }
     }
 
-    fn get_absolute_path_string(
-        current_directory: Option<PathBuf>,
-        item_name: &str,
-    ) -> Option<String> {
-        if let Some(current_dir) = current_directory {
-            let mut path = current_dir;
-            path.push(item_name);
-            Some(path.to_string_lossy().to_string())
-        } else {
-            None
+    /// Read the directory on a background thread. A new directory is shown as it is read,
+    /// a refresh of the same one replaces the listing when it is complete
+    fn start_listing(&mut self, streamed: bool, after_load: Option<AfterLoad>) {
+        let Some(directory) = self.current_directory.clone() else {
+            self.loading = None;
+            self.files.clear();
+            self.git_status.clear();
+            return;
+        };
+        if CONFIG
+            .get()
+            .and_then(|config| config.git_status)
+            .unwrap_or(true)
+        {
+            self.git_status.update(&self.ctx, &directory);
+        }
+
+        if streamed {
+            self.files.clear();
+            self.renaming = None;
+            // It may be another filesystem
+            self.disk_space = None;
+            self.file_types.start_listing();
         }
+        // What an unfinished refresh was to select still applies
+        let after_load = after_load.or_else(|| {
+            self.loading
+                .take()
+                .filter(|loading| !loading.streamed)
+                .and_then(|loading| loading.after_load)
+        });
+        self.loading = Some(Loading {
+            listing: Listing::start(&self.ctx, directory),
+            streamed,
+            held: Vec::new(),
+            after_load,
+            previous: None,
+        });
+    }
+
+    /// The banner of a directory that couldn't be read, with a button to its parent
+    fn show_listing_error(&mut self, ui: &mut egui::Ui) {
+        let Some((directory, message)) = &self.listing_error else {
+            return;
+        };
+
+        let mut go_up = None;
+        egui::Frame::new()
+            .fill(ui.visuals().extreme_bg_color)
+            .stroke(egui::Stroke::new(1.0, ui.visuals().warn_fg_color))
+            .inner_margin(4.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    let showing = match &self.current_directory {
+                        Some(current) if current != directory => {
+                            format!(" (showing {})", current.display())
+                        }
+                        _ => String::new(),
+                    };
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("⚠ {message}: {}{showing}", directory.display()),
+                    );
+                    if let Some(parent) = directory.parent()
+                        && ui.button("⬆ Go up").clicked()
+                    {
+                        go_up = Some(parent.to_path_buf());
+                    }
+                });
+            });
+
+        if let Some(parent) = go_up {
+            self.listing_error = None;
+            self.navigate_to(parent);
+        }
+    }
+
+    /// The "Loading… N items" row of a listing that takes a while
+    fn show_loading(&self, ui: &mut egui::Ui) {
+        let Some(loading) = &self.loading else {
+            return;
+        };
+
+        let elapsed = loading.listing.started_at.elapsed();
+        if !loading.streamed && elapsed < LOADING_DELAY {
+            ui.ctx().request_repaint_after(LOADING_DELAY - elapsed);
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label(format!("Loading… {} items", loading.listing.loaded));
+        });
     }
 }
@@ -0,0 +1,113 @@
+use std::{path::PathBuf, sync::mpsc};
+
+use eframe::egui;
+
+use crate::{
+    archive::{self, ArchiveEntry, ArchiveKind, EntryKind},
+    utils::to_human_readable_size,
+};
+
+/// How many entries are listed
+const LIMIT: usize = 500;
+
+/// The "Peek Contents" dialog, listing the first entries of an archive read on a background
+/// thread
+pub struct ArchivePeek {
+    name: String,
+    receiver: mpsc::Receiver<Result<(Vec<ArchiveEntry>, bool), String>>,
+    /// The entries and whether the archive has more, or why it couldn't be read
+    result: Option<Result<(Vec<ArchiveEntry>, bool), String>>,
+}
+
+impl ArchivePeek {
+    pub fn new(ctx: &egui::Context, path: PathBuf, kind: ArchiveKind) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        std::thread::spawn(move || {
+            let result = archive::peek(&path, kind, LIMIT).map_err(|e| e.to_string());
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+
+        Self {
+            name,
+            receiver,
+            result: None,
+        }
+    }
+
+    /// Draw the dialog. Returns `false` once it was closed
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        if self.result.is_none() {
+            self.result = self.receiver.try_recv().ok();
+        }
+
+        let mut open = true;
+        egui::Modal::new(egui::Id::new("archive_peek")).show(ctx, |ui| {
+            ui.set_width(420.0);
+            ui.heading(&self.name);
+            ui.separator();
+
+            match &self.result {
+                None => {
+                    ui.spinner();
+                }
+                Some(Err(e)) => {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        format!("Failed to read the archive: {e}"),
+                    );
+                }
+                Some(Ok((entries, more))) => {
+                    egui::ScrollArea::vertical()
+                        .max_height(360.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("archive_peek_grid")
+                                .num_columns(2)
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for entry in entries {
+                                        Self::show_entry(ui, entry);
+                                    }
+                                });
+                        });
+                    ui.weak(if *more {
+                        format!("Showing the first {LIMIT} entries")
+                    } else {
+                        format!("{} entries", entries.len())
+                    });
+                }
+            }
+
+            ui.separator();
+            if ui.button("Close").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                open = false;
+            }
+        });
+        open
+    }
+
+    fn show_entry(ui: &mut egui::Ui, entry: &ArchiveEntry) {
+        let name = if entry.kind == EntryKind::Directory {
+            format!("📁 {}", entry.name)
+        } else {
+            entry.name.clone()
+        };
+        if entry.path.is_none() {
+            ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {name}"))
+                .on_hover_text("It would end up outside of the destination, it isn't extracted");
+        } else {
+            ui.label(name);
+        }
+        ui.label(match entry.kind {
+            EntryKind::File => to_human_readable_size(entry.size),
+            EntryKind::Directory => "--".to_string(),
+            EntryKind::Other => "Not extracted".to_string(),
+        });
+        ui.end_row();
+    }
+}
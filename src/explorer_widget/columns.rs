@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+use crate::explorer_widget::FileItem;
+
+/// A column of the explorer's list
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Name,
+    Size,
+    Type,
+    Modified,
+    /// Mode bits like `ls -l` shows them
+    Permissions,
+    /// `user:group`
+    Owner,
+}
+
+impl Column {
+    /// All columns, in the order they are shown
+    pub const ALL: [Column; 6] = [
+        Column::Name,
+        Column::Size,
+        Column::Type,
+        Column::Modified,
+        Column::Permissions,
+        Column::Owner,
+    ];
+
+    /// Columns that can be hidden from the header's context menu
+    pub const OPTIONAL: [Column; 2] = [Column::Permissions, Column::Owner];
+
+    /// The columns shown without configuration
+    pub fn defaults() -> Vec<Column> {
+        Self::ALL
+            .into_iter()
+            .filter(|column| !Self::OPTIONAL.contains(column))
+            .collect()
+    }
+
+    /// `columns` in their usual order, with the name that is always shown
+    pub fn ordered(columns: &[Column]) -> Vec<Column> {
+        Self::ALL
+            .into_iter()
+            .filter(|column| *column == Column::Name || columns.contains(column))
+            .collect()
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Name => "Name",
+            Column::Size => "Size",
+            Column::Type => "Type",
+            Column::Modified => "Modified",
+            Column::Permissions => "Permissions",
+            Column::Owner => "Owner",
+        }
+    }
+
+    /// Columns can't be resized narrower than this. The name column keeps its default width
+    pub const MIN_WIDTH: f32 = 40.0;
+
+    /// Default width of the column. The name column takes the remaining space, at least this
+    /// much
+    pub fn width(self) -> f32 {
+        match self {
+            Column::Name => 100.0,
+            Column::Size => 80.0,
+            Column::Type => 80.0,
+            Column::Modified => 120.0,
+            Column::Permissions => 90.0,
+            Column::Owner => 110.0,
+        }
+    }
+
+    /// The text of the column for `file`. The name column is drawn with the icon instead
+    pub(super) fn text(self, file: &FileItem) -> &str {
+        match self {
+            Column::Name => &file.name,
+            Column::Size => &file.size,
+            Column::Type => &file.file_type,
+            Column::Modified => &file.modified_at,
+            Column::Permissions => &file.permissions,
+            Column::Owner => &file.owner,
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+};
+
+use eframe::egui;
+
+use crate::{
+    explorer_widget::size_scan::{ScanProgress, Totals},
+    utils::{to_display_path, to_human_readable_size},
+};
+
+const ROW_HEIGHT: f32 = 22.0;
+const NAME_WIDTH: f32 = 220.0;
+const SIZE_WIDTH: f32 = 90.0;
+
+/// An item of the analyzed directory and what was counted in it so far
+struct Child {
+    name: String,
+    path: PathBuf,
+    is_directory: bool,
+    totals: Arc<Totals>,
+}
+
+/// The items of a directory, added up one after another on a background thread. Stopped when
+/// dropped
+struct Scan {
+    receiver: mpsc::Receiver<Result<Vec<Child>, String>>,
+    cancelled: Arc<AtomicBool>,
+    /// The items once the directory was read, or why it couldn't be
+    children: Option<Result<Vec<Child>, String>>,
+}
+
+impl Scan {
+    fn start(ctx: &egui::Context, root: PathBuf, same_filesystem: bool) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let ctx = ctx.clone();
+        let thread_cancelled = Arc::clone(&cancelled);
+        std::thread::spawn(move || {
+            let children = match Self::read_children(&root) {
+                Ok(children) => children,
+                Err(e) => {
+                    let _ = sender.send(Err(e.to_string()));
+                    ctx.request_repaint();
+                    return;
+                }
+            };
+            let device = same_filesystem
+                .then(|| fs::metadata(&root).ok().map(|metadata| metadata.dev()))
+                .flatten();
+
+            let work: Vec<(PathBuf, Arc<Totals>)> = children
+                .iter()
+                .map(|child| (child.path.clone(), Arc::clone(&child.totals)))
+                .collect();
+            if sender.send(Ok(children)).is_err() {
+                return;
+            }
+            ctx.request_repaint();
+
+            for (path, totals) in work {
+                if thread_cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                totals.add_up(&ctx, vec![path], device, &thread_cancelled);
+            }
+            ctx.request_repaint();
+        });
+
+        Self {
+            receiver,
+            cancelled,
+            children: None,
+        }
+    }
+
+    fn read_children(root: &Path) -> std::io::Result<Vec<Child>> {
+        let mut children = Vec::new();
+        for entry in fs::read_dir(root)? {
+            let entry = entry?;
+            children.push(Child {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                path: entry.path(),
+                // Links aren't followed, they count as themselves
+                is_directory: entry.file_type().is_ok_and(|kind| kind.is_dir()),
+                totals: Arc::default(),
+            });
+        }
+        Ok(children)
+    }
+}
+
+impl Drop for Scan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The "Analyze Disk Usage" view: the items of a directory by the size of everything in them
+pub struct DiskUsage {
+    root: PathBuf,
+    /// Directories descended from, for Back
+    history: Vec<PathBuf>,
+    /// Leave out directories on other filesystems, like mounts below the root
+    same_filesystem: bool,
+    scan: Scan,
+}
+
+impl DiskUsage {
+    pub fn new(ctx: &egui::Context, root: PathBuf) -> Self {
+        Self {
+            scan: Scan::start(ctx, root.clone(), true),
+            root,
+            history: Vec::new(),
+            same_filesystem: true,
+        }
+    }
+
+    fn restart(&mut self, ctx: &egui::Context) {
+        self.scan = Scan::start(ctx, self.root.clone(), self.same_filesystem);
+    }
+
+    /// Draw the view in place of the listing. Returns `false` once it was closed
+    pub fn show(&mut self, ui: &mut egui::Ui, has_keyboard: bool) -> bool {
+        if self.scan.children.is_none() {
+            self.scan.children = self.scan.receiver.try_recv().ok();
+        }
+
+        let mut open = true;
+        let mut back = false;
+        let mut descend = None;
+
+        // Sorted by what was counted so far, the order settles as the scan goes on
+        let mut rows: Vec<(&Child, ScanProgress)> = match &self.scan.children {
+            Some(Ok(children)) => children
+                .iter()
+                .map(|child| (child, child.totals.progress()))
+                .collect(),
+            _ => Vec::new(),
+        };
+        rows.sort_by_key(|(_, progress)| std::cmp::Reverse(progress.bytes));
+        let total: u64 = rows.iter().map(|(_, progress)| progress.bytes).sum();
+        let largest = rows.first().map_or(0, |(_, progress)| progress.bytes);
+        let finished =
+            self.scan.children.is_some() && rows.iter().all(|(_, progress)| progress.finished);
+
+        ui.horizontal(|ui| {
+            if ui
+                .button("⬅ Back")
+                .on_hover_text("The directory analyzed before, or the listing")
+                .clicked()
+            {
+                back = true;
+            }
+            ui.strong(to_display_path(&self.root));
+            ui.label(to_human_readable_size(total));
+            if !finished {
+                ui.spinner();
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .button("✖")
+                    .on_hover_text("Back to the listing")
+                    .clicked()
+                {
+                    open = false;
+                }
+                if ui
+                    .checkbox(&mut self.same_filesystem, "Same filesystem")
+                    .on_hover_text("Leave out directories of other filesystems mounted below")
+                    .changed()
+                {
+                    descend = Some(self.root.clone());
+                }
+            });
+        });
+        if has_keyboard {
+            ui.input(|i| {
+                if i.key_pressed(egui::Key::Escape) {
+                    open = false;
+                }
+                if i.key_pressed(egui::Key::Backspace) {
+                    back = true;
+                }
+            });
+        }
+        ui.separator();
+
+        if let Some(Err(e)) = &self.scan.children {
+            ui.colored_label(
+                ui.visuals().error_fg_color,
+                format!("Failed to read the directory: {e}"),
+            );
+        }
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show_rows(ui, ROW_HEIGHT, rows.len(), |ui, range| {
+                for (child, progress) in &rows[range] {
+                    let response = ui
+                        .horizontal(|ui| {
+                            let name = if child.is_directory {
+                                format!("📁 {}", child.name)
+                            } else {
+                                child.name.clone()
+                            };
+                            let response = ui.add_sized(
+                                [NAME_WIDTH, ROW_HEIGHT],
+                                egui::Button::selectable(false, name).truncate(),
+                            );
+                            let mut size = to_human_readable_size(progress.bytes);
+                            if !progress.finished {
+                                size.push('…');
+                            }
+                            ui.add_sized([SIZE_WIDTH, ROW_HEIGHT], egui::Label::new(size));
+                            let fraction = if largest == 0 {
+                                0.0
+                            } else {
+                                progress.bytes as f32 / largest as f32
+                            };
+                            let mut bar = egui::ProgressBar::new(fraction);
+                            if total > 0 {
+                                bar = bar.text(format!(
+                                    "{:.1}%",
+                                    progress.bytes as f64 * 100.0 / total as f64
+                                ));
+                            }
+                            ui.add(bar);
+                            response
+                        })
+                        .inner;
+                    let response = if progress.errors > 0 {
+                        response.on_hover_text(format!(
+                            "{} item(s) couldn't be read and are missing",
+                            progress.errors
+                        ))
+                    } else {
+                        response
+                    };
+                    if child.is_directory && response.double_clicked() {
+                        descend = Some(child.path.clone());
+                    }
+                }
+            });
+
+        if let Some(directory) = descend {
+            if directory != self.root {
+                self.history
+                    .push(std::mem::replace(&mut self.root, directory));
+            }
+            self.restart(ui.ctx());
+        } else if back {
+            match self.history.pop() {
+                Some(parent) => {
+                    self.root = parent;
+                    self.restart(ui.ctx());
+                }
+                None => open = false,
+            }
+        }
+        open
+    }
+}
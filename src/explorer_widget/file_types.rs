@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    time::Duration,
+};
+
+use eframe::egui;
+
+use crate::{
+    icon_finder::{IconFinder, IconResolution},
+    utils::get_desc_from_mime_type,
+};
+
+/// How many names' types the worker remembers
+const CACHE_CAPACITY: usize = 512;
+/// Size of the themed icons looked up
+const ICON_SIZE: i32 = 48;
+/// Time per frame spent looking up icons of new types
+const ICON_TIME_BUDGET: Duration = Duration::from_millis(4);
+
+struct Job {
+    generation: u64,
+    name: String,
+}
+
+/// The type of a file, as found by the worker
+struct Found {
+    generation: u64,
+    name: String,
+    mime_type: String,
+    description: String,
+}
+
+/// What the explorer shows for a file once its type is known
+pub struct Resolved {
+    pub name: String,
+    pub description: String,
+    pub icon: egui::ImageSource<'static>,
+}
+
+/// Guesses MIME types and their descriptions on a worker thread, so that a large directory
+/// is listed at once and the types fill in afterwards
+pub struct FileTypes {
+    jobs: mpsc::Sender<Job>,
+    found: mpsc::Receiver<Found>,
+    /// Bumped for every listing, jobs and results of older ones are dropped
+    generation: Arc<AtomicU64>,
+    /// Icons by MIME type. Looked up here since GTK's icon theme belongs to the UI thread,
+    /// and there are only a few types per directory
+    icon_finder: IconFinder,
+    /// The icons uploaded so far by MIME type and the color symbolic icons were drawn in, none
+    /// where the theme's icon couldn't be loaded
+    textures: HashMap<(String, egui::Color32), Option<egui::TextureHandle>>,
+    /// Types found whose icons aren't looked up yet
+    waiting: Vec<Found>,
+    ctx: egui::Context,
+}
+
+impl FileTypes {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (found_sender, found) = mpsc::channel();
+        let generation = Arc::new(AtomicU64::new(0));
+
+        let worker_ctx = ctx.clone();
+        let current = Arc::clone(&generation);
+        std::thread::Builder::new()
+            .name("file-types".to_string())
+            .spawn(move || Self::work(&worker_ctx, &job_receiver, &found_sender, &current))
+            .expect("Failed to spawn the file type thread");
+
+        Self {
+            jobs,
+            found,
+            generation,
+            icon_finder: IconFinder::new(),
+            textures: HashMap::new(),
+            waiting: Vec::new(),
+            ctx: ctx.clone(),
+        }
+    }
+
+    fn work(
+        ctx: &egui::Context,
+        jobs: &mpsc::Receiver<Job>,
+        found: &mpsc::Sender<Found>,
+        current: &AtomicU64,
+    ) {
+        let mut cache: LruCache<(String, String)> = LruCache::new(CACHE_CAPACITY);
+        while let Ok(job) = jobs.recv() {
+            if job.generation != current.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            let key = cache_key(&job.name).to_string();
+            let (mime_type, description) = match cache.get(&key) {
+                Some(cached) => cached.clone(),
+                None => {
+                    // Guessed from the name alone, reading the content would be too slow
+                    let (mime_type, _) = gio::content_type_guess(Some(Path::new(&job.name)), None);
+                    let description = get_desc_from_mime_type(&mime_type);
+                    let value = (mime_type.to_string(), description);
+                    cache.insert(key, value.clone());
+                    value
+                }
+            };
+
+            let result = Found {
+                generation: job.generation,
+                name: job.name,
+                mime_type,
+                description,
+            };
+            if found.send(result).is_err() {
+                break;
+            }
+            ctx.request_repaint();
+        }
+    }
+
+    /// Forget the jobs of the previous listing
+    pub fn start_listing(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Look up the type of the file `name` of the current listing
+    pub fn request(&self, name: String) {
+        let _ = self.jobs.send(Job {
+            generation: self.generation.load(Ordering::Relaxed),
+            name,
+        });
+    }
+
+    /// Types found since the last call whose icons are ready. Icons are looked up for a few
+    /// milliseconds per frame, the files of types still waiting come with later calls
+    pub fn poll(&mut self) -> Vec<Resolved> {
+        let generation = self.generation.load(Ordering::Relaxed);
+        self.waiting.retain(|found| found.generation == generation);
+        while let Ok(found) = self.found.try_recv() {
+            if found.generation == generation {
+                self.icon_finder.request(&found.mime_type, ICON_SIZE);
+                self.waiting.push(found);
+            }
+        }
+
+        self.icon_finder.poll(ICON_TIME_BUDGET);
+        if self.icon_finder.has_requests() {
+            self.ctx.request_repaint();
+        }
+
+        let (ready, waiting): (Vec<Found>, Vec<Found>) = std::mem::take(&mut self.waiting)
+            .into_iter()
+            .partition(|found| self.icon_finder.is_resolved(&found.mime_type, ICON_SIZE));
+        self.waiting = waiting;
+        ready
+            .into_iter()
+            .map(|found| Resolved {
+                icon: self.icon(&found.mime_type),
+                name: found.name,
+                description: found.description,
+            })
+            .collect()
+    }
+
+    /// The themed icon of `mime_type`, or a generic one. Decoded and uploaded once, then the
+    /// texture is reused
+    pub fn icon(&mut self, mime_type: &str) -> egui::ImageSource<'static> {
+        if self.icon_finder.check_theme() {
+            self.textures.clear();
+        }
+
+        let foreground = self.ctx.style().visuals.text_color();
+        let texture = self
+            .textures
+            .entry((mime_type.to_string(), foreground))
+            .or_insert_with(|| {
+                let image = self
+                    .icon_finder
+                    .load_icon(mime_type, ICON_SIZE, foreground)?;
+                Some(self.ctx.load_texture(
+                    format!("icon:{mime_type}"),
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [image.width, image.height],
+                        &image.pixels,
+                    ),
+                    egui::TextureOptions::LINEAR,
+                ))
+            });
+        match texture {
+            Some(texture) => {
+                egui::ImageSource::Texture(egui::load::SizedTexture::from_handle(texture))
+            }
+            None => self
+                .icon_finder
+                .resolve(mime_type, ICON_SIZE)
+                .image_source(mime_type),
+        }
+    }
+
+    /// The type of the file at `path` and its icon, looked up right away
+    pub fn icon_for_path(&mut self, path: &Path) -> (String, egui::ImageSource<'static>) {
+        let (mime_type, _) = self.icon_finder.find_icon_for_path(path, ICON_SIZE);
+        let icon = self.icon(&mime_type);
+        (mime_type, icon)
+    }
+
+    /// Shown until the type of a file is known
+    pub fn placeholder_icon() -> egui::ImageSource<'static> {
+        IconResolution::default().image_source("")
+    }
+}
+
+/// Names with the same key get the same type: the part after the first dot, which keeps
+/// double extensions like `.tar.gz` apart, or the whole name for `Makefile` and the like
+fn cache_key(name: &str) -> &str {
+    name.trim_start_matches('.')
+        .split_once('.')
+        .map_or(name, |(_, extensions)| extensions)
+}
+
+/// A map that drops the least recently used entry when it is full
+struct LruCache<V> {
+    capacity: usize,
+    entries: HashMap<String, (V, u64)>,
+    clock: u64,
+}
+
+impl<V> LruCache<V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<&V> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(key).map(|(value, used)| {
+            *used = clock;
+            &*value
+        })
+    }
+
+    fn insert(&mut self, key: String, value: V) {
+        if self.entries.len() >= self.capacity
+            && let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone())
+        {
+            self.entries.remove(&oldest);
+        }
+        self.clock += 1;
+        self.entries.insert(key, (value, self.clock));
+    }
+}
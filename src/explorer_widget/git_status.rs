@@ -0,0 +1,189 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc,
+};
+
+use eframe::egui;
+
+/// The state of a file in git, or the most notable state below a directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    Ignored,
+    Untracked,
+    /// Changes are staged, and the work tree has no others
+    Staged,
+    /// The work tree has changes that aren't staged, or a merge conflict
+    Modified,
+}
+
+impl GitStatus {
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            GitStatus::Ignored => egui::Color32::DARK_GRAY,
+            GitStatus::Untracked => egui::Color32::GRAY,
+            GitStatus::Staged => egui::Color32::from_rgb(0x4c, 0xaf, 0x50),
+            GitStatus::Modified => egui::Color32::from_rgb(0xff, 0x98, 0x00),
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            GitStatus::Ignored => "Ignored by git",
+            GitStatus::Untracked => "Untracked",
+            GitStatus::Staged => "Staged",
+            GitStatus::Modified => "Modified",
+        }
+    }
+}
+
+/// Statuses of the current directory's items, read with `git status` on a background thread
+#[derive(Default)]
+pub struct GitStatuses {
+    statuses: HashMap<String, GitStatus>,
+    /// The directory being read, and where the result arrives
+    running: Option<(PathBuf, mpsc::Receiver<HashMap<String, GitStatus>>)>,
+    /// The directory changed again while it was read
+    stale: bool,
+}
+
+impl GitStatuses {
+    /// The status of the item `name` in the current directory
+    pub fn get(&self, name: &str) -> Option<GitStatus> {
+        self.statuses.get(name).copied()
+    }
+
+    /// Read the statuses of `directory` again
+    pub fn update(&mut self, ctx: &egui::Context, directory: &Path) {
+        match &self.running {
+            Some((running, _)) if running == directory => {
+                self.stale = true;
+                return;
+            }
+            // The result for the old directory is of no use
+            Some(_) => self.statuses.clear(),
+            None => {}
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let thread_directory = directory.to_path_buf();
+        std::thread::spawn(move || {
+            // Outside of a work tree there is nothing to show
+            let statuses = read_statuses(&thread_directory).unwrap_or_default();
+            if sender.send(statuses).is_ok() {
+                ctx.request_repaint();
+            }
+        });
+        self.running = Some((directory.to_path_buf(), receiver));
+        self.stale = false;
+    }
+
+    /// Take the result of a finished `git status`
+    pub fn poll(&mut self, ctx: &egui::Context, current: Option<&Path>) {
+        let Some((directory, receiver)) = &self.running else {
+            return;
+        };
+        let Ok(statuses) = receiver.try_recv() else {
+            return;
+        };
+
+        let directory = directory.clone();
+        self.running = None;
+        if current != Some(directory.as_path()) {
+            return;
+        }
+        self.statuses = statuses;
+        if self.stale {
+            self.update(ctx, &directory);
+        }
+    }
+
+    /// Forget the statuses, when git status is turned off or there is no directory
+    pub fn clear(&mut self) {
+        self.statuses.clear();
+        self.running = None;
+    }
+}
+
+/// Statuses by the name of the item of `directory` they are in. `None` when `directory` isn't
+/// in a git work tree
+fn read_statuses(directory: &Path) -> Option<HashMap<String, GitStatus>> {
+    let prefix = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(["rev-parse", "--show-prefix"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let prefix = String::from_utf8_lossy(&prefix.stdout)
+        .trim_end()
+        .to_string();
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args([
+            "status",
+            "--porcelain=v2",
+            "-z",
+            "--ignored",
+            "--untracked-files=normal",
+            "--",
+            ".",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    let mut statuses = HashMap::new();
+    let mut records = output.stdout.split(|&byte| byte == 0);
+    while let Some(record) = records.next() {
+        let record = String::from_utf8_lossy(record);
+        let mut fields = record.splitn(2, ' ');
+        let (Some(kind), Some(rest)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+
+        let (status, path) = match kind {
+            "1" | "2" | "u" => {
+                // Header fields before the path: 7 for changes, 8 for renames, 9 for conflicts
+                let skipped = match kind {
+                    "1" => 7,
+                    "2" => 8,
+                    _ => 9,
+                };
+                let Some(path) = rest.splitn(skipped + 1, ' ').nth(skipped) else {
+                    continue;
+                };
+                if kind == "2" {
+                    // A rename is followed by the original path
+                    records.next();
+                }
+                let xy = rest.as_bytes();
+                let status = if kind == "u" || xy.get(1).is_some_and(|&y| y != b'.') {
+                    GitStatus::Modified
+                } else {
+                    GitStatus::Staged
+                };
+                (status, path)
+            }
+            "?" => (GitStatus::Untracked, rest),
+            "!" => (GitStatus::Ignored, rest),
+            _ => continue,
+        };
+
+        // Paths are relative to the top of the work tree
+        let Some(relative) = path.strip_prefix(prefix.as_str()) else {
+            continue;
+        };
+        let name = relative.split('/').next().unwrap_or(relative);
+        if name.is_empty() {
+            continue;
+        }
+        let entry = statuses.entry(name.to_string()).or_insert(status);
+        *entry = (*entry).max(status);
+    }
+    Some(statuses)
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+use eframe::egui;
+
+use crate::{
+    explorer_widget::{FileItem, file_types::FileTypes},
+    utils::{disk_space, format_mode, group_name, to_human_readable_size, user_name},
+};
+
+/// Items sent to the UI at once
+const BATCH_SIZE: usize = 200;
+/// Longest time read items wait for their batch to fill, so that slow disks show progress
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+pub enum Update {
+    Batch(Vec<FileItem>),
+    /// The directory was read
+    Finished {
+        /// Why entries couldn't be read
+        errors: Vec<String>,
+        /// Available and total bytes of its filesystem, if they could be found
+        disk_space: Option<(u64, u64)>,
+    },
+    /// The directory itself couldn't be read
+    Failed(std::io::Error),
+}
+
+/// A directory being read on a background thread, stopped when dropped
+pub struct Listing {
+    receiver: mpsc::Receiver<Update>,
+    cancelled: Arc<AtomicBool>,
+    /// How many items arrived so far
+    pub loaded: usize,
+    pub started_at: Instant,
+}
+
+impl Listing {
+    pub fn start(ctx: &egui::Context, directory: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let ctx = ctx.clone();
+        let thread_cancelled = Arc::clone(&cancelled);
+        std::thread::spawn(move || {
+            let update = Self::read(&directory, &sender, &ctx, &thread_cancelled);
+            let _ = sender.send(update);
+            ctx.request_repaint();
+        });
+
+        Self {
+            receiver,
+            cancelled,
+            loaded: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    fn read(
+        directory: &Path,
+        sender: &mpsc::Sender<Update>,
+        ctx: &egui::Context,
+        cancelled: &AtomicBool,
+    ) -> Update {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) => return Update::Failed(e),
+        };
+
+        let mut batch = Vec::new();
+        if directory.parent().is_some() {
+            batch.push(FileItem {
+                name: "..".to_string(),
+                size: "--".to_string(),
+                bytes: None,
+                file_type: "Directory".to_string(),
+                modified_at: "--".to_string(),
+                is_directory: true,
+                is_hidden: false,
+                is_executable: false,
+                is_symlink: false,
+                link_target: None,
+                is_broken_link: false,
+                permissions: "--".to_string(),
+                owner: "--".to_string(),
+                error: None,
+                icon: FileTypes::placeholder_icon(),
+            });
+        }
+
+        let mut owners = Owners::default();
+
+        let mut errors = Vec::new();
+        let mut sent_at = Instant::now();
+        for entry in entries {
+            if cancelled.load(Ordering::Relaxed) {
+                break;
+            }
+
+            match entry {
+                Ok(entry) => {
+                    let (item, error) = Self::read_entry(&entry, &mut owners);
+                    batch.push(item);
+                    errors.extend(error);
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+
+            if batch.len() >= BATCH_SIZE || sent_at.elapsed() >= BATCH_INTERVAL {
+                if sender
+                    .send(Update::Batch(std::mem::take(&mut batch)))
+                    .is_err()
+                {
+                    break;
+                }
+                ctx.request_repaint();
+                sent_at = Instant::now();
+            }
+        }
+
+        if !batch.is_empty() {
+            let _ = sender.send(Update::Batch(batch));
+        }
+        Update::Finished {
+            errors,
+            disk_space: disk_space(directory),
+        }
+    }
+
+    /// The item for an entry. Fields whose metadata couldn't be read show "?", with the
+    /// error returned next to the item
+    fn read_entry(entry: &fs::DirEntry, owners: &mut Owners) -> (FileItem, Option<String>) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+
+        // The entry's own metadata, not following links
+        let link_metadata = entry.metadata();
+        let is_symlink = link_metadata
+            .as_ref()
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+        // Mode and owner are the link's own, like `ls -l` shows them
+        let (permissions, owner) = match &link_metadata {
+            Ok(metadata) => {
+                let kind = if is_symlink {
+                    'l'
+                } else if metadata.is_dir() {
+                    'd'
+                } else {
+                    '-'
+                };
+                (
+                    format!("{kind}{}", format_mode(metadata.mode())),
+                    owners.describe(metadata.uid(), metadata.gid()),
+                )
+            }
+            Err(_) => ("?".to_string(), "?".to_string()),
+        };
+        // Links are listed like their targets
+        let metadata = if is_symlink {
+            fs::metadata(&path)
+        } else {
+            link_metadata
+        };
+
+        let mut item = FileItem {
+            is_hidden: name.starts_with('.'),
+            name,
+            size: "?".to_string(),
+            bytes: None,
+            file_type: String::new(),
+            modified_at: "?".to_string(),
+            is_directory: false,
+            is_executable: false,
+            is_symlink,
+            link_target: is_symlink.then(|| fs::read_link(&path).ok()).flatten(),
+            is_broken_link: false,
+            permissions,
+            owner,
+            error: None,
+            icon: FileTypes::placeholder_icon(),
+        };
+
+        let metadata = match metadata {
+            Ok(metadata) => metadata,
+            Err(_) if is_symlink => {
+                item.is_broken_link = true;
+                item.file_type = "Broken link".to_string();
+                return (item, None);
+            }
+            Err(e) => {
+                item.error = Some(e.to_string());
+                let error = format!("\"{}\": {e}", item.name);
+                return (item, Some(error));
+            }
+        };
+
+        if metadata.is_dir() {
+            item.is_directory = true;
+            item.size = "--".to_string();
+            item.file_type = "Directory".to_string();
+            item.modified_at = "--".to_string();
+        } else {
+            item.size = to_human_readable_size(metadata.len());
+            item.bytes = Some(metadata.len());
+            if let Ok(modified) = metadata.modified() {
+                let modified: DateTime<Local> = modified.into();
+                item.modified_at = modified.format("%Y-%m-%d %H:%M").to_string();
+            }
+            item.is_executable = is_executable(&path, &metadata);
+        }
+        (item, None)
+    }
+
+    /// Updates that arrived since the last call
+    pub fn poll(&mut self) -> Vec<Update> {
+        let updates: Vec<Update> = self.receiver.try_iter().collect();
+        for update in &updates {
+            if let Update::Batch(items) = update {
+                self.loaded += items.len();
+            }
+        }
+        updates
+    }
+}
+
+impl Drop for Listing {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// `user:group` names by ID, each looked up once per listing
+#[derive(Default)]
+struct Owners {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl Owners {
+    fn describe(&mut self, uid: u32, gid: u32) -> String {
+        let user = self
+            .users
+            .entry(uid)
+            .or_insert_with(|| user_name(uid).unwrap_or_else(|| uid.to_string()));
+        let group = self
+            .groups
+            .entry(gid)
+            .or_insert_with(|| group_name(gid).unwrap_or_else(|| gid.to_string()));
+        format!("{user}:{group}")
+    }
+}
+
+/// Whether the file can be run: has an executable bit or is a script with a shebang
+fn is_executable(path: &Path, metadata: &fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return true;
+        }
+    }
+
+    let is_script = path
+        .extension()
+        .is_some_and(|extension| extension == "sh" || extension == "py");
+    let mut shebang = [0u8; 2];
+    is_script
+        && fs::File::open(path)
+            .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut shebang))
+            .is_ok()
+        && &shebang == b"#!"
+}
@@ -0,0 +1,194 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use eframe::egui;
+use gio::prelude::*;
+
+use crate::icon_finder::IconFinder;
+
+/// Size of the application icons in the menu
+const ICON_SIZE: i32 = 16;
+
+/// An application files can be opened with
+struct App {
+    info: gio::AppInfo,
+    name: String,
+    /// `file://` URI of its icon
+    icon: Option<String>,
+}
+
+impl App {
+    fn new(info: gio::AppInfo, icon_finder: &IconFinder) -> Self {
+        Self {
+            name: info.name().to_string(),
+            icon: info
+                .icon()
+                .and_then(|icon| icon_finder.find_gicon(&icon, ICON_SIZE))
+                .map(|path| format!("file://{}", path.display())),
+            info,
+        }
+    }
+
+    fn button(&self, ui: &mut egui::Ui) -> egui::Response {
+        match &self.icon {
+            Some(icon) => ui.add(egui::Button::image_and_text(
+                egui::Image::new(icon.as_str())
+                    .fit_to_exact_size(egui::Vec2::splat(ICON_SIZE as f32)),
+                &self.name,
+            )),
+            None => ui.button(&self.name),
+        }
+    }
+}
+
+/// The "Other application…" dialog
+struct Chooser {
+    paths: Vec<PathBuf>,
+    mime_type: String,
+    filter: String,
+}
+
+/// The "Open with…" menu and its dialog listing all applications
+#[derive(Default)]
+pub struct OpenWith {
+    /// Applications registered for a MIME type, read once per type
+    apps: HashMap<String, Vec<App>>,
+    /// All applications, read when the dialog is first opened
+    all_apps: Option<Vec<App>>,
+    chooser: Option<Chooser>,
+    /// Make the chosen application the default for the type
+    set_as_default: bool,
+    icon_finder: IconFinder,
+}
+
+impl OpenWith {
+    /// Whether the dialog is open and takes the keyboard
+    pub fn is_choosing(&self) -> bool {
+        self.chooser.is_some()
+    }
+
+    fn mime_type(path: &std::path::Path) -> String {
+        if path.is_dir() {
+            "inode/directory".to_string()
+        } else {
+            gio::content_type_guess(Some(path), None).0.to_string()
+        }
+    }
+
+    /// The contents of the submenu for `paths`, typed like the first of them
+    pub fn menu(&mut self, ui: &mut egui::Ui, paths: &[PathBuf]) -> Result<(), String> {
+        let Some(first) = paths.first() else {
+            return Ok(());
+        };
+        let mime_type = Self::mime_type(first);
+
+        let apps = self.apps.entry(mime_type.clone()).or_insert_with(|| {
+            gio::AppInfo::all_for_type(&mime_type)
+                .into_iter()
+                .map(|info| App::new(info, &self.icon_finder))
+                .collect()
+        });
+        let mut chosen = None;
+        if apps.is_empty() {
+            ui.weak("No applications for this type");
+        }
+        for app in apps.iter() {
+            if app.button(ui).clicked() {
+                chosen = Some(app.info.clone());
+            }
+        }
+
+        ui.separator();
+        ui.checkbox(&mut self.set_as_default, "Set as default");
+        if ui.button("Other application…").clicked() {
+            self.chooser = Some(Chooser {
+                paths: paths.to_vec(),
+                mime_type: mime_type.clone(),
+                filter: String::new(),
+            });
+            ui.close();
+        }
+
+        match chosen {
+            Some(info) => {
+                ui.close();
+                self.launch(&info, paths, &mime_type)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn launch(
+        &self,
+        info: &gio::AppInfo,
+        paths: &[PathBuf],
+        mime_type: &str,
+    ) -> Result<(), String> {
+        let files: Vec<gio::File> = paths.iter().map(gio::File::for_path).collect();
+        info.launch(&files, None::<&gio::AppLaunchContext>)
+            .map_err(|e| format!("Failed to open with {}: {e}", info.name()))?;
+        if self.set_as_default {
+            info.set_as_default_for_type(mime_type)
+                .map_err(|e| format!("Failed to set {} as the default: {e}", info.name()))?;
+        }
+        Ok(())
+    }
+
+    /// Draw the "Other application…" dialog, if it is open
+    pub fn show_chooser(&mut self, ctx: &egui::Context) -> Result<(), String> {
+        let Some(chooser) = &mut self.chooser else {
+            return Ok(());
+        };
+        let all_apps = self.all_apps.get_or_insert_with(|| {
+            let mut apps: Vec<App> = gio::AppInfo::all()
+                .into_iter()
+                .filter(|info| info.should_show())
+                .map(|info| App::new(info, &self.icon_finder))
+                .collect();
+            apps.sort_by_key(|app| app.name.to_lowercase());
+            apps
+        });
+
+        let mut chosen = None;
+        let mut closed = false;
+        egui::Modal::new(egui::Id::new("open_with")).show(ctx, |ui| {
+            ui.heading("Open with");
+            let filter = ui.add(
+                egui::TextEdit::singleline(&mut chooser.filter).hint_text("Filter applications"),
+            );
+            filter.request_focus();
+
+            let filter = chooser.filter.to_lowercase();
+            egui::ScrollArea::vertical()
+                .max_height(300.0)
+                .show(ui, |ui| {
+                    for app in all_apps
+                        .iter()
+                        .filter(|app| app.name.to_lowercase().contains(&filter))
+                    {
+                        if app.button(ui).clicked() {
+                            chosen = Some(app.info.clone());
+                        }
+                    }
+                });
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.set_as_default, "Set as default");
+                if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    closed = true;
+                }
+            });
+        });
+
+        if let Some(info) = chosen {
+            let Some(chooser) = self.chooser.take() else {
+                return Ok(());
+            };
+            return self.launch(&info, &chooser.paths, &chooser.mime_type);
+        }
+        if closed {
+            self.chooser = None;
+        }
+        Ok(())
+    }
+}
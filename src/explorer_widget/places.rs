@@ -0,0 +1,269 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use gio::glib::{UserDirectory, home_dir, user_special_dir};
+
+use crate::utils::{file_uri_to_path, path_to_file_uri};
+
+/// How often the mounted volumes are read again
+const MOUNTS_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Payload for reordering bookmarks by dragging, the index of the dragged one
+struct BookmarkDrag(usize);
+
+/// A directory listed in the sidebar
+struct Place {
+    label: String,
+    path: PathBuf,
+}
+
+/// The places sidebar: standard directories, mounted volumes and the bookmarks shared with
+/// other file managers through `~/.config/gtk-3.0/bookmarks`
+pub struct Places {
+    standard: Vec<Place>,
+    mounts: Vec<Place>,
+    mounts_read_at: Option<Instant>,
+    bookmarks: Vec<Place>,
+}
+
+impl Places {
+    pub fn new() -> Self {
+        let mut standard = vec![Place {
+            label: "Home".to_string(),
+            path: home_dir(),
+        }];
+        for (label, directory) in [
+            ("Desktop", UserDirectory::Desktop),
+            ("Documents", UserDirectory::Documents),
+            ("Downloads", UserDirectory::Downloads),
+            ("Music", UserDirectory::Music),
+            ("Pictures", UserDirectory::Pictures),
+            ("Videos", UserDirectory::Videos),
+        ] {
+            // Unset user directories point at the home directory
+            if let Some(path) = user_special_dir(directory).filter(|path| *path != home_dir()) {
+                standard.push(Place {
+                    label: label.to_string(),
+                    path,
+                });
+            }
+        }
+
+        Self {
+            standard,
+            mounts: Vec::new(),
+            mounts_read_at: None,
+            bookmarks: Self::load_bookmarks(),
+        }
+    }
+
+    fn bookmarks_path() -> PathBuf {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| home_dir().join(".config"));
+        config_home.join("gtk-3.0/bookmarks")
+    }
+
+    /// Read the bookmarks, lines of a `file://` URI and an optional label
+    fn load_bookmarks() -> Vec<Place> {
+        let Ok(content) = std::fs::read_to_string(Self::bookmarks_path()) else {
+            return Vec::new();
+        };
+
+        content
+            .lines()
+            .filter_map(|line| {
+                let (uri, label) = line.split_once(' ').unwrap_or((line, ""));
+                let path = file_uri_to_path(uri)?;
+                let label = if label.is_empty() {
+                    Self::default_label(&path)
+                } else {
+                    label.to_string()
+                };
+                Some(Place { label, path })
+            })
+            .collect()
+    }
+
+    fn save_bookmarks(&self) -> anyhow::Result<()> {
+        let path = Self::bookmarks_path();
+        // Bookmarks of other schemes (sftp://...) that we don't show are kept
+        let foreign: Vec<String> = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.starts_with("file://"))
+            .map(str::to_string)
+            .collect();
+
+        let mut content = String::new();
+        for bookmark in &self.bookmarks {
+            content.push_str(&path_to_file_uri(&bookmark.path));
+            if bookmark.label != Self::default_label(&bookmark.path) {
+                content.push(' ');
+                content.push_str(&bookmark.label);
+            }
+            content.push('\n');
+        }
+        for line in foreign {
+            content.push_str(&line);
+            content.push('\n');
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    fn default_label(path: &Path) -> String {
+        path.file_name()
+            .map_or("/".to_string(), |name| name.to_string_lossy().into_owned())
+    }
+
+    pub fn add_bookmark(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        if self.bookmarks.iter().any(|bookmark| bookmark.path == path) {
+            return Ok(());
+        }
+        self.bookmarks.push(Place {
+            label: Self::default_label(&path),
+            path,
+        });
+        self.save_bookmarks()
+    }
+
+    /// Removable and other mounted disks, from `/proc/mounts`
+    fn read_mounts() -> Vec<Place> {
+        let Ok(mounts) = std::fs::read_to_string("/proc/mounts") else {
+            return Vec::new();
+        };
+
+        mounts
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(' ');
+                let device = fields.next()?;
+                // Spaces and other special characters are escaped as octal `\040`
+                let mount_point = unescape_octal(fields.next()?);
+                let is_volume = device.starts_with("/dev/")
+                    && ["/media/", "/run/media/", "/mnt/"]
+                        .iter()
+                        .any(|prefix| mount_point.starts_with(prefix));
+                is_volume.then(|| {
+                    let path = PathBuf::from(mount_point);
+                    Place {
+                        label: Self::default_label(&path),
+                        path,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Draw the sidebar. Returns the directory clicked, and reports failures to save the
+    /// bookmarks through `Err`
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        current: Option<&Path>,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        if self
+            .mounts_read_at
+            .is_none_or(|read_at| read_at.elapsed() > MOUNTS_REFRESH_INTERVAL)
+        {
+            self.mounts = Self::read_mounts();
+            self.mounts_read_at = Some(Instant::now());
+        }
+
+        let mut target = None;
+        let mut moved = None;
+        let mut removed = None;
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.strong("Places");
+            for place in self.standard.iter().chain(&self.mounts) {
+                if Self::place_button(ui, place, current).clicked() {
+                    target = Some(place.path.clone());
+                }
+            }
+
+            if !self.bookmarks.is_empty() {
+                ui.separator();
+                ui.strong("Bookmarks");
+            }
+            for (index, bookmark) in self.bookmarks.iter().enumerate() {
+                let response = Self::place_button(ui, bookmark, current);
+                if response.clicked() {
+                    target = Some(bookmark.path.clone());
+                }
+
+                response.dnd_set_drag_payload(BookmarkDrag(index));
+                if let Some(from) = response.dnd_release_payload::<BookmarkDrag>() {
+                    moved = Some((from.0, index));
+                }
+                if response.dnd_hover_payload::<BookmarkDrag>().is_some() {
+                    ui.painter().hline(
+                        response.rect.x_range(),
+                        response.rect.top(),
+                        ui.visuals().selection.stroke,
+                    );
+                }
+
+                response.context_menu(|ui| {
+                    if ui.button("Remove bookmark").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+        });
+
+        let changed = moved.is_some() || removed.is_some();
+        if let Some((from, to)) = moved {
+            let bookmark = self.bookmarks.remove(from);
+            self.bookmarks.insert(to, bookmark);
+        }
+        if let Some(index) = removed {
+            self.bookmarks.remove(index);
+        }
+        if changed {
+            self.save_bookmarks()?;
+        }
+
+        Ok(target)
+    }
+
+    fn place_button(ui: &mut egui::Ui, place: &Place, current: Option<&Path>) -> egui::Response {
+        let selected = current == Some(place.path.as_path());
+        ui.add(
+            egui::Button::selectable(selected, &place.label)
+                .truncate()
+                .sense(egui::Sense::click_and_drag()),
+        )
+        .on_hover_text(place.path.to_string_lossy())
+    }
+}
+
+/// Decode the `\ooo` escapes of `/proc/mounts`
+fn unescape_octal(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(byte) = value
+                .get(i + 1..i + 4)
+                .and_then(|octal| u8::from_str_radix(octal, 8).ok())
+        {
+            unescaped.push(byte);
+            i += 4;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
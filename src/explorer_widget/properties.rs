@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Local};
+use eframe::egui;
+
+use crate::{
+    explorer_widget::{file_types::FileTypes, size_scan::SizeScan},
+    utils::{format_mode, get_desc_from_mime_type, group_name, to_human_readable_size, user_name},
+};
+
+const PERMISSION_CLASSES: [(&str, u32); 3] = [("Owner", 6), ("Group", 3), ("Others", 0)];
+const PERMISSION_BITS: [(&str, u32); 3] = [("Read", 0o4), ("Write", 0o2), ("Execute", 0o1)];
+
+/// The properties dialog of the selected items
+pub struct PropertiesDialog {
+    paths: Vec<PathBuf>,
+    /// MIME type, its description and its icon, of a single item
+    mime_type: Option<(String, String, egui::ImageSource<'static>)>,
+    scan: SizeScan,
+    /// Why the last change of the permissions failed
+    error: Option<String>,
+}
+
+impl PropertiesDialog {
+    pub fn new(ctx: &egui::Context, paths: Vec<PathBuf>, file_types: &mut FileTypes) -> Self {
+        let mime_type = match paths.as_slice() {
+            [path] => {
+                let (mime_type, icon) = file_types.icon_for_path(path);
+                let description = get_desc_from_mime_type(&mime_type);
+                Some((mime_type, description, icon))
+            }
+            _ => None,
+        };
+
+        Self {
+            scan: SizeScan::start(ctx, paths.clone()),
+            paths,
+            mime_type,
+            error: None,
+        }
+    }
+
+    /// Draw the dialog. Returns `false` once it was closed
+    pub fn show(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        egui::Modal::new(egui::Id::new("properties")).show(ctx, |ui| {
+            ui.set_max_width(480.0);
+            ui.horizontal(|ui| match self.paths.as_slice() {
+                [path] => {
+                    if let Some((_, _, icon)) = &self.mime_type {
+                        ui.add(
+                            egui::Image::new(icon.clone())
+                                .fit_to_exact_size(egui::vec2(32.0, 32.0)),
+                        );
+                    }
+                    ui.heading(
+                        path.file_name()
+                            .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy()),
+                    );
+                }
+                paths => {
+                    ui.heading(format!("{} items", paths.len()));
+                }
+            });
+            ui.separator();
+
+            egui::Grid::new("properties_grid")
+                .num_columns(2)
+                .spacing([16.0, 6.0])
+                .show(ui, |ui| {
+                    if let [path] = self.paths.as_slice() {
+                        ui.label("Location");
+                        ui.label(path.to_string_lossy());
+                        ui.end_row();
+                    }
+                    if let Some((mime_type, description, _)) = &self.mime_type {
+                        ui.label("Type");
+                        ui.label(format!("{description} ({mime_type})"));
+                        ui.end_row();
+                    }
+                    self.show_size(ui);
+                    if let [path] = self.paths.as_slice() {
+                        match fs::metadata(path) {
+                            Ok(metadata) => {
+                                // Applied right away, the next frame shows the result
+                                if let Some(mode) = Self::show_metadata(ui, &metadata) {
+                                    self.error = fs::set_permissions(
+                                        path,
+                                        fs::Permissions::from_mode(mode & 0o7777),
+                                    )
+                                    .err()
+                                    .map(|e| format!("Failed to change the permissions: {e}"));
+                                }
+                            }
+                            Err(e) => {
+                                ui.label("Error");
+                                ui.colored_label(ui.visuals().error_fg_color, e.to_string());
+                                ui.end_row();
+                            }
+                        }
+                    }
+                });
+
+            if let Some(error) = &self.error {
+                ui.colored_label(ui.visuals().error_fg_color, error);
+            }
+            ui.separator();
+            if ui.button("Close").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                open = false;
+            }
+        });
+        open
+    }
+
+    fn show_size(&self, ui: &mut egui::Ui) {
+        let progress = self.scan.progress();
+        ui.label("Size");
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "{} ({} bytes)",
+                to_human_readable_size(progress.bytes),
+                progress.bytes
+            ));
+            if !progress.finished {
+                ui.spinner();
+            }
+        });
+        ui.end_row();
+
+        if progress.directories > 0 || self.paths.len() > 1 {
+            ui.label("Contents");
+            let mut contents =
+                format!("{} files, {} folders", progress.files, progress.directories);
+            if progress.errors > 0 {
+                contents.push_str(&format!(", {} unreadable", progress.errors));
+            }
+            ui.label(contents);
+            ui.end_row();
+        }
+    }
+
+    /// Owner, permissions and times. Returns the mode a permission checkbox was toggled to
+    fn show_metadata(ui: &mut egui::Ui, metadata: &fs::Metadata) -> Option<u32> {
+        ui.label("Owner");
+        ui.label(user_name(metadata.uid()).unwrap_or_else(|| metadata.uid().to_string()));
+        ui.end_row();
+        ui.label("Group");
+        ui.label(group_name(metadata.gid()).unwrap_or_else(|| metadata.gid().to_string()));
+        ui.end_row();
+
+        let mode = metadata.mode();
+        ui.label("Permissions");
+        let mut changed = None;
+        ui.vertical(|ui| {
+            ui.monospace(format_mode(mode));
+            egui::Grid::new("permissions_grid").show(ui, |ui| {
+                ui.label("");
+                for (label, _) in PERMISSION_BITS {
+                    ui.label(label);
+                }
+                ui.end_row();
+                for (class, shift) in PERMISSION_CLASSES {
+                    ui.label(class);
+                    for (_, bit) in PERMISSION_BITS {
+                        let bit = bit << shift;
+                        let mut set = mode & bit != 0;
+                        if ui.checkbox(&mut set, "").changed() {
+                            changed = Some(mode ^ bit);
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+        ui.end_row();
+
+        let format_time = |time: Option<DateTime<Local>>| {
+            time.map_or("--".to_string(), |time| {
+                time.format("%Y-%m-%d %H:%M:%S").to_string()
+            })
+        };
+        let local = |time: std::io::Result<SystemTime>| time.ok().map(DateTime::<Local>::from);
+        let changed_at = DateTime::from_timestamp(metadata.ctime(), metadata.ctime_nsec() as u32)
+            .map(|time| time.with_timezone(&Local));
+        for (label, time) in [
+            ("Accessed", local(metadata.accessed())),
+            ("Modified", local(metadata.modified())),
+            ("Changed", changed_at),
+        ] {
+            ui.label(label);
+            ui.label(format_time(time));
+            ui.end_row();
+        }
+        changed
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use walkdir::WalkDir;
+
+/// How deep below the searched directory names are looked at
+const MAX_DEPTH: usize = 32;
+/// The search stops after this many matches
+const MAX_RESULTS: usize = 5000;
+/// How often the UI is asked to show new matches
+const REPAINT_INTERVAL: Duration = Duration::from_millis(100);
+const ROW_HEIGHT: f32 = 22.0;
+const NAME_WIDTH: f32 = 220.0;
+
+/// An item whose name matched
+pub struct Found {
+    pub path: PathBuf,
+    name: String,
+    /// The directory it is in, relative to the searched one
+    location: String,
+    is_directory: bool,
+}
+
+/// What the explorer should do after the search view was drawn
+pub enum SearchAction {
+    /// Show this item in its directory
+    Reveal(PathBuf),
+    Close,
+}
+
+/// A case-insensitive part of the name, or a glob with `*` and `?` matching the whole name
+struct Pattern {
+    query: Vec<char>,
+    is_glob: bool,
+}
+
+impl Pattern {
+    fn new(query: &str) -> Self {
+        Self {
+            query: query.to_lowercase().chars().collect(),
+            is_glob: query.contains(['*', '?']),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let name: Vec<char> = name.to_lowercase().chars().collect();
+        if self.query.is_empty() {
+            true
+        } else if self.is_glob {
+            crate::utils::glob_matches(&self.query, &name)
+        } else {
+            name.windows(self.query.len())
+                .any(|window| window == self.query.as_slice())
+        }
+    }
+}
+
+/// A search running on a background thread, stopped when dropped
+struct Scan {
+    receiver: mpsc::Receiver<Found>,
+    cancelled: Arc<AtomicBool>,
+    searched_directories: Arc<AtomicU64>,
+    finished: Arc<AtomicBool>,
+}
+
+impl Scan {
+    fn start(ctx: &egui::Context, root: PathBuf, query: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let searched_directories = Arc::new(AtomicU64::new(0));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let ctx = ctx.clone();
+        let pattern = Pattern::new(query);
+        let thread_cancelled = Arc::clone(&cancelled);
+        let thread_searched = Arc::clone(&searched_directories);
+        let thread_finished = Arc::clone(&finished);
+        std::thread::spawn(move || {
+            Self::run(
+                &ctx,
+                &root,
+                &pattern,
+                &sender,
+                &thread_searched,
+                &thread_cancelled,
+            );
+            thread_finished.store(true, Ordering::Relaxed);
+            ctx.request_repaint();
+        });
+
+        Self {
+            receiver,
+            cancelled,
+            searched_directories,
+            finished,
+        }
+    }
+
+    fn run(
+        ctx: &egui::Context,
+        root: &Path,
+        pattern: &Pattern,
+        sender: &mpsc::Sender<Found>,
+        searched: &AtomicU64,
+        cancelled: &AtomicBool,
+    ) {
+        let mut found = 0;
+        let mut repainted_at = Instant::now();
+        // Unreadable directories are skipped
+        for entry in WalkDir::new(root)
+            .max_depth(MAX_DEPTH)
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let is_directory = entry.file_type().is_dir();
+            if is_directory {
+                searched.fetch_add(1, Ordering::Relaxed);
+            }
+            let name = entry.file_name().to_string_lossy();
+            if entry.depth() > 0 && pattern.matches(&name) {
+                let location = entry
+                    .path()
+                    .parent()
+                    .and_then(|parent| parent.strip_prefix(root).ok())
+                    .map(|parent| parent.to_string_lossy().to_string())
+                    .filter(|parent| !parent.is_empty())
+                    .unwrap_or_else(|| ".".to_string());
+                let result = Found {
+                    name: name.to_string(),
+                    path: entry.into_path(),
+                    location,
+                    is_directory,
+                };
+                if sender.send(result).is_err() {
+                    return;
+                }
+                found += 1;
+                if found >= MAX_RESULTS {
+                    return;
+                }
+            }
+
+            if repainted_at.elapsed() >= REPAINT_INTERVAL {
+                ctx.request_repaint();
+                repainted_at = Instant::now();
+            }
+        }
+    }
+}
+
+impl Drop for Scan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Searching the current directory and everything below it by name
+pub struct Search {
+    root: PathBuf,
+    query: String,
+    /// The query of the running or finished scan
+    scanned_query: String,
+    scan: Option<Scan>,
+    results: Vec<Found>,
+    selected: Option<usize>,
+    /// The query field should take the focus
+    focus: bool,
+}
+
+impl Search {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            query: String::new(),
+            scanned_query: String::new(),
+            scan: None,
+            results: Vec::new(),
+            selected: None,
+            focus: true,
+        }
+    }
+
+    /// Draw the query field and the results in place of the listing. Keys are handled only
+    /// when the explorer `has_keyboard`
+    pub fn show(&mut self, ui: &mut egui::Ui, has_keyboard: bool) -> Option<SearchAction> {
+        if self.query != self.scanned_query {
+            self.scanned_query = self.query.clone();
+            self.results.clear();
+            self.selected = None;
+            self.scan = (!self.query.is_empty())
+                .then(|| Scan::start(ui.ctx(), self.root.clone(), &self.query));
+        }
+        if let Some(scan) = &self.scan {
+            self.results.extend(scan.receiver.try_iter());
+        }
+
+        let mut action = None;
+        let (up, down) = if has_keyboard {
+            ui.input_mut(|i| {
+                (
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                    i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                )
+            })
+        } else {
+            (false, false)
+        };
+        let last = self.results.len().checked_sub(1);
+        if up {
+            self.selected = self.selected.map(|index| index.saturating_sub(1)).or(last);
+        }
+        if down {
+            self.selected = match (self.selected, last) {
+                (Some(index), Some(last)) => Some((index + 1).min(last)),
+                (None, Some(_)) => Some(0),
+                _ => None,
+            };
+        }
+        let scroll_to_selected = up || down;
+
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("Part of the name, or a glob like *.rs")
+                    .desired_width(240.0),
+            );
+            if std::mem::take(&mut self.focus) {
+                response.request_focus();
+            }
+            if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                let index = self.selected.unwrap_or(0);
+                action = self
+                    .results
+                    .get(index)
+                    .map(|found| SearchAction::Reveal(found.path.clone()));
+                response.request_focus();
+            }
+
+            let finished = self
+                .scan
+                .as_ref()
+                .is_none_or(|scan| scan.finished.load(Ordering::Relaxed));
+            if !finished {
+                ui.spinner();
+            }
+            if let Some(scan) = &self.scan {
+                let searched = scan.searched_directories.load(Ordering::Relaxed);
+                let mut status = format!(
+                    "{} found, searched {searched} director{}",
+                    self.results.len(),
+                    if searched == 1 { "y" } else { "ies" }
+                );
+                if self.results.len() >= MAX_RESULTS {
+                    status.push_str(&format!(" (stopped at {MAX_RESULTS})"));
+                }
+                ui.label(status);
+            }
+            if ui
+                .button("✖")
+                .on_hover_text("Back to the listing")
+                .clicked()
+            {
+                action = Some(SearchAction::Close);
+            }
+        });
+        if has_keyboard && ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            action = Some(SearchAction::Close);
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show_rows(ui, ROW_HEIGHT, self.results.len(), |ui, rows| {
+                for index in rows {
+                    let found = &self.results[index];
+                    let selected = self.selected == Some(index);
+                    let response = ui
+                        .horizontal(|ui| {
+                            let name = if found.is_directory {
+                                format!("📁 {}", found.name)
+                            } else {
+                                found.name.clone()
+                            };
+                            let response = ui.add_sized(
+                                [NAME_WIDTH, ROW_HEIGHT],
+                                egui::Button::selectable(selected, name).truncate(),
+                            );
+                            ui.weak(&found.location);
+                            response
+                        })
+                        .inner;
+                    if selected && scroll_to_selected {
+                        response.scroll_to_me(None);
+                    }
+                    if response.clicked() {
+                        self.selected = Some(index);
+                    }
+                    if response.double_clicked() {
+                        action = Some(SearchAction::Reveal(found.path.clone()));
+                    }
+                }
+            });
+
+        action
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+
+/// How often the UI is asked to show the growing totals
+const REPAINT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Totals a scan adds to, shared with the UI
+#[derive(Default)]
+pub struct Totals {
+    bytes: AtomicU64,
+    files: AtomicU64,
+    directories: AtomicU64,
+    /// Entries that couldn't be read, their sizes are missing
+    errors: AtomicU64,
+    finished: AtomicBool,
+}
+
+/// The totals counted so far
+pub struct ScanProgress {
+    pub bytes: u64,
+    pub files: u64,
+    pub directories: u64,
+    pub errors: u64,
+    pub finished: bool,
+}
+
+/// Adds up the sizes of files and everything in directories on a background thread, stopped
+/// when dropped. Links are counted themselves, not followed
+pub struct SizeScan {
+    totals: Arc<Totals>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SizeScan {
+    pub fn start(ctx: &egui::Context, paths: Vec<PathBuf>) -> Self {
+        let totals = Arc::new(Totals::default());
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let ctx = ctx.clone();
+        let thread_totals = Arc::clone(&totals);
+        let thread_cancelled = Arc::clone(&cancelled);
+        std::thread::spawn(move || {
+            thread_totals.add_up(&ctx, paths, None, &thread_cancelled);
+            ctx.request_repaint();
+        });
+
+        Self { totals, cancelled }
+    }
+
+    pub fn progress(&self) -> ScanProgress {
+        self.totals.progress()
+    }
+}
+
+impl Drop for SizeScan {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Totals {
+    /// Add up `paths` and everything below them, then mark the totals finished. With a
+    /// `device`, directories on other filesystems are left out
+    pub fn add_up(
+        &self,
+        ctx: &egui::Context,
+        paths: Vec<PathBuf>,
+        device: Option<u64>,
+        cancelled: &AtomicBool,
+    ) {
+        self.scan(ctx, paths, device, cancelled);
+        self.finished.store(true, Ordering::Relaxed);
+    }
+
+    fn scan(
+        &self,
+        ctx: &egui::Context,
+        paths: Vec<PathBuf>,
+        device: Option<u64>,
+        cancelled: &AtomicBool,
+    ) {
+        let mut pending = paths;
+        let mut repainted_at = Instant::now();
+        while let Some(path) = pending.pop() {
+            if cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => {
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            };
+            if device.is_some_and(|device| metadata.dev() != device) {
+                continue;
+            }
+            if metadata.is_dir() {
+                self.directories.fetch_add(1, Ordering::Relaxed);
+                match fs::read_dir(&path) {
+                    Ok(entries) => pending.extend(entries.filter_map(|entry| match entry {
+                        Ok(entry) => Some(entry.path()),
+                        Err(_) => {
+                            self.errors.fetch_add(1, Ordering::Relaxed);
+                            None
+                        }
+                    })),
+                    Err(_) => {
+                        self.errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            } else {
+                self.files.fetch_add(1, Ordering::Relaxed);
+                self.bytes.fetch_add(metadata.len(), Ordering::Relaxed);
+            }
+
+            if repainted_at.elapsed() >= REPAINT_INTERVAL {
+                ctx.request_repaint();
+                repainted_at = Instant::now();
+            }
+        }
+    }
+
+    pub fn progress(&self) -> ScanProgress {
+        ScanProgress {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            files: self.files.load(Ordering::Relaxed),
+            directories: self.directories.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            finished: self.finished.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    path::Path,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Quiet time after the last change before the listing is refreshed, so that a burst of
+/// changes (an extracted archive, a build) refreshes once
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Watches the explorer's directory for changes
+pub struct DirectoryWatcher {
+    watcher: Option<notify::RecommendedWatcher>,
+    receiver: mpsc::Receiver<Instant>,
+    /// When the last change not yet refreshed for arrived
+    changed_at: Option<Instant>,
+    /// Why watching doesn't work, shown next to the refresh button
+    pub error: Option<String>,
+}
+
+impl DirectoryWatcher {
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Reading the directory for the refresh is an access too
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_)
+            ) {
+                let _ = sender.send(Instant::now());
+                ctx.request_repaint_after(DEBOUNCE);
+            }
+        });
+
+        match watcher {
+            Ok(watcher) => Self {
+                watcher: Some(watcher),
+                receiver,
+                changed_at: None,
+                error: None,
+            },
+            Err(e) => Self {
+                watcher: None,
+                receiver,
+                changed_at: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    /// Watch `path` instead of the previous directory
+    pub fn watch(&mut self, previous: Option<&Path>, path: Option<&Path>) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+
+        if let Some(previous) = previous {
+            let _ = watcher.unwatch(previous);
+        }
+        // Changes of the previous directory don't matter anymore
+        while self.receiver.try_recv().is_ok() {}
+        self.changed_at = None;
+
+        self.error = path
+            .and_then(|path| watcher.watch(path, RecursiveMode::NonRecursive).err())
+            .map(|e| e.to_string());
+        if let Some(error) = &self.error {
+            warn!("Not watching the explorer's directory, refresh with F5: {error}");
+        }
+    }
+
+    /// Whether the directory changed and has been quiet for long enough to refresh
+    pub fn poll(&mut self, ctx: &egui::Context) -> bool {
+        while let Ok(changed_at) = self.receiver.try_recv() {
+            self.changed_at = Some(changed_at);
+        }
+
+        match self.changed_at {
+            Some(changed_at) if changed_at.elapsed() >= DEBOUNCE => {
+                self.changed_at = None;
+                true
+            }
+            Some(changed_at) => {
+                ctx.request_repaint_after(DEBOUNCE - changed_at.elapsed());
+                false
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,837 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+
+use crate::archive::{self, ArchiveEntry, ArchiveKind, EntryKind};
+
+/// Size of the chunks files are copied in, between checks for cancellation
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// Minimum time between progress reports to the UI
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OperationKind {
+    Copy,
+    Move,
+    /// Unpack archives. Their size counts compressed, the number of items isn't known ahead
+    Extract,
+}
+
+/// What to do when the destination already has an item with the same name
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConflictChoice {
+    Skip,
+    Overwrite,
+    /// Use a numbered name, `name (2).ext`
+    Rename,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Progress {
+    pub files_done: u64,
+    pub files_total: u64,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+impl Progress {
+    fn add(&mut self, (files, bytes): (u64, u64)) {
+        self.files_done += files;
+        self.bytes_done += bytes;
+    }
+}
+
+enum Message {
+    Progress(Progress),
+    /// The worker waits for a `ConflictChoice` about this existing path
+    Conflict(PathBuf),
+    /// Everything is done; the errors that occurred on the way
+    Finished(Vec<String>),
+}
+
+/// A copy, move or extraction running on a background thread
+pub struct FileOperation {
+    pub kind: OperationKind,
+    pub progress: Progress,
+    /// The existing path the worker is asking about
+    pub conflict: Option<PathBuf>,
+    /// State of the "apply to all" checkbox of the conflict dialog
+    pub apply_to_all: bool,
+    receiver: mpsc::Receiver<Message>,
+    choice_sender: mpsc::Sender<(ConflictChoice, bool)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl FileOperation {
+    /// Start copying, moving or extracting `sources` into the directory `destination`
+    pub fn start(
+        ctx: &egui::Context,
+        kind: OperationKind,
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (choice_sender, choices) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let mut worker = Worker {
+            ctx: ctx.clone(),
+            sender,
+            choices,
+            cancelled: cancelled.clone(),
+            progress: Progress::default(),
+            last_report: Instant::now(),
+            choice_for_all: None,
+            errors: Vec::new(),
+        };
+        thread::spawn(move || worker.run(kind, &sources, &destination));
+
+        Self {
+            kind,
+            progress: Progress::default(),
+            conflict: None,
+            apply_to_all: false,
+            receiver,
+            choice_sender,
+            cancelled,
+        }
+    }
+
+    /// Take the worker's messages. Returns the errors once the operation has finished
+    pub fn poll(&mut self) -> Option<Vec<String>> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(Message::Progress(progress)) => self.progress = progress,
+                Ok(Message::Conflict(path)) => self.conflict = Some(path),
+                Ok(Message::Finished(errors)) => return Some(errors),
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => return Some(Vec::new()),
+            }
+        }
+    }
+
+    /// Answer the pending conflict
+    pub fn resolve_conflict(&mut self, choice: ConflictChoice) {
+        if self.conflict.take().is_some() {
+            let _ = self.choice_sender.send((choice, self.apply_to_all));
+        }
+    }
+
+    /// Stop after the current chunk. Files already copied stay
+    pub fn cancel(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        // The worker may be waiting for an answer
+        self.resolve_conflict(ConflictChoice::Skip);
+    }
+}
+
+impl Drop for FileOperation {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+struct Worker {
+    ctx: egui::Context,
+    sender: mpsc::Sender<Message>,
+    choices: mpsc::Receiver<(ConflictChoice, bool)>,
+    cancelled: Arc<AtomicBool>,
+    progress: Progress,
+    last_report: Instant,
+    /// Answer given with "apply to all"
+    choice_for_all: Option<ConflictChoice>,
+    errors: Vec<String>,
+}
+
+impl Worker {
+    fn run(&mut self, kind: OperationKind, sources: &[PathBuf], destination: &Path) {
+        if kind == OperationKind::Extract {
+            for archive in sources {
+                if self.is_cancelled() {
+                    break;
+                }
+                self.extract(archive, destination);
+            }
+        } else {
+            self.transfer(kind, sources, destination);
+        }
+
+        self.report(true);
+        let _ = self
+            .sender
+            .send(Message::Finished(std::mem::take(&mut self.errors)));
+        self.ctx.request_repaint();
+    }
+
+    fn transfer(&mut self, kind: OperationKind, sources: &[PathBuf], destination: &Path) {
+        for source in sources {
+            let (files, bytes) = measure(source);
+            self.progress.files_total += files;
+            self.progress.bytes_total += bytes;
+        }
+        self.report(true);
+
+        for source in sources {
+            if self.is_cancelled() {
+                break;
+            }
+            let Some(name) = source.file_name() else {
+                continue;
+            };
+            if destination.starts_with(source) {
+                self.errors.push(format!(
+                    "Can't put \"{}\" into itself",
+                    source.to_string_lossy()
+                ));
+                continue;
+            }
+            // Moving into the same directory changes nothing
+            if kind == OperationKind::Move && source.parent() == Some(destination) {
+                self.progress.add(measure(source));
+                continue;
+            }
+
+            let target = destination.join(name);
+            if kind == OperationKind::Move {
+                self.move_entry(source, target);
+            } else {
+                self.copy(source, target);
+            }
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Send the progress to the UI, at most every `PROGRESS_INTERVAL` unless `force`d
+    fn report(&mut self, force: bool) {
+        if force || self.last_report.elapsed() >= PROGRESS_INTERVAL {
+            self.last_report = Instant::now();
+            let _ = self.sender.send(Message::Progress(self.progress.clone()));
+            self.ctx.request_repaint();
+        }
+    }
+
+    fn error(&mut self, action: &str, path: &Path, e: io::Error) {
+        self.errors.push(format!(
+            "Failed to {action} \"{}\": {e}",
+            path.to_string_lossy()
+        ));
+    }
+
+    fn ask(&mut self, existing: &Path) -> ConflictChoice {
+        if let Some(choice) = self.choice_for_all {
+            return choice;
+        }
+
+        self.report(true);
+        let _ = self.sender.send(Message::Conflict(existing.to_path_buf()));
+        self.ctx.request_repaint();
+        match self.choices.recv() {
+            Ok((choice, apply_to_all)) => {
+                if apply_to_all {
+                    self.choice_for_all = Some(choice);
+                }
+                choice
+            }
+            Err(_) => ConflictChoice::Skip,
+        }
+    }
+
+    /// Resolve a conflict at `target`. Returns the path to write to and whether it is an
+    /// existing directory to merge into, or `None` to skip `source`
+    fn prepare_target(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        target: PathBuf,
+    ) -> Option<(PathBuf, bool)> {
+        let Ok(existing) = target.symlink_metadata() else {
+            return Some((target, false));
+        };
+
+        match self.ask(&target) {
+            ConflictChoice::Skip => None,
+            ConflictChoice::Rename => {
+                let parent = target.parent()?;
+                let name = target.file_name()?.to_string_lossy().into_owned();
+                Some((parent.join(unique_name(parent, &name)), false))
+            }
+            // Overwriting an item with itself would only destroy it
+            ConflictChoice::Overwrite if source == target => None,
+            // Removing a directory the source is in would remove the source with it
+            ConflictChoice::Overwrite if source.starts_with(&target) => {
+                self.errors.push(format!(
+                    "Can't replace \"{}\" with an item inside it",
+                    target.to_string_lossy()
+                ));
+                None
+            }
+            ConflictChoice::Overwrite if metadata.is_dir() && existing.is_dir() => {
+                Some((target, true))
+            }
+            ConflictChoice::Overwrite => {
+                let result = if existing.is_dir() {
+                    fs::remove_dir_all(&target)
+                } else {
+                    fs::remove_file(&target)
+                };
+                match result {
+                    Ok(()) => Some((target, false)),
+                    Err(e) => {
+                        self.error("replace", &target, e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Copy `source` to `target` recursively. Returns whether everything was copied
+    fn copy(&mut self, source: &Path, target: PathBuf) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+        let metadata = match source.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.error("read", source, e);
+                return false;
+            }
+        };
+        let Some((target, merge)) = self.prepare_target(source, &metadata, target) else {
+            self.progress.add(measure(source));
+            return false;
+        };
+
+        self.copy_prepared(source, &metadata, &target, merge)
+    }
+
+    fn copy_prepared(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        target: &Path,
+        merge: bool,
+    ) -> bool {
+        let complete = if metadata.is_symlink() {
+            // Links are copied as links, not what they point to
+            match fs::read_link(source).and_then(|link| std::os::unix::fs::symlink(link, target)) {
+                Ok(()) => true,
+                Err(e) => {
+                    self.error("copy", source, e);
+                    false
+                }
+            }
+        } else if metadata.is_dir() {
+            self.copy_directory(source, metadata, target, merge)
+        } else {
+            match self.copy_file(source, metadata, target) {
+                Ok(complete) => complete,
+                Err(e) => {
+                    self.error("copy", source, e);
+                    let _ = fs::remove_file(target);
+                    false
+                }
+            }
+        };
+
+        self.progress.files_done += 1;
+        self.report(false);
+        complete
+    }
+
+    fn copy_directory(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        target: &Path,
+        merge: bool,
+    ) -> bool {
+        if !merge && let Err(e) = fs::create_dir(target) {
+            self.error("create", target, e);
+            return false;
+        }
+        let entries = match fs::read_dir(source) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.error("read", source, e);
+                return false;
+            }
+        };
+
+        let mut complete = true;
+        for entry in entries {
+            match entry {
+                Ok(entry) => complete &= self.copy(&entry.path(), target.join(entry.file_name())),
+                Err(e) => {
+                    self.error("read", source, e);
+                    complete = false;
+                }
+            }
+        }
+
+        // After the contents, which would change the time and might not be writable otherwise
+        if let Err(e) = fs::File::open(target)
+            .and_then(|dir| dir.set_modified(metadata.modified()?))
+            .and_then(|_| fs::set_permissions(target, metadata.permissions()))
+        {
+            self.error("set the attributes of", target, e);
+        }
+        complete
+    }
+
+    /// Copy a regular file. Returns `Ok(false)` if cancelled, the partial copy is removed then
+    fn copy_file(
+        &mut self,
+        source: &Path,
+        metadata: &fs::Metadata,
+        target: &Path,
+    ) -> io::Result<bool> {
+        let mut reader = fs::File::open(source)?;
+        let mut writer = fs::File::create(target)?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+
+        loop {
+            if self.is_cancelled() {
+                drop(writer);
+                fs::remove_file(target)?;
+                return Ok(false);
+            }
+
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read])?;
+            self.progress.bytes_done += read as u64;
+            self.report(false);
+        }
+
+        writer.set_modified(metadata.modified()?)?;
+        writer.set_permissions(metadata.permissions())?;
+        Ok(true)
+    }
+
+    fn move_entry(&mut self, source: &Path, target: PathBuf) {
+        let metadata = match source.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.error("read", source, e);
+                return;
+            }
+        };
+        let Some((target, merge)) = self.prepare_target(source, &metadata, target) else {
+            self.progress.add(measure(source));
+            return;
+        };
+
+        if !merge {
+            let size = measure(source);
+            match fs::rename(source, &target) {
+                Ok(()) => {
+                    self.progress.add(size);
+                    self.report(false);
+                    return;
+                }
+                // Across filesystems it has to be copied
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+                Err(e) => {
+                    self.error("move", source, e);
+                    self.progress.add(size);
+                    return;
+                }
+            }
+        }
+
+        // Only remove the source once everything has arrived
+        if self.copy_prepared(source, &metadata, &target, merge) {
+            let result = if metadata.is_dir() {
+                fs::remove_dir_all(source)
+            } else {
+                fs::remove_file(source)
+            };
+            if let Err(e) = result {
+                self.error("remove", source, e);
+            }
+        }
+    }
+
+    /// Unpack `archive` into `destination`, which is created if needed
+    fn extract(&mut self, archive: &Path, destination: &Path) {
+        let name = archive
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let Some((kind, _)) = ArchiveKind::split(&name) else {
+            self.errors
+                .push(format!("\"{name}\" isn't an archive that can be extracted"));
+            return;
+        };
+        self.progress.bytes_total += archive.metadata().map_or(0, |metadata| metadata.len());
+        self.report(true);
+        if let Err(e) = fs::create_dir_all(destination) {
+            self.error("create", destination, e);
+            return;
+        }
+
+        let read = AtomicU64::new(0);
+        let base = self.progress.bytes_done;
+        let result = archive::read_entries(archive, kind, &read, |entry, contents| {
+            self.extract_entry(entry, contents, destination, &read, base);
+            !self.is_cancelled()
+        });
+        if let Err(e) = result {
+            self.errors
+                .push(format!("Failed to extract \"{name}\": {e}"));
+        }
+    }
+
+    fn extract_entry(
+        &mut self,
+        entry: ArchiveEntry,
+        contents: &mut dyn Read,
+        destination: &Path,
+        read: &AtomicU64,
+        base: u64,
+    ) {
+        let Some(path) = entry.path else {
+            self.errors.push(format!(
+                "Skipped \"{}\", it would end up outside of the destination",
+                entry.name
+            ));
+            return;
+        };
+        // The archive's root, `./`
+        if path.as_os_str().is_empty() {
+            return;
+        }
+
+        let target = destination.join(path);
+        match entry.kind {
+            // An existing directory is merged into
+            EntryKind::Directory => {
+                if let Err(e) = fs::create_dir_all(&target) {
+                    self.error("create", &target, e);
+                }
+            }
+            EntryKind::Other => self.errors.push(format!(
+                "Skipped \"{}\", links and special files aren't extracted",
+                entry.name
+            )),
+            EntryKind::File => {
+                if let Some(parent) = target.parent()
+                    && let Err(e) = fs::create_dir_all(parent)
+                {
+                    self.error("create", parent, e);
+                    return;
+                }
+                let Some(target) = self.prepare_extract_target(target) else {
+                    self.progress.files_done += 1;
+                    return;
+                };
+                if let Err(e) = self.write_entry(contents, &target, entry.mode, read, base) {
+                    self.error("extract", &target, e);
+                    let _ = fs::remove_file(&target);
+                }
+            }
+        }
+
+        self.progress.files_done += 1;
+        self.progress.bytes_done = base + read.load(Ordering::Relaxed);
+        self.report(false);
+    }
+
+    /// Resolve a conflict with an existing item at `target`. Returns the path to write to, or
+    /// `None` to skip the entry
+    fn prepare_extract_target(&mut self, target: PathBuf) -> Option<PathBuf> {
+        let Ok(existing) = target.symlink_metadata() else {
+            return Some(target);
+        };
+
+        match self.ask(&target) {
+            ConflictChoice::Skip => None,
+            ConflictChoice::Rename => {
+                let parent = target.parent()?;
+                let name = target.file_name()?.to_string_lossy().into_owned();
+                Some(parent.join(unique_name(parent, &name)))
+            }
+            // Removed first, not to write through a link
+            ConflictChoice::Overwrite => {
+                let result = if existing.is_dir() {
+                    fs::remove_dir_all(&target)
+                } else {
+                    fs::remove_file(&target)
+                };
+                match result {
+                    Ok(()) => Some(target),
+                    Err(e) => {
+                        self.error("replace", &target, e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write an entry's contents to the new file `target`. Returns `Ok(false)` if cancelled,
+    /// the partial file is removed then
+    fn write_entry(
+        &mut self,
+        contents: &mut dyn Read,
+        target: &Path,
+        mode: Option<u32>,
+        read: &AtomicU64,
+        base: u64,
+    ) -> io::Result<bool> {
+        let mut writer = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(target)?;
+        let mut buffer = vec![0; CHUNK_SIZE];
+
+        loop {
+            if self.is_cancelled() {
+                drop(writer);
+                fs::remove_file(target)?;
+                return Ok(false);
+            }
+
+            let count = contents.read(&mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..count])?;
+            self.progress.bytes_done = base + read.load(Ordering::Relaxed);
+            self.report(false);
+        }
+
+        if let Some(mode) = mode {
+            writer.set_permissions(fs::Permissions::from_mode(mode & 0o777))?;
+        }
+        Ok(true)
+    }
+}
+
+/// Number of items and bytes of regular files under `path`, including itself
+fn measure(path: &Path) -> (u64, u64) {
+    let Ok(metadata) = path.symlink_metadata() else {
+        return (0, 0);
+    };
+    if !metadata.is_dir() {
+        return (
+            1,
+            if metadata.is_file() {
+                metadata.len()
+            } else {
+                0
+            },
+        );
+    }
+
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| measure(&entry.path()))
+        .fold((1, 0), |(files, bytes), (f, b)| (files + f, bytes + b))
+}
+
+/// `name`, or `name (2)`, `name (3)`, ... before the extension if something with that name
+/// exists in `dir`
+pub fn unique_name(dir: &Path, name: &str) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        // Not for dotfiles like `.bashrc`
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name, None),
+    };
+
+    (1..)
+        .map(|n| match (n, extension) {
+            (1, _) => name.to_string(),
+            (n, Some(extension)) => format!("{stem} ({n}).{extension}"),
+            (n, None) => format!("{stem} ({n})"),
+        })
+        .find(|candidate| dir.join(candidate).symlink_metadata().is_err())
+        .unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for one test, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "explotty-file-operation-{}-{name}",
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Run an operation to the end, answering every conflict with `choice`
+    fn run(
+        kind: OperationKind,
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+        choice: ConflictChoice,
+    ) -> Vec<String> {
+        let mut operation =
+            FileOperation::start(&egui::Context::default(), kind, sources, destination);
+        let started = Instant::now();
+        loop {
+            if let Some(errors) = operation.poll() {
+                return errors;
+            }
+            if operation.conflict.is_some() {
+                operation.resolve_conflict(choice);
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "operation hung"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn overwrite_replaces_the_existing_file() {
+        let dir = TempDir::new("overwrite");
+        let (source, destination) = (dir.0.join("source"), dir.0.join("destination"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+        fs::write(source.join("file"), "new").unwrap();
+        fs::write(destination.join("file"), "old").unwrap();
+
+        let errors = run(
+            OperationKind::Copy,
+            vec![source.join("file")],
+            destination.clone(),
+            ConflictChoice::Overwrite,
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(fs::read_to_string(destination.join("file")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(source.join("file")).unwrap(), "new");
+    }
+
+    #[test]
+    fn overwrite_refuses_to_remove_the_directory_of_the_source() {
+        for kind in [OperationKind::Copy, OperationKind::Move] {
+            let dir = TempDir::new(&format!("overwrite-parent-{kind:?}"));
+            // Pasting `foo/foo` next to `foo` would replace `foo`, which holds the source
+            let parent = dir.0.join("foo");
+            fs::create_dir_all(&parent).unwrap();
+            fs::write(parent.join("foo"), "source").unwrap();
+
+            let errors = run(
+                kind,
+                vec![parent.join("foo")],
+                dir.0.clone(),
+                ConflictChoice::Overwrite,
+            );
+            assert_eq!(errors.len(), 1, "{kind:?}: {errors:?}");
+            assert_eq!(
+                fs::read_to_string(parent.join("foo")).unwrap(),
+                "source",
+                "{kind:?}"
+            );
+        }
+    }
+
+    /// A tar with the files `entries`, their names written as they are
+    fn write_tar(path: &Path, entries: &[(&str, &str)]) {
+        let mut builder = tar::Builder::new(fs::File::create(path).unwrap());
+        for (name, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            // `set_path` refuses `..`, which is the point here
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_cksum();
+            builder.append(&header, contents.as_bytes()).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut writer = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, contents) in entries {
+            writer.start_file(*name, options).unwrap();
+            io::Write::write_all(&mut writer, contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn entries_escaping_the_destination_are_skipped_and_reported() {
+        let entries = [
+            ("../escaped", "outside"),
+            ("a/../../escaped", "outside"),
+            ("/tmp/explotty-absolute", "outside"),
+            ("./inside", "inside"),
+        ];
+        type Writer = fn(&Path, &[(&str, &str)]);
+        let writers: [(&str, Writer); 2] = [("crafted.tar", write_tar), ("crafted.zip", write_zip)];
+        for (name, write) in writers {
+            let dir = TempDir::new(&format!("extract-{name}"));
+            let archive = dir.0.join(name);
+            write(&archive, &entries);
+            let destination = dir.0.join("out");
+
+            let errors = run(
+                OperationKind::Extract,
+                vec![archive],
+                destination.clone(),
+                ConflictChoice::Skip,
+            );
+            let skipped: Vec<&str> = errors
+                .iter()
+                .filter_map(|error| error.strip_prefix("Skipped \""))
+                .filter_map(|error| {
+                    error.strip_suffix("\", it would end up outside of the destination")
+                })
+                .collect();
+            assert_eq!(
+                skipped,
+                ["../escaped", "a/../../escaped", "/tmp/explotty-absolute"],
+                "{name}: {errors:?}"
+            );
+            assert_eq!(errors.len(), 3, "{name}: {errors:?}");
+            assert_eq!(
+                fs::read_to_string(destination.join("inside")).unwrap(),
+                "inside",
+                "{name}"
+            );
+            assert!(!dir.0.join("escaped").exists(), "{name}");
+            assert!(!Path::new("/tmp/explotty-absolute").exists(), "{name}");
+        }
+    }
+}
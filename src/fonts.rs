@@ -0,0 +1,248 @@
+use eframe::egui::{FontData, FontDefinitions, FontFamily, FontTweak};
+use font_kit::{
+    family_name::FamilyName,
+    handle::Handle,
+    properties::{Properties, Style, Weight},
+    source::SystemSource,
+};
+
+use crate::config::{Config, FontSpec};
+
+const SANS_SERIF_FONT_ID: &str = "System Sans Serif";
+const MONOSPACE_FONT_ID: &str = "System Monospace";
+
+/// Where fonts are looked up by family. font-kit's system source, or anything else handing out
+/// handles
+pub trait FontSource {
+    fn select(&self, family: FamilyName) -> anyhow::Result<Handle>;
+}
+
+impl FontSource for SystemSource {
+    fn select(&self, family: FamilyName) -> anyhow::Result<Handle> {
+        Ok(self.select_best_match(&[family], &Properties::new())?)
+    }
+}
+
+/// The fonts of `config` (the system's defaults without one) in front of egui's own: the UI
+/// font for proportional text, the terminal font and its fallbacks in order for monospace
+pub fn resolve(
+    config: Option<&Config>,
+    source: &impl FontSource,
+) -> anyhow::Result<FontDefinitions> {
+    let ui_font = config.and_then(Config::ui_font);
+    let terminal_font = config.and_then(Config::terminal_font);
+    let fallbacks = config.map(Config::fallback_fonts).unwrap_or_default();
+
+    let mut fonts = FontDefinitions::default();
+
+    fonts.font_data.insert(
+        SANS_SERIF_FONT_ID.to_string(),
+        font_data(ui_font.as_ref(), FamilyName::SansSerif, source)?.into(),
+    );
+    fonts.font_data.insert(
+        MONOSPACE_FONT_ID.to_string(),
+        font_data(terminal_font.as_ref(), FamilyName::Monospace, source)?.into(),
+    );
+
+    let mut monospace = vec![MONOSPACE_FONT_ID.to_string()];
+    for (index, fallback) in fallbacks.iter().enumerate() {
+        // Numbered, so the same family can come twice with different adjustments
+        let id = format!("Fallback {}: {}", index + 1, fallback.label());
+        let data = font_data(Some(fallback), FamilyName::Monospace, source)?;
+        fonts.font_data.insert(id.clone(), data.into());
+        monospace.push(id);
+    }
+
+    if let Some(vec) = fonts.families.get_mut(&FontFamily::Proportional) {
+        vec.insert(0, SANS_SERIF_FONT_ID.to_string());
+        info!("Proportional font family: {vec:?}");
+    }
+    if let Some(vec) = fonts.families.get_mut(&FontFamily::Monospace) {
+        vec.splice(0..0, monospace);
+        info!("Monospace font family: {vec:?}");
+    }
+
+    Ok(fonts)
+}
+
+/// The files of the terminal font of `config` in regular, bold, italic and bold italic, for
+/// embedding. A font given by its file is used for all four
+pub fn terminal_font_styles(config: Option<&Config>) -> anyhow::Result<[Vec<u8>; 4]> {
+    let spec = config.and_then(Config::terminal_font);
+    if let Some(FontSpec {
+        path: Some(path), ..
+    }) = &spec
+    {
+        let path = crate::utils::expand_home(&path.to_string_lossy());
+        let (font, _) = font_bytes(Handle::from_path(path, 0))?;
+        return Ok([font.clone(), font.clone(), font.clone(), font]);
+    }
+
+    let family = match spec.and_then(|spec| spec.family) {
+        Some(family) => FamilyName::Title(family),
+        None => FamilyName::Monospace,
+    };
+    let source = SystemSource::new();
+    let load = |weight: Weight, style: Style| -> anyhow::Result<Vec<u8>> {
+        let handle = source.select_best_match(
+            std::slice::from_ref(&family),
+            Properties::new().weight(weight).style(style),
+        )?;
+        Ok(font_bytes(handle)?.0)
+    };
+    Ok([
+        load(Weight::NORMAL, Style::Normal)?,
+        load(Weight::BOLD, Style::Normal)?,
+        load(Weight::NORMAL, Style::Italic)?,
+        load(Weight::BOLD, Style::Italic)?,
+    ])
+}
+
+/// The contents of the file of `handle`, with the index of the font in it
+fn font_bytes(handle: Handle) -> anyhow::Result<(Vec<u8>, u32)> {
+    Ok(match handle {
+        Handle::Memory { bytes, font_index } => (bytes.to_vec(), font_index),
+        Handle::Path { path, font_index } => (
+            std::fs::read(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", path.display()))?,
+            font_index,
+        ),
+    })
+}
+
+/// Load `spec`: its file if it names one, else its family, else `default`
+fn font_data(
+    spec: Option<&FontSpec>,
+    default: FamilyName,
+    source: &impl FontSource,
+) -> anyhow::Result<FontData> {
+    let handle = match spec {
+        Some(FontSpec {
+            path: Some(path), ..
+        }) => Handle::from_path(crate::utils::expand_home(&path.to_string_lossy()), 0),
+        Some(FontSpec {
+            family: Some(family),
+            ..
+        }) => source.select(FamilyName::Title(family.clone()))?,
+        _ => source.select(default)?,
+    };
+
+    let (font, index) = font_bytes(handle)?;
+
+    let mut data = FontData::from_owned(font);
+    data.index = index;
+    data.tweak = FontTweak {
+        scale: spec.and_then(|spec| spec.size_adjust).unwrap_or(1.0),
+        ..Default::default()
+    };
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Hands out the family's name as the font's bytes, so tests can tell the fonts apart
+    struct FakeSource;
+
+    impl FontSource for FakeSource {
+        fn select(&self, family: FamilyName) -> anyhow::Result<Handle> {
+            let name = match family {
+                FamilyName::Title(name) if name == "Missing" => anyhow::bail!("no such font"),
+                FamilyName::Title(name) => name,
+                FamilyName::Monospace => "default monospace".to_string(),
+                FamilyName::SansSerif => "default sans".to_string(),
+                other => format!("{other:?}"),
+            };
+            Ok(Handle::from_memory(Arc::new(name.into_bytes()), 0))
+        }
+    }
+
+    fn config(text: &str) -> Config {
+        toml::from_str(text).unwrap()
+    }
+
+    /// The first fonts of `family` and what their data is
+    fn family(fonts: &FontDefinitions, family: FontFamily, count: usize) -> Vec<(String, String)> {
+        fonts.families[&family]
+            .iter()
+            .take(count)
+            .map(|id| {
+                let data = String::from_utf8_lossy(&fonts.font_data[id].font).into_owned();
+                (id.clone(), data)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn system_defaults_without_a_config() {
+        let fonts = resolve(None, &FakeSource).unwrap();
+        assert_eq!(
+            family(&fonts, FontFamily::Proportional, 1),
+            [(SANS_SERIF_FONT_ID.into(), "default sans".into())]
+        );
+        assert_eq!(
+            family(&fonts, FontFamily::Monospace, 1),
+            [(MONOSPACE_FONT_ID.into(), "default monospace".into())]
+        );
+        // egui's own fonts stay behind them
+        assert!(fonts.families[&FontFamily::Monospace].len() > 1);
+    }
+
+    #[test]
+    fn fallbacks_follow_the_terminal_font_in_order() {
+        let config = config(
+            r#"
+            [fonts]
+            terminal_font = { family = "Iosevka", size_adjust = 1.1 }
+            fallbacks = [
+                { family = "Noto Sans CJK JP", size_adjust = 0.9 },
+                { family = "Noto Sans CJK JP" },
+            ]
+            "#,
+        );
+        let fonts = resolve(Some(&config), &FakeSource).unwrap();
+        assert_eq!(
+            family(&fonts, FontFamily::Monospace, 3),
+            [
+                (MONOSPACE_FONT_ID.into(), "Iosevka".into()),
+                (
+                    "Fallback 1: Noto Sans CJK JP".into(),
+                    "Noto Sans CJK JP".into()
+                ),
+                (
+                    "Fallback 2: Noto Sans CJK JP".into(),
+                    "Noto Sans CJK JP".into()
+                ),
+            ]
+        );
+        let scale = |id: &str| fonts.font_data[id].tweak.scale;
+        assert_eq!(scale(MONOSPACE_FONT_ID), 1.1);
+        assert_eq!(scale("Fallback 1: Noto Sans CJK JP"), 0.9);
+        assert_eq!(scale("Fallback 2: Noto Sans CJK JP"), 1.0);
+    }
+
+    #[test]
+    fn a_path_bypasses_the_source() {
+        let path = std::env::temp_dir().join(format!("explotty-fonts-{}.ttf", std::process::id()));
+        std::fs::write(&path, b"font file").unwrap();
+        let config = config(&format!(
+            "[fonts]\nterminal_font = {{ family = \"Missing\", path = {:?} }}\n",
+            path.to_string_lossy()
+        ));
+        let fonts = resolve(Some(&config), &FakeSource);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            family(&fonts.unwrap(), FontFamily::Monospace, 1),
+            [(MONOSPACE_FONT_ID.into(), "font file".into())]
+        );
+    }
+
+    #[test]
+    fn a_missing_family_fails() {
+        let config = config("[fonts]\nfallbacks = [{ family = \"Missing\" }]\n");
+        assert!(resolve(Some(&config), &FakeSource).is_err());
+    }
+}
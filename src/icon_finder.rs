@@ -0,0 +1,367 @@
+//! Icons of content types from the desktop's icon theme
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use eframe::egui;
+use gio::{glib::object::Cast, prelude::FileExt};
+
+#[cfg(feature = "backend-freedesktop")]
+mod freedesktop;
+#[cfg(not(feature = "backend-freedesktop"))]
+mod gtk_theme;
+
+/// Bundled icons for when the theme has nothing at all
+const FILE_ICON: egui::ImageSource<'static> = egui::include_image!("../assets/icons/file.svg");
+const FOLDER_ICON: egui::ImageSource<'static> = egui::include_image!("../assets/icons/folder.svg");
+
+/// What was found for a content type
+#[derive(Clone, Debug, Default)]
+pub struct IconResolution {
+    /// The icon file, none if neither the type's icons nor the generic ones are in the theme
+    pub path: Option<PathBuf>,
+    /// The icon names tried, most specific first
+    pub names: Vec<String>,
+    /// The file found is a symbolic (monochrome) icon
+    pub is_symbolic: bool,
+}
+
+impl IconResolution {
+    /// The icon as an egui image, the bundled one of the kind of type without a file
+    pub fn image_source(&self, content_type: &str) -> egui::ImageSource<'static> {
+        match &self.path {
+            Some(path) => egui::ImageSource::Uri(format!("file://{}", path.display()).into()),
+            None if content_type == "inode/directory" => FOLDER_ICON,
+            None => FILE_ICON,
+        }
+    }
+}
+
+/// Decoded pixels of an icon, unpremultiplied RGBA row by row
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Where the icon files come from and how they are rasterized
+pub trait IconBackend {
+    /// Icon names of `content_type`, most specific first, down to its generic icon
+    fn icon_names(&self, content_type: &str) -> Vec<String>;
+
+    /// The file of the icon `name` in the theme, the one closest to `size` pixels
+    fn lookup(&self, name: &str, size: i32) -> Option<PathBuf>;
+
+    /// Rasterize `path`, found for `resolution`, at `size` pixels. Symbolic icons are drawn in
+    /// `foreground`
+    fn load(
+        &self,
+        resolution: &IconResolution,
+        path: &Path,
+        size: i32,
+        foreground: egui::Color32,
+    ) -> anyhow::Result<RgbaImage>;
+
+    /// Whether the theme changed since the last call
+    fn take_theme_changed(&self) -> bool;
+}
+
+#[cfg(not(feature = "backend-freedesktop"))]
+type Backend = gtk_theme::GtkBackend;
+#[cfg(feature = "backend-freedesktop")]
+type Backend = freedesktop::FreedesktopBackend;
+
+/// Looks up icons in the desktop's icon theme, remembering the files found per content type
+/// and size until the theme changes. The theme is GTK's by default, or read from the icon
+/// directories by the `backend-freedesktop` feature. GTK objects belong to the thread that
+/// initialized GTK, so this does too: it isn't `Send`, and other threads hand over content types
+/// (as the file type worker does) for the UI thread to look up.
+///
+/// `resolve` looks up one type right away. For many at once, `request` queues them and `poll`
+/// does a frame's worth at a time, so a cold directory doesn't stall the UI
+pub struct IconFinder<B: IconBackend = Backend> {
+    backend: B,
+    cache: HashMap<(String, i32), IconResolution>,
+    /// Lookups waiting for `poll`, each type and size once
+    queue: VecDeque<(String, i32)>,
+}
+
+impl Default for IconFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IconFinder {
+    /// Use the desktop's icon theme. With GTK, GTK has to be initialized on this thread
+    /// already, without it no icons are found
+    pub fn new() -> Self {
+        Self::with_backend(Backend::new())
+    }
+}
+
+impl<B: IconBackend> IconFinder<B> {
+    /// Look icons up in `backend`
+    fn with_backend(backend: B) -> Self {
+        Self {
+            backend,
+            cache: HashMap::new(),
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Forget what was found if the icon theme changed since the last call. Returns whether it
+    /// did, for callers keeping icons of their own
+    pub fn check_theme(&mut self) -> bool {
+        let changed = self.backend.take_theme_changed();
+        if changed {
+            self.cache.clear();
+        }
+        changed
+    }
+
+    /// Queue a lookup for `poll`, unless the type was looked up or queued already
+    pub fn request(&mut self, content_type: &str, size: i32) {
+        let key = (content_type.to_string(), size);
+        if !self.cache.contains_key(&key) && !self.queue.contains(&key) {
+            self.queue.push_back(key);
+        }
+    }
+
+    /// Do queued lookups for up to `budget` (at least one), returning the types done. The rest
+    /// stay queued for the next call
+    pub fn poll(&mut self, budget: Duration) -> Vec<(String, i32, IconResolution)> {
+        let deadline = Instant::now() + budget;
+        let mut done = Vec::new();
+        while let Some((content_type, size)) = self.queue.pop_front() {
+            let resolution = self.resolve(&content_type, size);
+            done.push((content_type, size, resolution));
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+        done
+    }
+
+    /// Whether queued lookups are left for `poll`
+    pub fn has_requests(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Whether the icon of `content_type` at `size` was looked up already
+    pub fn is_resolved(&self, content_type: &str, size: i32) -> bool {
+        self.cache.contains_key(&(content_type.to_string(), size))
+    }
+
+    /// The icon of `content_type` at `size` pixels: one of the type's own icons, else its
+    /// generic icon (`text-x-generic`, `folder`, ...), else `application-octet-stream`
+    pub fn resolve(&mut self, content_type: &str, size: i32) -> IconResolution {
+        self.check_theme();
+
+        let key = (content_type.to_string(), size);
+        if let Some(resolution) = self.cache.get(&key) {
+            return resolution.clone();
+        }
+
+        let mut names = self.backend.icon_names(content_type);
+        if !names.iter().any(|name| name == "application-octet-stream") {
+            names.push("application-octet-stream".to_string());
+        }
+        let path = names.iter().find_map(|name| self.lookup(name, size));
+
+        let resolution = IconResolution {
+            is_symbolic: path.as_ref().is_some_and(|path| {
+                path.file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy().ends_with("-symbolic"))
+            }),
+            path,
+            names,
+        };
+        if resolution.path.is_none() {
+            debug!(
+                "No icon for {content_type} in the theme, tried {:?}",
+                resolution.names
+            );
+        }
+        self.cache.insert(key, resolution.clone());
+        resolution
+    }
+
+    /// The icon of `content_type` rasterized at `size` pixels, whatever format the theme has it
+    /// in. Symbolic icons are drawn in `foreground`. None if the theme has no icon for the type
+    /// or its file can't be loaded
+    pub fn load_icon(
+        &mut self,
+        content_type: &str,
+        size: i32,
+        foreground: egui::Color32,
+    ) -> Option<RgbaImage> {
+        let resolution = self.resolve(content_type, size);
+        let path = resolution.path.as_ref()?;
+        match self.backend.load(&resolution, path, size, foreground) {
+            Ok(image) => Some(image),
+            Err(e) => {
+                warn!("Failed to load the icon {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /// The content type of `path`, guessed from its name (`inode/directory` for a directory),
+    /// and its icon at `size` pixels
+    pub fn find_icon_for_path(&mut self, path: &Path, size: i32) -> (String, IconResolution) {
+        let content_type = if path.is_dir() {
+            "inode/directory".to_string()
+        } else {
+            gio::content_type_guess(Some(path), None).0.to_string()
+        };
+        let resolution = self.resolve(&content_type, size);
+        (content_type, resolution)
+    }
+
+    /// The file of a themed icon or of an icon that is a file already
+    pub fn find_gicon(&self, icon: &gio::Icon, size: i32) -> Option<PathBuf> {
+        if let Some(file_icon) = icon.downcast_ref::<gio::FileIcon>() {
+            return file_icon.file().path();
+        }
+
+        let themed_icon = icon.downcast_ref::<gio::ThemedIcon>()?;
+        themed_icon
+            .names()
+            .iter()
+            .find_map(|name| self.lookup(name, size))
+    }
+
+    fn lookup(&self, name: &str, size: i32) -> Option<PathBuf> {
+        self.backend.lookup(name, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+
+    /// A theme that has the icons in `files`, answering with the names of `names`
+    struct FakeBackend {
+        names: HashMap<&'static str, Vec<&'static str>>,
+        files: Vec<&'static str>,
+        lookups: RefCell<Vec<String>>,
+    }
+
+    impl FakeBackend {
+        fn new(files: &[&'static str]) -> Self {
+            Self {
+                names: HashMap::from([(
+                    "application/x-unknown-thing",
+                    vec!["application-x-unknown-thing", "application-x-generic"],
+                )]),
+                files: files.to_vec(),
+                lookups: RefCell::new(Vec::new()),
+            }
+        }
+    }
+
+    impl IconBackend for FakeBackend {
+        fn icon_names(&self, content_type: &str) -> Vec<String> {
+            self.names
+                .get(content_type)
+                .into_iter()
+                .flatten()
+                .map(|name| name.to_string())
+                .collect()
+        }
+
+        fn lookup(&self, name: &str, _size: i32) -> Option<PathBuf> {
+            self.lookups.borrow_mut().push(name.to_string());
+            self.files
+                .contains(&name)
+                .then(|| PathBuf::from(format!("/icons/{name}.svg")))
+        }
+
+        fn load(
+            &self,
+            _resolution: &IconResolution,
+            path: &Path,
+            _size: i32,
+            _foreground: egui::Color32,
+        ) -> anyhow::Result<RgbaImage> {
+            anyhow::bail!("{} is not drawn in tests", path.display())
+        }
+
+        fn take_theme_changed(&self) -> bool {
+            false
+        }
+    }
+
+    fn bundled_uri(source: &egui::ImageSource<'static>) -> Option<String> {
+        match source {
+            egui::ImageSource::Bytes { uri, .. } => Some(uri.to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn unknown_types_fall_back_to_generic_icons_then_the_bundled_one() {
+        let content_type = "application/x-unknown-thing";
+        let tried = [
+            "application-x-unknown-thing",
+            "application-x-generic",
+            "application-octet-stream",
+        ];
+
+        // The theme has the generic icon of the type
+        let mut finder = IconFinder::with_backend(FakeBackend::new(&[
+            "application-x-generic",
+            "application-octet-stream",
+        ]));
+        let resolution = finder.resolve(content_type, 48);
+        assert_eq!(resolution.names, tried);
+        assert_eq!(
+            resolution.path.as_deref(),
+            Some(Path::new("/icons/application-x-generic.svg"))
+        );
+
+        // Only the icon of any file
+        let mut finder = IconFinder::with_backend(FakeBackend::new(&["application-octet-stream"]));
+        let resolution = finder.resolve(content_type, 48);
+        assert_eq!(
+            resolution.path.as_deref(),
+            Some(Path::new("/icons/application-octet-stream.svg"))
+        );
+
+        // Nothing at all: every name is tried, then the bundled icon is used
+        let mut finder = IconFinder::with_backend(FakeBackend::new(&[]));
+        let resolution = finder.resolve(content_type, 48);
+        assert_eq!(resolution.path, None);
+        assert_eq!(*finder.backend.lookups.borrow(), tried);
+        assert_eq!(
+            bundled_uri(&resolution.image_source(content_type)),
+            bundled_uri(&FILE_ICON)
+        );
+        assert_eq!(
+            bundled_uri(&IconResolution::default().image_source("inode/directory")),
+            bundled_uri(&FOLDER_ICON)
+        );
+        assert_ne!(bundled_uri(&FILE_ICON), bundled_uri(&FOLDER_ICON));
+
+        // A type the backend knows nothing of still gets the icon of any file
+        let resolution = finder.resolve("x-nothing/at-all", 48);
+        assert_eq!(resolution.names, ["application-octet-stream"]);
+    }
+
+    #[test]
+    fn resolutions_are_cached_per_type_and_size() {
+        let mut finder = IconFinder::with_backend(FakeBackend::new(&["application-octet-stream"]));
+        finder.resolve("application/x-unknown-thing", 48);
+        let lookups = finder.backend.lookups.borrow().len();
+        finder.resolve("application/x-unknown-thing", 48);
+        assert_eq!(finder.backend.lookups.borrow().len(), lookups);
+        assert!(finder.is_resolved("application/x-unknown-thing", 48));
+        assert!(!finder.is_resolved("application/x-unknown-thing", 16));
+    }
+}
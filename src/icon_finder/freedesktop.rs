@@ -0,0 +1,572 @@
+//! Icons looked up in the icon directories as the freedesktop icon theme specification
+//! describes, without GTK (`backend-freedesktop` feature)
+
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use eframe::egui;
+use resvg::{tiny_skia, usvg};
+
+use super::{IconBackend, IconResolution, RgbaImage};
+
+/// Icon file formats looked for, in order of preference. XPM isn't supported
+const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+/// Scale of the icons looked up; the finder asks for sizes in pixels already
+const SCALE: i32 = 1;
+
+/// The theme every theme falls back to
+const FALLBACK_THEME: &str = "hicolor";
+
+/// How a theme directory's icons match sizes
+#[derive(Debug)]
+enum SizeType {
+    Fixed,
+    Scalable { min: i32, max: i32 },
+    Threshold(i32),
+}
+
+/// A directory of a theme, relative to the theme's directory in each base directory
+#[derive(Debug)]
+struct ThemeDirectory {
+    path: String,
+    size: i32,
+    scale: i32,
+    size_type: SizeType,
+}
+
+impl ThemeDirectory {
+    fn matches_size(&self, size: i32, scale: i32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+        match self.size_type {
+            SizeType::Fixed => self.size == size,
+            SizeType::Scalable { min, max } => (min..=max).contains(&size),
+            SizeType::Threshold(threshold) => {
+                (self.size - threshold..=self.size + threshold).contains(&size)
+            }
+        }
+    }
+
+    fn size_distance(&self, size: i32, scale: i32) -> i32 {
+        let (min, max) = match self.size_type {
+            SizeType::Fixed => (self.size, self.size),
+            SizeType::Scalable { min, max } => (min, max),
+            SizeType::Threshold(threshold) => (self.size - threshold, self.size + threshold),
+        };
+        if size * scale < min * self.scale {
+            min * self.scale - size * scale
+        } else if size * scale > max * self.scale {
+            size * scale - max * self.scale
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Theme {
+    name: String,
+    directories: Vec<ThemeDirectory>,
+}
+
+pub struct FreedesktopBackend {
+    /// Directories holding themes and unthemed icons, searched in order
+    base_dirs: Vec<PathBuf>,
+    /// The configured theme and the themes it inherits from, nearest first, hicolor last
+    themes: Vec<Theme>,
+    /// Icon names of MIME types from shared-mime-info's `icons` file
+    mime_icons: HashMap<String, String>,
+    /// Generic icon names of MIME types from shared-mime-info's `generic-icons` file
+    mime_generic_icons: HashMap<String, String>,
+}
+
+impl FreedesktopBackend {
+    pub fn new() -> Self {
+        let data_dirs = data_dirs();
+        let mut base_dirs: Vec<PathBuf> = env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".icons"))
+            .into_iter()
+            .chain(data_dirs.iter().map(|dir| dir.join("icons")))
+            .collect();
+        base_dirs.push(PathBuf::from("/usr/share/pixmaps"));
+
+        let theme_name = configured_theme().unwrap_or_else(|| FALLBACK_THEME.to_string());
+        let mime_dirs: Vec<PathBuf> = data_dirs.iter().map(|dir| dir.join("mime")).collect();
+        Self::with_dirs(base_dirs, &theme_name, &mime_dirs)
+    }
+
+    /// The theme `theme_name` from `base_dirs`, with the icon names of MIME types from
+    /// `mime_dirs`
+    fn with_dirs(base_dirs: Vec<PathBuf>, theme_name: &str, mime_dirs: &[PathBuf]) -> Self {
+        let themes = load_themes(&base_dirs, theme_name);
+        if themes.is_empty() {
+            warn!("No icon theme found, file icons are unavailable");
+        }
+
+        Self {
+            base_dirs,
+            themes,
+            mime_icons: read_mime_icons(mime_dirs, "icons"),
+            mime_generic_icons: read_mime_icons(mime_dirs, "generic-icons"),
+        }
+    }
+
+    /// LookupIcon of the specification: a file in a directory matching the size, else the one
+    /// in the directory closest to it
+    fn lookup_in_theme(&self, theme: &Theme, name: &str, size: i32) -> Option<PathBuf> {
+        let files = || {
+            theme.directories.iter().flat_map(move |directory| {
+                self.base_dirs.iter().flat_map(move |base_dir| {
+                    EXTENSIONS.iter().map(move |extension| {
+                        let file = base_dir
+                            .join(&theme.name)
+                            .join(&directory.path)
+                            .join(format!("{name}.{extension}"));
+                        (directory, file)
+                    })
+                })
+            })
+        };
+
+        if let Some((_, file)) = files()
+            .filter(|(directory, _)| directory.matches_size(size, SCALE))
+            .find(|(_, file)| file.is_file())
+        {
+            return Some(file);
+        }
+        files()
+            .filter(|(_, file)| file.is_file())
+            .min_by_key(|(directory, _)| directory.size_distance(size, SCALE))
+            .map(|(_, file)| file)
+    }
+
+    /// An icon outside the themes, directly in a base directory
+    fn lookup_unthemed(&self, name: &str) -> Option<PathBuf> {
+        self.base_dirs.iter().find_map(|base_dir| {
+            EXTENSIONS
+                .iter()
+                .map(|extension| base_dir.join(format!("{name}.{extension}")))
+                .find(|file| file.is_file())
+        })
+    }
+}
+
+impl IconBackend for FreedesktopBackend {
+    fn icon_names(&self, content_type: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut push = |name: String| {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        };
+
+        if let Some(icon) = self.mime_icons.get(content_type) {
+            push(icon.clone());
+        }
+        push(content_type.replace('/', "-"));
+        match self.mime_generic_icons.get(content_type) {
+            Some(generic) => push(generic.clone()),
+            None if content_type == "inode/directory" => push("folder".to_string()),
+            None => {
+                let media = content_type.split('/').next().unwrap_or(content_type);
+                push(format!("{media}-x-generic"));
+            }
+        }
+        names
+    }
+
+    fn lookup(&self, name: &str, size: i32) -> Option<PathBuf> {
+        self.themes
+            .iter()
+            .find_map(|theme| self.lookup_in_theme(theme, name, size))
+            .or_else(|| self.lookup_unthemed(name))
+    }
+
+    fn load(
+        &self,
+        resolution: &IconResolution,
+        path: &Path,
+        size: i32,
+        foreground: egui::Color32,
+    ) -> anyhow::Result<RgbaImage> {
+        let data = fs::read(path)?;
+        let pixmap = if path.extension().is_some_and(|extension| extension == "svg") {
+            render_svg(&data, size)?
+        } else {
+            // Drawn scaled like any other texture
+            tiny_skia::Pixmap::decode_png(&data)?
+        };
+
+        let mut pixels = Vec::with_capacity(pixmap.pixels().len() * 4);
+        for pixel in pixmap.pixels() {
+            let pixel = pixel.demultiply();
+            pixels.extend_from_slice(&[pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]);
+        }
+        if resolution.is_symbolic {
+            // Symbolic icons are drawn in one color; keep only their shape
+            let [r, g, b, a] = foreground.to_srgba_unmultiplied();
+            for pixel in pixels.chunks_exact_mut(4) {
+                let alpha = (u16::from(pixel[3]) * u16::from(a) / 255) as u8;
+                pixel.copy_from_slice(&[r, g, b, alpha]);
+            }
+        }
+        Ok(RgbaImage {
+            width: pixmap.width() as usize,
+            height: pixmap.height() as usize,
+            pixels,
+        })
+    }
+
+    /// The directories aren't watched, a theme switched to is picked up on the next start
+    fn take_theme_changed(&self) -> bool {
+        false
+    }
+}
+
+/// `$XDG_DATA_HOME` and `$XDG_DATA_DIRS`, with their defaults
+fn data_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .collect()
+}
+
+/// The icon theme set in GTK's `settings.ini`, which desktops keep in sync with their own setting
+fn configured_theme() -> Option<String> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    let settings = fs::read_to_string(config_home.join("gtk-3.0/settings.ini")).ok()?;
+    parse_ini(&settings)
+        .get("Settings")?
+        .get("gtk-icon-theme-name")
+        .cloned()
+}
+
+/// `theme_name` and the themes it inherits from, depth first, then hicolor
+fn load_themes(base_dirs: &[PathBuf], theme_name: &str) -> Vec<Theme> {
+    let mut themes = Vec::new();
+    let mut seen = HashSet::new();
+    let mut pending = vec![theme_name.to_string()];
+    while let Some(name) = pending.pop() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        match load_theme(base_dirs, &name) {
+            Ok((theme, inherits)) => {
+                themes.push(theme);
+                pending.extend(inherits.into_iter().rev());
+            }
+            Err(e) => debug!("Skipping the icon theme {name}: {e:#}"),
+        }
+        if pending.is_empty() && !seen.contains(FALLBACK_THEME) {
+            pending.push(FALLBACK_THEME.to_string());
+        }
+    }
+    themes
+}
+
+/// The theme `name` from the first `index.theme` found for it, with the themes it inherits from
+fn load_theme(base_dirs: &[PathBuf], name: &str) -> anyhow::Result<(Theme, Vec<String>)> {
+    let index_path = base_dirs
+        .iter()
+        .map(|base_dir| base_dir.join(name).join("index.theme"))
+        .find(|index_path| index_path.is_file())
+        .context("no index.theme")?;
+    let index = parse_ini(
+        &fs::read_to_string(&index_path)
+            .with_context(|| format!("failed to read {}", index_path.display()))?,
+    );
+    let theme_section = index.get("Icon Theme").context("no [Icon Theme] section")?;
+
+    let list = |key: &str| -> Vec<String> {
+        theme_section
+            .get(key)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let mut paths = list("Directories");
+    paths.extend(list("ScaledDirectories"));
+
+    let directories = paths
+        .into_iter()
+        .filter_map(|path| {
+            let section = index.get(&path)?;
+            let number = |key: &str| section.get(key).and_then(|value| value.parse().ok());
+            let size = number("Size")?;
+            let size_type = match section.get("Type").map(String::as_str) {
+                Some("Fixed") => SizeType::Fixed,
+                Some("Scalable") => SizeType::Scalable {
+                    min: number("MinSize").unwrap_or(size),
+                    max: number("MaxSize").unwrap_or(size),
+                },
+                _ => SizeType::Threshold(number("Threshold").unwrap_or(2)),
+            };
+            Some(ThemeDirectory {
+                size,
+                scale: number("Scale").unwrap_or(1),
+                size_type,
+                path,
+            })
+        })
+        .collect();
+
+    let theme = Theme {
+        name: name.to_string(),
+        directories,
+    };
+    Ok((theme, list("Inherits")))
+}
+
+/// Sections of an ini-style file, keys and values trimmed. Comments and lines outside of
+/// sections are skipped
+fn parse_ini(text: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut current = None;
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            current = Some(name.to_string());
+            sections.entry(name.to_string()).or_default();
+        } else if let (Some(section), Some((key, value))) = (&current, line.split_once('='))
+            && let Some(entries) = sections.get_mut(section)
+        {
+            entries
+                .entry(key.trim().to_string())
+                .or_insert_with(|| value.trim().to_string());
+        }
+    }
+    sections
+}
+
+/// The `type:icon` lines of shared-mime-info's `file_name` in `mime_dirs`, the earlier
+/// directories winning
+fn read_mime_icons(mime_dirs: &[PathBuf], file_name: &str) -> HashMap<String, String> {
+    let mut icons = HashMap::new();
+    for mime_dir in mime_dirs {
+        let Ok(text) = fs::read_to_string(mime_dir.join(file_name)) else {
+            continue;
+        };
+        for (mime_type, icon) in text.lines().filter_map(|line| line.split_once(':')) {
+            icons
+                .entry(mime_type.trim().to_string())
+                .or_insert_with(|| icon.trim().to_string());
+        }
+    }
+    icons
+}
+
+/// An SVG rendered into a `size` pixel square, keeping its aspect ratio
+fn render_svg(data: &[u8], size: i32) -> anyhow::Result<tiny_skia::Pixmap> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+    let size = size.max(1) as u32;
+    let mut pixmap = tiny_skia::Pixmap::new(size, size).context("invalid icon size")?;
+    let scale = size as f32 / tree.size().width().max(tree.size().height());
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon_finder::IconFinder;
+
+    /// The fixture theme `Child`, inheriting from `Parent`, then hicolor
+    fn fixture_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/icon_finder/fixtures")
+    }
+
+    fn backend() -> FreedesktopBackend {
+        let fixtures = fixture_dir();
+        FreedesktopBackend::with_dirs(
+            vec![fixtures.join("icons"), fixtures.join("pixmaps")],
+            "Child",
+            &[fixtures.join("mime")],
+        )
+    }
+
+    /// `path` relative to the fixture directory
+    fn relative(path: Option<PathBuf>) -> Option<String> {
+        let fixtures = fixture_dir();
+        path.map(|path| {
+            path.strip_prefix(&fixtures)
+                .unwrap_or(&path)
+                .display()
+                .to_string()
+        })
+    }
+
+    #[test]
+    fn themes_are_inherited_depth_first_then_hicolor() {
+        let backend = backend();
+        let names: Vec<&str> = backend.themes.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Child", "Parent", "hicolor"]);
+
+        let child = &backend.themes[0];
+        let directories: Vec<(&str, i32, i32)> = child
+            .directories
+            .iter()
+            .map(|d| (d.path.as_str(), d.size, d.scale))
+            .collect();
+        assert_eq!(
+            directories,
+            [
+                ("16x16/mimetypes", 16, 1),
+                ("48x48/mimetypes", 48, 1),
+                ("16x16@2/mimetypes", 16, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn lookups_pick_the_directory_of_the_size() {
+        let backend = backend();
+        let cases = [
+            // Fixed size
+            (16, "icons/Child/16x16/mimetypes/text-x-python.png"),
+            // Within the threshold of 48
+            (48, "icons/Child/48x48/mimetypes/text-x-python.png"),
+            (46, "icons/Child/48x48/mimetypes/text-x-python.png"),
+            // 16 at scale 2 is exactly 32 pixels
+            (32, "icons/Child/16x16@2/mimetypes/text-x-python.png"),
+            // No size matches: the closest of the nearest theme beats an inherited scalable one
+            (100, "icons/Child/48x48/mimetypes/text-x-python.png"),
+        ];
+        for (size, expected) in cases {
+            assert_eq!(
+                relative(backend.lookup("text-x-python", size)).as_deref(),
+                Some(expected),
+                "size {size}"
+            );
+        }
+    }
+
+    #[test]
+    fn lookups_fall_back_to_inherited_themes_hicolor_and_unthemed_icons() {
+        let backend = backend();
+        let cases = [
+            (
+                "text-x-generic",
+                Some("icons/Parent/scalable/mimetypes/text-x-generic.svg"),
+            ),
+            (
+                "application-octet-stream",
+                Some("icons/hicolor/48x48/mimetypes/application-octet-stream.png"),
+            ),
+            ("unthemed", Some("pixmaps/unthemed.png")),
+            ("missing", None),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(
+                relative(backend.lookup(name, 48)).as_deref(),
+                expected,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn icon_names_come_from_shared_mime_info_then_the_type() {
+        let backend = backend();
+        let cases: [(&str, &[&str]); 4] = [
+            ("text/x-python", &["text-x-python", "text-x-script"]),
+            (
+                "application/x-fixture",
+                &[
+                    "fixture-icon",
+                    "application-x-fixture",
+                    "application-x-generic",
+                ],
+            ),
+            ("inode/directory", &["inode-directory", "folder"]),
+            ("text/plain", &["text-plain", "text-x-generic"]),
+        ];
+        for (content_type, expected) in cases {
+            assert_eq!(backend.icon_names(content_type), expected, "{content_type}");
+        }
+    }
+
+    #[test]
+    fn the_finder_resolves_through_the_fixture_theme() {
+        let mut finder = IconFinder::with_backend(backend());
+        let resolution = finder.resolve("text/plain", 48);
+        assert_eq!(
+            relative(resolution.path).as_deref(),
+            Some("icons/Parent/scalable/mimetypes/text-x-generic.svg")
+        );
+        let resolution = finder.resolve("application/x-unknown-thing", 48);
+        assert_eq!(
+            relative(resolution.path).as_deref(),
+            Some("icons/hicolor/48x48/mimetypes/application-octet-stream.png")
+        );
+    }
+
+    #[test]
+    fn icons_are_rasterized_and_symbolic_ones_recolored() {
+        let backend = backend();
+        let foreground = egui::Color32::from_rgb(0xcc, 0x00, 0x00);
+        let load = |name: &str, size, is_symbolic| {
+            let path = backend.lookup(name, size).unwrap();
+            let resolution = IconResolution {
+                path: Some(path.clone()),
+                names: vec![name.to_string()],
+                is_symbolic,
+            };
+            backend.load(&resolution, &path, size, foreground).unwrap()
+        };
+
+        let png = load("text-x-python", 16, false);
+        assert_eq!((png.width, png.height), (1, 1));
+        assert_eq!(png.pixels, [0x34, 0x65, 0xa4, 0xff]);
+
+        let svg = load("text-x-generic", 24, false);
+        assert_eq!((svg.width, svg.height), (24, 24));
+        assert_eq!(
+            svg.pixels[(12 * 24 + 12) * 4..][..4],
+            [0x34, 0x65, 0xa4, 0xff]
+        );
+
+        let symbolic = load("text-x-generic-symbolic", 24, true);
+        assert_eq!(
+            symbolic.pixels[(12 * 24 + 12) * 4..][..4],
+            [0xcc, 0x00, 0x00, 0xff]
+        );
+        assert!(
+            symbolic
+                .pixels
+                .chunks_exact(4)
+                .all(|p| p[..3] == [0xcc, 0, 0])
+        );
+    }
+}
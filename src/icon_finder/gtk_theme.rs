@@ -0,0 +1,136 @@
+//! Icons from GTK's default icon theme
+
+use std::{
+    cell::Cell,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use eframe::egui;
+use gio::glib::object::Cast;
+use gtk::traits::IconThemeExt;
+
+use super::{IconBackend, IconResolution, RgbaImage};
+
+pub struct GtkBackend {
+    theme: Option<gtk::IconTheme>,
+    /// Set when the theme changed, until the finder takes it
+    theme_changed: Rc<Cell<bool>>,
+}
+
+impl GtkBackend {
+    pub fn new() -> Self {
+        let theme_changed = Rc::new(Cell::new(false));
+        let theme = if gtk::is_initialized_main_thread() {
+            gtk::IconTheme::default()
+        } else {
+            warn!("GTK isn't initialized on this thread, file icons are unavailable");
+            None
+        };
+        if let Some(theme) = &theme {
+            let theme_changed = Rc::clone(&theme_changed);
+            theme.connect_changed(move |_| theme_changed.set(true));
+        }
+
+        Self {
+            theme,
+            theme_changed,
+        }
+    }
+}
+
+impl IconBackend for GtkBackend {
+    fn icon_names(&self, content_type: &str) -> Vec<String> {
+        let icon = gio::content_type_get_icon(content_type);
+        let mut names: Vec<String> = icon
+            .downcast_ref::<gio::ThemedIcon>()
+            .map(|themed_icon| {
+                themed_icon
+                    .names()
+                    .iter()
+                    .map(|name| name.to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if let Some(generic) = gio::content_type_get_generic_icon_name(content_type)
+            && !names.iter().any(|name| *name == generic)
+        {
+            names.push(generic.to_string());
+        }
+        names
+    }
+
+    fn lookup(&self, name: &str, size: i32) -> Option<PathBuf> {
+        self.theme
+            .as_ref()?
+            .lookup_icon(name, size, gtk::IconLookupFlags::empty())
+            .and_then(|icon_info| icon_info.filename())
+    }
+
+    fn load(
+        &self,
+        resolution: &IconResolution,
+        path: &Path,
+        size: i32,
+        foreground: egui::Color32,
+    ) -> anyhow::Result<RgbaImage> {
+        let theme = self
+            .theme
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("no icon theme"))?;
+        let icon_info = resolution
+            .names
+            .iter()
+            .find_map(|name| {
+                theme
+                    .lookup_icon(name, size, gtk::IconLookupFlags::FORCE_SIZE)
+                    .filter(|icon_info| icon_info.filename().as_deref() == Some(path))
+            })
+            .ok_or_else(|| anyhow::anyhow!("not in the theme at {size} pixels"))?;
+
+        let pixbuf = if resolution.is_symbolic {
+            let [r, g, b, a] = foreground.to_srgba_unmultiplied();
+            let foreground = gtk::gdk::RGBA::new(
+                f64::from(r) / 255.0,
+                f64::from(g) / 255.0,
+                f64::from(b) / 255.0,
+                f64::from(a) / 255.0,
+            );
+            icon_info
+                .load_symbolic(&foreground, None, None, None)
+                .map(|(pixbuf, _)| pixbuf)?
+        } else {
+            icon_info.load_icon()?
+        };
+        rgba_image(&pixbuf).ok_or_else(|| anyhow::anyhow!("unsupported pixel format"))
+    }
+
+    fn take_theme_changed(&self) -> bool {
+        self.theme_changed.replace(false)
+    }
+}
+
+fn rgba_image(pixbuf: &gtk::gdk_pixbuf::Pixbuf) -> Option<RgbaImage> {
+    let (width, height) = (pixbuf.width() as usize, pixbuf.height() as usize);
+    let channels = pixbuf.n_channels() as usize;
+    let rowstride = pixbuf.rowstride() as usize;
+    if pixbuf.bits_per_sample() != 8 || !(3..=4).contains(&channels) {
+        return None;
+    }
+
+    let bytes = pixbuf.read_pixel_bytes();
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        let start = row * rowstride;
+        let line = bytes.get(start..start + width * channels)?;
+        for pixel in line.chunks_exact(channels) {
+            pixels.extend_from_slice(&pixel[..3]);
+            pixels.push(if channels == 4 { pixel[3] } else { u8::MAX });
+        }
+    }
+    Some(RgbaImage {
+        width,
+        height,
+        pixels,
+    })
+}
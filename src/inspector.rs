@@ -0,0 +1,85 @@
+//! A side panel listing the sequences the terminal applied (`inspector` feature)
+
+use std::collections::VecDeque;
+
+use eframe::egui;
+
+use crate::{term::trace::TraceEvent, terminal_widget::TerminalWidget};
+
+/// Events listed; older ones are dropped
+const MAX_EVENTS: usize = 500;
+
+#[derive(Default)]
+pub struct Inspector {
+    events: VecDeque<(u64, TraceEvent)>,
+    /// Number of events taken so far, identifying them across drops
+    taken: u64,
+    /// Show only the sequences no handler took
+    unhandled_only: bool,
+    /// The event whose rows are highlighted on the screen
+    selected: Option<u64>,
+}
+
+impl Inspector {
+    /// Take the events of the terminal of `widget` and show them in a panel at the right
+    pub fn show(&mut self, ctx: &egui::Context, widget: &mut TerminalWidget) {
+        for event in widget.term.take_trace() {
+            if self.events.len() == MAX_EVENTS {
+                self.events.pop_front();
+            }
+            self.events.push_back((self.taken, event));
+            self.taken += 1;
+        }
+
+        egui::SidePanel::right("inspector")
+            .resizable(true)
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("Inspector");
+                    ui.checkbox(&mut self.unhandled_only, "Unhandled only");
+                    ui.checkbox(&mut widget.inspect_cells, "Cell tooltips");
+                    if ui.button("Clear").clicked() {
+                        self.events.clear();
+                        self.selected = None;
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .stick_to_bottom(true)
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        egui::Grid::new("inspector_events")
+                            .striped(true)
+                            .num_columns(3)
+                            .show(ui, |ui| {
+                                for (id, event) in &self.events {
+                                    if self.unhandled_only && event.handler.is_some() {
+                                        continue;
+                                    }
+                                    ui.label(event.class);
+                                    let selected = self.selected == Some(*id);
+                                    let raw = egui::RichText::new(&event.raw).monospace();
+                                    if ui.selectable_label(selected, raw).clicked() {
+                                        self.selected = (!selected).then_some(*id);
+                                    }
+                                    match event.handler {
+                                        Some(handler) => ui.weak(handler),
+                                        None => ui
+                                            .colored_label(ui.visuals().warn_fg_color, "unhandled"),
+                                    };
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+
+        widget.highlighted_rows = self.selected.and_then(|selected| {
+            self.events
+                .iter()
+                .find(|(id, _)| *id == selected)
+                .map(|(_, event)| event.rows.clone())
+        });
+    }
+}
@@ -0,0 +1,422 @@
+use std::{collections::BTreeMap, fmt};
+
+use eframe::egui;
+use serde::Deserialize;
+
+/// A key with the modifiers that have to be held, e.g. `Ctrl+Shift+T`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub modifiers: egui::Modifiers,
+    pub key: egui::Key,
+}
+
+impl Chord {
+    /// Parse `Ctrl+Shift+T`: modifiers (`Ctrl`, `Shift`, `Alt`) and a key as egui names it,
+    /// joined with `+`
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        // The key can be `+` itself
+        let (modifiers, key) = match text.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => text.rsplit_once('+').unwrap_or(("", text)),
+        };
+
+        let mut chord_modifiers = egui::Modifiers::NONE;
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            chord_modifiers |= match modifier.trim().to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => egui::Modifiers::CTRL,
+                "shift" => egui::Modifiers::SHIFT,
+                "alt" => egui::Modifiers::ALT,
+                _ => anyhow::bail!("unknown modifier `{modifier}` in \"{text}\""),
+            };
+        }
+
+        let key = key.trim();
+        let key = egui::Key::from_name(key)
+            .or_else(|| egui::Key::from_name(&key.to_ascii_uppercase()))
+            .ok_or_else(|| anyhow::anyhow!("unknown key `{key}` in \"{text}\""))?;
+        Ok(Self {
+            modifiers: chord_modifiers,
+            key,
+        })
+    }
+
+    /// Take a press of the chord out of this frame's events. The modifiers have to match
+    /// exactly, so `Ctrl+F` doesn't fire for `Ctrl+Shift+F`
+    pub fn consume(&self, input: &mut egui::InputState) -> bool {
+        let mut found = false;
+        input.events.retain(|event| {
+            let matches = !found
+                && matches!(
+                    event,
+                    egui::Event::Key { key, pressed: true, modifiers, .. }
+                        if *key == self.key && modifiers.matches_exact(self.modifiers)
+                );
+            found |= matches;
+            !matches
+        });
+        found
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.ctrl || self.modifiers.command {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+
+/// What an action is bound to in the configuration file: a chord, or nothing for `""`
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Binding(pub Option<Chord>);
+
+impl TryFrom<String> for Binding {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> anyhow::Result<Self> {
+        if text.trim().is_empty() {
+            return Ok(Self(None));
+        }
+        Chord::parse(&text).map(|chord| Self(Some(chord)))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(chord) => chord.fmt(f),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An entry of the action registry
+pub struct ActionInfo<A> {
+    pub action: A,
+    /// The key in the configuration file
+    pub name: &'static str,
+    pub default: &'static str,
+    pub description: &'static str,
+}
+
+/// The actions of one scope: the part of the window that has the keyboard when they fire
+pub trait Action: Copy + Ord + 'static {
+    /// The table of `[keybindings]` with the actions of the scope
+    const SCOPE: &'static str;
+
+    fn registry() -> &'static [ActionInfo<Self>];
+
+    fn overrides(keybindings: &Keybindings) -> &BTreeMap<Self, Binding>;
+
+    fn overrides_mut(keybindings: &mut Keybindings) -> &mut BTreeMap<Self, Binding>;
+
+    fn info(self) -> &'static ActionInfo<Self> {
+        Self::registry()
+            .iter()
+            .find(|info| info.action == self)
+            .expect("every action is registered")
+    }
+}
+
+/// Actions of the terminal and the window, which also fire while the explorer has the
+/// keyboard unless the explorer binds the same chord
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminalAction {
+    NewWindow,
+    NewTab,
+    CloseTab,
+    PreviousTab,
+    NextTab,
+    SwitchPane,
+    ToggleExplorer,
+    OpenSettings,
+    CommandPalette,
+    ToggleRecording,
+    SaveAsPdf,
+    ToggleAlwaysOnTop,
+    IncreaseOpacity,
+    DecreaseOpacity,
+    #[cfg(feature = "inspector")]
+    ToggleInspector,
+}
+
+impl Action for TerminalAction {
+    const SCOPE: &'static str = "terminal";
+
+    fn registry() -> &'static [ActionInfo<Self>] {
+        &[
+            ActionInfo {
+                action: Self::NewWindow,
+                name: "new_window",
+                default: "Ctrl+Shift+N",
+                description: "New window",
+            },
+            ActionInfo {
+                action: Self::NewTab,
+                name: "new_tab",
+                default: "Ctrl+Shift+T",
+                description: "New tab",
+            },
+            ActionInfo {
+                action: Self::CloseTab,
+                name: "close_tab",
+                default: "Ctrl+Shift+W",
+                description: "Close tab",
+            },
+            ActionInfo {
+                action: Self::PreviousTab,
+                name: "previous_tab",
+                default: "Ctrl+PageUp",
+                description: "Previous tab",
+            },
+            ActionInfo {
+                action: Self::NextTab,
+                name: "next_tab",
+                default: "Ctrl+PageDown",
+                description: "Next tab",
+            },
+            ActionInfo {
+                action: Self::SwitchPane,
+                name: "switch_pane",
+                default: "F6",
+                description: "Switch explorer pane",
+            },
+            ActionInfo {
+                action: Self::ToggleExplorer,
+                name: "toggle_explorer",
+                default: "Ctrl+Shift+B",
+                description: "Show or hide the explorer",
+            },
+            ActionInfo {
+                action: Self::OpenSettings,
+                name: "open_settings",
+                default: "Ctrl+Comma",
+                description: "Settings",
+            },
+            ActionInfo {
+                action: Self::CommandPalette,
+                name: "command_palette",
+                default: "Ctrl+Shift+P",
+                description: "Command palette",
+            },
+            ActionInfo {
+                action: Self::ToggleRecording,
+                name: "toggle_recording",
+                default: "Ctrl+Shift+R",
+                description: "Start or stop recording the terminal (asciicast)",
+            },
+            ActionInfo {
+                action: Self::SaveAsPdf,
+                name: "save_as_pdf",
+                default: "Ctrl+Shift+S",
+                description: "Save the screen or the selection as a PDF",
+            },
+            ActionInfo {
+                action: Self::ToggleAlwaysOnTop,
+                name: "toggle_always_on_top",
+                default: "Ctrl+Shift+O",
+                description: "Keep the window above the others, or stop",
+            },
+            ActionInfo {
+                action: Self::IncreaseOpacity,
+                name: "increase_opacity",
+                default: "Ctrl+Alt+ArrowUp",
+                description: "Make the terminal's background more opaque",
+            },
+            ActionInfo {
+                action: Self::DecreaseOpacity,
+                name: "decrease_opacity",
+                default: "Ctrl+Alt+ArrowDown",
+                description: "Make the terminal's background more transparent",
+            },
+            #[cfg(feature = "inspector")]
+            ActionInfo {
+                action: Self::ToggleInspector,
+                name: "toggle_inspector",
+                default: "Ctrl+Shift+F12",
+                description: "Show or hide the sequence inspector",
+            },
+        ]
+    }
+
+    fn overrides(keybindings: &Keybindings) -> &BTreeMap<Self, Binding> {
+        &keybindings.terminal
+    }
+
+    fn overrides_mut(keybindings: &mut Keybindings) -> &mut BTreeMap<Self, Binding> {
+        &mut keybindings.terminal
+    }
+}
+
+/// Actions of the explorer, firing while it has the keyboard
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ExplorerAction {
+    Refresh,
+    EditPath,
+    ToggleHidden,
+    Filter,
+    Search,
+    SelectAll,
+    Rename,
+    Trash,
+    Delete,
+    Parent,
+}
+
+impl Action for ExplorerAction {
+    const SCOPE: &'static str = "explorer";
+
+    fn registry() -> &'static [ActionInfo<Self>] {
+        &[
+            ActionInfo {
+                action: Self::Refresh,
+                name: "refresh",
+                default: "F5",
+                description: "Refresh",
+            },
+            ActionInfo {
+                action: Self::EditPath,
+                name: "edit_path",
+                default: "Ctrl+L",
+                description: "Type a path",
+            },
+            ActionInfo {
+                action: Self::ToggleHidden,
+                name: "toggle_hidden",
+                default: "Ctrl+H",
+                description: "Show hidden files",
+            },
+            ActionInfo {
+                action: Self::Filter,
+                name: "filter",
+                default: "Ctrl+F",
+                description: "Filter",
+            },
+            ActionInfo {
+                action: Self::Search,
+                name: "search",
+                default: "Ctrl+Shift+F",
+                description: "Search below the directory",
+            },
+            ActionInfo {
+                action: Self::SelectAll,
+                name: "select_all",
+                default: "Ctrl+A",
+                description: "Select all",
+            },
+            ActionInfo {
+                action: Self::Rename,
+                name: "rename",
+                default: "F2",
+                description: "Rename",
+            },
+            ActionInfo {
+                action: Self::Trash,
+                name: "trash",
+                default: "Delete",
+                description: "Move to the trash",
+            },
+            ActionInfo {
+                action: Self::Delete,
+                name: "delete",
+                default: "Shift+Delete",
+                description: "Delete permanently",
+            },
+            ActionInfo {
+                action: Self::Parent,
+                name: "parent",
+                default: "Alt+ArrowUp",
+                description: "Parent directory",
+            },
+        ]
+    }
+
+    fn overrides(keybindings: &Keybindings) -> &BTreeMap<Self, Binding> {
+        &keybindings.explorer
+    }
+
+    fn overrides_mut(keybindings: &mut Keybindings) -> &mut BTreeMap<Self, Binding> {
+        &mut keybindings.explorer
+    }
+}
+
+/// The `[keybindings]` section: for each scope the actions bound to other chords than their
+/// defaults
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keybindings {
+    pub terminal: BTreeMap<TerminalAction, Binding>,
+    pub explorer: BTreeMap<ExplorerAction, Binding>,
+}
+
+impl Keybindings {
+    /// The chord `action` is bound to, if any
+    pub fn chord<A: Action>(&self, action: A) -> Option<Chord> {
+        match A::overrides(self).get(&action) {
+            Some(binding) => binding.0,
+            None => Some(Chord::parse(action.info().default).expect("default chords parse")),
+        }
+    }
+
+    /// Whether `action` was pressed in this frame, taking the press out of the events
+    pub fn pressed<A: Action>(&self, input: &mut egui::InputState, action: A) -> bool {
+        self.chord(action).is_some_and(|chord| chord.consume(input))
+    }
+
+    /// The action of the scope bound to `chord`
+    pub fn action_for<A: Action>(&self, chord: Chord) -> Option<A> {
+        A::registry()
+            .iter()
+            .map(|info| info.action)
+            .find(|&action| self.chord(action) == Some(chord))
+    }
+
+    /// Bind `action` to `binding`, keeping no override when that's the default
+    pub fn set<A: Action>(&mut self, action: A, binding: Binding) {
+        let overrides = A::overrides_mut(self);
+        if binding.0 == Chord::parse(action.info().default).ok() {
+            overrides.remove(&action);
+        } else {
+            overrides.insert(action, binding);
+        }
+    }
+
+    /// Unbind the actions of the scope that share a chord with another one. Of the actions
+    /// on one chord one bound in the file wins over those left at their defaults, then the
+    /// first in the registry. Returns the unbound actions with the one kept and the chord
+    pub fn resolve_conflicts<A: Action>(&mut self) -> Vec<(A, A, Chord)> {
+        let mut unbound = Vec::new();
+        let mut kept: Vec<(Chord, A)> = Vec::new();
+        let actions: Vec<A> = A::registry().iter().map(|info| info.action).collect();
+        let explicit =
+            |keybindings: &Self, action: &A| A::overrides(keybindings).contains_key(action);
+
+        // Explicit bindings claim their chords first
+        let (first, second): (Vec<A>, Vec<A>) = actions
+            .into_iter()
+            .partition(|action| explicit(self, action));
+        for action in first.into_iter().chain(second) {
+            let Some(chord) = self.chord(action) else {
+                continue;
+            };
+            match kept.iter().find(|(kept_chord, _)| *kept_chord == chord) {
+                Some(&(_, winner)) => {
+                    A::overrides_mut(self).insert(action, Binding(None));
+                    unbound.push((action, winner, chord));
+                }
+                None => kept.push((chord, action)),
+            }
+        }
+        unbound
+    }
+}
@@ -1,25 +1,199 @@
 mod app;
+mod app_window;
+mod archive;
+mod asciicast;
+mod command_palette;
 mod config;
+mod dropdown;
 mod explorer_widget;
-mod logging;
-mod parser;
-mod terminal_buffer;
-mod terminal_cell;
+mod file_operation;
+mod fonts;
+mod icon_finder;
+#[cfg(feature = "inspector")]
+mod inspector;
+mod keybindings;
+mod pdf;
+mod session_log;
+mod settings_window;
+mod state;
+mod system_theme;
+mod term;
+mod terminal_session;
 mod terminal_widget;
+mod toast;
 mod utils;
 
 #[macro_use]
 extern crate log;
 
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
 
 use crate::app::App;
 
-static CONFIG: OnceLock<Arc<config::Config>> = OnceLock::new();
+static CONFIG: config::SharedConfig = config::SharedConfig::new();
+
+const USAGE: &str = "Usage: explotty [OPTIONS] [-e COMMAND [ARGS...]]
+
+Options:
+  -e, --command COMMAND [ARGS...]  Run COMMAND instead of the shell (must be last)
+  --working-directory DIR          Start in DIR
+  --config PATH                    Read the configuration from PATH
+  --title TITLE                    Initial window title
+  --fixed-title                    Ignore title changes requested by applications
+  --log-output FILE                Record the terminal output to FILE
+  --log-timing FILE                Write scriptreplay timing data for --log-output to FILE
+  --record FILE                    Record the first tab to FILE in the asciicast v2 format
+  --play FILE                      Play an asciicast v2 recording back instead of running a shell
+  --dropdown                       Start hidden, sliding down from the top of the screen when
+                                   toggled; toggles the running one if there is one
+  --toggle                         Show or hide the running drop-down explotty and exit
+  --print-default-config           Print a commented configuration file and exit
+  --init-config                    Write that file to the first place it is looked for, unless
+                                   a configuration file exists, and exit
+  -h, --help                       Show this help";
+
+/// Options given on the command line
+#[derive(Default, Debug, Clone, PartialEq)]
+struct Args {
+    command: Option<Vec<String>>,
+    working_directory: Option<String>,
+    config_path: Option<String>,
+    title: Option<String>,
+    fixed_title: bool,
+    log_output: Option<String>,
+    log_timing: Option<String>,
+    record: Option<String>,
+    play: Option<String>,
+    dropdown: bool,
+    toggle: bool,
+    print_default_config: bool,
+    init_config: bool,
+    help: bool,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        fn value(
+            option: &str,
+            inline: Option<&str>,
+            args: &mut impl Iterator<Item = String>,
+        ) -> anyhow::Result<String> {
+            match inline {
+                Some(value) => Ok(value.to_string()),
+                None => args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("{option} requires a value")),
+            }
+        }
+
+        while let Some(arg) = args.next() {
+            let (option, inline) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option, Some(value)),
+                _ => (arg.as_str(), None),
+            };
+
+            match option {
+                "-e" | "--command" => {
+                    // Everything after -e belongs to the command
+                    let command: Vec<String> = inline
+                        .map(str::to_string)
+                        .into_iter()
+                        .chain(args.by_ref())
+                        .collect();
+                    if command.is_empty() {
+                        anyhow::bail!("{option} requires a command");
+                    }
+                    parsed.command = Some(command);
+                }
+                "--working-directory" => {
+                    parsed.working_directory = Some(value(option, inline, &mut args)?)
+                }
+                "--config" => parsed.config_path = Some(value(option, inline, &mut args)?),
+                "--title" => parsed.title = Some(value(option, inline, &mut args)?),
+                "--fixed-title" => parsed.fixed_title = true,
+                "--log-output" => parsed.log_output = Some(value(option, inline, &mut args)?),
+                "--log-timing" => parsed.log_timing = Some(value(option, inline, &mut args)?),
+                "--record" => parsed.record = Some(value(option, inline, &mut args)?),
+                "--play" => parsed.play = Some(value(option, inline, &mut args)?),
+                "--dropdown" => parsed.dropdown = true,
+                "--toggle" => parsed.toggle = true,
+                "--print-default-config" => parsed.print_default_config = true,
+                "--init-config" => parsed.init_config = true,
+                "-h" | "--help" => parsed.help = true,
+                _ => anyhow::bail!("unknown option: {arg}"),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Override the configuration file with the options given on the command line
+    fn apply(&self, config: &mut config::Config) {
+        if self.working_directory.is_some() {
+            config.working_directory = self.working_directory.clone();
+        }
+        if let Some(command) = &self.command {
+            config.command = Some(command.clone());
+            // A window started for a single command goes away with it, unless configured
+            // otherwise
+            config
+                .exit_behavior
+                .get_or_insert(config::ExitBehavior::Close);
+        }
+        if self.log_output.is_some() {
+            config.log_output = self.log_output.clone();
+        }
+        if self.log_timing.is_some() {
+            config.log_timing = self.log_timing.clone();
+        }
+        if self.dropdown {
+            config.dropdown.enabled = true;
+        }
+        config.title = self.title.clone();
+        config.fixed_title = self.fixed_title;
+    }
+}
 
 fn main() -> eframe::Result {
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
 
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("explotty: {e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    if args.print_default_config {
+        print!("{}", config::DEFAULT_CONFIG);
+        return Ok(());
+    }
+    if args.toggle {
+        if let Err(e) = dropdown::send(dropdown::Command::Toggle) {
+            eprintln!("explotty: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if args.init_config {
+        match config::Config::init() {
+            Ok(path) => println!("Wrote {path}"),
+            Err(e) => {
+                eprintln!("explotty: {e}");
+                std::process::exit(1);
+            }
+        }
+        return Ok(());
+    }
+
+    // Only the window needs GTK, the options above work without a display
     if gtk::init().is_err() {
         eprintln!("Failed to initialize GTK");
         return Err(eframe::Error::AppCreation(
@@ -27,27 +201,209 @@ fn main() -> eframe::Result {
         ));
     }
 
-    let config_path = config::Config::get_first_existing_path();
-    match config_path {
-        Some(path) => {
-            let config = config::Config::load(&path).unwrap_or_default();
-            CONFIG.set(Arc::new(config)).unwrap();
-        }
+    // A recording that can't be played is reported before a window opens
+    let player = match &args.play {
+        Some(path) => match asciicast::Player::load(std::path::Path::new(path)) {
+            Ok(player) => Some(player),
+            Err(e) => {
+                eprintln!("explotty: {e}");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let config_path = args
+        .config_path
+        .clone()
+        .or_else(config::Config::get_first_existing_path);
+    let mut config = match &config_path {
+        Some(path) => config::Config::load(path).unwrap_or_else(|e| {
+            error!("Failed to load {path}: {e}, using default settings");
+            config::Config::default()
+        }),
         None => {
             warn!("No configuration file found, using default settings");
-            CONFIG.set(Arc::new(config::Config::default())).unwrap();
+            config::Config::default()
         }
+    };
+
+    // Command line arguments take precedence over the configuration file
+    args.apply(&mut config);
+
+    // A second `--dropdown` summons the first one
+    let dropdown = config.dropdown.enabled;
+    if dropdown && dropdown::send(dropdown::Command::Toggle).is_ok() {
+        return Ok(());
+    }
+
+    let title = config
+        .title
+        .clone()
+        .unwrap_or_else(|| "explotty".to_string());
+    let state = if config.restore_session.unwrap_or(true) {
+        state::State::load()
+    } else {
+        None
+    };
+
+    CONFIG.set(Arc::new(config));
+
+    let mut viewport = eframe::egui::ViewportBuilder::default()
+        .with_inner_size(
+            state
+                .as_ref()
+                .and_then(|state| state.window_size)
+                .unwrap_or([800.0, 600.0]),
+        )
+        .with_title(title)
+        .with_transparent(true);
+    let on_top = state
+        .as_ref()
+        .and_then(|state| state.always_on_top)
+        .or_else(|| CONFIG.get().and_then(|config| config.start_on_top));
+    if on_top == Some(true) {
+        viewport = viewport.with_window_level(eframe::egui::WindowLevel::AlwaysOnTop);
+    }
+    if dropdown {
+        // Placed and shown when summoned
+        viewport = viewport
+            .with_visible(false)
+            .with_decorations(false)
+            .with_taskbar(false);
+    } else if let Some(position) = state.as_ref().and_then(|state| state.window_position) {
+        viewport = viewport.with_position(position);
     }
 
     let options = eframe::NativeOptions {
-        viewport: eframe::egui::ViewportBuilder::default()
-            .with_inner_size(eframe::egui::vec2(800.0, 600.0)),
+        viewport,
         ..Default::default()
     };
 
     eframe::run_native(
         "explotty",
         options,
-        Box::new(|cc| Ok(Box::new(App::new(cc)))),
+        Box::new(|cc| Ok(Box::new(App::new(cc, state, config_path, args, player)))),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Args> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let cases = [
+            (&[][..], Args::default()),
+            (
+                &["-e", "htop"],
+                Args {
+                    command: Some(strings(&["htop"])),
+                    ..Default::default()
+                },
+            ),
+            (
+                &["--command=vim", "notes.txt"],
+                Args {
+                    command: Some(strings(&["vim", "notes.txt"])),
+                    ..Default::default()
+                },
+            ),
+            // Everything after -e goes to the command, options and `--` included
+            (
+                &["--title", "T", "-e", "ls", "--", "-la", "--help"],
+                Args {
+                    title: Some("T".into()),
+                    command: Some(strings(&["ls", "--", "-la", "--help"])),
+                    ..Default::default()
+                },
+            ),
+            (
+                &["-e", "--", "rm", "-i"],
+                Args {
+                    command: Some(strings(&["--", "rm", "-i"])),
+                    ..Default::default()
+                },
+            ),
+            (
+                &["--working-directory", "/tmp"],
+                Args {
+                    working_directory: Some("/tmp".into()),
+                    ..Default::default()
+                },
+            ),
+            (
+                &["--working-directory=/srv/a=b", "--fixed-title"],
+                Args {
+                    working_directory: Some("/srv/a=b".into()),
+                    fixed_title: true,
+                    ..Default::default()
+                },
+            ),
+            (
+                &["--working-directory", "-e", "-e", "top"],
+                Args {
+                    working_directory: Some("-e".into()),
+                    command: Some(strings(&["top"])),
+                    ..Default::default()
+                },
+            ),
+            (
+                &["--print-default-config", "-h"],
+                Args {
+                    print_default_config: true,
+                    help: true,
+                    ..Default::default()
+                },
+            ),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(parse(args).unwrap(), expected, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases: [(&[&str], &str); 5] = [
+            (&["-e"], "-e requires a command"),
+            (
+                &["--working-directory"],
+                "--working-directory requires a value",
+            ),
+            (&["--config"], "--config requires a value"),
+            (&["--bogus"], "unknown option: --bogus"),
+            (&["--", "ls"], "unknown option: --"),
+        ];
+        for (args, error) in cases {
+            assert_eq!(parse(args).unwrap_err().to_string(), error, "{args:?}");
+        }
+    }
+
+    #[test]
+    fn apply_overrides_the_config() {
+        let mut config = config::Config::default();
+        config.working_directory = Some("/home".into());
+        parse(&["--working-directory", "/tmp", "-e", "htop"])
+            .unwrap()
+            .apply(&mut config);
+        assert_eq!(config.working_directory.as_deref(), Some("/tmp"));
+        assert_eq!(config.command, Some(strings(&["htop"])));
+        // A single command closes the window when it exits, unless configured otherwise
+        assert_eq!(config.exit_behavior, Some(config::ExitBehavior::Close));
+
+        let mut config = config::Config::default();
+        config.working_directory = Some("/home".into());
+        config.exit_behavior = Some(config::ExitBehavior::Hold);
+        parse(&["-e", "make"]).unwrap().apply(&mut config);
+        assert_eq!(config.working_directory.as_deref(), Some("/home"));
+        assert_eq!(config.exit_behavior, Some(config::ExitBehavior::Hold));
+    }
+}
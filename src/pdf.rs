@@ -0,0 +1,171 @@
+//! Saving cells as a PDF: the runs of `term::export` in the terminal font, on the same cell
+//! grid and on pages in the palette's background, a screen of lines to a page
+
+use std::{
+    fs::File,
+    io::{BufWriter, Cursor},
+    path::Path,
+};
+
+use printpdf::{Color, IndirectFontRef, Mm, PdfDocument, PdfLayerReference, Pt, Rect, Rgb};
+
+use crate::term::{
+    color::{Palette, Rgba},
+    export,
+    terminal_cell::TerminalCell,
+};
+
+/// Size of the text, in points
+const FONT_SIZE: f32 = 10.0;
+/// Size of a cell in points, as the terminal lays out its font
+const CELL_WIDTH: f32 = FONT_SIZE * 0.6;
+const CELL_HEIGHT: f32 = FONT_SIZE * 1.2;
+/// Space around the cells, in points
+const MARGIN: f32 = 18.0;
+
+/// Write `lines` to the PDF `path`, `rows_per_page` of them to a page, with `fonts` embedded
+/// (regular, bold, italic and bold italic)
+pub fn save(
+    path: &Path,
+    lines: &[&[TerminalCell]],
+    palette: &Palette,
+    rows_per_page: usize,
+    fonts: [Vec<u8>; 4],
+) -> anyhow::Result<()> {
+    let rows_per_page = rows_per_page.max(1);
+    let cols = lines
+        .iter()
+        .map(|line| line.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let page = Page {
+        width: cols as f32 * CELL_WIDTH + 2.0 * MARGIN,
+        height: rows_per_page as f32 * CELL_HEIGHT + 2.0 * MARGIN,
+    };
+
+    let (document, first_page, first_layer) =
+        PdfDocument::new("explotty", page.mm_width(), page.mm_height(), "Terminal");
+    // A family without a style has the regular file picked for it, which is embedded once
+    let mut embedded: Vec<IndirectFontRef> = Vec::new();
+    for (index, font) in fonts.iter().enumerate() {
+        let embedded_font = match fonts[..index].iter().position(|other| other == font) {
+            Some(same) => embedded[same].clone(),
+            None => document
+                .add_external_font(Cursor::new(font))
+                .map_err(|e| anyhow::anyhow!("failed to embed the terminal font: {e}"))?,
+        };
+        embedded.push(embedded_font);
+    }
+    let fonts = embedded;
+
+    // An empty selection still makes a page
+    let mut chunks: Vec<&[&[TerminalCell]]> = lines.chunks(rows_per_page).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    for (index, chunk) in chunks.into_iter().enumerate() {
+        let layer = match index {
+            0 => document.get_page(first_page).get_layer(first_layer),
+            _ => {
+                let (next_page, layer) =
+                    document.add_page(page.mm_width(), page.mm_height(), "Terminal");
+                document.get_page(next_page).get_layer(layer)
+            }
+        };
+        page.draw(&layer, chunk, palette, &fonts);
+    }
+
+    let file = File::create(path)
+        .map_err(|e| anyhow::anyhow!("failed to create {}: {e}", path.display()))?;
+    document
+        .save(&mut BufWriter::new(file))
+        .map_err(|e| anyhow::anyhow!("failed to write {}: {e}", path.display()))?;
+    Ok(())
+}
+
+/// The size of a page in points
+struct Page {
+    width: f32,
+    height: f32,
+}
+
+impl Page {
+    fn mm_width(&self) -> Mm {
+        Pt(self.width).into()
+    }
+
+    fn mm_height(&self) -> Mm {
+        Pt(self.height).into()
+    }
+
+    fn draw(
+        &self,
+        layer: &PdfLayerReference,
+        lines: &[&[TerminalCell]],
+        palette: &Palette,
+        fonts: &[IndirectFontRef],
+    ) {
+        self.fill(layer, palette.background, 0.0, 0.0, self.width, self.height);
+
+        for (row, line) in lines.iter().enumerate() {
+            let top = MARGIN + row as f32 * CELL_HEIGHT;
+            let baseline = top + (CELL_HEIGHT - FONT_SIZE) / 2.0 + FONT_SIZE * 0.8;
+            for run in export::runs(line, palette) {
+                let left = MARGIN + run.col as f32 * CELL_WIDTH;
+                let width = run.width as f32 * CELL_WIDTH;
+                if let Some(bg) = run.bg {
+                    self.fill(layer, bg, left, top, width, CELL_HEIGHT);
+                }
+                let fg = run.fg.unwrap_or(palette.foreground);
+                if run.underline {
+                    self.fill(layer, fg, left, baseline + 1.0, width, 0.6);
+                }
+                if run.strikethrough {
+                    self.fill(layer, fg, left, baseline - FONT_SIZE * 0.3, width, 0.6);
+                }
+                if run.text.trim().is_empty() {
+                    continue;
+                }
+                let font = &fonts[usize::from(run.bold) + 2 * usize::from(run.italic)];
+                layer.set_fill_color(color(fg));
+                layer.use_text(
+                    run.text,
+                    FONT_SIZE,
+                    Pt(left).into(),
+                    Pt(self.height - baseline).into(),
+                    font,
+                );
+            }
+        }
+    }
+
+    /// Fill the rectangle at `left`, `top` from the top left corner with `fill`
+    fn fill(
+        &self,
+        layer: &PdfLayerReference,
+        fill: Rgba,
+        left: f32,
+        top: f32,
+        width: f32,
+        height: f32,
+    ) {
+        layer.set_fill_color(color(fill));
+        layer.add_rect(Rect::new(
+            Pt(left).into(),
+            Pt(self.height - top - height).into(),
+            Pt(left + width).into(),
+            Pt(self.height - top).into(),
+        ));
+    }
+}
+
+fn color(color: Rgba) -> Color {
+    let [r, g, b, _] = color.to_array();
+    Color::Rgb(Rgb::new(
+        f32::from(r) / 255.0,
+        f32::from(g) / 255.0,
+        f32::from(b) / 255.0,
+        None,
+    ))
+}
@@ -0,0 +1,313 @@
+//! Recording of the PTY output to files.
+//! `SessionLog` writes what the shell printed to a `script`-style typescript, optionally with
+//! timing data for `scriptreplay`. The `debug-logging` feature additionally dumps sanitized
+//! input for debugging; the output is traced by the inspector.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard, Weak,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+
+use crate::CONFIG;
+
+#[cfg(feature = "debug-logging")]
+const INPUT_LOG_FILE: &str = "input_log.txt";
+
+/// How often buffered log data is written out, also when the shell prints nothing more
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of sessions that opened a log so far, used to give each tab its own file
+static SESSION_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Output of one PTY recorded to a typescript file
+pub struct SessionLog {
+    path: PathBuf,
+    timing_path: Option<PathBuf>,
+    /// Shared with the thread flushing them every `FLUSH_INTERVAL`
+    files: Arc<Mutex<LogFiles>>,
+    /// Size of the typescript file, compared with `max_bytes`
+    written: u64,
+    max_bytes: Option<u64>,
+    last_write: Instant,
+}
+
+/// The open typescript and timing files
+struct LogFiles {
+    output: BufWriter<File>,
+    timing: Option<BufWriter<File>>,
+    /// Whether something was written since the last flush
+    unflushed: bool,
+}
+
+impl LogFiles {
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()?;
+        if let Some(timing) = &mut self.timing {
+            timing.flush()?;
+        }
+        self.unflushed = false;
+        Ok(())
+    }
+}
+
+impl SessionLog {
+    /// Open the log configured with `log_output`/`log_timing`, if any.
+    /// The first session writes to the configured paths, later tabs append `.2`, `.3`, ...
+    pub fn from_config() -> Option<Self> {
+        let config = CONFIG.get()?;
+        let path = config.log_output.as_ref()?;
+
+        let number = SESSION_COUNT.fetch_add(1, Ordering::Relaxed) + 1;
+        let numbered = |path: &str| {
+            if number == 1 {
+                PathBuf::from(path)
+            } else {
+                PathBuf::from(format!("{path}.{number}"))
+            }
+        };
+
+        let path = numbered(path);
+        let timing_path = config.log_timing.as_deref().map(numbered);
+        match Self::open(path.clone(), timing_path, config.log_max_bytes) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                error!("Failed to open output log {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    fn open(
+        path: PathBuf,
+        timing_path: Option<PathBuf>,
+        max_bytes: Option<u64>,
+    ) -> std::io::Result<Self> {
+        let output = Self::open_append(&path)?;
+        let written = output.metadata()?.len();
+        let timing = match &timing_path {
+            Some(timing_path) => Some(BufWriter::new(Self::open_append(timing_path)?)),
+            None => None,
+        };
+
+        let mut log = Self {
+            path,
+            timing_path,
+            files: Arc::new(Mutex::new(LogFiles {
+                output: BufWriter::new(output),
+                timing,
+                unflushed: false,
+            })),
+            written,
+            max_bytes,
+            last_write: Instant::now(),
+        };
+        log.write_header()?;
+        log.spawn_flusher();
+        Ok(log)
+    }
+
+    /// Flush the files every `FLUSH_INTERVAL` while there is unwritten data, so the output
+    /// before a pause isn't lost if the application is killed. Ends with the log
+    fn spawn_flusher(&self) {
+        let files = Arc::downgrade(&self.files);
+        let path = self.path.clone();
+        let result = std::thread::Builder::new()
+            .name("session-log-flush".to_string())
+            .spawn(move || {
+                while let Some(files) = Self::after_interval(&files) {
+                    let mut files = files.lock().unwrap_or_else(|e| e.into_inner());
+                    if files.unflushed
+                        && let Err(e) = files.flush()
+                    {
+                        error!("Failed to write output log {}: {e}", path.display());
+                    }
+                }
+            });
+        if let Err(e) = result {
+            warn!("Failed to start flushing the output log, it is written when full: {e}");
+        }
+    }
+
+    /// Wait for the next flush, then the files unless the log was dropped meanwhile
+    fn after_interval(files: &Weak<Mutex<LogFiles>>) -> Option<Arc<Mutex<LogFiles>>> {
+        std::thread::sleep(FLUSH_INTERVAL);
+        files.upgrade()
+    }
+
+    fn files(&self) -> MutexGuard<'_, LogFiles> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn open_append(path: &Path) -> std::io::Result<File> {
+        OpenOptions::new().append(true).create(true).open(path)
+    }
+
+    /// `scriptreplay` skips the first line of the typescript, so it has to be a header
+    fn write_header(&mut self) -> std::io::Result<()> {
+        let header = format!(
+            "Script started on {}\n",
+            Local::now().format("%Y-%m-%d %H:%M:%S%:z")
+        );
+        let mut files = self.files();
+        files.output.write_all(header.as_bytes())?;
+        files.unflushed = true;
+        drop(files);
+        self.written += header.len() as u64;
+        Ok(())
+    }
+
+    /// Record a chunk of output
+    pub fn write(&mut self, data: &[u8]) {
+        if let Err(e) = self.try_write(data) {
+            error!("Failed to write output log {}: {e}", self.path.display());
+        }
+    }
+
+    fn try_write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if let Some(max_bytes) = self.max_bytes
+            && self.written + data.len() as u64 > max_bytes
+        {
+            self.rotate()?;
+        }
+
+        let now = Instant::now();
+        let delay = now.duration_since(self.last_write).as_secs_f64();
+        self.last_write = now;
+
+        let mut files = self.files();
+        if let Some(timing) = &mut files.timing {
+            // `script -t` format: seconds since the previous chunk and the chunk's length
+            writeln!(timing, "{delay:.6} {}", data.len())?;
+        }
+        files.output.write_all(data)?;
+        files.unflushed = true;
+        drop(files);
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    /// Move the full log to `<path>.old` (replacing an older one) and start a new file
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let mut files = self.files();
+        files.flush()?;
+
+        let rotated = |path: &Path| PathBuf::from(format!("{}.old", path.display()));
+        std::fs::rename(&self.path, rotated(&self.path))?;
+        files.output = BufWriter::new(Self::open_append(&self.path)?);
+        if let Some(timing_path) = &self.timing_path {
+            std::fs::rename(timing_path, rotated(timing_path))?;
+            files.timing = Some(BufWriter::new(Self::open_append(timing_path)?));
+        }
+        drop(files);
+
+        self.written = 0;
+        self.write_header()
+    }
+}
+
+impl Drop for SessionLog {
+    fn drop(&mut self) {
+        let _ = self.files().flush();
+    }
+}
+
+#[cfg(feature = "debug-logging")]
+fn sanitize(data: &[u8]) -> String {
+    data.iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b.is_ascii_whitespace() {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "debug-logging")]
+fn append_line(path: &str, line: &str) {
+    if let Ok(mut file) = OpenOptions::new().append(true).create(true).open(path) {
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+#[allow(unused_variables)]
+pub fn log_input_data(data: &[u8]) {
+    #[cfg(feature = "debug-logging")]
+    append_line(INPUT_LOG_FILE, &sanitize(data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "explotty-session-log-{}-{name}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn output_before_a_pause_is_written_out() {
+        let dir = TempDir::new("pause");
+        let path = dir.0.join("typescript");
+        let timing_path = dir.0.join("timing");
+        let mut log = SessionLog::open(path.clone(), Some(timing_path.clone()), None).unwrap();
+        log.write(b"user@host:~$ ");
+
+        // Nothing more is printed, the log is still open
+        let deadline = Instant::now() + FLUSH_INTERVAL * 5;
+        while !std::fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("user@host:~$ ")
+        {
+            assert!(Instant::now() < deadline, "the output was never flushed");
+            std::thread::sleep(FLUSH_INTERVAL / 10);
+        }
+        assert!(
+            std::fs::read_to_string(&timing_path)
+                .unwrap()
+                .ends_with(" 13\n")
+        );
+        drop(log);
+    }
+
+    #[test]
+    fn a_full_log_is_rotated() {
+        let dir = TempDir::new("rotate");
+        let path = dir.0.join("typescript");
+        let mut log = SessionLog::open(path.clone(), None, Some(200)).unwrap();
+        log.write(&[b'a'; 100]);
+        log.write(&[b'b'; 100]);
+        drop(log);
+
+        let old = std::fs::read_to_string(dir.0.join("typescript.old")).unwrap();
+        assert!(old.starts_with("Script started on "));
+        assert!(old.ends_with(&"a".repeat(100)));
+        let new = std::fs::read_to_string(&path).unwrap();
+        assert!(new.starts_with("Script started on "));
+        assert!(new.ends_with(&"b".repeat(100)));
+    }
+}
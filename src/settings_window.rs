@@ -0,0 +1,965 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui;
+use font_kit::source::SystemSource;
+use toml_edit::DocumentMut;
+
+use crate::{
+    config::{
+        ANSI_COLOR_NAMES, BACKGROUND_OPACITY_RANGE, Colors, Config, ExitBehavior, OpenDirectory,
+        TAB_WIDTH_RANGE, UiTheme,
+    },
+    keybindings::{Action, Binding, Chord, ExplorerAction, Keybindings, TerminalAction},
+    term::{
+        CursorStyle,
+        color::{Palette, Rgba, THEMES},
+        terminal_buffer::DEFAULT_TAB_WIDTH,
+    },
+    terminal_widget::DEFAULT_FONT_SIZE,
+};
+
+const CURSOR_STYLES: [(CursorStyle, &str); 3] = [
+    (CursorStyle::Block, "block"),
+    (CursorStyle::Underline, "underline"),
+    (CursorStyle::Bar, "bar"),
+];
+
+const UI_THEMES: [(UiTheme, &str); 3] = [
+    (UiTheme::Dark, "dark"),
+    (UiTheme::Light, "light"),
+    (UiTheme::System, "system"),
+];
+
+const EXIT_BEHAVIORS: [(ExitBehavior, &str); 3] = [
+    (ExitBehavior::Hold, "hold"),
+    (ExitBehavior::Restart, "restart"),
+    (ExitBehavior::Close, "close"),
+];
+
+const OPEN_DIRECTORY: [(OpenDirectory, &str); 2] = [
+    (OpenDirectory::Shell, "shell"),
+    (OpenDirectory::Explorer, "explorer"),
+];
+
+/// Colors of the `[colors]` section with a picker, besides colors 0–15
+const COLOR_KEYS: [(&str, &str); 4] = [
+    ("foreground", "Foreground"),
+    ("background", "Background"),
+    ("cursor", "Cursor"),
+    ("selection_background", "Selection"),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Appearance,
+    Terminal,
+    Explorer,
+    Keyboard,
+}
+
+/// The action whose keybinding is waiting for the next key press
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Capture {
+    Terminal(TerminalAction),
+    Explorer(ExplorerAction),
+}
+
+/// The Settings window (Ctrl+,). Changes apply right away and are written to the configuration
+/// file with Save; closing the window undoes what wasn't saved
+pub struct SettingsWindow {
+    tab: Tab,
+    /// The settings as edited
+    config: Config,
+    /// The settings of the file, restored when the window closes without saving
+    saved: Config,
+    /// Where Save writes to
+    path: PathBuf,
+    /// Families of the installed fonts
+    font_families: Vec<String>,
+    /// Why the chosen fonts couldn't be loaded
+    pub font_error: Option<String>,
+    /// The outcome of the last Save
+    save_result: Option<Result<(), String>>,
+    /// The keybinding being changed on the Keyboard tab
+    capturing: Option<Capture>,
+}
+
+/// What the window asks the app to do
+pub enum SettingsAction {
+    /// Apply this configuration
+    Apply(Box<Config>),
+    /// The window was closed
+    Close,
+}
+
+impl SettingsWindow {
+    pub fn new(config: &Config, path: PathBuf) -> Self {
+        let mut font_families = SystemSource::new().all_families().unwrap_or_else(|e| {
+            warn!("Failed to list the installed fonts: {e}");
+            Vec::new()
+        });
+        font_families.sort();
+        font_families.dedup();
+
+        Self {
+            tab: Tab::Appearance,
+            config: config.clone(),
+            saved: config.clone(),
+            path,
+            font_families,
+            font_error: None,
+            save_result: None,
+            capturing: None,
+        }
+    }
+
+    /// Draw the window's contents. Returns what the app has to do about the changes
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<SettingsAction> {
+        let mut changed = false;
+        let mut close = false;
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.tab, Tab::Appearance, "Appearance");
+            ui.selectable_value(&mut self.tab, Tab::Terminal, "Terminal");
+            ui.selectable_value(&mut self.tab, Tab::Explorer, "Explorer");
+            ui.selectable_value(&mut self.tab, Tab::Keyboard, "Keyboard");
+        });
+        ui.separator();
+
+        egui::TopBottomPanel::bottom("settings_buttons").show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.save_result = Some(self.save().map_err(|e| e.to_string()));
+                    if let Some(Ok(())) = self.save_result {
+                        self.saved = self.config.clone();
+                    }
+                }
+                if ui
+                    .button("Revert")
+                    .on_hover_text("Back to the settings of the file")
+                    .clicked()
+                {
+                    self.config = self.saved.clone();
+                    changed = true;
+                }
+                if ui.button("Close").clicked() {
+                    close = true;
+                }
+                match &self.save_result {
+                    Some(Ok(())) => {
+                        ui.weak(format!("Saved to {}", self.path.display()));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, format!("Not saved: {e}"));
+                    }
+                    None => {}
+                }
+            });
+        });
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                changed |= match self.tab {
+                    Tab::Appearance => self.show_appearance(ui),
+                    Tab::Terminal => self.show_terminal(ui),
+                    Tab::Explorer => self.show_explorer(ui),
+                    Tab::Keyboard => self.show_keyboard(ui),
+                };
+            });
+
+        if close {
+            self.config = self.saved.clone();
+            return Some(SettingsAction::Close);
+        }
+        if changed {
+            self.save_result = None;
+            self.config.update_palette();
+            return Some(SettingsAction::Apply(Box::new(self.config.clone())));
+        }
+        None
+    }
+
+    /// The settings of the file, for when the window goes away without Save
+    pub fn saved(&self) -> &Config {
+        &self.saved
+    }
+
+    fn show_appearance(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        ui.heading("Fonts");
+        egui::Grid::new("settings_fonts")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Interface font");
+                changed |= self.font_picker(ui, "ui_font", FontField::Ui);
+                ui.end_row();
+
+                ui.label("Terminal font");
+                changed |= self.font_picker(ui, "terminal_font", FontField::Terminal);
+                ui.end_row();
+
+                ui.label("Font size");
+                let mut font_size = self.config.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+                if ui
+                    .add(egui::Slider::new(&mut font_size, 6.0..=72.0).step_by(0.5))
+                    .changed()
+                {
+                    self.config.font_size = Some(font_size);
+                    changed = true;
+                }
+                ui.end_row();
+            });
+        if let Some(error) = &self.font_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Interface theme");
+            let mut ui_theme = self.config.ui_theme.unwrap_or_default();
+            if choice(ui, "settings_ui_theme", &mut ui_theme, &UI_THEMES) {
+                self.config.ui_theme = Some(ui_theme);
+                changed = true;
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.heading("Window");
+        let mut start_on_top = self.config.start_on_top.unwrap_or(false);
+        if ui
+            .checkbox(&mut start_on_top, "Keep windows above the others")
+            .on_hover_text("Toggled per window with the keyboard too")
+            .changed()
+        {
+            self.config.start_on_top = Some(start_on_top);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Background opacity");
+            let mut opacity = self.config.background_opacity.unwrap_or(1.0);
+            if ui
+                .add(egui::Slider::new(&mut opacity, BACKGROUND_OPACITY_RANGE).step_by(0.05))
+                .on_hover_text("Needs a desktop with a compositor")
+                .changed()
+            {
+                self.config.background_opacity = Some(opacity);
+                changed = true;
+            }
+        });
+
+        ui.add_space(8.0);
+        ui.heading("Colors");
+        ui.horizontal(|ui| {
+            ui.label("Theme");
+            let current = self
+                .config
+                .theme
+                .clone()
+                .unwrap_or_else(|| THEMES[0].into());
+            egui::ComboBox::from_id_salt("settings_theme")
+                .selected_text(&current)
+                .show_ui(ui, |ui| {
+                    for theme in THEMES {
+                        if ui.selectable_label(current == theme, theme).clicked() {
+                            self.config.theme = Some(theme.to_string());
+                            changed = true;
+                        }
+                    }
+                });
+            if self.config.colors.is_some()
+                && ui
+                    .button("Use the theme's colors")
+                    .on_hover_text("Forget the colors changed below")
+                    .clicked()
+            {
+                if let Some(colors) = &mut self.config.colors {
+                    colors.clear();
+                }
+                changed = true;
+            }
+        });
+
+        let palette = self.config.palette().clone();
+        egui::Grid::new("settings_colors")
+            .num_columns(4)
+            .show(ui, |ui| {
+                for (index, (key, label)) in COLOR_KEYS.iter().enumerate() {
+                    ui.label(*label);
+                    changed |= self.color_picker(ui, key, color_of(&palette, key));
+                    if index % 2 == 1 {
+                        ui.end_row();
+                    }
+                }
+            });
+        ui.label("Colors 0–15");
+        egui::Grid::new("settings_ansi_colors").show(ui, |ui| {
+            for (index, key) in ANSI_COLOR_NAMES.iter().enumerate() {
+                changed |= self.color_picker(ui, key, palette.ansi[index]);
+                if index % 8 == 7 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        let mut bold_is_bright = palette.bold_is_bright;
+        if ui
+            .checkbox(&mut bold_is_bright, "Bright colors for bold text")
+            .changed()
+        {
+            self.config
+                .colors
+                .get_or_insert_with(Colors::default)
+                .set_bold_is_bright(Some(bold_is_bright));
+            changed = true;
+        }
+
+        changed
+    }
+
+    fn font_picker(&mut self, ui: &mut egui::Ui, id: &str, field: FontField) -> bool {
+        let family = match field {
+            FontField::Ui => &mut self.config.ui_font_family,
+            FontField::Terminal => &mut self.config.terminal_font_family,
+        };
+        let mut changed = false;
+        egui::ComboBox::from_id_salt(id)
+            .selected_text(family.as_deref().unwrap_or("System default"))
+            .width(220.0)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(family.is_none(), "System default")
+                    .clicked()
+                {
+                    *family = None;
+                    changed = true;
+                }
+                for name in &self.font_families {
+                    if ui
+                        .selectable_label(family.as_deref() == Some(name.as_str()), name)
+                        .clicked()
+                    {
+                        *family = Some(name.clone());
+                        changed = true;
+                    }
+                }
+            });
+        let overridden = match field {
+            FontField::Ui => &self.config.fonts.ui_font,
+            FontField::Terminal => &self.config.fonts.terminal_font,
+        };
+        if let Some(spec) = overridden {
+            ui.weak(format!("[fonts] uses {}", spec.label()));
+        }
+        changed
+    }
+
+    fn color_picker(&mut self, ui: &mut egui::Ui, key: &str, color: Rgba) -> bool {
+        let [r, g, b, _] = color.to_array();
+        let mut rgb = [r, g, b];
+        let response = ui.color_edit_button_srgb(&mut rgb).on_hover_text(key);
+        if response.changed() {
+            self.config
+                .colors
+                .get_or_insert_with(Colors::default)
+                .set(key, Rgba::from_rgb(rgb[0], rgb[1], rgb[2]));
+            return true;
+        }
+        false
+    }
+
+    fn show_terminal(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        egui::Grid::new("settings_terminal")
+            .num_columns(2)
+            .show(ui, |ui| {
+                ui.label("Shell");
+                ui.horizontal(|ui| {
+                    let mut shell = self.config.shell.clone().unwrap_or_default();
+                    if ui
+                        .add(egui::TextEdit::singleline(&mut shell).hint_text("$SHELL"))
+                        .changed()
+                    {
+                        self.config.shell = (!shell.is_empty()).then_some(shell);
+                        changed = true;
+                    }
+                    if let Some(shell) = &self.config.shell
+                        && !is_executable_command(shell)
+                    {
+                        ui.colored_label(ui.visuals().error_fg_color, "Not found");
+                    }
+                });
+                ui.end_row();
+
+                ui.label("TERM");
+                let mut term = self.config.term.clone().unwrap_or_default();
+                if ui
+                    .add(egui::TextEdit::singleline(&mut term).hint_text("xterm-256color"))
+                    .changed()
+                {
+                    self.config.term = (!term.is_empty()).then_some(term);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("Scrollback lines");
+                let mut scrollback_lines = self
+                    .config
+                    .scrollback_lines
+                    .unwrap_or(crate::term::DEFAULT_SCROLLBACK_LINES);
+                if ui
+                    .add(egui::DragValue::new(&mut scrollback_lines).range(0..=1_000_000))
+                    .changed()
+                {
+                    self.config.scrollback_lines = Some(scrollback_lines);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("Tab width");
+                ui.horizontal(|ui| {
+                    let mut tab_width = self.config.tab_width.unwrap_or(DEFAULT_TAB_WIDTH as u8);
+                    if ui
+                        .add(egui::DragValue::new(&mut tab_width).range(TAB_WIDTH_RANGE))
+                        .changed()
+                    {
+                        self.config.tab_width = Some(tab_width);
+                        changed = true;
+                    }
+                    let mut insert_spaces = self.config.tab_insert_spaces.unwrap_or(false);
+                    if ui
+                        .checkbox(&mut insert_spaces, "TAB writes spaces")
+                        .on_hover_text("Instead of moving the cursor to the next tab stop")
+                        .changed()
+                    {
+                        self.config.tab_insert_spaces = Some(insert_spaces);
+                        changed = true;
+                    }
+                });
+                ui.end_row();
+
+                ui.label("Cursor");
+                ui.horizontal(|ui| {
+                    let mut style = self.config.cursor_style.unwrap_or_default();
+                    if choice(ui, "settings_cursor_style", &mut style, &CURSOR_STYLES) {
+                        self.config.cursor_style = Some(style);
+                        changed = true;
+                    }
+                    let mut blink = self.config.cursor_blink.unwrap_or(false);
+                    if ui.checkbox(&mut blink, "Blink").changed() {
+                        self.config.cursor_blink = Some(blink);
+                        changed = true;
+                    }
+                });
+                ui.end_row();
+
+                ui.label("When the shell exits");
+                let mut exit_behavior = self.config.exit_behavior.unwrap_or_default();
+                if choice(
+                    ui,
+                    "settings_exit_behavior",
+                    &mut exit_behavior,
+                    &EXIT_BEHAVIORS,
+                ) {
+                    self.config.exit_behavior = Some(exit_behavior);
+                    changed = true;
+                }
+                ui.end_row();
+
+                ui.label("");
+                let mut confirm_close = self.config.confirm_close.unwrap_or(true);
+                if ui
+                    .checkbox(&mut confirm_close, "Ask before closing running jobs")
+                    .changed()
+                {
+                    self.config.confirm_close = Some(confirm_close);
+                    changed = true;
+                }
+                ui.end_row();
+            });
+
+        changed
+    }
+
+    fn show_explorer(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        let mut explorer_visible = self.config.explorer_visible.unwrap_or(true);
+        if ui
+            .checkbox(
+                &mut explorer_visible,
+                "Show the explorer below the terminal",
+            )
+            .changed()
+        {
+            self.config.explorer_visible = Some(explorer_visible);
+            changed = true;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Opening a directory changes");
+            let mut open_directory = self.config.open_directory.unwrap_or_default();
+            if choice(
+                ui,
+                "settings_open_directory",
+                &mut open_directory,
+                &OPEN_DIRECTORY,
+            ) {
+                self.config.open_directory = Some(open_directory);
+                changed = true;
+            }
+            ui.weak("'s directory");
+        });
+
+        let mut git_status = self.config.git_status.unwrap_or(true);
+        if ui
+            .checkbox(&mut git_status, "Show the git status of items")
+            .changed()
+        {
+            self.config.git_status = Some(git_status);
+            changed = true;
+        }
+        let mut runs_executables = self.config.double_click_runs_executables.unwrap_or(false);
+        if ui
+            .checkbox(
+                &mut runs_executables,
+                "Run executables in the terminal when opened",
+            )
+            .changed()
+        {
+            self.config.double_click_runs_executables = Some(runs_executables);
+            changed = true;
+        }
+        let mut run_in_new_tab = self.config.run_in_new_tab.unwrap_or(false);
+        if ui
+            .checkbox(
+                &mut run_in_new_tab,
+                "Run commands from the explorer in a new tab",
+            )
+            .changed()
+        {
+            self.config.run_in_new_tab = Some(run_in_new_tab);
+            changed = true;
+        }
+
+        changed
+    }
+
+    fn show_keyboard(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut changed = false;
+
+        ui.heading("Terminal and window");
+        changed |= self.keybinding_grid(ui, Capture::Terminal);
+
+        ui.add_space(8.0);
+        ui.heading("Explorer");
+        ui.weak("While the explorer has the keyboard these win over the shortcuts above");
+        changed |= self.keybinding_grid(ui, Capture::Explorer);
+
+        changed
+    }
+
+    /// A row per action of the scope: click the chord, then press the new one
+    fn keybinding_grid<A: Action>(&mut self, ui: &mut egui::Ui, capture: fn(A) -> Capture) -> bool {
+        let mut changed = false;
+        egui::Grid::new(("settings_keybindings", A::SCOPE))
+            .num_columns(3)
+            .show(ui, |ui| {
+                for info in A::registry() {
+                    let keybindings = &mut self.config.keybindings;
+                    ui.label(info.description);
+
+                    let chord = keybindings.chord(info.action);
+                    let capturing = self.capturing == Some(capture(info.action));
+                    let text = match (capturing, chord) {
+                        (true, _) => "Press a key…".to_string(),
+                        (false, Some(chord)) => chord.to_string(),
+                        (false, None) => "Unbound".to_string(),
+                    };
+                    if ui
+                        .selectable_label(capturing, text)
+                        .on_hover_text(format!("keybindings.{}.{}", A::SCOPE, info.name))
+                        .clicked()
+                    {
+                        self.capturing = (!capturing).then(|| capture(info.action));
+                    }
+                    if capturing && let Some(pressed) = capture_chord(ui) {
+                        // Escape cancels
+                        if let Some(chord) = pressed {
+                            keybindings.set(info.action, Binding(Some(chord)));
+                            changed = true;
+                        }
+                        self.capturing = None;
+                    }
+
+                    ui.horizontal(|ui| {
+                        if chord.is_some() && ui.small_button("Unbind").clicked() {
+                            keybindings.set(info.action, Binding(None));
+                            changed = true;
+                        }
+                        if A::overrides(keybindings).contains_key(&info.action)
+                            && ui.small_button("Default").clicked()
+                        {
+                            A::overrides_mut(keybindings).remove(&info.action);
+                            changed = true;
+                        }
+                        let other = A::registry().iter().find(|other| {
+                            other.action != info.action
+                                && chord.is_some()
+                                && keybindings.chord(other.action) == chord
+                        });
+                        if let Some(other) = other {
+                            ui.colored_label(
+                                ui.visuals().error_fg_color,
+                                format!("Also bound to {}", other.description),
+                            );
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        changed
+    }
+
+    /// Write the settings changed in the window into the configuration file. Comments, the
+    /// keys the window doesn't show and the ones left alone are kept as they are
+    fn save(&self) -> anyhow::Result<()> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut document: DocumentMut = text.parse()?;
+        let (new, old) = (&self.config, &self.saved);
+
+        update(
+            &mut document,
+            "ui_font_family",
+            &new.ui_font_family,
+            &old.ui_font_family,
+            Clone::clone,
+        );
+        update(
+            &mut document,
+            "terminal_font_family",
+            &new.terminal_font_family,
+            &old.terminal_font_family,
+            Clone::clone,
+        );
+        update(
+            &mut document,
+            "font_size",
+            &new.font_size,
+            &old.font_size,
+            |size| f64::from(*size),
+        );
+        update(
+            &mut document,
+            "ui_theme",
+            &new.ui_theme,
+            &old.ui_theme,
+            |theme| name_of(*theme, &UI_THEMES),
+        );
+        update(
+            &mut document,
+            "start_on_top",
+            &new.start_on_top,
+            &old.start_on_top,
+            |on_top| *on_top,
+        );
+        update(
+            &mut document,
+            "background_opacity",
+            &new.background_opacity,
+            &old.background_opacity,
+            // The slider's steps, not the float's digits
+            |opacity| (f64::from(*opacity) * 100.0).round() / 100.0,
+        );
+        update(&mut document, "theme", &new.theme, &old.theme, Clone::clone);
+        update(&mut document, "shell", &new.shell, &old.shell, Clone::clone);
+        update(&mut document, "term", &new.term, &old.term, Clone::clone);
+        update(
+            &mut document,
+            "scrollback_lines",
+            &new.scrollback_lines,
+            &old.scrollback_lines,
+            |lines| *lines as i64,
+        );
+        update(
+            &mut document,
+            "cursor_style",
+            &new.cursor_style,
+            &old.cursor_style,
+            |style| name_of(*style, &CURSOR_STYLES),
+        );
+        update(
+            &mut document,
+            "tab_width",
+            &new.tab_width,
+            &old.tab_width,
+            |width| i64::from(*width),
+        );
+        update(
+            &mut document,
+            "tab_insert_spaces",
+            &new.tab_insert_spaces,
+            &old.tab_insert_spaces,
+            |spaces| *spaces,
+        );
+        update(
+            &mut document,
+            "cursor_blink",
+            &new.cursor_blink,
+            &old.cursor_blink,
+            |blink| *blink,
+        );
+        update(
+            &mut document,
+            "exit_behavior",
+            &new.exit_behavior,
+            &old.exit_behavior,
+            |behavior| name_of(*behavior, &EXIT_BEHAVIORS),
+        );
+        update(
+            &mut document,
+            "confirm_close",
+            &new.confirm_close,
+            &old.confirm_close,
+            |confirm| *confirm,
+        );
+        update(
+            &mut document,
+            "explorer_visible",
+            &new.explorer_visible,
+            &old.explorer_visible,
+            |visible| *visible,
+        );
+        update(
+            &mut document,
+            "open_directory",
+            &new.open_directory,
+            &old.open_directory,
+            |open| name_of(*open, &OPEN_DIRECTORY),
+        );
+        update(
+            &mut document,
+            "git_status",
+            &new.git_status,
+            &old.git_status,
+            |git| *git,
+        );
+        update(
+            &mut document,
+            "double_click_runs_executables",
+            &new.double_click_runs_executables,
+            &old.double_click_runs_executables,
+            |runs| *runs,
+        );
+        update(
+            &mut document,
+            "run_in_new_tab",
+            &new.run_in_new_tab,
+            &old.run_in_new_tab,
+            |new_tab| *new_tab,
+        );
+
+        let no_colors = Colors::default();
+        let new_colors = new.colors.as_ref().unwrap_or(&no_colors);
+        let old_colors = old.colors.as_ref().unwrap_or(&no_colors);
+        let keys = COLOR_KEYS
+            .iter()
+            .map(|(key, _)| *key)
+            .chain(ANSI_COLOR_NAMES);
+        for key in keys {
+            let (new_color, old_color) = (new_colors.get(key), old_colors.get(key));
+            if new_color == old_color {
+                continue;
+            }
+            let table = colors_table(&mut document)?;
+            match new_color {
+                Some(color) => {
+                    table[key] = toml_edit::value(format!(
+                        "#{:02x}{:02x}{:02x}",
+                        color.r(),
+                        color.g(),
+                        color.b()
+                    ));
+                }
+                None => {
+                    table.remove(key);
+                }
+            }
+        }
+        if new_colors.bold_is_bright() != old_colors.bold_is_bright() {
+            let table = colors_table(&mut document)?;
+            match new_colors.bold_is_bright() {
+                Some(bold_is_bright) => table["bold_is_bright"] = toml_edit::value(bold_is_bright),
+                None => {
+                    table.remove("bold_is_bright");
+                }
+            }
+        }
+
+        save_keybindings::<TerminalAction>(&mut document, &new.keybindings, &old.keybindings)?;
+        save_keybindings::<ExplorerAction>(&mut document, &new.keybindings, &old.keybindings)?;
+
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(&self.path, document.to_string())?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum FontField {
+    Ui,
+    Terminal,
+}
+
+/// The color `key` of `COLOR_KEYS` in `palette`
+fn color_of(palette: &Palette, key: &str) -> Rgba {
+    match key {
+        "foreground" => palette.foreground,
+        "background" => palette.background,
+        "cursor" => palette.cursor,
+        _ => palette.selection_background,
+    }
+}
+
+/// A combo box choosing one of `options`, each with its name in the config file
+fn choice<T: Copy + PartialEq>(
+    ui: &mut egui::Ui,
+    id: &str,
+    current: &mut T,
+    options: &[(T, &'static str)],
+) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(name_of(*current, options))
+        .show_ui(ui, |ui| {
+            for (value, name) in options {
+                changed |= ui.selectable_value(current, *value, *name).changed();
+            }
+        });
+    changed
+}
+
+fn name_of<T: PartialEq>(value: T, options: &[(T, &'static str)]) -> &'static str {
+    options
+        .iter()
+        .find(|(option, _)| *option == value)
+        .map_or("", |(_, name)| name)
+}
+
+/// Set `key` of the file to the new value if it was changed, or remove it to use the default
+fn update<T: PartialEq, V: Into<toml_edit::Value>>(
+    document: &mut DocumentMut,
+    key: &str,
+    new: &Option<T>,
+    old: &Option<T>,
+    to_value: impl Fn(&T) -> V,
+) {
+    if new == old {
+        return;
+    }
+    match new {
+        Some(value) => document[key] = toml_edit::value(to_value(value)),
+        None => {
+            document.remove(key);
+        }
+    }
+}
+
+/// The `[colors]` table of the file, added if there is none
+fn colors_table(document: &mut DocumentMut) -> anyhow::Result<&mut toml_edit::Table> {
+    document
+        .entry("colors")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("colors in the file isn't a table"))
+}
+
+/// Write the keybindings of a scope that changed into `[keybindings.<scope>]`, removing the
+/// ones back at their defaults
+fn save_keybindings<A: Action>(
+    document: &mut DocumentMut,
+    new: &Keybindings,
+    old: &Keybindings,
+) -> anyhow::Result<()> {
+    for info in A::registry() {
+        let new_binding = A::overrides(new).get(&info.action);
+        if new_binding == A::overrides(old).get(&info.action) {
+            continue;
+        }
+        let keybindings = document
+            .entry("keybindings")
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_mut()
+            .ok_or_else(|| anyhow::anyhow!("keybindings in the file isn't a table"))?;
+        let table = keybindings
+            .entry(A::SCOPE)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| anyhow::anyhow!("keybindings.{} in the file isn't a table", A::SCOPE))?;
+        match new_binding {
+            Some(binding) => table[info.name] = toml_edit::value(binding.to_string()),
+            None => {
+                table.remove(info.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The chord pressed in this frame, taken out of the events. `Some(None)` for Escape
+fn capture_chord(ui: &egui::Ui) -> Option<Option<Chord>> {
+    ui.input_mut(|i| {
+        let position = i
+            .events
+            .iter()
+            .position(|event| matches!(event, egui::Event::Key { pressed: true, .. }))?;
+        let egui::Event::Key { key, modifiers, .. } = i.events.remove(position) else {
+            unreachable!("found a key event");
+        };
+        if key == egui::Key::Escape && modifiers.is_none() {
+            return Some(None);
+        }
+
+        let mut chord_modifiers = egui::Modifiers::NONE;
+        if modifiers.ctrl || modifiers.command {
+            chord_modifiers |= egui::Modifiers::CTRL;
+        }
+        if modifiers.shift {
+            chord_modifiers |= egui::Modifiers::SHIFT;
+        }
+        if modifiers.alt {
+            chord_modifiers |= egui::Modifiers::ALT;
+        }
+        Some(Some(Chord {
+            modifiers: chord_modifiers,
+            key,
+        }))
+    })
+}
+
+/// Whether `command` is an executable path, or found in `PATH`
+fn is_executable_command(command: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    let is_executable = |path: &Path| {
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    };
+    if command.contains('/') {
+        return is_executable(Path::new(command));
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|directory| is_executable(&directory.join(command)))
+    })
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use gio::glib::home_dir;
+use serde::{Deserialize, Serialize};
+
+use crate::explorer_widget::columns::Column;
+
+/// Layout of the state file written by this version. Fields are only ever added, so older
+/// files load with the new ones missing and newer files with the unknown ones ignored
+pub const VERSION: u32 = 1;
+
+/// How often the state is compared with the one saved last and written when it changed, so
+/// that a crash loses little
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What is remembered from the previous run, stored in `~/.local/state/explotty/state.toml`
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct State {
+    /// Layout version of the file, 0 for files from before it was written
+    pub version: u32,
+    /// Working directory of the active tab
+    pub cwd: Option<PathBuf>,
+    /// Inner size of the window in points
+    pub window_size: Option<[f32; 2]>,
+    /// Outer position of the window in points
+    pub window_position: Option<[f32; 2]>,
+    pub explorer_height: Option<f32>,
+    pub font_size: Option<f32>,
+    /// Whether the explorer lists dotfiles
+    pub show_hidden: Option<bool>,
+    /// The columns shown in the explorer
+    pub explorer_columns: Option<Vec<Column>>,
+    /// Whether the explorer is split into two panes
+    pub explorer_split: Option<bool>,
+    /// Directory of the explorer's second pane
+    pub explorer_second_directory: Option<PathBuf>,
+    /// Widths of the explorer's columns that were resized
+    pub explorer_column_widths: Option<BTreeMap<Column, f32>>,
+    /// Whether the window was kept above the others
+    pub always_on_top: Option<bool>,
+}
+
+impl State {
+    fn path() -> PathBuf {
+        let state_home = std::env::var_os("XDG_STATE_HOME")
+            .map(PathBuf::from)
+            .filter(|path| path.is_absolute())
+            .unwrap_or_else(|| home_dir().join(".local/state"));
+        state_home.join("explotty/state.toml")
+    }
+
+    /// Load the state of the previous run. A missing or corrupted file just means there is
+    /// nothing to restore; a corrupted one is moved aside to `state.toml.bad` so that it can
+    /// be looked at and isn't read again.
+    pub fn load() -> Option<Self> {
+        Self::load_from(&Self::path())
+    }
+
+    fn load_from(path: &Path) -> Option<Self> {
+        let toml_str = std::fs::read_to_string(path).ok()?;
+        match toml::de::from_str::<Self>(&toml_str) {
+            Ok(state) => Some(state.migrate()),
+            Err(e) => {
+                let aside = path.with_extension("toml.bad");
+                match std::fs::rename(path, &aside) {
+                    Ok(()) => warn!(
+                        "Ignoring corrupted state file {}, moved it to {}: {e}",
+                        path.display(),
+                        aside.display()
+                    ),
+                    Err(rename_error) => warn!(
+                        "Ignoring corrupted state file {} (not moved aside: {rename_error}): {e}",
+                        path.display()
+                    ),
+                }
+                None
+            }
+        }
+    }
+
+    /// Bring a file of an older layout up to date
+    fn migrate(mut self) -> Self {
+        if self.version > VERSION {
+            info!(
+                "The state file is from a newer explotty (version {}), reading what is known",
+                self.version
+            );
+        }
+        // Version 0 had the same fields, only without the version
+        self.version = VERSION;
+        self
+    }
+
+    /// Write the state, through a temporary file so that a crash never leaves half of it
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.save_to(&Self::path())
+    }
+
+    fn save_to(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temporary = path.with_extension("toml.tmp");
+        std::fs::write(&temporary, toml::ser::to_string(self)?)?;
+        std::fs::rename(temporary, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A state file in a directory of its own under the system's temporary directory
+    struct TempState(PathBuf);
+
+    impl TempState {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("explotty-state-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            Self(dir.join("explotty/state.toml"))
+        }
+
+        fn write(&self, contents: &str) {
+            std::fs::create_dir_all(self.0.parent().unwrap()).unwrap();
+            std::fs::write(&self.0, contents).unwrap();
+        }
+    }
+
+    impl Drop for TempState {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(self.0.parent().unwrap().parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = TempState::new("round-trip");
+        let state = State {
+            version: VERSION,
+            cwd: Some("/home/me/projects".into()),
+            window_size: Some([800.0, 600.0]),
+            window_position: Some([10.0, 20.0]),
+            explorer_height: Some(240.0),
+            font_size: Some(15.5),
+            show_hidden: Some(true),
+            explorer_columns: Some(vec![Column::Size, Column::Modified]),
+            explorer_split: Some(false),
+            explorer_second_directory: Some("/tmp".into()),
+            explorer_column_widths: Some(BTreeMap::from([(Column::Size, 90.0)])),
+            always_on_top: Some(true),
+        };
+        state.save_to(&file.0).unwrap();
+        assert_eq!(State::load_from(&file.0), Some(state));
+        // Written through a temporary file that is gone afterwards
+        assert!(!file.0.with_extension("toml.tmp").exists());
+    }
+
+    #[test]
+    fn missing_file_restores_nothing() {
+        let file = TempState::new("missing");
+        assert_eq!(State::load_from(&file.0), None);
+    }
+
+    #[test]
+    fn files_of_other_versions_migrate() {
+        let file = TempState::new("migrate");
+
+        // Version 0 had no version field
+        file.write("font_size = 12.0\nshow_hidden = true\n");
+        let state = State::load_from(&file.0).unwrap();
+        assert_eq!(state.version, VERSION);
+        assert_eq!(state.font_size, Some(12.0));
+        assert_eq!(state.show_hidden, Some(true));
+        assert_eq!(state.window_size, None);
+
+        // A newer version's unknown fields are ignored, the known ones read
+        file.write("version = 99\nfont_size = 13.0\nzoom_per_tab = [1.0, 2.0]\n");
+        let state = State::load_from(&file.0).unwrap();
+        assert_eq!(state.version, VERSION);
+        assert_eq!(state.font_size, Some(13.0));
+    }
+
+    #[test]
+    fn corrupted_file_is_moved_aside() {
+        let file = TempState::new("corrupted");
+        file.write("font_size = \"big\"\n[[[");
+        assert_eq!(State::load_from(&file.0), None);
+        assert!(!file.0.exists());
+        let aside = file.0.with_extension("toml.bad");
+        assert_eq!(
+            std::fs::read_to_string(aside).unwrap(),
+            "font_size = \"big\"\n[[["
+        );
+
+        // The next run starts fresh and saves over it
+        let state = State {
+            version: VERSION,
+            ..Default::default()
+        };
+        state.save_to(&file.0).unwrap();
+        assert_eq!(State::load_from(&file.0), Some(state));
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicU8, Ordering},
+};
+
+use eframe::egui;
+use gio::prelude::*;
+
+const SCHEMA: &str = "org.gnome.desktop.interface";
+const KEY: &str = "color-scheme";
+
+const UNKNOWN: u8 = 0;
+const DARK: u8 = 1;
+const LIGHT: u8 = 2;
+
+/// The desktop's light or dark preference (the `color-scheme` GSettings key), kept up to date
+/// by a thread listening for changes
+pub struct SystemTheme {
+    preference: Arc<AtomicU8>,
+}
+
+impl SystemTheme {
+    /// Start listening. Without the GNOME schema the preference stays unknown
+    pub fn new(ctx: &egui::Context) -> Self {
+        let preference = Arc::new(AtomicU8::new(UNKNOWN));
+
+        let thread_preference = Arc::clone(&preference);
+        let ctx = ctx.clone();
+        let result = std::thread::Builder::new()
+            .name("system-theme".to_string())
+            .spawn(move || {
+                // GSettings delivers its signals to the main context it was created in, and
+                // nothing iterates the default one
+                let main_context = gio::glib::MainContext::new();
+                let _ = main_context.with_thread_default(|| {
+                    let Some(settings) = color_scheme_settings() else {
+                        debug!("No {SCHEMA} {KEY} setting, following the windowing system");
+                        return;
+                    };
+                    thread_preference.store(read_preference(&settings), Ordering::Relaxed);
+                    ctx.request_repaint();
+
+                    settings.connect_changed(Some(KEY), move |settings, _| {
+                        thread_preference.store(read_preference(settings), Ordering::Relaxed);
+                        ctx.request_repaint();
+                    });
+                    gio::glib::MainLoop::new(Some(&main_context), false).run();
+                });
+            });
+        if let Err(e) = result {
+            warn!("Failed to start watching the desktop color scheme: {e}");
+        }
+
+        Self { preference }
+    }
+
+    /// The theme the desktop prefers, if it said so
+    pub fn theme(&self) -> Option<egui::Theme> {
+        match self.preference.load(Ordering::Relaxed) {
+            DARK => Some(egui::Theme::Dark),
+            LIGHT => Some(egui::Theme::Light),
+            _ => None,
+        }
+    }
+}
+
+fn color_scheme_settings() -> Option<gio::Settings> {
+    let schema = gio::SettingsSchemaSource::default()?.lookup(SCHEMA, true)?;
+    if !schema.has_key(KEY) {
+        return None;
+    }
+    Some(gio::Settings::new_full(
+        &schema,
+        None::<&gio::SettingsBackend>,
+        None,
+    ))
+}
+
+/// `prefer-dark` and `prefer-light`; `default` leaves the choice to the application, which
+/// for GTK means light
+fn read_preference(settings: &gio::Settings) -> u8 {
+    match settings.string(KEY).as_str() {
+        "prefer-dark" => DARK,
+        _ => LIGHT,
+    }
+}
@@ -0,0 +1,525 @@
+//! The terminal emulation core.
+//! Everything in here is independent of egui: bytes from the PTY go in through `Term::feed`,
+//! and the resulting screen state is read back by the widget (or anything else) for drawing.
+
+pub mod color;
+pub mod export;
+pub mod graphics;
+pub mod highlight;
+pub mod parser;
+pub mod shell_integration;
+pub mod terminal_buffer;
+pub mod terminal_cell;
+#[cfg(feature = "inspector")]
+pub mod trace;
+
+use serde::Deserialize;
+
+use crate::{
+    config::HighlightRule,
+    term::{
+        color::Palette,
+        graphics::Graphics,
+        highlight::{HighlightSpan, Highlighter, RuleEvent},
+        parser::{
+            dispatcher::SequenceDispatcher, handler_context::HandlerContext,
+            sequence_tokenizer::SequenceTokenizer,
+        },
+        shell_integration::{CommandEvent, Listing, ShellIntegration},
+        terminal_buffer::TerminalBuffer,
+        terminal_cell::TerminalCell,
+    },
+};
+
+/// Shape of the cursor, set in the config or by the application (DECSCUSR)
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorStyle {
+    #[default]
+    Block,
+    Underline,
+    Bar,
+}
+
+/// Mouse events the application asked to be reported
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// Presses and releases (?1000h)
+    Click,
+    /// Also motion while a button is held (?1002h)
+    Drag,
+    /// Also motion without a button (?1003h)
+    Motion,
+}
+
+/// Lines of scrollback kept unless configured otherwise
+pub const DEFAULT_SCROLLBACK_LINES: usize = 1000;
+
+/// Whether LF also returns the carriage (LNM) until the application says otherwise, on start
+/// and after a reset (RIS)
+pub const DEFAULT_NEW_LINE_MODE: bool = true;
+
+pub struct Term {
+    pub buffer: TerminalBuffer,
+    pub show_cursor: bool,
+    tokenizer: SequenceTokenizer,
+    dispatcher: SequenceDispatcher,
+    pub bracket_paste_mode: bool,
+    // Storage location for current screen information used when Alternative Screen Buffer is used
+    saved_screen_buffer: Option<TerminalBuffer>,
+    // DEC Private Mode states
+    pub decckm_mode: bool, // DECCKM - Cursor Key Application Mode (?1h/l)
+    pub decom_mode: bool,  // DECOM - Origin Mode (?6h/l)
+    pub decawm_mode: bool, // DECAWM - Auto Wrap Mode (?7h/l)
+    pub reverse_video_mode: bool, // DECSCNM - Screen Reverse Video (?5h/l)
+    pub max_scroll_lines: usize,
+    pub scrollback_buffer: Vec<Vec<TerminalCell>>,
+    pub new_line_mode: bool,
+    pub insert_mode: bool,             // IRM - Insert/Replace Mode (4h/l)
+    pub mouse_tracking: MouseTracking, // Mouse reporting (?1000h, ?1002h, ?1003h)
+    pub sgr_mouse_mode: bool,          // SGR mouse coordinates (?1006h)
+    // TAB writes spaces up to the next stop instead of moving the cursor there
+    pub tab_insert_spaces: bool,
+    // The answer to ENQ, set with `set_answerback`
+    answerback: String,
+    // Set by BEL, taken by the UI with `take_bell`
+    bell: bool,
+    // Window title set by the application, taken by the UI with `take_title`
+    title: Option<String>,
+    // Bytes to send back to the application (e.g. cursor position reports), taken with `take_replies`
+    replies: Vec<u8>,
+    shell_integration: ShellIntegration,
+    /// Colors SGR sequences refer to, set with `set_palette`
+    palette: Palette,
+    pub cursor_style: CursorStyle,
+    pub cursor_blink: bool,
+    /// The configured cursor, which DECSCUSR 0 goes back to
+    default_cursor: (CursorStyle, bool),
+    /// Images placed by applications (kitty graphics protocol)
+    pub graphics: Graphics,
+    /// Lines scrolled from the primary screen into the history so far
+    scrolled_lines: u64,
+    /// Spans of the output matching the `[[highlight]]` rules
+    highlighter: Highlighter,
+    /// The sequences applied since the inspector last looked
+    #[cfg(feature = "inspector")]
+    trace: trace::Trace,
+}
+
+impl Term {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            buffer: TerminalBuffer::new(width, height),
+            show_cursor: true,
+            tokenizer: SequenceTokenizer::new(),
+            dispatcher: SequenceDispatcher::new(),
+            bracket_paste_mode: false,
+            saved_screen_buffer: None,
+            // Initialize DEC Private Mode states to their default values
+            decckm_mode: false,        // Cursor key normal mode
+            decom_mode: false,         // Absolute origin mode
+            decawm_mode: true,         // Auto wrap mode enabled by default
+            reverse_video_mode: false, // Normal video mode
+            max_scroll_lines: DEFAULT_SCROLLBACK_LINES,
+            scrollback_buffer: Vec::new(),
+            new_line_mode: DEFAULT_NEW_LINE_MODE,
+            insert_mode: false,
+            mouse_tracking: MouseTracking::Off,
+            sgr_mouse_mode: false,
+            tab_insert_spaces: false,
+            answerback: String::new(),
+            bell: false,
+            title: None,
+            replies: Vec::new(),
+            shell_integration: ShellIntegration::default(),
+            palette: Palette::default(),
+            cursor_style: CursorStyle::default(),
+            cursor_blink: false,
+            default_cursor: (CursorStyle::default(), false),
+            graphics: Graphics::default(),
+            scrolled_lines: 0,
+            highlighter: Highlighter::default(),
+            #[cfg(feature = "inspector")]
+            trace: trace::Trace::default(),
+        }
+    }
+
+    /// Use `style` and `blink` for the cursor, unless the application changed it
+    pub fn set_default_cursor(&mut self, style: CursorStyle, blink: bool) {
+        if (self.cursor_style, self.cursor_blink) == self.default_cursor {
+            self.cursor_style = style;
+            self.cursor_blink = blink;
+        }
+        self.default_cursor = (style, blink);
+    }
+
+    /// Put the tab stops every `tab_width` columns on both screens
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.buffer.set_tab_width(tab_width);
+        if let Some(saved) = &mut self.saved_screen_buffer {
+            saved.set_tab_width(tab_width);
+        }
+    }
+
+    /// Answer ENQ with `answerback`, left without its control characters so it can't inject
+    /// commands. Empty sends nothing
+    pub fn set_answerback(&mut self, answerback: &str) {
+        self.answerback = answerback.chars().filter(|ch| !ch.is_control()).collect();
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Use `palette` for the output from now on, e.g. to go back to the configured theme. Text
+    /// already on the screen keeps its colors
+    pub fn set_palette(&mut self, palette: Palette) {
+        if self.buffer.current_fg_color == self.palette.foreground {
+            self.buffer.current_fg_color = palette.foreground;
+        }
+        self.palette = palette;
+    }
+
+    /// Parse the output of the PTY and apply it to the screen
+    pub fn feed(&mut self, data: &[u8]) {
+        let tokens = self.tokenizer.feed(data);
+
+        for token in tokens {
+            let mut handler_ctx = HandlerContext {
+                buffer: &mut self.buffer,
+                scrollback_buffer: &mut self.scrollback_buffer,
+                saved_screen_buffer: &mut self.saved_screen_buffer,
+                max_scroll_lines: &mut self.max_scroll_lines,
+                decckm_mode: &mut self.decckm_mode,
+                decom_mode: &mut self.decom_mode,
+                decawm_mode: &mut self.decawm_mode,
+                reverse_video_mode: &mut self.reverse_video_mode,
+                show_cursor: &mut self.show_cursor,
+                bracket_paste_mode: &mut self.bracket_paste_mode,
+                new_line_mode: &mut self.new_line_mode,
+                insert_mode: &mut self.insert_mode,
+                mouse_tracking: &mut self.mouse_tracking,
+                sgr_mouse_mode: &mut self.sgr_mouse_mode,
+                bell: &mut self.bell,
+                tab_insert_spaces: self.tab_insert_spaces,
+                answerback: &self.answerback,
+                title: &mut self.title,
+                reply: &mut self.replies,
+                shell_integration: &mut self.shell_integration,
+                palette: &self.palette,
+                cursor_style: &mut self.cursor_style,
+                cursor_blink: &mut self.cursor_blink,
+                default_cursor: self.default_cursor,
+                graphics: &mut self.graphics,
+                scrolled_lines: &mut self.scrolled_lines,
+            };
+
+            #[cfg(not(feature = "inspector"))]
+            self.dispatcher.dispatch(&mut handler_ctx, token);
+            #[cfg(feature = "inspector")]
+            {
+                let pending = trace::PendingTrace::new(&token, handler_ctx.buffer);
+                let handler = self.dispatcher.dispatch(&mut handler_ctx, token);
+                if let Some(pending) = pending {
+                    self.trace.push(pending.finish(handler, &self.buffer));
+                }
+            }
+        }
+        let first_line = self.first_line();
+        self.graphics
+            .collect_garbage(first_line, self.saved_screen_buffer.is_some());
+        self.shell_integration.collect_garbage(first_line);
+        self.match_highlights();
+    }
+
+    /// Match the lines of the primary screen the cursor left against the highlight rules
+    fn match_highlights(&mut self) {
+        let first_line = self.first_line();
+        if self.saved_screen_buffer.is_none() {
+            let screen_line = self.scrolled_lines;
+            let (screen, scrollback) = (&self.buffer.cells, &self.scrollback_buffer);
+            self.highlighter
+                .match_lines(screen_line + self.buffer.cursor_y as u64, |line| match line
+                    .checked_sub(screen_line)
+                {
+                    Some(row) => screen.get(row as usize).map(Vec::as_slice),
+                    None => scrollback
+                        .get((line - first_line) as usize)
+                        .map(Vec::as_slice),
+                });
+        }
+        self.highlighter.collect_garbage(first_line);
+    }
+
+    /// Use the `[[highlight]]` rules `rules`
+    pub fn set_highlight_rules(&mut self, rules: &[HighlightRule]) {
+        self.highlighter.set_rules(rules, self.scrolled_lines);
+    }
+
+    /// The spans of `line` matching highlight rules, with their rules
+    pub fn highlights(&self, line: u64) -> impl Iterator<Item = (&HighlightSpan, &HighlightRule)> {
+        self.highlighter
+            .spans(line)
+            .iter()
+            .filter_map(|span| Some((span, self.highlighter.rules.get(span.rule)?)))
+    }
+
+    /// Whether a rule with `action = "mark"` marked `line`
+    pub fn is_marked(&self, line: u64) -> bool {
+        self.highlighter.is_marked(line)
+    }
+
+    /// Take the actions the highlight rules fired since the last call
+    pub fn take_rule_events(&mut self) -> Vec<RuleEvent> {
+        std::mem::take(&mut self.highlighter.events)
+    }
+
+    /// The line shown in the top row of the primary screen, counted from the first line ever
+    pub fn screen_line(&self) -> u64 {
+        self.scrolled_lines
+    }
+
+    /// The oldest line left in the history, counted like `screen_line`
+    pub fn first_line(&self) -> u64 {
+        self.scrolled_lines
+            .saturating_sub(self.scrollback_buffer.len() as u64)
+    }
+
+    /// Whether the application switched to the alternate screen
+    pub fn is_alternate_screen(&self) -> bool {
+        self.saved_screen_buffer.is_some()
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if width == self.buffer.width && height == self.buffer.height {
+            return;
+        }
+
+        self.buffer.resize(width, height);
+
+        // Adjust existing scrollback lines to new width
+        for line in &mut self.scrollback_buffer {
+            if line.len() < width {
+                line.resize(width, TerminalCell::default());
+            } else if line.len() > width {
+                line.truncate(width);
+            }
+        }
+    }
+
+    /// The cells of the visible screen, row by row
+    pub fn screen_cells(&self) -> &[Vec<TerminalCell>] {
+        &self.buffer.cells
+    }
+
+    /// The text of the visible screen, one line per row with trailing blanks trimmed
+    #[cfg(test)]
+    pub fn screen_text(&self) -> String {
+        self.buffer
+            .cells
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .filter(|cell| !cell.wide_tail)
+                    .map(|cell| cell.character)
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Take the window title requested by the application since the last call, if any
+    pub fn take_title(&mut self) -> Option<String> {
+        self.title.take()
+    }
+
+    /// Whether the application rang the bell since the last call
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
+    /// Take the sequences applied since the last call, oldest first
+    #[cfg(feature = "inspector")]
+    pub fn take_trace(&mut self) -> std::collections::VecDeque<trace::TraceEvent> {
+        self.trace.take()
+    }
+
+    /// Take the replies to queries made by the application since the last call.
+    /// They have to be written to the PTY this terminal is attached to.
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.replies)
+    }
+
+    /// The command line being typed at the prompt, if the shell marks its prompts (OSC 133)
+    pub fn command_line(&self) -> Option<String> {
+        self.shell_integration.command_line(&self.buffer)
+    }
+
+    /// Take the commands started and finished since the last call, as reported through OSC 133
+    pub fn take_command_events(&mut self) -> Vec<CommandEvent> {
+        std::mem::take(&mut self.shell_integration.events)
+    }
+
+    /// The listing of files that `line` (counted like `screen_line`) is part of
+    pub fn listing_at(&self, line: u64) -> Option<&Listing> {
+        self.shell_integration
+            .listings
+            .iter()
+            .find(|listing| listing.lines.contains(&line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Feed the recorded `frames` from `snapshots/` one after another into a terminal of the
+    /// given size, comparing the screen after each with the `.txt` stored next to it.
+    /// With `UPDATE_SNAPSHOTS` set the expectations are written instead.
+    fn assert_snapshots(width: usize, height: usize, frames: &[&str]) {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/term/snapshots");
+        let mut term = Term::new(width, height);
+        for frame in frames {
+            term.feed(&std::fs::read(dir.join(format!("{frame}.bytes"))).unwrap());
+            let screen = term.screen_text() + "\n";
+            let expected_path = dir.join(format!("{frame}.txt"));
+            if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+                std::fs::write(&expected_path, &screen).unwrap();
+                continue;
+            }
+            let expected = std::fs::read_to_string(&expected_path).unwrap();
+            assert_eq!(screen, expected, "screen after {frame}.bytes");
+        }
+    }
+
+    #[test]
+    fn ls_color() {
+        assert_snapshots(40, 6, &["ls_color"]);
+    }
+
+    #[test]
+    fn vim_session() {
+        assert_snapshots(40, 9, &["vim_open", "vim_insert", "vim_quit"]);
+    }
+
+    #[test]
+    fn htop_frames() {
+        assert_snapshots(40, 8, &["htop_1", "htop_2"]);
+    }
+
+    #[test]
+    fn reset_restores_the_initial_modes() {
+        let mut term = Term::new(10, 3);
+        term.feed(b"\x1b[20l\x1b[4h\x1b[?7l");
+        term.feed(b"\x1bc");
+        assert_eq!(term.new_line_mode, DEFAULT_NEW_LINE_MODE);
+        assert!(!term.insert_mode);
+        assert!(term.decawm_mode);
+    }
+
+    #[test]
+    fn ls_color_keeps_the_colors() {
+        let mut term = Term::new(40, 6);
+        term.feed(include_bytes!("term/snapshots/ls_color.bytes"));
+        let palette = term.palette();
+        let docs = &term.screen_cells()[1][0];
+        assert_eq!(docs.character, 'd');
+        assert!(docs.bold);
+        assert_eq!(docs.fg_color, palette.ansi[4]);
+        let readme = &term.screen_cells()[1][24];
+        assert_eq!(readme.character, 'R');
+        assert!(!readme.bold);
+        assert_eq!(readme.fg_color, palette.foreground);
+    }
+
+    #[test]
+    fn random_bytes_leave_a_working_terminal() {
+        // xorshift64, enough to make up input without a dependency
+        let mut state = 0x0dd_ba11_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut term = Term::new(20, 5);
+        for _ in 0..500 {
+            let len = (next() % 256) as usize;
+            let bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            term.feed(&bytes);
+        }
+
+        // Whatever was left open is ended and the terminal reset, after which it works as new
+        term.feed(b"\x18\x07\x1b\\\x1bc");
+        term.feed(b"alive");
+        assert!(
+            term.screen_text().starts_with("alive"),
+            "{}",
+            term.screen_text()
+        );
+    }
+
+    #[test]
+    fn leaving_the_alternate_screen_restores_the_primary_one() {
+        let mut term = Term::new(20, 6);
+        // A colored prompt with a scroll region set
+        term.feed(b"\x1b[2;5r\x1b[4;1H\x1b[31m$ vim\x1b[32m");
+        let prompt = (term.buffer.cursor_x, term.buffer.cursor_y);
+        let (color, background) = (term.buffer.current_fg_color, term.buffer.current_bg_color);
+
+        // vim, running a shell that runs vim again: the nested one starts over on the same
+        // alternate screen, and leaving it goes back to the primary screen as in xterm
+        term.feed(b"\x1b[?1049h\x1b[r\x1b[44m\x1b[1;1Hfile\x1b[5;3H\x1b7");
+        term.feed(b"\x1b[?1049h\x1b[35mnested");
+        assert!(term.is_alternate_screen());
+        term.feed(b"\x1b[?1049l");
+        // The outer vim quitting after it changes nothing more
+        term.feed(b"\x1b[?1049l");
+
+        assert!(!term.is_alternate_screen());
+        assert_eq!((term.buffer.cursor_x, term.buffer.cursor_y), prompt);
+        assert_eq!(term.buffer.current_fg_color, color);
+        assert_eq!(term.buffer.current_bg_color, background);
+        assert_eq!(
+            (
+                term.buffer.scroll_region_top,
+                term.buffer.scroll_region_bottom
+            ),
+            (1, 4)
+        );
+        assert_eq!(term.screen_text(), "\n\n\n$ vim\n\n");
+        assert_eq!(term.screen_cells()[3][0].fg_color, term.palette().ansi[1]);
+    }
+
+    #[test]
+    fn every_scroll_of_the_primary_screen_keeps_history() {
+        // LF, IND at the bottom row and SU
+        for scroll in [&b"\n"[..], b"\x1bD", b"\x1b[S"] {
+            let mut term = Term::new(10, 3);
+            term.feed(b"first\x1b[3;1H");
+            term.feed(scroll);
+            assert_eq!(term.scrollback_buffer.len(), 1, "{scroll:?}");
+            assert_eq!(term.scrollback_buffer[0][0].character, 'f', "{scroll:?}");
+
+            // The alternate screen has no history
+            term.feed(b"\x1b[?1049h\x1b[3;1H");
+            term.feed(scroll);
+            assert_eq!(
+                term.scrollback_buffer.len(),
+                1,
+                "{scroll:?} on the alternate screen"
+            );
+        }
+
+        // Nor does a scroll region below the top of the screen
+        let mut term = Term::new(10, 3);
+        term.feed(b"\x1b[2;3r\x1b[3;1H\n\x1bD\x1b[S");
+        assert!(term.scrollback_buffer.is_empty());
+    }
+}
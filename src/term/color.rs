@@ -0,0 +1,232 @@
+/// An RGBA color used by the terminal core.
+/// Kept independent of egui so the emulation can run without any UI types; the widget converts
+/// it to `egui::Color32` when drawing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rgba(pub [u8; 4]);
+
+impl Rgba {
+    pub const TRANSPARENT: Self = Self([0, 0, 0, 0]);
+    pub const BLACK: Self = Self::from_rgb(0, 0, 0);
+    pub const RED: Self = Self::from_rgb(255, 0, 0);
+    pub const GREEN: Self = Self::from_rgb(0, 255, 0);
+    pub const YELLOW: Self = Self::from_rgb(255, 255, 0);
+    pub const BLUE: Self = Self::from_rgb(0, 0, 255);
+    pub const MAGENTA: Self = Self::from_rgb(255, 0, 255);
+    pub const CYAN: Self = Self::from_rgb(0, 255, 255);
+    pub const WHITE: Self = Self::from_rgb(255, 255, 255);
+
+    pub const fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        Self([r, g, b, 255])
+    }
+
+    pub const fn from_gray(l: u8) -> Self {
+        Self::from_rgb(l, l, l)
+    }
+
+    pub const fn r(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub const fn g(&self) -> u8 {
+        self.0[1]
+    }
+
+    pub const fn b(&self) -> u8 {
+        self.0[2]
+    }
+
+    pub const fn a(&self) -> u8 {
+        self.0[3]
+    }
+
+    pub const fn to_array(self) -> [u8; 4] {
+        self.0
+    }
+}
+
+/// The colors the terminal is drawn with, from the `[colors]` section of the config
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// Colors 0–15: black, red, green, yellow, blue, magenta, cyan, white and their bright
+    /// variants
+    pub ansi: [Rgba; 16],
+    pub foreground: Rgba,
+    pub background: Rgba,
+    pub cursor: Rgba,
+    pub selection_background: Rgba,
+    /// Text of the selection, `None` keeps the colors of the text
+    pub selection_foreground: Option<Rgba>,
+    /// Draw bold text in colors 0–7 with their bright variants
+    pub bold_is_bright: bool,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let normal = [
+            Rgba::BLACK,
+            Rgba::RED,
+            Rgba::GREEN,
+            Rgba::YELLOW,
+            Rgba::BLUE,
+            Rgba::MAGENTA,
+            Rgba::CYAN,
+            Rgba::WHITE,
+        ];
+        let mut ansi = [Rgba::BLACK; 16];
+        for (index, color) in normal.into_iter().enumerate() {
+            ansi[index] = color;
+            ansi[index + 8] = to_bright(color);
+        }
+
+        Self {
+            ansi,
+            foreground: Rgba::WHITE,
+            background: Rgba::BLACK,
+            cursor: Rgba([255, 255, 255, 128]),
+            selection_background: Rgba([100, 100, 100, 100]),
+            selection_foreground: None,
+            bold_is_bright: true,
+        }
+    }
+}
+
+/// Names of the built-in themes, for `theme = "..."` in the config
+pub const THEMES: [&str; 5] = [
+    "default-dark",
+    "solarized-dark",
+    "solarized-light",
+    "dracula",
+    "gruvbox",
+];
+
+/// `0xrrggbb` as a color
+const fn hex(rgb: u32) -> Rgba {
+    Rgba::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+const SOLARIZED_ANSI: [u32; 16] = [
+    0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5, 0x002b36,
+    0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+];
+
+impl Palette {
+    /// The built-in theme called `name`, one of `THEMES`
+    pub fn theme(name: &str) -> Option<Self> {
+        match name {
+            "default-dark" => Some(Self::default()),
+            "solarized-dark" => Some(Self::solarized_dark()),
+            "solarized-light" => Some(Self::solarized_light()),
+            "dracula" => Some(Self::dracula()),
+            "gruvbox" => Some(Self::gruvbox()),
+            _ => None,
+        }
+    }
+
+    /// A theme with an opaque selection, the cursor drawn translucent over the text
+    fn from_hex(
+        ansi: [u32; 16],
+        foreground: u32,
+        background: u32,
+        cursor: u32,
+        selection: u32,
+    ) -> Self {
+        let [r, g, b, _] = hex(cursor).to_array();
+        Self {
+            ansi: ansi.map(hex),
+            foreground: hex(foreground),
+            background: hex(background),
+            cursor: Rgba([r, g, b, 128]),
+            selection_background: hex(selection),
+            selection_foreground: None,
+            bold_is_bright: true,
+        }
+    }
+
+    pub fn solarized_dark() -> Self {
+        Self {
+            // The bright colors of Solarized are its grays, not brighter colors
+            bold_is_bright: false,
+            ..Self::from_hex(SOLARIZED_ANSI, 0x839496, 0x002b36, 0x93a1a1, 0x073642)
+        }
+    }
+
+    pub fn solarized_light() -> Self {
+        Self {
+            bold_is_bright: false,
+            ..Self::from_hex(SOLARIZED_ANSI, 0x657b83, 0xfdf6e3, 0x586e75, 0xeee8d5)
+        }
+    }
+
+    pub fn dracula() -> Self {
+        Self::from_hex(
+            [
+                0x21222c, 0xff5555, 0x50fa7b, 0xf1fa8c, 0xbd93f9, 0xff79c6, 0x8be9fd, 0xf8f8f2,
+                0x6272a4, 0xff6e6e, 0x69ff94, 0xffffa5, 0xd6acff, 0xff92df, 0xa4ffff, 0xffffff,
+            ],
+            0xf8f8f2,
+            0x282a36,
+            0xf8f8f2,
+            0x44475a,
+        )
+    }
+
+    pub fn gruvbox() -> Self {
+        Self::from_hex(
+            [
+                0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984,
+                0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+            ],
+            0xebdbb2,
+            0x282828,
+            0xebdbb2,
+            0x504945,
+        )
+    }
+
+    /// Color `color_index` of the 256 color palette: the 16 configured colors, the 6x6x6 color
+    /// cube and the grayscale ramp
+    pub fn indexed(&self, color_index: u8) -> Rgba {
+        if color_index < 16 {
+            self.ansi[color_index as usize]
+        } else if (16..232).contains(&color_index) {
+            // 6x6x6 rgb color cube
+            let r_6 = (color_index - 16) / 36;
+            let g_6 = ((color_index - 16) % 36) / 6;
+            let b_6 = (color_index - 16) % 6;
+
+            let rgb: (u8, u8, u8) = [r_6, g_6, b_6]
+                .map(|x| match x {
+                    0 => 0,
+                    1 => 95,
+                    2 => 135,
+                    3 => 175,
+                    4 => 215,
+                    5 => 255,
+                    _ => unreachable!(),
+                })
+                .into();
+
+            Rgba::from_rgb(rgb.0, rgb.1, rgb.2)
+        } else {
+            // 232..=255
+            // Grayscale colors
+            let gray_value = (color_index - 232) * 10 + 8; // 8, 18, ..., 238
+            Rgba::from_gray(gray_value)
+        }
+    }
+
+    /// The bright variant of `color` for bold text, if it is one of colors 0–7
+    pub fn brightened(&self, color: Rgba) -> Option<Rgba> {
+        let index = self.ansi[..8].iter().position(|&ansi| ansi == color)?;
+        Some(self.ansi[index + 8])
+    }
+}
+
+pub fn to_bright(color: Rgba) -> Rgba {
+    let rgb = color.to_array();
+    Rgba::from_rgb(
+        (rgb[0] as f32 * 1.2).min(255.0) as u8,
+        (rgb[1] as f32 * 1.2).min(255.0) as u8,
+        (rgb[2] as f32 * 1.2).min(255.0) as u8,
+    )
+}
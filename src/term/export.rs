@@ -0,0 +1,354 @@
+//! Cells as text that keeps their colors and attributes: HTML for documents and chats, SGR
+//! sequences for pasting into another terminal, and runs of cells for laying them out on a
+//! grid elsewhere, e.g. in a PDF
+
+use std::fmt::Write;
+
+use crate::term::{
+    color::{Palette, Rgba},
+    terminal_cell::TerminalCell,
+};
+
+/// The looks of a cell that survive the export
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Style {
+    /// None for the palette's foreground
+    fg: Option<Rgba>,
+    /// None where the background shows through
+    bg: Option<Rgba>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+}
+
+impl Style {
+    const PLAIN: Self = Self {
+        fg: None,
+        bg: None,
+        bold: false,
+        italic: false,
+        underline: false,
+        strikethrough: false,
+    };
+
+    fn of_run(run: &Run) -> Self {
+        Self {
+            fg: run.fg,
+            bg: run.bg,
+            bold: run.bold,
+            italic: run.italic,
+            underline: run.underline,
+            strikethrough: run.strikethrough,
+        }
+    }
+
+    fn of(cell: &TerminalCell, palette: &Palette) -> Self {
+        Self {
+            fg: (cell.fg_color != palette.foreground).then_some(cell.fg_color),
+            bg: (cell.bg_color.a() != 0).then_some(cell.bg_color),
+            bold: cell.bold,
+            italic: cell.italic,
+            underline: cell.underline,
+            strikethrough: cell.strikethrough,
+        }
+    }
+
+    /// Inline CSS, empty for plain text
+    fn css(&self) -> String {
+        let mut css = String::new();
+        if let Some(fg) = self.fg {
+            let _ = write!(css, "color:{};", hex(fg));
+        }
+        if let Some(bg) = self.bg {
+            let _ = write!(css, "background-color:{};", hex(bg));
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        match (self.underline, self.strikethrough) {
+            (true, true) => css.push_str("text-decoration:underline line-through;"),
+            (true, false) => css.push_str("text-decoration:underline;"),
+            (false, true) => css.push_str("text-decoration:line-through;"),
+            (false, false) => {}
+        }
+        css
+    }
+
+    /// An SGR sequence switching to this style from any other
+    fn sgr(&self) -> String {
+        let mut sgr = String::from("\x1b[0");
+        for (on, code) in [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.strikethrough, "9"),
+        ] {
+            if on {
+                sgr.push(';');
+                sgr.push_str(code);
+            }
+        }
+        if let Some(fg) = self.fg {
+            let _ = write!(sgr, ";38;2;{};{};{}", fg.r(), fg.g(), fg.b());
+        }
+        if let Some(bg) = self.bg {
+            let _ = write!(sgr, ";48;2;{};{};{}", bg.r(), bg.g(), bg.b());
+        }
+        sgr.push('m');
+        sgr
+    }
+}
+
+fn hex(color: Rgba) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
+
+/// The cells of a line with their columns and styles: the tails of wide characters skipped,
+/// hidden characters blanked and trailing plain blanks cut
+fn styled_chars<'a>(
+    line: &'a [TerminalCell],
+    palette: &'a Palette,
+) -> impl Iterator<Item = (usize, char, Style)> + 'a {
+    let end = line
+        .iter()
+        .rposition(|cell| {
+            !cell.wide_tail
+                && ((cell.character != ' ' && !cell.hidden)
+                    || Style::of(cell, palette).bg.is_some())
+        })
+        .map_or(0, |last| last + 1);
+    line[..end]
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| !cell.wide_tail)
+        .map(|(col, cell)| {
+            let character = if cell.hidden { ' ' } else { cell.character };
+            (col, character, Style::of(cell, palette))
+        })
+}
+
+/// Cells of a line with the same looks, to be drawn from `col` on one character per cell
+pub struct Run {
+    pub col: usize,
+    /// Cells the run covers
+    pub width: usize,
+    pub text: String,
+    /// None for the palette's foreground
+    pub fg: Option<Rgba>,
+    /// None where the background shows through
+    pub bg: Option<Rgba>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+/// The runs of `line`, laid out as `to_html` does. A wide character is a run by itself, two
+/// cells wide, so the runs stay on the grid whatever the width of its glyph
+pub fn runs(line: &[TerminalCell], palette: &Palette) -> Vec<Run> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut next_col = 0;
+    for (col, character, style) in styled_chars(line, palette) {
+        let wide = line.get(col + 1).is_some_and(|next| next.wide_tail);
+        match runs.last_mut() {
+            Some(run) if !wide && col == next_col && Style::of_run(run) == style => {
+                run.text.push(character);
+                run.width += 1;
+            }
+            _ => runs.push(Run {
+                col,
+                width: if wide { 2 } else { 1 },
+                text: character.to_string(),
+                fg: style.fg,
+                bg: style.bg,
+                bold: style.bold,
+                italic: style.italic,
+                underline: style.underline,
+                strikethrough: style.strikethrough,
+            }),
+        }
+        // The cell after a wide character starts a run of its own
+        next_col = if wide { usize::MAX } else { col + 1 };
+    }
+    runs
+}
+
+/// `lines` as a `<pre>` block in the palette's colors, each run of cells with the same looks in
+/// a `<span>` with inline styles
+pub fn to_html(lines: &[&[TerminalCell]], palette: &Palette) -> String {
+    let mut html = format!(
+        "<pre style=\"color:{};background-color:{};\">",
+        hex(palette.foreground),
+        hex(palette.background)
+    );
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            html.push('\n');
+        }
+        let mut current = Style::PLAIN;
+        for (_, character, style) in styled_chars(line, palette) {
+            if style != current {
+                if current != Style::PLAIN {
+                    html.push_str("</span>");
+                }
+                if style != Style::PLAIN {
+                    let _ = write!(html, "<span style=\"{}\">", style.css());
+                }
+                current = style;
+            }
+            match character {
+                '<' => html.push_str("&lt;"),
+                '>' => html.push_str("&gt;"),
+                '&' => html.push_str("&amp;"),
+                '"' => html.push_str("&quot;"),
+                _ => html.push(character),
+            }
+        }
+        if current != Style::PLAIN {
+            html.push_str("</span>");
+        }
+    }
+    html.push_str("</pre>");
+    html
+}
+
+/// `lines` as text with SGR sequences for their colors and attributes, reset at the end of
+/// every line. Colors are written as 24-bit ones, as the cells have them
+pub fn to_ansi(lines: &[&[TerminalCell]], palette: &Palette) -> String {
+    let mut ansi = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            ansi.push('\n');
+        }
+        let mut current = Style::PLAIN;
+        for (_, character, style) in styled_chars(line, palette) {
+            if style != current {
+                ansi.push_str(&style.sgr());
+                current = style;
+            }
+            ansi.push(character);
+        }
+        if current != Style::PLAIN {
+            ansi.push_str("\x1b[0m");
+        }
+    }
+    ansi
+}
+
+/// `lines` as plain text, as `to_html` and `to_ansi` lay it out
+pub fn to_text(lines: &[&[TerminalCell]], palette: &Palette) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            styled_chars(line, palette)
+                .map(|(_, character, _)| character)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::Term;
+
+    /// Colors, attributes and characters HTML has to escape on the first line, a reversed
+    /// cell between wide characters on the second, hidden text on the third
+    const SCREEN: &[u8] =
+        b"a\x1b[31mr\x1b[42mg\x1b[0m \x1b[1mb\x1b[3mi\x1b[0;4mu\x1b[9ms\x1b[0m<&>\r\n\
+        \xe4\xb8\xad\x1b[7mx\x1b[0m\xe6\x96\x87!\r\n\
+        h\x1b[8mid\x1b[0mx\x1b[8mhid";
+
+    fn term() -> Term {
+        let mut term = Term::new(20, 3);
+        term.feed(SCREEN);
+        term
+    }
+
+    fn lines(term: &Term) -> Vec<&[TerminalCell]> {
+        term.screen_cells().iter().map(Vec::as_slice).collect()
+    }
+
+    /// Columns 1 to 5 of the first two lines, as a block selection takes them: the selection
+    /// starts on the tail of `中`
+    fn selection(term: &Term) -> Vec<&[TerminalCell]> {
+        lines(term)[..2].iter().map(|line| &line[1..6]).collect()
+    }
+
+    #[test]
+    fn html_has_a_span_per_style() {
+        let term = term();
+        assert_eq!(
+            to_html(&lines(&term), term.palette()),
+            "<pre style=\"color:#ffffff;background-color:#000000;\">\
+             a<span style=\"color:#ff0000;\">r</span>\
+             <span style=\"color:#ff0000;background-color:#00ff00;\">g</span> \
+             <span style=\"font-weight:bold;\">b</span>\
+             <span style=\"font-weight:bold;font-style:italic;\">i</span>\
+             <span style=\"text-decoration:underline;\">u</span>\
+             <span style=\"text-decoration:underline line-through;\">s</span>\
+             &lt;&amp;&gt;\n\
+             中<span style=\"color:#000000;background-color:#ffffff;\">x</span>文!\n\
+             h  x\
+             </pre>"
+        );
+    }
+
+    #[test]
+    fn ansi_switches_styles_with_full_sgr_sequences() {
+        let term = term();
+        assert_eq!(
+            to_ansi(&lines(&term), term.palette()),
+            "a\x1b[0;38;2;255;0;0mr\x1b[0;38;2;255;0;0;48;2;0;255;0mg\x1b[0m \
+             \x1b[0;1mb\x1b[0;1;3mi\x1b[0;4mu\x1b[0;4;9ms\x1b[0m<&>\n\
+             中\x1b[0;38;2;0;0;0;48;2;255;255;255mx\x1b[0m文!\n\
+             h  x"
+        );
+    }
+
+    #[test]
+    fn selections_drop_cut_wide_characters_and_reset_every_line() {
+        let term = term();
+        let selection = selection(&term);
+        assert_eq!(
+            to_html(&selection, term.palette()),
+            "<pre style=\"color:#ffffff;background-color:#000000;\">\
+             <span style=\"color:#ff0000;\">r</span>\
+             <span style=\"color:#ff0000;background-color:#00ff00;\">g</span> \
+             <span style=\"font-weight:bold;\">b</span>\
+             <span style=\"font-weight:bold;font-style:italic;\">i</span>\n\
+             <span style=\"color:#000000;background-color:#ffffff;\">x</span>文!\
+             </pre>"
+        );
+        assert_eq!(
+            to_ansi(&selection, term.palette()),
+            "\x1b[0;38;2;255;0;0mr\x1b[0;38;2;255;0;0;48;2;0;255;0mg\x1b[0m \
+             \x1b[0;1mb\x1b[0;1;3mi\x1b[0m\n\
+             \x1b[0;38;2;0;0;0;48;2;255;255;255mx\x1b[0m文!"
+        );
+        assert_eq!(to_text(&selection, term.palette()), "rg bi\nx文!");
+    }
+
+    #[test]
+    fn wide_characters_are_runs_of_two_cells() {
+        let term = term();
+        let runs: Vec<(usize, usize, String)> = runs(lines(&term)[1], term.palette())
+            .into_iter()
+            .map(|run| (run.col, run.width, run.text))
+            .collect();
+        assert_eq!(
+            runs,
+            [
+                (0, 2, "中".to_string()),
+                (2, 1, "x".to_string()),
+                (3, 2, "文".to_string()),
+                (5, 1, "!".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Images applications put on the screen with the kitty graphics protocol, kept with their
+//! placements until they scroll out of the history or are deleted
+
+use std::{collections::HashMap, sync::Arc};
+
+/// Pixels kept for images at most; the oldest ones go first when more arrive
+const MAX_IMAGE_BYTES: usize = 128 * 1024 * 1024;
+
+/// Size of a cell in pixels until the UI tells the real one
+const DEFAULT_CELL_PIXELS: (u32, u32) = (8, 16);
+
+/// A transmitted image, unpremultiplied RGBA row by row
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Arc<[u8]>,
+    /// Unique for every image stored, also when an id is reused, so the UI knows when to upload
+    /// it again
+    pub serial: u64,
+}
+
+/// An image shown on the screen, its top left corner at a cell
+#[derive(Clone, Debug)]
+pub struct Placement {
+    pub image_id: u32,
+    pub placement_id: u32,
+    /// Line of the top row, counted from the first line ever on the primary screen: the screen
+    /// row plus the lines scrolled into the history before
+    pub line: u64,
+    pub col: usize,
+    /// Cells covered
+    pub cols: usize,
+    pub rows: usize,
+    /// Stretched over the cells, given with `c` or `r`, instead of drawn at its size in pixels
+    pub stretch: bool,
+    /// Placed on the alternate screen, which has no history and is cleared on leaving
+    pub alternate: bool,
+}
+
+/// A transmission arriving in chunks (`m=1`): the control data of the first chunk and the
+/// payload so far
+pub struct PendingTransmission {
+    pub control: String,
+    pub payload: String,
+}
+
+pub struct Graphics {
+    pub images: HashMap<u32, Image>,
+    pub placements: Vec<Placement>,
+    /// Size of a cell in pixels, for images placed without a size in cells
+    pub cell_pixels: (u32, u32),
+    pub pending: Option<PendingTransmission>,
+    /// Id of the last image that was given one by the terminal (`I=` without `i=`)
+    last_assigned_id: u32,
+    serials: u64,
+}
+
+impl Default for Graphics {
+    fn default() -> Self {
+        Self {
+            images: HashMap::new(),
+            placements: Vec::new(),
+            cell_pixels: DEFAULT_CELL_PIXELS,
+            pending: None,
+            last_assigned_id: 0,
+            serials: 0,
+        }
+    }
+}
+
+impl Graphics {
+    /// Keep `pixels` as image `id`, replacing an image with the same id and its placements
+    pub fn store(&mut self, id: u32, width: u32, height: u32, pixels: Vec<u8>) {
+        self.placements.retain(|placement| placement.image_id != id);
+        self.serials += 1;
+        self.images.insert(
+            id,
+            Image {
+                width,
+                height,
+                pixels: pixels.into(),
+                serial: self.serials,
+            },
+        );
+        self.enforce_limit(id);
+    }
+
+    /// An id no image has, for an image the application numbered instead (`I=`)
+    pub fn assign_id(&mut self) -> u32 {
+        loop {
+            // Counting down from the highest ids, which applications are unlikely to pick
+            self.last_assigned_id = match self.last_assigned_id {
+                id if id > 1 << 24 => id - 1,
+                _ => u32::MAX,
+            };
+            if !self.images.contains_key(&self.last_assigned_id) {
+                return self.last_assigned_id;
+            }
+        }
+    }
+
+    /// The cells covered by `image` unless given, from the cell size in pixels
+    pub fn cells_for(
+        &self,
+        image: &Image,
+        cols: Option<usize>,
+        rows: Option<usize>,
+    ) -> (usize, usize) {
+        let (cell_width, cell_height) = self.cell_pixels;
+        let cols = cols
+            .filter(|&cols| cols > 0)
+            .unwrap_or_else(|| image.width.div_ceil(cell_width.max(1)) as usize);
+        let rows = rows
+            .filter(|&rows| rows > 0)
+            .unwrap_or_else(|| image.height.div_ceil(cell_height.max(1)) as usize);
+        (cols.max(1), rows.max(1))
+    }
+
+    /// Remove the placements matching `remove`, and with `free_images` the images left without
+    /// one
+    pub fn delete(&mut self, remove: impl Fn(&Placement) -> bool, free_images: bool) {
+        let mut removed_ids = Vec::new();
+        self.placements.retain(|placement| {
+            let keep = !remove(placement);
+            if !keep {
+                removed_ids.push(placement.image_id);
+            }
+            keep
+        });
+        if free_images {
+            for id in removed_ids {
+                if !self
+                    .placements
+                    .iter()
+                    .any(|placement| placement.image_id == id)
+                {
+                    self.images.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Drop everything, for a terminal reset
+    pub fn clear(&mut self) {
+        self.images.clear();
+        self.placements.clear();
+        self.pending = None;
+    }
+
+    /// Drop the placements above `first_line`, the first one left in the history, and those of
+    /// the alternate screen once it is left
+    pub fn collect_garbage(&mut self, first_line: u64, on_alternate_screen: bool) {
+        self.placements.retain(|placement| {
+            if placement.alternate {
+                on_alternate_screen
+            } else {
+                placement.line + placement.rows as u64 > first_line
+            }
+        });
+    }
+
+    /// Free the oldest images other than `keep` while over `MAX_IMAGE_BYTES`, those without
+    /// placements first
+    fn enforce_limit(&mut self, keep: u32) {
+        let mut total: usize = self.images.values().map(|image| image.pixels.len()).sum();
+        while total > MAX_IMAGE_BYTES {
+            let placed = |id: &u32| self.placements.iter().any(|p| p.image_id == *id);
+            let Some(oldest) = self
+                .images
+                .iter()
+                .filter(|(id, _)| **id != keep)
+                .min_by_key(|(id, image)| (placed(id), image.serial))
+                .map(|(id, _)| *id)
+            else {
+                break;
+            };
+            debug!("Freeing image {oldest} to stay under the image memory limit");
+            if let Some(image) = self.images.remove(&oldest) {
+                total -= image.pixels.len();
+            }
+            self.placements
+                .retain(|placement| placement.image_id != oldest);
+        }
+    }
+}
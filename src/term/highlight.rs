@@ -0,0 +1,247 @@
+//! The `[[highlight]]` rules applied to the output: a line is matched once it is complete,
+//! when the cursor has moved below it, and the spans found are kept with its line number
+//! until the line leaves the history. Rules with an `action` fire it in the same pass, for
+//! lines that are new output only
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+    time::Instant,
+};
+
+use regex::RegexSet;
+
+use crate::{
+    config::{HighlightRule, RuleAction},
+    term::terminal_cell::TerminalCell,
+};
+
+/// Bytes of a line the rules are matched against at most, to bound the cost of long lines
+const MAX_MATCHED_LEN: usize = 4096;
+
+/// Cells of a line matched by a rule
+#[derive(Debug, Clone)]
+pub struct HighlightSpan {
+    pub cols: Range<usize>,
+    /// Index of the rule in `Highlighter::rules`
+    pub rule: usize,
+}
+
+/// The action of a rule that fired, for the session to carry out
+#[derive(Debug, Clone)]
+pub struct RuleEvent {
+    pub action: RuleAction,
+    /// The rule's message with the captures filled in
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct Highlighter {
+    pub rules: Vec<HighlightRule>,
+    /// All the rules' expressions, to find the rules matching a line in one pass
+    set: Option<RegexSet>,
+    /// The spans of the lines that matched, by line counted like `Term::screen_line`
+    spans: BTreeMap<u64, Vec<HighlightSpan>>,
+    /// The first line that isn't matched yet
+    next_line: u64,
+    /// The first line never matched; lines before it that are matched again, after the
+    /// cursor went back up or the rules changed, don't fire actions
+    live_line: u64,
+    /// When each rule last fired its action
+    fired_at: Vec<Option<Instant>>,
+    /// Lines marked by rules with `action = "mark"`
+    marks: BTreeSet<u64>,
+    /// Actions fired since the session last took them
+    pub events: Vec<RuleEvent>,
+}
+
+impl Highlighter {
+    /// Use `rules` from now on; the lines still on the screen are matched again
+    pub fn set_rules(&mut self, rules: &[HighlightRule], screen_line: u64) {
+        if self.rules == rules {
+            return;
+        }
+        self.set = match RegexSet::new(rules.iter().map(|rule| rule.regex.as_str())) {
+            Ok(set) if !rules.is_empty() => Some(set),
+            Ok(_) => None,
+            Err(e) => {
+                // Each expression compiled by itself, so only the size limit is left
+                error!("Failed to compile the highlight rules: {e}");
+                None
+            }
+        };
+        self.rules = rules.to_vec();
+        self.fired_at = vec![None; rules.len()];
+        self.spans.clear();
+        self.next_line = self.next_line.min(screen_line);
+    }
+
+    /// Match the lines before `end`, the cursor's line, that weren't matched yet. `line`
+    /// gives the cells of a line, `None` for one that is gone
+    pub fn match_lines<'a>(&mut self, end: u64, line: impl Fn(u64) -> Option<&'a [TerminalCell]>) {
+        // The cursor went up, the lines from its one on are written again
+        if end < self.next_line {
+            self.spans.split_off(&end);
+            self.marks.split_off(&end);
+            self.next_line = end;
+            return;
+        }
+        if self.set.is_none() {
+            self.next_line = end;
+            self.live_line = self.live_line.max(end);
+            return;
+        }
+        for number in self.next_line..end {
+            let Some(cells) = line(number) else {
+                continue;
+            };
+            self.match_line(number, cells);
+        }
+        self.next_line = end;
+        self.live_line = self.live_line.max(end);
+    }
+
+    /// Keep the spans of the rules matching the text of `cells`, and fire the actions of the
+    /// rules when it is new output
+    fn match_line(&mut self, number: u64, cells: &[TerminalCell]) {
+        let Some(set) = &self.set else {
+            return;
+        };
+        // The text with the column of every character, from its byte offset
+        let mut text = String::new();
+        let mut cols = Vec::new();
+        for (col, cell) in cells.iter().enumerate() {
+            if cell.wide_tail {
+                continue;
+            }
+            if text.len() + cell.character.len_utf8() > MAX_MATCHED_LEN {
+                break;
+            }
+            cols.push((text.len(), col));
+            text.push(cell.character);
+        }
+        let text = text.trim_end();
+        let col_at = |offset: usize| match cols.binary_search_by_key(&offset, |&(start, _)| start) {
+            Ok(index) => cols[index].1,
+            Err(index) => cols.get(index).map_or(cells.len(), |&(_, col)| col),
+        };
+
+        let mut spans = Vec::new();
+        for index in set.matches(text).iter() {
+            let rule = &self.rules[index];
+            if rule.draws() {
+                for found in rule.regex.find_iter(text) {
+                    if found.is_empty() {
+                        continue;
+                    }
+                    spans.push(HighlightSpan {
+                        cols: col_at(found.start())..col_at(found.end()),
+                        rule: index,
+                    });
+                }
+            }
+
+            let Some(action) = rule.action else {
+                continue;
+            };
+            let cooling_down =
+                self.fired_at[index].is_some_and(|fired_at| fired_at.elapsed() < rule.cooldown);
+            if number < self.live_line || cooling_down {
+                continue;
+            }
+            self.fired_at[index] = Some(Instant::now());
+            match action {
+                RuleAction::Mark => {
+                    self.marks.insert(number);
+                }
+                RuleAction::Notify | RuleAction::Bell => {
+                    let mut message = String::new();
+                    if let Some(captures) = rule.regex.captures(text) {
+                        captures.expand(&rule.message, &mut message);
+                    }
+                    self.events.push(RuleEvent { action, message });
+                }
+            }
+        }
+        if !spans.is_empty() {
+            self.spans.insert(number, spans);
+        }
+    }
+
+    /// The spans of `line`, later ones drawn over earlier ones
+    pub fn spans(&self, line: u64) -> &[HighlightSpan] {
+        self.spans.get(&line).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_marked(&self, line: u64) -> bool {
+        self.marks.contains(&line)
+    }
+
+    /// Forget the lines before `first_line`, the first one left in the history
+    pub fn collect_garbage(&mut self, first_line: u64) {
+        self.spans = self.spans.split_off(&first_line);
+        self.marks = self.marks.split_off(&first_line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use regex::Regex;
+
+    use super::*;
+    use crate::term::Term;
+
+    /// A terminal notifying of every `BUILD <status>` line, with no cooldown
+    fn watching_builds() -> Term {
+        let mut term = Term::new(20, 5);
+        term.set_highlight_rules(&[HighlightRule {
+            regex: Regex::new(r"BUILD (\w+)").unwrap(),
+            fg: None,
+            bg: None,
+            bold: false,
+            underline: false,
+            action: Some(RuleAction::Notify),
+            message: "Build ${1}".to_string(),
+            cooldown: Duration::ZERO,
+        }]);
+        term
+    }
+
+    fn messages(term: &mut Term) -> Vec<String> {
+        term.take_rule_events()
+            .into_iter()
+            .map(|event| event.message)
+            .collect()
+    }
+
+    #[test]
+    fn a_line_fires_once_when_completed() {
+        let mut term = watching_builds();
+        term.feed(b"hello\r\nBUILD FAIL");
+        // Not complete until the cursor leaves it
+        assert!(messages(&mut term).is_empty());
+        term.feed(b"ED\r\n");
+        assert_eq!(messages(&mut term), ["Build FAILED"]);
+    }
+
+    #[test]
+    fn a_redrawn_region_fires_nothing_again() {
+        let mut term = watching_builds();
+        term.feed(b"hello\r\nBUILD FAILED\r\n");
+        assert_eq!(messages(&mut term).len(), 1);
+
+        // The program redraws the screen with the same text
+        term.feed(b"\x1b[H\x1b[2J");
+        term.feed(b"hello\r\nBUILD FAILED\r\n");
+        assert!(messages(&mut term).is_empty());
+        // Also when the cursor goes back up in the middle of the region
+        term.feed(b"\x1b[2;1HBUILD FAILED\r\n");
+        assert!(messages(&mut term).is_empty());
+
+        // A line that wasn't there before is new output
+        term.feed(b"\x1b[3;1HBUILD FAILED\r\n");
+        assert_eq!(messages(&mut term), ["Build FAILED"]);
+    }
+}
@@ -0,0 +1,357 @@
+use unicode_width::UnicodeWidthChar;
+
+use crate::term::parser::{
+    handler_context::HandlerContext,
+    handlers::{
+        csi_sequence_handler::CsiSequenceHandler, dcs_sequence_handler::DcsSequenceHandler,
+        esc_sequence_handler::EscSequenceHandler, kitty_graphics_handler::KittyGraphicsHandler,
+        osc_sequence_handler::OscSequenceHandler, sgr_sequence_handler::SgrSequenceHandler,
+        vt100_sequence_handler::VT100SequenceHandler,
+    },
+    sequence_handler::SequenceHandler,
+    sequence_token::SequenceToken,
+};
+
+/// Priority of the built-in handlers. Handlers with a higher priority are asked first
+pub const DEFAULT_PRIORITY: i32 = 0;
+
+struct Registration {
+    priority: i32,
+    handler: Box<dyn SequenceHandler>,
+}
+
+/// The handlers of one token class, highest priority first
+#[derive(Default)]
+struct Handlers(Vec<Registration>);
+
+impl Handlers {
+    /// Add `handler` after those with the same or a higher priority
+    fn register(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        let index = self
+            .0
+            .iter()
+            .position(|registration| registration.priority < priority)
+            .unwrap_or(self.0.len());
+        self.0.insert(
+            index,
+            Registration {
+                priority,
+                handler: Box::new(handler),
+            },
+        );
+    }
+
+    /// Ask the handlers in order until one takes the sequence. Returns the name of that one
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> Option<&'static str> {
+        self.0
+            .iter()
+            .find(|registration| registration.handler.handle(ctx, sequence))
+            .map(|registration| registration.handler.name())
+    }
+}
+
+pub struct SequenceDispatcher {
+    csi_handlers: Handlers,
+    osc_handlers: Handlers,
+    dcs_handlers: Handlers,
+    apc_handlers: Handlers,
+    esc_handlers: Handlers,
+    vt100_handlers: Handlers,
+    sgr_handlers: Handlers,
+}
+
+impl SequenceDispatcher {
+    /// A dispatcher with the built-in handlers registered at `DEFAULT_PRIORITY`
+    pub fn new() -> Self {
+        let mut dispatcher = Self {
+            csi_handlers: Handlers::default(),
+            osc_handlers: Handlers::default(),
+            dcs_handlers: Handlers::default(),
+            apc_handlers: Handlers::default(),
+            esc_handlers: Handlers::default(),
+            vt100_handlers: Handlers::default(),
+            sgr_handlers: Handlers::default(),
+        };
+        dispatcher.register_csi(CsiSequenceHandler, DEFAULT_PRIORITY);
+        dispatcher.register_osc(OscSequenceHandler, DEFAULT_PRIORITY);
+        dispatcher.register_dcs(DcsSequenceHandler, DEFAULT_PRIORITY);
+        dispatcher.register_apc(KittyGraphicsHandler, DEFAULT_PRIORITY);
+        dispatcher.register_esc(EscSequenceHandler, DEFAULT_PRIORITY);
+        dispatcher.register_vt100(VT100SequenceHandler, DEFAULT_PRIORITY);
+        dispatcher.register_sgr(SgrSequenceHandler, DEFAULT_PRIORITY);
+        dispatcher
+    }
+
+    pub fn register_csi(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.csi_handlers.register(handler, priority);
+    }
+
+    pub fn register_osc(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.osc_handlers.register(handler, priority);
+    }
+
+    pub fn register_dcs(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.dcs_handlers.register(handler, priority);
+    }
+
+    pub fn register_apc(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.apc_handlers.register(handler, priority);
+    }
+
+    pub fn register_esc(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.esc_handlers.register(handler, priority);
+    }
+
+    pub fn register_vt100(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.vt100_handlers.register(handler, priority);
+    }
+
+    pub fn register_sgr(&mut self, handler: impl SequenceHandler + 'static, priority: i32) {
+        self.sgr_handlers.register(handler, priority);
+    }
+
+    /// Apply `token`. Returns the name of the handler that took it, none if no handler did
+    pub fn dispatch(&self, ctx: &mut HandlerContext, token: SequenceToken) -> Option<&'static str> {
+        match token {
+            SequenceToken::Csi(seq) => self.csi_handlers.handle(ctx, &seq),
+            SequenceToken::Osc(seq) => self.osc_handlers.handle(ctx, &seq),
+            SequenceToken::Dcs(seq) => self.dcs_handlers.handle(ctx, &seq),
+            SequenceToken::Apc(seq) => self.apc_handlers.handle(ctx, &seq),
+            SequenceToken::Esc(first, second) => {
+                let seq: String = std::iter::once(first).chain(second).collect();
+                self.esc_handlers.handle(ctx, &seq)
+            }
+            SequenceToken::VT100(seq) => self.vt100_handlers.handle(ctx, &seq),
+            SequenceToken::Sgr(seq) => self.sgr_handlers.handle(ctx, &seq),
+            token @ (SequenceToken::Character(_)
+            | SequenceToken::Text(_)
+            | SequenceToken::ControlChar(_)) => {
+                Self::apply_text(ctx, token);
+                Some("SequenceDispatcher")
+            }
+        }
+    }
+
+    /// Apply text and control characters, which the dispatcher handles itself
+    fn apply_text(ctx: &mut HandlerContext, token: SequenceToken) {
+        match token {
+            SequenceToken::Character(ch) => Self::put_char(ctx, ch),
+            SequenceToken::Text(text) => {
+                if *ctx.insert_mode {
+                    for ch in text.chars() {
+                        Self::put_char(ctx, ch);
+                    }
+                } else {
+                    let auto_wrap = *ctx.decawm_mode;
+                    ctx.with_scrollback(|buffer, scrollback| {
+                        buffer.put_str(&text, auto_wrap, scrollback)
+                    });
+                }
+            }
+            SequenceToken::ControlChar(code) => match code {
+                b'\r' => ctx.buffer.carriage_return(),
+                b'\n' => {
+                    let lmn_mode = *ctx.new_line_mode;
+                    ctx.with_scrollback(|buffer, scrollback| buffer.new_line(lmn_mode, scrollback));
+                }
+                b'\t' => {
+                    if ctx.tab_insert_spaces {
+                        let count = ctx.buffer.next_tab_stop(1) - ctx.buffer.cursor_col();
+                        let auto_wrap = *ctx.decawm_mode;
+                        ctx.with_scrollback(|buffer, scrollback| {
+                            for _ in 0..count {
+                                buffer.put_char(' ', auto_wrap, scrollback);
+                            }
+                        });
+                    } else {
+                        ctx.buffer.tab_forward(1);
+                    }
+                }
+                b'\x08' => ctx.buffer.backspace(),
+                b'\x07' => *ctx.bell = true,
+                // ENQ: the configured answerback, if any
+                b'\x05' => ctx.reply.extend_from_slice(ctx.answerback.as_bytes()),
+                b'\x03' => {
+                    let (auto_wrap, lmn_mode) = (*ctx.decawm_mode, *ctx.new_line_mode);
+                    ctx.with_scrollback(|buffer, scrollback| {
+                        buffer.put_char('^', auto_wrap, scrollback);
+                        buffer.put_char('C', auto_wrap, scrollback);
+                        buffer.new_line(lmn_mode, scrollback);
+                    });
+                }
+                // Ignored on purpose: NUL is padding, SO and SI switch to character sets that
+                // aren't supported (yet), and the rest of C0 has no effect on a screen
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Put a character at the cursor, making room for it first in insert mode
+    fn put_char(ctx: &mut HandlerContext, ch: char) {
+        let auto_wrap = *ctx.decawm_mode;
+        if *ctx.insert_mode {
+            // IRM: shift the rest of the line right to make room for the character. A pending
+            // wrap is resolved first, `insert_chars` would pull the cursor back onto the line
+            let display_width = UnicodeWidthChar::width(ch).unwrap_or(1);
+            if display_width > 0 {
+                ctx.with_scrollback(|buffer, scrollback| {
+                    buffer.resolve_pending_wrap(display_width, auto_wrap, scrollback)
+                });
+                ctx.buffer.insert_chars(display_width);
+            }
+        }
+        ctx.with_scrollback(|buffer, scrollback| buffer.put_char(ch, auto_wrap, scrollback));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::term::Term;
+
+    /// The screen after feeding `input` to a terminal of `width`×3 cells
+    fn screen(width: usize, input: &str) -> String {
+        let mut term = Term::new(width, 3);
+        term.feed(input.as_bytes());
+        term.screen_text()
+    }
+
+    #[test]
+    fn insert_mode_shifts_the_rest_of_the_line() {
+        assert_eq!(screen(10, "abcdef\r\x1b[4hXY"), "XYabcdef\n\n");
+        // The last cell falls off the line
+        assert_eq!(screen(5, "abcde\r\x1b[4hX"), "Xabcd\n\n");
+        assert_eq!(screen(10, "abcdef\r\x1b[4h\x1b[4lXY"), "XYcdef\n\n");
+    }
+
+    #[test]
+    fn insert_at_the_end_of_a_full_line_wraps() {
+        assert_eq!(screen(5, "abcde\x1b[4hf"), "abcde\nf\n");
+        // Without auto wrap the character goes into the last column
+        assert_eq!(screen(5, "\x1b[?7labcde\x1b[4hf"), "abcdf\n\n");
+    }
+
+    #[test]
+    fn insert_mode_with_wide_characters() {
+        assert_eq!(screen(6, "abcd\r\x1b[4h日"), "日abcd\n\n");
+        // A wide character pushed halfway off the line is blanked
+        assert_eq!(screen(5, "abc日\r\x1b[4hX"), "Xabc\n\n");
+        assert_eq!(screen(5, "abcde\x1b[4h日"), "abcde\n日\n");
+    }
+
+    #[test]
+    fn each_class_reaches_its_handler() {
+        let mut term = Term::new(10, 3);
+        term.feed(b"\x1b[?2004h");
+        assert!(term.bracket_paste_mode);
+
+        term.feed(b"\x1b[31mx");
+        assert_eq!(term.screen_cells()[0][0].fg_color, term.palette().ansi[1]);
+
+        term.feed(b"\x1b[2J");
+        assert_eq!(term.screen_text(), "\n\n");
+        // ED doesn't move the cursor
+        assert_eq!((term.buffer.cursor_x, term.buffer.cursor_y), (1, 0));
+    }
+
+    /// A handler that notes down that it was asked, and takes the sequence if `takes`
+    struct Recorder {
+        name: &'static str,
+        takes: bool,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl SequenceHandler for Recorder {
+        fn handle(&self, _ctx: &mut HandlerContext, _sequence: &str) -> bool {
+            self.log.borrow_mut().push(self.name);
+            self.takes
+        }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+    }
+
+    /// Register a recorder on the OSC handlers of `term` for each of `(name, takes, priority)`,
+    /// then feed a title and return the recorders asked and the title set, if any
+    fn osc_order(recorders: &[(&'static str, bool, i32)]) -> (Vec<&'static str>, Option<String>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut term = Term::new(10, 3);
+        for &(name, takes, priority) in recorders {
+            let log = log.clone();
+            term.dispatcher
+                .register_osc(Recorder { name, takes, log }, priority);
+        }
+        term.feed(b"\x1b]0;title\x07");
+        let asked = log.borrow().clone();
+        (asked, term.take_title())
+    }
+
+    #[test]
+    fn higher_priorities_are_asked_first() {
+        let (asked, title) =
+            osc_order(&[("low", false, -1), ("high", false, 10), ("mid", false, 5)]);
+        // The built-in handler at the default priority comes between them and takes the title
+        assert_eq!(asked, ["high", "mid"]);
+        assert_eq!(title.as_deref(), Some("title"));
+    }
+
+    #[test]
+    fn declining_falls_through_to_the_built_in_handler() {
+        let (asked, title) = osc_order(&[("first", false, 1), ("second", false, 1)]);
+        assert_eq!(asked, ["first", "second"]);
+        assert_eq!(title.as_deref(), Some("title"));
+    }
+
+    #[test]
+    fn taking_stops_the_dispatch() {
+        let (asked, title) = osc_order(&[("taker", true, 2), ("never", false, 1)]);
+        assert_eq!(asked, ["taker"]);
+        assert_eq!(title, None);
+    }
+
+    #[test]
+    fn equal_priorities_keep_the_registration_order() {
+        let (asked, _) = osc_order(&[("a", false, 1), ("b", false, 1), ("c", true, 1)]);
+        assert_eq!(asked, ["a", "b", "c"]);
+        // Registered after the built-in handler at the same priority, so never asked
+        let (asked, title) = osc_order(&[("late", true, DEFAULT_PRIORITY)]);
+        assert!(asked.is_empty());
+        assert_eq!(title.as_deref(), Some("title"));
+    }
+
+    #[test]
+    fn only_the_c0_controls_that_move_the_cursor_move_it() {
+        type Expected = (u8, (usize, usize), &'static str);
+        let moving: [Expected; 6] = [
+            (0x03, (6, 2), "abcd\n    ^C\n"),
+            (0x08, (3, 1), "abcd\n\n"),
+            (0x09, (8, 1), "abcd\n\n"),
+            (0x0a, (4, 2), "abcd\n\n"),
+            (0x0d, (0, 1), "abcd\n\n"),
+            // ESC starts a sequence, tested with the tokenizer
+            (0x1b, (4, 1), "abcd\n\n"),
+        ];
+        for code in 0..0x20 {
+            let mut term = Term::new(10, 3);
+            // Without LNM, so LF keeps the column
+            term.feed(b"\x1b[20labcd\x1b[2;5H");
+            term.feed(&[code]);
+            let (_, position, screen) = moving
+                .iter()
+                .find(|(moving, _, _)| *moving == code)
+                .copied()
+                .unwrap_or((code, (4, 1), "abcd\n\n"));
+            let buffer = &term.buffer;
+            assert_eq!(
+                (buffer.cursor_x, buffer.cursor_y),
+                position,
+                "control {code:#04x}"
+            );
+            assert_eq!(term.screen_text(), screen, "control {code:#04x}");
+        }
+    }
+}
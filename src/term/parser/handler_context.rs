@@ -0,0 +1,103 @@
+use crate::term::{
+    CursorStyle, MouseTracking,
+    color::Palette,
+    graphics::Graphics,
+    shell_integration::ShellIntegration,
+    terminal_buffer::{DiscardScrollback, ScrollbackSink, TerminalBuffer},
+    terminal_cell::TerminalCell,
+};
+
+pub struct HandlerContext<'a> {
+    pub buffer: &'a mut TerminalBuffer,
+    pub scrollback_buffer: &'a mut Vec<Vec<TerminalCell>>,
+    pub saved_screen_buffer: &'a mut Option<TerminalBuffer>,
+    pub max_scroll_lines: &'a mut usize,
+
+    // DEC private mode flags
+    pub decckm_mode: &'a mut bool,
+    pub decom_mode: &'a mut bool,
+    pub decawm_mode: &'a mut bool,
+    pub reverse_video_mode: &'a mut bool,
+    pub show_cursor: &'a mut bool,
+    pub bracket_paste_mode: &'a mut bool,
+    pub new_line_mode: &'a mut bool,
+    pub mouse_tracking: &'a mut MouseTracking,
+    pub sgr_mouse_mode: &'a mut bool,
+
+    // ANSI mode flags
+    pub insert_mode: &'a mut bool,
+
+    // Other
+    /// Window title requested by the application (OSC 0), picked up by the UI
+    pub title: &'a mut Option<String>,
+    /// TAB writes spaces instead of moving the cursor
+    pub tab_insert_spaces: bool,
+    /// The application rang the bell (BEL)
+    pub bell: &'a mut bool,
+    /// Sent for ENQ
+    pub answerback: &'a str,
+    /// Answers to the application (e.g. DSR), written back to the PTY by the owner of the terminal
+    pub reply: &'a mut Vec<u8>,
+    /// Command marks of the shell (OSC 133)
+    pub shell_integration: &'a mut ShellIntegration,
+    /// The configured colors
+    pub palette: &'a Palette,
+    pub cursor_style: &'a mut CursorStyle,
+    pub cursor_blink: &'a mut bool,
+    /// The configured cursor style and blinking, for DECSCUSR 0
+    pub default_cursor: (CursorStyle, bool),
+    /// Images of the kitty graphics protocol
+    pub graphics: &'a mut Graphics,
+    /// Lines scrolled from the primary screen into the history so far, which numbers the lines
+    /// images and listings are anchored to
+    pub scrolled_lines: &'a mut u64,
+}
+
+/// Feeds lines scrolled off the screen into the scrollback buffer, limited to `max_lines`
+struct ScrollbackWriter<'a> {
+    lines: &'a mut Vec<Vec<TerminalCell>>,
+    max_lines: usize,
+    scrolled_lines: &'a mut u64,
+}
+
+impl ScrollbackSink for ScrollbackWriter<'_> {
+    fn push_line(&mut self, line: Vec<TerminalCell>) {
+        self.lines.push(line);
+        *self.scrolled_lines += 1;
+
+        // Limit the size of scrollback buffer
+        if self.lines.len() > self.max_lines {
+            let excess = self.lines.len() - self.max_lines;
+            self.lines.drain(..excess);
+        }
+    }
+}
+
+impl HandlerContext<'_> {
+    /// The line of the cursor counted from the first line ever on the primary screen, or its
+    /// row on the alternate screen
+    pub fn cursor_line(&self) -> u64 {
+        match self.saved_screen_buffer.is_some() {
+            true => self.buffer.cursor_y as u64,
+            false => *self.scrolled_lines + self.buffer.cursor_y as u64,
+        }
+    }
+
+    /// Run `f` on the buffer together with the sink every scrolling operation must use.
+    /// Only the primary screen keeps history; on the alternate screen scrolled lines are dropped.
+    pub fn with_scrollback<R>(
+        &mut self,
+        f: impl FnOnce(&mut TerminalBuffer, &mut dyn ScrollbackSink) -> R,
+    ) -> R {
+        if self.saved_screen_buffer.is_some() {
+            f(self.buffer, &mut DiscardScrollback)
+        } else {
+            let mut writer = ScrollbackWriter {
+                lines: self.scrollback_buffer,
+                max_lines: *self.max_scroll_lines,
+                scrolled_lines: self.scrolled_lines,
+            };
+            f(self.buffer, &mut writer)
+        }
+    }
+}
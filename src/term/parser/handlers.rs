@@ -1,5 +1,7 @@
 pub mod csi_sequence_handler;
 pub mod dcs_sequence_handler;
+pub mod esc_sequence_handler;
+pub mod kitty_graphics_handler;
 pub mod osc_sequence_handler;
 pub mod sgr_sequence_handler;
 pub mod vt100_sequence_handler;
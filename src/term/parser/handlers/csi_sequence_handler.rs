@@ -0,0 +1,325 @@
+use crate::term::{
+    CursorStyle,
+    parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler},
+};
+
+pub struct CsiSequenceHandler;
+
+impl SequenceHandler for CsiSequenceHandler {
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool {
+        match sequence {
+            // Cursor Control - Cursor Movement
+            ch if ch.ends_with('A') => {
+                // Cursor Up
+                let num = sequence.trim_end_matches('A').parse::<usize>().unwrap_or(1);
+                ctx.buffer.move_cursor(
+                    ctx.buffer.cursor_col(),
+                    ctx.buffer.cursor_y.saturating_sub(num),
+                );
+            }
+            ch if ch.ends_with('B') => {
+                // Cursor Down
+                let num = sequence.trim_end_matches('B').parse::<usize>().unwrap_or(1);
+                ctx.buffer.move_cursor(
+                    ctx.buffer.cursor_col(),
+                    ctx.buffer.cursor_y.saturating_add(num),
+                );
+            }
+            ch if ch.ends_with('C') => {
+                // Cursor Right
+                let num = sequence.trim_end_matches('C').parse::<usize>().unwrap_or(1);
+                ctx.buffer.move_cursor(
+                    ctx.buffer.cursor_col().saturating_add(num),
+                    ctx.buffer.cursor_y,
+                );
+            }
+            ch if ch.ends_with('D') => {
+                // Cursor Left
+                let num = sequence.trim_end_matches('D').parse::<usize>().unwrap_or(1);
+                ctx.buffer.move_cursor(
+                    ctx.buffer.cursor_col().saturating_sub(num),
+                    ctx.buffer.cursor_y,
+                );
+            }
+            ch if ch.ends_with('E') => {
+                // Cursor Next Line
+                let num = sequence.trim_end_matches('E').parse::<usize>().unwrap_or(1);
+                ctx.buffer
+                    .move_cursor(0, ctx.buffer.cursor_y.saturating_add(num));
+            }
+            ch if ch.ends_with('F') => {
+                // Cursor Previous Line
+                let num = sequence.trim_end_matches('F').parse::<usize>().unwrap_or(1);
+                ctx.buffer
+                    .move_cursor(0, ctx.buffer.cursor_y.saturating_sub(num));
+            }
+            ch if ch.ends_with('G') => {
+                // Cursor Horizontal Absolute
+                let num = sequence.trim_end_matches('G').parse::<usize>().unwrap_or(1);
+                ctx.buffer
+                    .move_cursor(num.saturating_sub(1), ctx.buffer.cursor_y);
+            }
+            ch if ch.ends_with('H') || ch.ends_with('f') => {
+                // Cursor Position (CSI H or CSI f)
+                let parts: Vec<&str> = sequence.trim_end_matches(['H', 'f']).split(';').collect();
+                let row = parts
+                    .first()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1);
+                let col = parts
+                    .get(1)
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1);
+                ctx.buffer
+                    .move_cursor(col.saturating_sub(1), row.saturating_sub(1));
+            }
+
+            // Cursor Control - History of Cursor Position
+            ch if ch.ends_with('s') => {
+                // Save Cursor Position
+                ctx.buffer.save_cursor();
+            }
+            ch if ch.ends_with('u') => {
+                // Restore Cursor Position
+                ctx.buffer.restore_cursor();
+            }
+
+            // Cursor Control - Report Cursor Position
+            ch if ch.ends_with("6n") => {
+                let x = ctx.buffer.cursor_col() + 1; // Convert to 1-based index, clamping the phantom column
+                let y = ctx.buffer.cursor_y + 1; // Convert to 1-based index
+                let response = format!("\x1b[{y};{x}R");
+
+                // Send the response back to the application
+                ctx.reply.extend_from_slice(response.as_bytes());
+            }
+            // Device Status Report - Operating status: always OK
+            "5n" => ctx.reply.extend_from_slice(b"\x1b[0n"),
+
+            // Primary Device Attributes: a VT220 with ANSI color
+            "c" | "0c" => ctx.reply.extend_from_slice(b"\x1b[?62;22c"),
+            // Secondary Device Attributes: terminal type, version and ROM cartridge
+            ">c" | ">0c" => ctx.reply.extend_from_slice(b"\x1b[>1;10;0c"),
+            // DECRQM for ANSI modes - Report whether a mode is set (1), reset (2) or not
+            // recognized (0)
+            ch if ch.ends_with("$p") => {
+                let mode = sequence.trim_end_matches("$p");
+                let state = match mode.parse::<u16>() {
+                    Ok(4) => Some(*ctx.insert_mode),
+                    Ok(20) => Some(*ctx.new_line_mode),
+                    _ => None,
+                };
+                let state = match state {
+                    Some(true) => 1,
+                    Some(false) => 2,
+                    None => 0,
+                };
+                ctx.reply
+                    .extend_from_slice(format!("\x1b[{mode};{state}$y").as_bytes());
+            }
+
+            // Erase in Display/Line - Erase in Display
+            ch if ch.ends_with('J') => {
+                let num = sequence.trim_end_matches('J').parse::<u16>().unwrap_or(0);
+                match num {
+                    0..=2 => ctx.buffer.erase_display(num),
+                    3 => {
+                        // Erase the scrollback buffer only
+                        ctx.scrollback_buffer.clear();
+                        // The view of a terminal scrolled back changes all the same
+                        ctx.buffer.touch_all();
+                    }
+                    _ => {
+                        warn!("Unsupported erase in display parameter: {num}");
+                    }
+                }
+            }
+
+            // Erase in Display/Line - Erase in Line
+            ch if ch.ends_with('K') => {
+                let num = sequence.trim_end_matches('K').parse::<u16>().unwrap_or(0);
+                match num {
+                    0..=2 => ctx.buffer.erase_line(num),
+                    _ => {
+                        warn!("Unsupported erase in line parameter: {num}");
+                    }
+                }
+            }
+
+            // Scroll Control - Scroll Up
+            ch if ch.ends_with('S') => {
+                let num = sequence.trim_end_matches('S').parse::<usize>().unwrap_or(1);
+                ctx.with_scrollback(|buffer, scrollback| buffer.scroll_up(num.max(1), scrollback));
+            }
+
+            // Scroll Control - Scroll Down
+            ch if ch.ends_with('T') => {
+                let num = sequence.trim_end_matches('T').parse::<usize>().unwrap_or(1);
+                ctx.buffer.scroll_down(num.max(1));
+            }
+
+            // Scroll Control - Set Scrolling Region (DECSTBM)
+            ch if ch.ends_with('r') => {
+                let parts: Vec<&str> = sequence.trim_end_matches('r').split(';').collect();
+                let top = parts
+                    .first()
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1);
+                let bottom = parts
+                    .get(1)
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(ctx.buffer.height);
+                ctx.buffer
+                    .set_scroll_region(top.saturating_sub(1), bottom.saturating_sub(1));
+                // DECSTBM moves the cursor to the home position
+                ctx.buffer.move_cursor(0, 0);
+            }
+
+            // Tab stops
+            ch if ch.ends_with('I') => {
+                // Cursor Horizontal Tabulation (CHT)
+                let num = sequence.trim_end_matches('I').parse::<usize>().unwrap_or(1);
+                ctx.buffer.tab_forward(num.max(1));
+            }
+            ch if ch.ends_with('Z') => {
+                // Cursor Backward Tabulation (CBT)
+                let num = sequence.trim_end_matches('Z').parse::<usize>().unwrap_or(1);
+                ctx.buffer.tab_backward(num.max(1));
+            }
+            ch if ch.ends_with('g') => {
+                // Tab Clear (TBC): 0 at the cursor, 3 all
+                match sequence.trim_end_matches('g') {
+                    "" | "0" => ctx.buffer.clear_tab_stops(false),
+                    "3" => ctx.buffer.clear_tab_stops(true),
+                    other => warn!("Unsupported tab clear: {other}"),
+                }
+            }
+
+            // Insert/delete lines/characters
+            ch if ch.ends_with('L') => {
+                // Insert lines
+                let num = sequence.trim_end_matches('L').parse::<usize>().unwrap_or(1);
+                ctx.buffer.insert_lines(num.max(1));
+            }
+            ch if ch.ends_with('M') => {
+                // Delete lines
+                let num = sequence.trim_end_matches('M').parse::<usize>().unwrap_or(1);
+                ctx.buffer.delete_lines(num.max(1));
+            }
+            ch if ch.ends_with('P') => {
+                // Delete characters
+                let num = sequence.trim_end_matches('P').parse::<usize>().unwrap_or(1);
+                ctx.buffer.delete_chars(num.max(1));
+            }
+            ch if ch.ends_with('X') => {
+                // Erase characters
+                let num = sequence.trim_end_matches('X').parse::<usize>().unwrap_or(1);
+                ctx.buffer.erase_chars(num.max(1));
+            }
+            ch if ch.ends_with('@') => {
+                // Insert characters
+                let num = sequence.trim_end_matches('@').parse::<usize>().unwrap_or(1);
+                ctx.buffer.insert_chars(num.max(1));
+            }
+
+            // Set Mode/Reset Mode (ANSI modes, DEC private modes are handled by the VT100 handler)
+            ch if ch.ends_with('h') || ch.ends_with('l') => {
+                let is_set = ch.ends_with('h');
+                for param in sequence.trim_end_matches(['h', 'l']).split(';') {
+                    match param.parse::<u16>() {
+                        Ok(4) => {
+                            // IRM - Insert/Replace Mode
+                            *ctx.insert_mode = is_set;
+                            debug!("Insert mode set to: {is_set}");
+                        }
+                        Ok(20) => {
+                            // LNM - New Line Mode
+                            *ctx.new_line_mode = is_set;
+                            debug!("New Line Mode set to: {is_set}");
+                        }
+                        _ => {
+                            warn!(
+                                "Unsupported ANSI mode parameter: {param}{}",
+                                if is_set { 'h' } else { 'l' }
+                            );
+                        }
+                    }
+                }
+            }
+
+            // CSI n d (Vertical Line Position Absolute - VPA)
+            ch if ch.ends_with('d') => {
+                let row = sequence.trim_end_matches('d').parse::<usize>().unwrap_or(1);
+                ctx.buffer
+                    .move_cursor(ctx.buffer.cursor_col(), row.saturating_sub(1));
+            }
+
+            // CSI Ps SP q (Set Cursor Style - DECSCUSR)
+            ch if ch.ends_with(" q") => {
+                let (style, blink) = match ch.trim_end_matches(" q") {
+                    "" | "0" => ctx.default_cursor,
+                    "1" => (CursorStyle::Block, true),
+                    "2" => (CursorStyle::Block, false),
+                    "3" => (CursorStyle::Underline, true),
+                    "4" => (CursorStyle::Underline, false),
+                    "5" => (CursorStyle::Bar, true),
+                    "6" => (CursorStyle::Bar, false),
+                    other => {
+                        warn!("Unsupported cursor style: {other}");
+                        return true;
+                    }
+                };
+                *ctx.cursor_style = style;
+                *ctx.cursor_blink = blink;
+            }
+
+            // Other CSI sequences
+            _ => {
+                warn!("Unhandled CSI sequence: {sequence}");
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::term::Term;
+
+    /// What a terminal of 20×5 cells answers to `input`
+    fn replies(input: &[u8]) -> String {
+        let mut term = Term::new(20, 5);
+        term.feed(input);
+        String::from_utf8(term.take_replies()).unwrap()
+    }
+
+    #[test]
+    fn status_reports() {
+        assert_eq!(replies(b"\x1b[6n"), "\x1b[1;1R");
+        assert_eq!(replies(b"\x1b[3;7H\x1b[6n"), "\x1b[3;7R");
+        // The phantom column after the last cell reports as the last cell
+        assert_eq!(
+            replies(b"\x1b[2;1H01234567890123456789\x1b[6n"),
+            "\x1b[2;20R"
+        );
+        assert_eq!(replies(b"\x1b[5n"), "\x1b[0n");
+    }
+
+    #[test]
+    fn device_attributes() {
+        assert_eq!(replies(b"\x1b[c"), "\x1b[?62;22c");
+        assert_eq!(replies(b"\x1b[0c"), "\x1b[?62;22c");
+        assert_eq!(replies(b"\x1b[>c"), "\x1b[>1;10;0c");
+        assert_eq!(replies(b"\x1b[>0c"), "\x1b[>1;10;0c");
+    }
+
+    #[test]
+    fn ansi_mode_reports() {
+        assert_eq!(replies(b"\x1b[4$p"), "\x1b[4;2$y");
+        assert_eq!(replies(b"\x1b[4h\x1b[4$p"), "\x1b[4;1$y");
+        assert_eq!(replies(b"\x1b[20$p"), "\x1b[20;1$y");
+        assert_eq!(replies(b"\x1b[20l\x1b[20$p"), "\x1b[20;2$y");
+        assert_eq!(replies(b"\x1b[12$p"), "\x1b[12;0$y");
+    }
+}
@@ -0,0 +1,14 @@
+use crate::term::parser::sequence_handler::SequenceHandler;
+
+pub struct DcsSequenceHandler;
+
+impl SequenceHandler for DcsSequenceHandler {
+    fn handle(
+        &self,
+        _ctx: &mut crate::term::parser::handler_context::HandlerContext,
+        sequence: &str,
+    ) -> bool {
+        debug!("Unhandled DCS sequence: {sequence:?}");
+        false
+    }
+}
@@ -0,0 +1,89 @@
+use crate::term::{
+    DEFAULT_NEW_LINE_MODE, MouseTracking,
+    parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler},
+    terminal_buffer::TerminalBuffer,
+    terminal_cell::TerminalCell,
+};
+
+/// Escape sequences without parameters: a final byte, optionally after an intermediate byte
+/// (`7`, `M`, `#8`, `(B`, ...)
+pub struct EscSequenceHandler;
+
+impl EscSequenceHandler {
+    /// RIS: everything back to the state of a new terminal, except the scrollback and the
+    /// configured tab width
+    fn full_reset(ctx: &mut HandlerContext) {
+        ctx.saved_screen_buffer.take();
+        ctx.graphics.clear();
+
+        let mut buffer = TerminalBuffer::new(ctx.buffer.width, ctx.buffer.height);
+        buffer.copy_tab_stops_from(ctx.buffer);
+        buffer.reset_tab_stops();
+        buffer.current_fg_color = ctx.palette.foreground;
+        *ctx.buffer = buffer;
+
+        *ctx.decckm_mode = false;
+        *ctx.decom_mode = false;
+        *ctx.decawm_mode = true;
+        *ctx.reverse_video_mode = false;
+        *ctx.show_cursor = true;
+        *ctx.bracket_paste_mode = false;
+        *ctx.new_line_mode = DEFAULT_NEW_LINE_MODE;
+        *ctx.mouse_tracking = MouseTracking::Off;
+        *ctx.sgr_mouse_mode = false;
+        *ctx.insert_mode = false;
+        (*ctx.cursor_style, *ctx.cursor_blink) = ctx.default_cursor;
+    }
+
+    /// DECALN: fill the screen with `E` for aligning the display
+    fn screen_alignment(ctx: &mut HandlerContext) {
+        let cell = TerminalCell {
+            character: 'E',
+            fg_color: ctx.palette.foreground,
+            ..Default::default()
+        };
+        for line in &mut ctx.buffer.cells {
+            line.fill(cell.clone());
+        }
+        ctx.buffer.touch_all();
+        let height = ctx.buffer.height;
+        ctx.buffer.set_scroll_region(0, height.saturating_sub(1));
+        ctx.buffer.move_cursor(0, 0);
+    }
+}
+
+impl SequenceHandler for EscSequenceHandler {
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool {
+        match sequence {
+            "7" => ctx.buffer.save_cursor(),    // DECSC
+            "8" => ctx.buffer.restore_cursor(), // DECRC
+            "D" => ctx.with_scrollback(|buffer, scrollback| buffer.index(scrollback)), // IND
+            "E" => {
+                // NEL
+                ctx.buffer.carriage_return();
+                ctx.with_scrollback(|buffer, scrollback| buffer.index(scrollback));
+            }
+            "M" => ctx.buffer.reverse_index(), // RI
+            "H" => ctx.buffer.set_tab_stop(),  // HTS
+            "c" => {
+                Self::full_reset(ctx);
+                debug!("Terminal reset (RIS)");
+            }
+            "#8" => Self::screen_alignment(ctx),
+            // DECKPAM / DECKPNM: egui doesn't tell keypad keys apart, so there is nothing to
+            // switch
+            "=" | ">" => {}
+            // ST closing a string sequence that was already taken as a whole
+            "\\" => {}
+            _ if sequence.starts_with(['(', ')', '*', '+', '-', '.', '/']) => {
+                // Character set designation; only UTF-8 is supported
+                debug!("Ignoring character set designation ESC {sequence}");
+            }
+            _ => {
+                debug!("Unhandled escape sequence: ESC {sequence}");
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,414 @@
+use std::io::Read;
+
+use base64::{
+    Engine,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+use resvg::tiny_skia;
+
+use crate::term::{
+    graphics::{PendingTransmission, Placement},
+    parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler},
+};
+
+/// Widest and tallest image accepted, in pixels
+const MAX_IMAGE_SIDE: u32 = 10_000;
+/// Longest payload collected from chunks, base64 encoded
+const MAX_PAYLOAD_LEN: usize = 64 * 1024 * 1024;
+/// Most bytes compressed data may inflate to: the pixels of the largest image accepted
+const MAX_INFLATED_LEN: u64 = MAX_IMAGE_SIDE as u64 * MAX_IMAGE_SIDE as u64 * 4;
+
+/// Base64 as kitty clients send it, with or without the padding
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// The control data of a graphics command, `a=T,f=100,i=1,...` before the payload
+struct Command {
+    /// `a`: t transmit, T transmit and display, p display, d delete, q query
+    action: char,
+    /// `f`: 24 RGB, 32 RGBA, 100 PNG
+    format: u32,
+    /// `t`: only d (direct, in the payload) is supported
+    medium: char,
+    /// `o`: z for zlib
+    compression: Option<char>,
+    /// `i`
+    id: u32,
+    /// `I`: a number the terminal answers with the id it chose
+    number: u32,
+    /// `p`
+    placement_id: u32,
+    /// `s` and `v`: the size of raw pixel data
+    width: u32,
+    height: u32,
+    /// `c` and `r`: the cells to cover
+    cols: Option<usize>,
+    rows: Option<usize>,
+    /// `m`: more chunks follow
+    more: bool,
+    /// `q`: 1 suppresses OK replies, 2 errors too
+    quiet: u32,
+    /// `d`: what to delete
+    delete: char,
+    /// `C`: 1 leaves the cursor where it is
+    keep_cursor: bool,
+}
+
+impl Command {
+    fn parse(control: &str) -> Self {
+        let mut command = Self {
+            action: 't',
+            format: 32,
+            medium: 'd',
+            compression: None,
+            id: 0,
+            number: 0,
+            placement_id: 0,
+            width: 0,
+            height: 0,
+            cols: None,
+            rows: None,
+            more: false,
+            quiet: 0,
+            delete: 'a',
+            keep_cursor: false,
+        };
+        for (key, value) in control.split(',').filter_map(|pair| pair.split_once('=')) {
+            let letter = value.chars().next().unwrap_or_default();
+            let number = value.parse::<u32>().unwrap_or(0);
+            match key {
+                "a" => command.action = letter,
+                "f" => command.format = number,
+                "t" => command.medium = letter,
+                "o" => command.compression = Some(letter),
+                "i" => command.id = number,
+                "I" => command.number = number,
+                "p" => command.placement_id = number,
+                "s" => command.width = number,
+                "v" => command.height = number,
+                "c" => command.cols = Some(number as usize),
+                "r" => command.rows = Some(number as usize),
+                "m" => command.more = number == 1,
+                "q" => command.quiet = number,
+                "d" => command.delete = letter,
+                "C" => command.keep_cursor = number == 1,
+                _ => debug!("Ignoring kitty graphics key {key}={value}"),
+            }
+        }
+        command
+    }
+}
+
+/// The kitty graphics protocol (`ESC _ G ... ST`): images transmitted directly as PNG or raw
+/// pixels, in one piece or in chunks, displayed at the cursor and deleted again
+pub struct KittyGraphicsHandler;
+
+impl KittyGraphicsHandler {
+    fn run(ctx: &mut HandlerContext, command: &Command, payload: &str) {
+        let result = match command.action {
+            't' | 'T' | 'q' => Self::transmit(ctx, command, payload),
+            'p' => Self::place(ctx, command.id, command),
+            'd' => {
+                Self::delete(ctx, command);
+                return;
+            }
+            other => Err(format!("EINVAL:unsupported action {other}")),
+        };
+        if let Err(e) = &result {
+            debug!("Kitty graphics command failed: {e}");
+        }
+        Self::reply(ctx, command, result);
+    }
+
+    /// Decode and keep the image, and with `a=T` display it. A query only checks it can be
+    /// decoded. Returns the id of the image
+    fn transmit(ctx: &mut HandlerContext, command: &Command, payload: &str) -> Result<u32, String> {
+        let (width, height, pixels) = Self::decode(command, payload)?;
+        if command.action == 'q' {
+            return Ok(command.id);
+        }
+
+        let id = match command.id {
+            0 => ctx.graphics.assign_id(),
+            id => id,
+        };
+        ctx.graphics.store(id, width, height, pixels);
+        if command.action == 'T' {
+            Self::place(ctx, id, command)?;
+        }
+        Ok(id)
+    }
+
+    fn decode(command: &Command, payload: &str) -> Result<(u32, u32, Vec<u8>), String> {
+        if command.medium != 'd' {
+            return Err(format!(
+                "EINVAL:unsupported transmission medium {}",
+                command.medium
+            ));
+        }
+        let mut data = BASE64
+            .decode(payload.trim())
+            .map_err(|e| format!("EINVAL:malformed base64: {e}"))?;
+        match command.compression {
+            None => {}
+            Some('z') => {
+                let mut inflated = Vec::new();
+                flate2::read::ZlibDecoder::new(data.as_slice())
+                    .take(MAX_INFLATED_LEN + 1)
+                    .read_to_end(&mut inflated)
+                    .map_err(|e| format!("EINVAL:malformed zlib data: {e}"))?;
+                if inflated.len() as u64 > MAX_INFLATED_LEN {
+                    return Err(format!(
+                        "EINVAL:data inflates to more than {MAX_INFLATED_LEN} bytes"
+                    ));
+                }
+                data = inflated;
+            }
+            Some(other) => return Err(format!("EINVAL:unsupported compression {other}")),
+        }
+
+        let (width, height, pixels) = match command.format {
+            100 => {
+                // The pixmap is allocated at the size the header claims, so check it first
+                let (width, height) =
+                    png_size(&data).ok_or_else(|| "EBADPNG:no PNG header".to_string())?;
+                check_size(width, height)?;
+                let pixmap =
+                    tiny_skia::Pixmap::decode_png(&data).map_err(|e| format!("EBADPNG:{e}"))?;
+                let mut pixels = Vec::with_capacity(pixmap.data().len());
+                for pixel in pixmap.pixels() {
+                    let pixel = pixel.demultiply();
+                    pixels.extend_from_slice(&[
+                        pixel.red(),
+                        pixel.green(),
+                        pixel.blue(),
+                        pixel.alpha(),
+                    ]);
+                }
+                (pixmap.width(), pixmap.height(), pixels)
+            }
+            format @ (24 | 32) => {
+                let channels = format as usize / 8;
+                let expected = command.width as usize * command.height as usize * channels;
+                if expected == 0 || data.len() < expected {
+                    return Err(format!(
+                        "ENODATA:{} bytes for {}x{} pixels",
+                        data.len(),
+                        command.width,
+                        command.height
+                    ));
+                }
+                let pixels = match channels {
+                    3 => data[..expected]
+                        .chunks_exact(3)
+                        .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], u8::MAX])
+                        .collect(),
+                    _ => data[..expected].to_vec(),
+                };
+                (command.width, command.height, pixels)
+            }
+            other => return Err(format!("EINVAL:unsupported format {other}")),
+        };
+        check_size(width, height)?;
+        Ok((width, height, pixels))
+    }
+
+    /// Show image `id` with its top left corner at the cursor, which then moves past it
+    fn place(ctx: &mut HandlerContext, id: u32, command: &Command) -> Result<u32, String> {
+        let image = ctx
+            .graphics
+            .images
+            .get(&id)
+            .ok_or_else(|| format!("ENOENT:no image with id {id}"))?;
+        let (cols, rows) = ctx.graphics.cells_for(image, command.cols, command.rows);
+
+        let alternate = ctx.saved_screen_buffer.is_some();
+        let (col, line) = (ctx.buffer.cursor_x, ctx.cursor_line());
+        if command.placement_id != 0 {
+            ctx.graphics.placements.retain(|placement| {
+                placement.image_id != id || placement.placement_id != command.placement_id
+            });
+        }
+        ctx.graphics.placements.push(Placement {
+            image_id: id,
+            placement_id: command.placement_id,
+            line,
+            col,
+            cols,
+            rows,
+            stretch: command.cols.is_some() || command.rows.is_some(),
+            alternate,
+        });
+
+        if !command.keep_cursor {
+            // To the cell after the image on its last row, scrolling as needed
+            for _ in 1..rows {
+                ctx.with_scrollback(|buffer, scrollback| buffer.index(scrollback));
+            }
+            ctx.buffer.cursor_x = (col + cols).min(ctx.buffer.width.saturating_sub(1));
+        }
+        Ok(id)
+    }
+
+    fn delete(ctx: &mut HandlerContext, command: &Command) {
+        let free_images = command.delete.is_ascii_uppercase();
+        match command.delete.to_ascii_lowercase() {
+            'a' => ctx.graphics.delete(|_| true, free_images),
+            'i' => ctx.graphics.delete(
+                |placement| {
+                    placement.image_id == command.id
+                        && (command.placement_id == 0
+                            || placement.placement_id == command.placement_id)
+                },
+                free_images,
+            ),
+            'c' => {
+                let (col, line) = (ctx.buffer.cursor_x, ctx.cursor_line());
+                ctx.graphics.delete(
+                    |placement| {
+                        (placement.col..placement.col + placement.cols).contains(&col)
+                            && (placement.line..placement.line + placement.rows as u64)
+                                .contains(&line)
+                    },
+                    free_images,
+                );
+            }
+            other => debug!("Unsupported kitty graphics deletion d={other}"),
+        }
+    }
+
+    /// Answer a command that named its image, unless it asked to stay quiet
+    fn reply(ctx: &mut HandlerContext, command: &Command, result: Result<u32, String>) {
+        if command.id == 0 && command.number == 0 {
+            return;
+        }
+        let (id, message) = match result {
+            Ok(_) if command.quiet >= 1 => return,
+            Err(_) if command.quiet >= 2 => return,
+            Ok(id) => (id, "OK".to_string()),
+            Err(message) => (command.id, message),
+        };
+        let mut keys = format!("i={id}");
+        if command.number != 0 {
+            keys.push_str(&format!(",I={}", command.number));
+        }
+        if command.placement_id != 0 {
+            keys.push_str(&format!(",p={}", command.placement_id));
+        }
+        ctx.reply
+            .extend_from_slice(format!("\x1b_G{keys};{message}\x1b\\").as_bytes());
+    }
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), String> {
+    if width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE {
+        return Err(format!(
+            "EINVAL:image of {width}x{height} pixels is too large"
+        ));
+    }
+    Ok(())
+}
+
+/// The width and height in the IHDR chunk of a PNG, which comes right after the signature
+fn png_size(data: &[u8]) -> Option<(u32, u32)> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+impl SequenceHandler for KittyGraphicsHandler {
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool {
+        let Some(command) = sequence.strip_prefix('G') else {
+            debug!("Unhandled APC sequence: {sequence:?}");
+            return false;
+        };
+        let (control, payload) = command.split_once(';').unwrap_or((command, ""));
+
+        // The chunks after the first carry only `m` (and `q`), the rest is in the first
+        if let Some(pending) = &mut ctx.graphics.pending {
+            pending.payload.push_str(payload);
+            if pending.payload.len() > MAX_PAYLOAD_LEN {
+                warn!("Dropping a kitty graphics transmission larger than {MAX_PAYLOAD_LEN} bytes");
+                ctx.graphics.pending = None;
+                return true;
+            }
+            if Command::parse(control).more {
+                return true;
+            }
+            let pending = ctx.graphics.pending.take().expect("checked above");
+            Self::run(ctx, &Command::parse(&pending.control), &pending.payload);
+            return true;
+        }
+
+        let parsed = Command::parse(control);
+        if parsed.more {
+            ctx.graphics.pending = Some(PendingTransmission {
+                control: control.to_string(),
+                payload: payload.to_string(),
+            });
+        } else {
+            Self::run(ctx, &parsed, payload);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::Term;
+
+    /// A PNG of `width`×`height` red pixels
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut pixmap = tiny_skia::Pixmap::new(width, height).unwrap();
+        pixmap.fill(tiny_skia::Color::from_rgba8(255, 0, 0, 255));
+        pixmap.encode_png().unwrap()
+    }
+
+    #[test]
+    fn png_in_two_chunks_is_stored_placed_and_acknowledged() {
+        let mut term = Term::new(80, 24);
+        let payload = BASE64.encode(png(2, 1));
+        let (first, second) = payload.split_at(payload.len() / 2);
+        term.feed(format!("\x1b_Ga=T,f=100,i=1,m=1;{first}\x1b\\").as_bytes());
+        assert!(term.take_replies().is_empty());
+        term.feed(format!("\x1b_Gm=0;{second}\x1b\\").as_bytes());
+
+        assert_eq!(term.take_replies(), b"\x1b_Gi=1;OK\x1b\\");
+        let image = &term.graphics.images[&1];
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(&image.pixels[..4], &[255, 0, 0, 255]);
+        let [placement] = term.graphics.placements.as_slice() else {
+            panic!("expected one placement");
+        };
+        assert_eq!(
+            (placement.image_id, placement.line, placement.col),
+            (1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn png_header_claiming_a_huge_size_is_rejected_before_decoding() {
+        let mut data = png(1, 1);
+        data[16..20].copy_from_slice(&100_000u32.to_be_bytes());
+        data[20..24].copy_from_slice(&100_000u32.to_be_bytes());
+        let mut term = Term::new(80, 24);
+        term.feed(format!("\x1b_Ga=t,f=100,i=2;{}\x1b\\", BASE64.encode(data)).as_bytes());
+
+        let reply = String::from_utf8(term.take_replies()).unwrap();
+        assert!(reply.starts_with("\x1b_Gi=2;EINVAL:"), "{reply:?}");
+        assert!(term.graphics.images.is_empty());
+    }
+
+    #[test]
+    fn png_size_reads_the_header() {
+        assert_eq!(png_size(&png(3, 5)), Some((3, 5)));
+        assert_eq!(png_size(b"not a png"), None);
+    }
+}
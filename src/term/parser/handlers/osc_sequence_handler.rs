@@ -0,0 +1,163 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use crate::term::{
+    parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler},
+    shell_integration::CommandEvent,
+};
+
+pub struct OscSequenceHandler;
+
+impl SequenceHandler for OscSequenceHandler {
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool {
+        // `Ps ; Pt`. The payload may contain `;` itself, and some programs leave out the `;`
+        // when there is no payload
+        let sequence = sequence.trim_end_matches('\x07');
+        let (code, payload) = sequence.split_once(';').unwrap_or((sequence, ""));
+
+        match code.trim().parse::<u16>() {
+            Ok(0 | 2) => {
+                // Set title (OSC 0/2), an empty one resets it.
+                // The UI applies the title to the window
+                *ctx.title = Some(payload.to_string());
+            }
+            Ok(code @ (10 | 11)) if payload == "?" => {
+                // Query of the default foreground (OSC 10) or background (OSC 11)
+                let color = if code == 10 {
+                    ctx.palette.foreground
+                } else {
+                    ctx.palette.background
+                };
+                let [r, g, b, _] = color.0;
+                ctx.reply.extend_from_slice(
+                    format!("\x1b]{code};rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}\x1b\\")
+                        .as_bytes(),
+                );
+            }
+            Ok(133) => {
+                // Semantic prompt marks (OSC 133)
+                Self::handle_command_mark(ctx, payload);
+            }
+            _ => {
+                Self::report_unhandled(code, sequence);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+const MAX_REPORTED_CODES: usize = 64;
+
+/// OSC codes already reported as unhandled
+static REPORTED_CODES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+impl OscSequenceHandler {
+    /// Log an unhandled code the first time it comes, programs tend to repeat them a lot
+    fn report_unhandled(code: &str, sequence: &str) {
+        let Ok(mut reported) = REPORTED_CODES.lock() else {
+            return;
+        };
+        // Garbage codes could fill the set forever
+        if reported.len() >= MAX_REPORTED_CODES {
+            return;
+        }
+        if reported.insert(code.to_string()) {
+            debug!("Unhandled OSC sequence (further OSC {code} are not logged): {sequence:?}");
+        }
+    }
+
+    fn handle_command_mark(ctx: &mut HandlerContext, mark: &str) {
+        let mut params = mark.split(';');
+        match params.next() {
+            Some("A") => {
+                ctx.shell_integration.input_start = None;
+            }
+            Some("B") => {
+                ctx.shell_integration.input_start =
+                    Some((ctx.buffer.cursor_col(), ctx.buffer.cursor_y));
+            }
+            Some("C") => {
+                // The command line is what was typed after the prompt, read back from the screen
+                let command = ctx
+                    .shell_integration
+                    .command_line(ctx.buffer)
+                    .unwrap_or_default();
+                ctx.shell_integration.input_start = None;
+                // The output starts on the line after the command line
+                let line = ctx.cursor_line() + u64::from(ctx.buffer.cursor_x > 0);
+                if ctx.saved_screen_buffer.is_none() {
+                    ctx.shell_integration.command_started(&command, line);
+                }
+                ctx.shell_integration
+                    .events
+                    .push(CommandEvent::Started { command });
+            }
+            Some("D") => {
+                let exit_code = params.next().and_then(|code| code.parse().ok());
+                // The output ends with the line of the cursor if something was written on it
+                let line = ctx.cursor_line() + u64::from(ctx.buffer.cursor_x > 0);
+                ctx.shell_integration.command_finished(line);
+                ctx.shell_integration
+                    .events
+                    .push(CommandEvent::Finished { exit_code });
+            }
+            _ => {
+                warn!("Unhandled OSC 133 mark: {mark}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::term::Term;
+
+    fn title(input: &[u8]) -> Option<String> {
+        let mut term = Term::new(20, 3);
+        term.feed(input);
+        term.take_title()
+    }
+
+    #[test]
+    fn titles_keep_their_semicolons() {
+        assert_eq!(title(b"\x1b]0;a;b;c\x07").as_deref(), Some("a;b;c"));
+        assert_eq!(title(b"\x1b]2;x;y\x1b\\").as_deref(), Some("x;y"));
+        assert_eq!(title(b"\x1b]2;;\x07").as_deref(), Some(";"));
+    }
+
+    #[test]
+    fn titles_without_a_payload_reset() {
+        assert_eq!(title(b"\x1b]2\x07").as_deref(), Some(""));
+        assert_eq!(title(b"\x1b]0;\x1b\\").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn other_codes_are_not_titles() {
+        assert_eq!(title(b"\x1b]x;title\x07"), None);
+        assert_eq!(title(b"\x1b];title\x07"), None);
+        assert_eq!(title(b"\x1b]02x;title\x07"), None);
+        assert_eq!(title(b"\x1b]1;icon\x07"), None);
+    }
+
+    #[test]
+    fn color_queries_reply_with_the_palette() {
+        let mut term = Term::new(20, 3);
+        let [r, g, b, _] = term.palette().foreground.0;
+        term.feed(b"\x1b]10;?\x07");
+        assert_eq!(
+            String::from_utf8(term.take_replies()).unwrap(),
+            format!("\x1b]10;rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}\x1b\\")
+        );
+
+        let [r, g, b, _] = term.palette().background.0;
+        term.feed(b"\x1b]11;?\x1b\\");
+        assert_eq!(
+            String::from_utf8(term.take_replies()).unwrap(),
+            format!("\x1b]11;rgb:{r:02x}{r:02x}/{g:02x}{g:02x}/{b:02x}{b:02x}\x1b\\")
+        );
+
+        // Setting the colors isn't supported and doesn't answer
+        term.feed(b"\x1b]10;#ffffff\x07");
+        assert!(term.take_replies().is_empty());
+    }
+}
@@ -1,14 +1,14 @@
-use eframe::egui::Color32;
-
-use crate::parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler};
-use crate::terminal_widget::color;
+use crate::term::{
+    color::Rgba,
+    parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler},
+};
 
 pub struct SgrSequenceHandler;
 
 impl SgrSequenceHandler {
     fn reset_sgr(ctx: &mut HandlerContext) {
-        ctx.buffer.current_fg_color = Color32::WHITE;
-        ctx.buffer.current_bg_color = Color32::TRANSPARENT;
+        ctx.buffer.current_fg_color = ctx.palette.foreground;
+        ctx.buffer.current_bg_color = Rgba::TRANSPARENT;
         ctx.buffer.current_bold = false;
         ctx.buffer.current_underline = false;
         ctx.buffer.current_italic = false;
@@ -16,14 +16,30 @@ impl SgrSequenceHandler {
         ctx.buffer.current_strikethrough = false;
         ctx.buffer.current_hidden = false;
     }
+
+    /// Swap foreground and background. The default background is transparent; as the color of
+    /// text it becomes the configured background
+    fn swap_colors(ctx: &mut HandlerContext) {
+        if ctx.buffer.current_bg_color == Rgba::TRANSPARENT {
+            ctx.buffer.current_bg_color = ctx.palette.background;
+        }
+        std::mem::swap(
+            &mut ctx.buffer.current_fg_color,
+            &mut ctx.buffer.current_bg_color,
+        );
+    }
 }
 
 impl SequenceHandler for SgrSequenceHandler {
-    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) {
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool {
+        // The tokenizer hands over the final byte too. It goes once here, not per parameter,
+        // so that the last parameter of `38;5;n` and `38;2;r;g;b` parses
+        let sequence = sequence.strip_suffix('m').unwrap_or(sequence);
+
         // Special case: ESC[m or ESC[0m
         if sequence.is_empty() || sequence == "0" {
             Self::reset_sgr(ctx);
-            return;
+            return true;
         }
 
         // We need to pre-scan for extended color specifications (38/48 with 2 or 5)
@@ -36,7 +52,7 @@ impl SequenceHandler for SgrSequenceHandler {
                 continue;
             }
 
-            match token.trim_end_matches('m') {
+            match token {
                 // Reset
                 "0" | "" => Self::reset_sgr(ctx),
                 // Bold
@@ -44,7 +60,7 @@ impl SequenceHandler for SgrSequenceHandler {
                 // Faint (simulate by darkening fg)
                 "2" => {
                     let c = ctx.buffer.current_fg_color;
-                    ctx.buffer.current_fg_color = Color32::from_rgb(
+                    ctx.buffer.current_fg_color = Rgba::from_rgb(
                         (c.r() as u16 * 4 / 5) as u8,
                         (c.g() as u16 * 4 / 5) as u8,
                         (c.b() as u16 * 4 / 5) as u8,
@@ -59,12 +75,7 @@ impl SequenceHandler for SgrSequenceHandler {
                 // Rapid Blink (treated same as regular blink)
                 "6" => ctx.buffer.current_blink = true,
                 // Reverse video
-                "7" => {
-                    std::mem::swap(
-                        &mut ctx.buffer.current_fg_color,
-                        &mut ctx.buffer.current_bg_color,
-                    );
-                }
+                "7" => Self::swap_colors(ctx),
                 // Conceal / Hidden (proper flag-based implementation)
                 "8" => {
                     ctx.buffer.current_hidden = true;
@@ -93,10 +104,7 @@ impl SequenceHandler for SgrSequenceHandler {
                     // the original colors without maintaining a color state stack.
                     // This is a known limitation mentioned in the issue.
                     // For now, we swap again to reverse the effect (may not be perfectly accurate)
-                    std::mem::swap(
-                        &mut ctx.buffer.current_fg_color,
-                        &mut ctx.buffer.current_bg_color,
-                    );
+                    Self::swap_colors(ctx);
                 }
                 // Reveal (disable hidden)
                 "28" => ctx.buffer.current_hidden = false,
@@ -104,52 +112,52 @@ impl SequenceHandler for SgrSequenceHandler {
                 "29" => ctx.buffer.current_strikethrough = false,
 
                 // Foreground basic colors 30-37
-                "30" => ctx.buffer.current_fg_color = Color32::BLACK,
-                "31" => ctx.buffer.current_fg_color = Color32::RED,
-                "32" => ctx.buffer.current_fg_color = Color32::GREEN,
-                "33" => ctx.buffer.current_fg_color = Color32::YELLOW,
-                "34" => ctx.buffer.current_fg_color = Color32::BLUE,
-                "35" => ctx.buffer.current_fg_color = Color32::MAGENTA,
-                "36" => ctx.buffer.current_fg_color = Color32::CYAN,
-                "37" => ctx.buffer.current_fg_color = Color32::WHITE,
+                "30" => ctx.buffer.current_fg_color = ctx.palette.ansi[0],
+                "31" => ctx.buffer.current_fg_color = ctx.palette.ansi[1],
+                "32" => ctx.buffer.current_fg_color = ctx.palette.ansi[2],
+                "33" => ctx.buffer.current_fg_color = ctx.palette.ansi[3],
+                "34" => ctx.buffer.current_fg_color = ctx.palette.ansi[4],
+                "35" => ctx.buffer.current_fg_color = ctx.palette.ansi[5],
+                "36" => ctx.buffer.current_fg_color = ctx.palette.ansi[6],
+                "37" => ctx.buffer.current_fg_color = ctx.palette.ansi[7],
                 // Default foreground
-                "39" => ctx.buffer.current_fg_color = Color32::WHITE,
+                "39" => ctx.buffer.current_fg_color = ctx.palette.foreground,
                 // Background basic colors 40-47
-                "40" => ctx.buffer.current_bg_color = Color32::BLACK,
-                "41" => ctx.buffer.current_bg_color = Color32::RED,
-                "42" => ctx.buffer.current_bg_color = Color32::GREEN,
-                "43" => ctx.buffer.current_bg_color = Color32::YELLOW,
-                "44" => ctx.buffer.current_bg_color = Color32::BLUE,
-                "45" => ctx.buffer.current_bg_color = Color32::MAGENTA,
-                "46" => ctx.buffer.current_bg_color = Color32::CYAN,
-                "47" => ctx.buffer.current_bg_color = Color32::WHITE,
+                "40" => ctx.buffer.current_bg_color = ctx.palette.ansi[0],
+                "41" => ctx.buffer.current_bg_color = ctx.palette.ansi[1],
+                "42" => ctx.buffer.current_bg_color = ctx.palette.ansi[2],
+                "43" => ctx.buffer.current_bg_color = ctx.palette.ansi[3],
+                "44" => ctx.buffer.current_bg_color = ctx.palette.ansi[4],
+                "45" => ctx.buffer.current_bg_color = ctx.palette.ansi[5],
+                "46" => ctx.buffer.current_bg_color = ctx.palette.ansi[6],
+                "47" => ctx.buffer.current_bg_color = ctx.palette.ansi[7],
                 // Default background
-                "49" => ctx.buffer.current_bg_color = Color32::TRANSPARENT,
+                "49" => ctx.buffer.current_bg_color = Rgba::TRANSPARENT,
 
                 // Bright foreground 90-97
-                "90" => ctx.buffer.current_fg_color = color::to_bright(Color32::BLACK),
-                "91" => ctx.buffer.current_fg_color = color::to_bright(Color32::RED),
-                "92" => ctx.buffer.current_fg_color = color::to_bright(Color32::GREEN),
-                "93" => ctx.buffer.current_fg_color = color::to_bright(Color32::YELLOW),
-                "94" => ctx.buffer.current_fg_color = color::to_bright(Color32::BLUE),
-                "95" => ctx.buffer.current_fg_color = color::to_bright(Color32::MAGENTA),
-                "96" => ctx.buffer.current_fg_color = color::to_bright(Color32::CYAN),
-                "97" => ctx.buffer.current_fg_color = color::to_bright(Color32::WHITE),
+                "90" => ctx.buffer.current_fg_color = ctx.palette.ansi[8],
+                "91" => ctx.buffer.current_fg_color = ctx.palette.ansi[9],
+                "92" => ctx.buffer.current_fg_color = ctx.palette.ansi[10],
+                "93" => ctx.buffer.current_fg_color = ctx.palette.ansi[11],
+                "94" => ctx.buffer.current_fg_color = ctx.palette.ansi[12],
+                "95" => ctx.buffer.current_fg_color = ctx.palette.ansi[13],
+                "96" => ctx.buffer.current_fg_color = ctx.palette.ansi[14],
+                "97" => ctx.buffer.current_fg_color = ctx.palette.ansi[15],
 
                 // Bright background 100-107
-                "100" => ctx.buffer.current_bg_color = color::to_bright(Color32::BLACK),
-                "101" => ctx.buffer.current_bg_color = color::to_bright(Color32::RED),
-                "102" => ctx.buffer.current_bg_color = color::to_bright(Color32::GREEN),
-                "103" => ctx.buffer.current_bg_color = color::to_bright(Color32::YELLOW),
-                "104" => ctx.buffer.current_bg_color = color::to_bright(Color32::BLUE),
-                "105" => ctx.buffer.current_bg_color = color::to_bright(Color32::MAGENTA),
-                "106" => ctx.buffer.current_bg_color = color::to_bright(Color32::CYAN),
-                "107" => ctx.buffer.current_bg_color = color::to_bright(Color32::WHITE),
+                "100" => ctx.buffer.current_bg_color = ctx.palette.ansi[8],
+                "101" => ctx.buffer.current_bg_color = ctx.palette.ansi[9],
+                "102" => ctx.buffer.current_bg_color = ctx.palette.ansi[10],
+                "103" => ctx.buffer.current_bg_color = ctx.palette.ansi[11],
+                "104" => ctx.buffer.current_bg_color = ctx.palette.ansi[12],
+                "105" => ctx.buffer.current_bg_color = ctx.palette.ansi[13],
+                "106" => ctx.buffer.current_bg_color = ctx.palette.ansi[14],
+                "107" => ctx.buffer.current_bg_color = ctx.palette.ansi[15],
 
                 // Extended color foreground/background 38/48
                 "38" | "48" => {
                     // Expect either ;5;idx or ;2;r;g;b
-                    let is_fg = token.trim_end_matches('m') == "38";
+                    let is_fg = token == "38";
                     let Some(mode) = tokens.next() else {
                         break;
                     };
@@ -158,7 +166,7 @@ impl SequenceHandler for SgrSequenceHandler {
                             if let Some(idx_str) = tokens.next()
                                 && let Ok(idx) = idx_str.parse::<u8>()
                             {
-                                let col = color::process_256_color_palette(idx);
+                                let col = ctx.palette.indexed(idx);
                                 if is_fg {
                                     ctx.buffer.current_fg_color = col;
                                 } else {
@@ -179,7 +187,7 @@ impl SequenceHandler for SgrSequenceHandler {
                                 .next()
                                 .and_then(|s| s.parse::<u8>().ok())
                                 .unwrap_or(0);
-                            let col = Color32::from_rgb(r, g, b);
+                            let col = Rgba::from_rgb(r, g, b);
                             if is_fg {
                                 ctx.buffer.current_fg_color = col;
                             } else {
@@ -198,5 +206,6 @@ impl SequenceHandler for SgrSequenceHandler {
                 }
             }
         }
+        true
     }
 }
@@ -0,0 +1,243 @@
+use crate::term::{
+    MouseTracking,
+    parser::{handler_context::HandlerContext, sequence_handler::SequenceHandler},
+    terminal_buffer::TerminalBuffer,
+};
+
+pub struct VT100SequenceHandler;
+
+impl VT100SequenceHandler {
+    fn parse_dec_private_mode(&self, sequence: &str) -> Option<(Vec<u16>, bool)> {
+        // DEC Private Mode sequences have format: ? Pn h/l or ? Pn ; Pm h/l
+        if !sequence.starts_with('?') {
+            return None;
+        }
+
+        let (params_str, is_set) = if sequence.ends_with('h') {
+            (sequence.strip_prefix('?')?.strip_suffix('h')?, true)
+        } else if sequence.ends_with('l') {
+            (sequence.strip_prefix('?')?.strip_suffix('l')?, false)
+        } else {
+            return None;
+        };
+
+        // Parse parameter numbers (can be semicolon-separated)
+        let mut params = Vec::new();
+        for param_str in params_str.split(';') {
+            if let Ok(param) = param_str.trim().parse::<u16>() {
+                params.push(param);
+            } else {
+                // Invalid parameter format
+                return None;
+            }
+        }
+
+        if params.is_empty() {
+            None
+        } else {
+            Some((params, is_set))
+        }
+    }
+
+    /// Whether a DEC private mode is set, for the modes set with `?Pn h`
+    fn mode_state(ctx: &HandlerContext, mode: u16) -> Option<bool> {
+        Some(match mode {
+            1 => *ctx.decckm_mode,
+            5 => *ctx.reverse_video_mode,
+            6 => *ctx.decom_mode,
+            7 => *ctx.decawm_mode,
+            20 => *ctx.new_line_mode,
+            25 => *ctx.show_cursor,
+            47 | 1047 | 1049 => ctx.saved_screen_buffer.is_some(),
+            1000 => *ctx.mouse_tracking == MouseTracking::Click,
+            1002 => *ctx.mouse_tracking == MouseTracking::Drag,
+            1003 => *ctx.mouse_tracking == MouseTracking::Motion,
+            1006 => *ctx.sgr_mouse_mode,
+            2004 => *ctx.bracket_paste_mode,
+            _ => return None,
+        })
+    }
+
+    /// Handle alternate screen buffer switching
+    /// The primary screen is kept as a whole (cells, cursor, scroll region, attributes and saved
+    /// cursor) so nothing done on the alternate screen can leak back into it.
+    fn enter_alternate_screen(ctx: &mut HandlerContext, save_cursor: bool) {
+        if ctx.saved_screen_buffer.is_some() {
+            // Already on the alternate screen, just start over with a clean one
+            ctx.buffer.clear_range(None, None);
+            return;
+        }
+
+        if save_cursor {
+            ctx.buffer.save_cursor();
+        }
+
+        // The alternate screen starts blank but inherits the cursor and attributes, as in xterm
+        let mut new_buffer = TerminalBuffer::new(ctx.buffer.width, ctx.buffer.height);
+        new_buffer.move_cursor(ctx.buffer.cursor_col(), ctx.buffer.cursor_y);
+        new_buffer.copy_attributes_from(&ctx.buffer.make_cell(' '));
+        new_buffer.copy_tab_stops_from(ctx.buffer);
+
+        *ctx.saved_screen_buffer = Some(std::mem::replace(ctx.buffer, new_buffer));
+    }
+
+    /// Handle alternate screen buffer switching back
+    fn leave_alternate_screen(ctx: &mut HandlerContext, restore_cursor: bool) {
+        let Some(mut saved_buffer) = ctx.saved_screen_buffer.take() else {
+            warn!("No saved screen buffer to switch back to");
+            return;
+        };
+
+        // The terminal may have been resized while the alternate screen was active
+        if saved_buffer.width != ctx.buffer.width || saved_buffer.height != ctx.buffer.height {
+            saved_buffer.resize(ctx.buffer.width, ctx.buffer.height);
+        }
+        saved_buffer.copy_tab_stops_from(ctx.buffer);
+        // Its rows are older than the alternate screen's ones that were drawn over them
+        saved_buffer.touch_all();
+
+        *ctx.buffer = saved_buffer;
+
+        if restore_cursor {
+            ctx.buffer.restore_cursor();
+        }
+    }
+}
+
+impl SequenceHandler for VT100SequenceHandler {
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool {
+        if let Some(mode) = sequence
+            .strip_prefix('?')
+            .and_then(|s| s.strip_suffix("$p"))
+        {
+            // DECRQM - Report whether a mode is set (1), reset (2) or not recognized (0)
+            let state = match mode
+                .parse()
+                .ok()
+                .and_then(|mode| Self::mode_state(ctx, mode))
+            {
+                Some(true) => 1,
+                Some(false) => 2,
+                None => 0,
+            };
+            ctx.reply
+                .extend_from_slice(format!("\x1b[?{mode};{state}$y").as_bytes());
+            return true;
+        }
+
+        let Some((params, is_set)) = self.parse_dec_private_mode(sequence) else {
+            debug!("Unhandled DEC private sequence: {sequence}");
+            return false;
+        };
+        for &param in &params {
+            match param {
+                1 => {
+                    // DECCKM - Cursor Key Application Mode
+                    *ctx.decckm_mode = is_set;
+                    debug!("DECCKM mode set to: {is_set}");
+                }
+                5 => {
+                    // DECSCNM - Screen Reverse Video Mode
+                    *ctx.reverse_video_mode = is_set;
+                    if is_set {
+                        warn!(
+                            "DECSCNM (Screen Reverse Video) enabled but rendering not implemented"
+                        );
+                    }
+                    debug!("DECSCNM mode set to: {is_set}");
+                }
+                6 => {
+                    // DECOM - Origin Mode
+                    *ctx.decom_mode = is_set;
+                    if is_set {
+                        warn!(
+                            "DECOM (Origin Mode) enabled but margin-relative positioning not fully implemented"
+                        );
+                    }
+                    debug!("DECOM mode set to: {is_set}");
+                }
+                7 => {
+                    // DECAWM - Auto Wrap Mode
+                    *ctx.decawm_mode = is_set;
+                    debug!("DECAWM mode set to: {is_set}");
+                }
+                20 => {
+                    // LNM - New Line Mode
+                    *ctx.new_line_mode = is_set;
+                    debug!("New Line Mode set to: {is_set}");
+                }
+                25 => {
+                    // DECTCEM - Cursor Show/Hide
+                    *ctx.show_cursor = is_set;
+                    debug!("Cursor visibility set to: {is_set}");
+                }
+                47 | 1047 => {
+                    // Alternate Screen Buffer (without saving the cursor)
+                    if is_set {
+                        Self::enter_alternate_screen(ctx, false);
+                        debug!("Entered alternate screen buffer");
+                    } else {
+                        Self::leave_alternate_screen(ctx, false);
+                        debug!("Left alternate screen buffer");
+                    }
+                }
+                1049 => {
+                    // Alternate Screen Buffer, saving the cursor before switching and
+                    // restoring it after switching back
+                    if is_set {
+                        Self::enter_alternate_screen(ctx, true);
+                        debug!("Entered alternate screen buffer");
+                    } else {
+                        Self::leave_alternate_screen(ctx, true);
+                        debug!("Left alternate screen buffer");
+                    }
+                }
+                1000 | 1002 | 1003 => {
+                    // Mouse reporting; resetting any of the modes turns it off
+                    *ctx.mouse_tracking = match (is_set, param) {
+                        (false, _) => MouseTracking::Off,
+                        (true, 1000) => MouseTracking::Click,
+                        (true, 1002) => MouseTracking::Drag,
+                        _ => MouseTracking::Motion,
+                    };
+                    debug!("Mouse tracking set to: {:?}", ctx.mouse_tracking);
+                }
+                1006 => {
+                    // SGR mouse coordinates
+                    *ctx.sgr_mouse_mode = is_set;
+                    debug!("SGR mouse mode set to: {is_set}");
+                }
+                2004 => {
+                    // Bracketed Paste Mode
+                    *ctx.bracket_paste_mode = is_set;
+                    debug!("Bracketed paste mode set to: {is_set}");
+                }
+                _ => {
+                    warn!(
+                        "Unsupported DEC Private Mode parameter: ?{}{}",
+                        param,
+                        if is_set { 'h' } else { 'l' }
+                    );
+                }
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::term::Term;
+
+    #[test]
+    fn mode_reports() {
+        let mut term = Term::new(20, 5);
+        term.feed(b"\x1b[?7$p");
+        assert_eq!(term.take_replies(), b"\x1b[?7;1$y");
+        term.feed(b"\x1b[?7l\x1b[?7$p\x1b[?2004h\x1b[?2004$p");
+        assert_eq!(term.take_replies(), b"\x1b[?7;2$y\x1b[?2004;1$y");
+        // A mode that isn't known
+        term.feed(b"\x1b[?4242$p");
+        assert_eq!(term.take_replies(), b"\x1b[?4242;0$y");
+    }
+}
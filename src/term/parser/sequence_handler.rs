@@ -0,0 +1,12 @@
+use crate::term::parser::handler_context::HandlerContext;
+
+pub trait SequenceHandler {
+    /// Apply `sequence`. Returns false to leave it to the next handler of the token class
+    fn handle(&self, ctx: &mut HandlerContext, sequence: &str) -> bool;
+
+    /// The name of the handler in traces
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+}
@@ -0,0 +1,13 @@
+#[derive(Debug, Clone)]
+pub enum SequenceToken {
+    Csi(String),             // ESC [
+    Osc(String),             // ESC ]
+    Dcs(String),             // ESC P
+    Apc(String),             // ESC _
+    Esc(char, Option<char>), // ESC 7, ESC # 8, ESC ( B, etc.
+    VT100(String),           // Other VT100 sequences
+    Sgr(String),             // SGR sequences
+    Character(char),         // Normal character
+    Text(String),            // Run of normal characters
+    ControlChar(u8),         // C0 controls but ESC: CR, LF, TAB, BS, etc.
+}
@@ -0,0 +1,411 @@
+use std::time::{Duration, Instant};
+
+use crate::term::parser::sequence_token::SequenceToken;
+
+/// Longest CSI sequence waited for; longer ones are abandoned and their bytes taken as text
+const MAX_CSI_LEN: usize = 1024;
+/// Longest string sequence (OSC, DCS, ...) waited for
+const MAX_STRING_LEN: usize = 64 * 1024;
+/// How long an ESC with nothing after it waits for the rest of its sequence
+const LONE_ESC_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct SequenceTokenizer {
+    buffer: Vec<u8>,
+    /// Bytes of the incomplete sequence at the start of the buffer already searched for its
+    /// end, so a long sequence arriving in pieces isn't searched from the start every time
+    scanned: usize,
+    /// Since when the buffer holds nothing but an ESC
+    lone_esc_since: Option<Instant>,
+}
+
+impl SequenceTokenizer {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            scanned: 0,
+            lone_esc_since: None,
+        }
+    }
+
+    /// Add incoming bytes to the tokenizer buffer, and extract complete sequences
+    pub fn feed(&mut self, data: &[u8]) -> Vec<SequenceToken> {
+        if self
+            .lone_esc_since
+            .is_some_and(|since| since.elapsed() > LONE_ESC_TIMEOUT)
+        {
+            debug!("Dropping an ESC that wasn't followed by a sequence in time");
+            self.buffer.clear();
+            self.scanned = 0;
+        }
+
+        self.buffer.extend_from_slice(data);
+        let mut tokens = Vec::new();
+        let mut cursor = 0;
+        let mut scanned = 0;
+
+        while cursor < self.buffer.len() {
+            match self.buffer[cursor] {
+                b'\x1b' => {
+                    // Detect escape sequences
+                    // Only the sequence left incomplete by the last feed was scanned before
+                    let resume = if cursor == 0 { self.scanned } else { 0 };
+                    match self.parse_escape_sequence(&self.buffer[cursor..], resume) {
+                        Ok((token, consumed)) => {
+                            tokens.extend(token);
+                            cursor += consumed;
+                        }
+                        Err(searched) => {
+                            // Incomplete sequence -> leave in the buffer for next feed
+                            scanned = searched;
+                            break;
+                        }
+                    }
+                }
+                // The other C0 controls: the dispatcher decides what they do, if anything
+                code if code < 32 => {
+                    tokens.push(SequenceToken::ControlChar(code));
+                    cursor += 1;
+                }
+                // DEL is ignored
+                127 => cursor += 1,
+                _ => {
+                    // A run of text up to the next control character, decoded at once
+                    let end = self.buffer[cursor..]
+                        .iter()
+                        .position(|&b| b < 32 || b == 127)
+                        .map_or(self.buffer.len(), |len| cursor + len);
+                    let run = &self.buffer[cursor..end];
+                    let (valid_len, invalid_len) = match std::str::from_utf8(run) {
+                        Ok(_) => (run.len(), None),
+                        Err(e) => (e.valid_up_to(), e.error_len()),
+                    };
+                    if valid_len == 0 {
+                        match invalid_len {
+                            // The maximal invalid prefix becomes one replacement character
+                            Some(len) => {
+                                tokens.push(SequenceToken::Character(char::REPLACEMENT_CHARACTER));
+                                cursor += len;
+                            }
+                            // A character split across feeds, the rest may still come
+                            None if end == self.buffer.len() => break,
+                            // Cut short by a control character
+                            None => {
+                                tokens.push(SequenceToken::Character(char::REPLACEMENT_CHARACTER));
+                                cursor = end;
+                            }
+                        }
+                        continue;
+                    }
+
+                    let text = String::from_utf8_lossy(&run[..valid_len]).into_owned();
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => tokens.push(SequenceToken::Character(ch)),
+                        _ => tokens.push(SequenceToken::Text(text)),
+                    }
+                    cursor += valid_len;
+                }
+            }
+        }
+
+        self.buffer.drain(..cursor);
+        self.scanned = scanned;
+        self.lone_esc_since = match self.buffer.as_slice() {
+            [b'\x1b'] => self.lone_esc_since.or_else(|| Some(Instant::now())),
+            _ => None,
+        };
+        tokens
+    }
+
+    /// Parse an escape sequence starting at the beginning of bytes. Returns the token (none
+    /// for sequences that are dropped) and the number of bytes consumed. An incomplete
+    /// sequence returns how far it was searched, to `resume` from when more bytes arrive.
+    /// Sequences growing past their limit are abandoned by consuming only the ESC, so the rest
+    /// comes out as text
+    fn parse_escape_sequence(
+        &self,
+        bytes: &[u8],
+        resume: usize,
+    ) -> Result<(Option<SequenceToken>, usize), usize> {
+        if bytes.len() < 2 || bytes[0] != b'\x1b' {
+            return Err(0);
+        }
+
+        let limit = match bytes[1] {
+            b'[' => MAX_CSI_LEN,
+            b']' | b'P' | b'X' | b'^' | b'_' => MAX_STRING_LEN,
+            _ => usize::MAX,
+        };
+        let parsed = self.parse_complete_sequence(bytes, resume);
+        if parsed.is_none() && bytes.len() - 2 > limit {
+            warn!(
+                "Abandoning an escape sequence longer than {limit} bytes (ESC {})",
+                char::from(bytes[1]).escape_debug()
+            );
+            return Ok((None, 1));
+        }
+        parsed.ok_or(bytes.len() - 2)
+    }
+
+    fn parse_complete_sequence(
+        &self,
+        bytes: &[u8],
+        resume: usize,
+    ) -> Option<(Option<SequenceToken>, usize)> {
+        match bytes[1] {
+            b'[' => self.parse_csi(&bytes[2..], resume).map(|(s, len)| match s {
+                // DEC private modes, `?` before the parameters
+                s if s.starts_with('?') => (Some(SequenceToken::VT100(s)), len + 2),
+                // Only plain parameters are SGR; `>4;2m` and the like are other sequences
+                // with the same final byte
+                s if s.strip_suffix('m').is_some_and(|params| {
+                    params
+                        .bytes()
+                        .all(|b| b.is_ascii_digit() || b == b';' || b == b':')
+                }) =>
+                {
+                    (Some(SequenceToken::Sgr(s)), len + 2)
+                }
+                _ => (Some(SequenceToken::Csi(s)), len + 2),
+            }),
+            b']' => self
+                .parse_osc(&bytes[2..], resume)
+                .map(|(s, len)| (Some(SequenceToken::Osc(s)), len + 2)),
+            b'P' => self
+                .parse_osc(&bytes[2..], resume)
+                .map(|(s, len)| (Some(SequenceToken::Dcs(s)), len + 2)),
+            b'_' => self
+                .parse_osc(&bytes[2..], resume)
+                .map(|(s, len)| (Some(SequenceToken::Apc(s)), len + 2)),
+            // SOS and PM: strings up to ST nothing here understands
+            introducer @ (b'X' | b'^') => self.parse_osc(&bytes[2..], resume).map(|(s, len)| {
+                debug!(
+                    "Ignoring ESC {} string: {s:?}",
+                    char::from(introducer).escape_debug()
+                );
+                (None, len + 2)
+            }),
+            // Intermediate bytes (`ESC (`, `ESC #`, `ESC %`, ...) followed by a final byte
+            intermediate @ 0x20..=0x2f => {
+                let &final_byte = bytes.get(2)?;
+                if final_byte < 0x20 || final_byte == 0x7f {
+                    // Not a sequence after all, the control character is processed on its own
+                    debug!("Malformed escape sequence ESC {}", char::from(intermediate));
+                    return Some((None, 2));
+                }
+                Some((
+                    Some(SequenceToken::Esc(
+                        char::from(intermediate),
+                        Some(char::from(final_byte)),
+                    )),
+                    3,
+                ))
+            }
+            // Single final byte: ESC 7, ESC M, ESC c, ...
+            final_byte @ 0x30..=0x7e => {
+                Some((Some(SequenceToken::Esc(char::from(final_byte), None)), 2))
+            }
+            // ESC before a control character or outside of ASCII: drop the ESC alone
+            other => {
+                debug!("Malformed escape sequence ESC {other:#04x}");
+                Some((None, 1))
+            }
+        }
+    }
+
+    /// Find the end of the CSI sequence and return it
+    fn parse_csi(&self, bytes: &[u8], resume: usize) -> Option<(String, usize)> {
+        for (i, &byte) in bytes.iter().enumerate().skip(resume) {
+            // Final bytes are in the range 0x40..=0x7E (@, A-Z, a-z, etc.)
+            if (0x40..=0x7e).contains(&byte) {
+                let sequence = String::from_utf8_lossy(&bytes[..=i]).to_string();
+                return Some((sequence, i + 1));
+            }
+        }
+        None // Incomplete sequence
+    }
+
+    /// Find the end of a string sequence (OSC, DCS, ...), terminated by BEL or ST, and
+    /// return it
+    fn parse_osc(&self, bytes: &[u8], resume: usize) -> Option<(String, usize)> {
+        // The last byte searched may be the ESC of an ST split across feeds
+        let mut i = resume.saturating_sub(1);
+        while i < bytes.len() {
+            if bytes[i] == b'\x07' {
+                // BEL terminator
+                let sequence = String::from_utf8_lossy(&bytes[..i]).to_string();
+                return Some((sequence, i + 1));
+            }
+            if i + 1 < bytes.len() && bytes[i] == b'\x1b' && bytes[i + 1] == b'\\' {
+                // ESC \ terminator
+                let sequence = String::from_utf8_lossy(&bytes[..i]).to_string();
+                return Some((sequence, i + 2));
+            }
+            i += 1;
+        }
+        None // Incomplete sequence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64*, enough to make up input without a dependency
+    struct Random(u64);
+
+    impl Random {
+        fn new(seed: u64) -> Self {
+            Self(seed.max(1))
+        }
+
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        /// Bytes weighted towards the ones that start and shape sequences
+        fn bytes(&mut self, len: usize) -> Vec<u8> {
+            const INTERESTING: &[u8] = b"\x1b\x1b\x1b[]P_X^\\#%()?;:0123456789$ mhHJK\x07\x18\r\n";
+            (0..len)
+                .map(|_| {
+                    let value = self.next();
+                    match value % 4 {
+                        0 => (value >> 8) as u8,
+                        _ => INTERESTING[(value >> 8) as usize % INTERESTING.len()],
+                    }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn random_bytes_never_wedge_the_stream() {
+        let mut random = Random::new(0x5eed);
+        let mut tokenizer = SequenceTokenizer::new();
+        for _ in 0..2000 {
+            let len = (random.next() % 64) as usize;
+            let bytes = random.bytes(len);
+            tokenizer.feed(&bytes);
+
+            // Whatever is kept back is the start of a sequence or of a character that may
+            // still be completed, never a finished sequence nobody consumes
+            let kept = tokenizer.buffer.as_slice();
+            match kept {
+                [] => {}
+                [b'\x1b'] | [b'\x1b', 0x20..=0x2f] => {}
+                [b'\x1b', b'[', rest @ ..] => {
+                    assert!(rest.len() <= MAX_CSI_LEN);
+                    assert!(!rest.iter().any(|b| (0x40..=0x7e).contains(b)));
+                }
+                [b'\x1b', b']' | b'P' | b'X' | b'^' | b'_', rest @ ..] => {
+                    assert!(rest.len() <= MAX_STRING_LEN);
+                }
+                partial => assert!(
+                    partial.len() < 4 && std::str::from_utf8(partial).is_err(),
+                    "stuck on {partial:?}"
+                ),
+            }
+        }
+    }
+
+    /// The text the tokens make up, ignoring everything else
+    fn text(tokens: &[SequenceToken]) -> String {
+        tokens
+            .iter()
+            .filter_map(|token| match token {
+                SequenceToken::Character(ch) => Some(ch.to_string()),
+                SequenceToken::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn an_unterminated_osc_stays_bounded() {
+        let mut tokenizer = SequenceTokenizer::new();
+        let mut output = text(&tokenizer.feed(b"\x1b]0;"));
+        let chunk = vec![b'x'; 4096];
+        for _ in 0..(10 * 1024 * 1024 / chunk.len()) {
+            output += &text(&tokenizer.feed(&chunk));
+            assert!(tokenizer.buffer.len() <= MAX_STRING_LEN + 2);
+        }
+
+        // Given up on at the limit, after which every chunk comes straight out as text
+        assert_eq!(output.len(), 3 + 10 * 1024 * 1024);
+        assert!(output.starts_with("]0;xxx"));
+        assert!(tokenizer.buffer.is_empty());
+    }
+
+    #[test]
+    fn uniformly_random_bytes_stay_bounded() {
+        let mut random = Random::new(0xb17e5);
+        let mut tokenizer = SequenceTokenizer::new();
+        for _ in 0..4000 {
+            let len = (random.next() % 512) as usize;
+            let bytes: Vec<u8> = (0..len).map(|_| random.next() as u8).collect();
+            tokenizer.feed(&bytes);
+            assert!(tokenizer.buffer.len() <= MAX_STRING_LEN + 2);
+        }
+    }
+
+    #[test]
+    fn characters_split_across_feeds() {
+        let input = "日本語 🎉 text";
+        for split in 0..=input.len() {
+            let mut tokenizer = SequenceTokenizer::new();
+            let (first, second) = input.as_bytes().split_at(split);
+            let mut output = text(&tokenizer.feed(first));
+            output += &text(&tokenizer.feed(second));
+            assert_eq!(output, input, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_becomes_replacement_characters() {
+        let cases: &[(&[u8], &str)] = &[
+            // Lone continuation bytes
+            (b"\x80abc", "\u{fffd}abc"),
+            (b"a\xbf\xbfb", "a\u{fffd}\u{fffd}b"),
+            // Overlong encodings of '/'
+            (b"\xc0\xaf", "\u{fffd}\u{fffd}"),
+            (b"\xe0\x80\xaf", "\u{fffd}\u{fffd}\u{fffd}"),
+            // A surrogate
+            (b"\xed\xa0\x80", "\u{fffd}\u{fffd}\u{fffd}"),
+            // A character cut short by a control character
+            (b"\xf0\x9f\x8e\nok", "\u{fffd}ok"),
+        ];
+        for (input, expected) in cases {
+            let mut tokenizer = SequenceTokenizer::new();
+            assert_eq!(text(&tokenizer.feed(input)), *expected, "{input:?}");
+            assert!(tokenizer.buffer.is_empty(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn a_partial_character_waits_for_the_rest() {
+        let mut tokenizer = SequenceTokenizer::new();
+        assert_eq!(text(&tokenizer.feed(b"a\xf0\x9f")), "a");
+        assert_eq!(text(&tokenizer.feed(b"\x8e\x89")), "🎉");
+        assert!(tokenizer.buffer.is_empty());
+    }
+
+    #[test]
+    fn csi_sequences_are_classified() {
+        let mut tokenizer = SequenceTokenizer::new();
+        let tokens = tokenizer.feed(b"\x1b[?2004h\x1b[31m\x1b[1;2m\x1b[>4;2m\x1b[2J");
+        let classes: Vec<String> = tokens.iter().map(|token| format!("{token:?}")).collect();
+        assert_eq!(
+            classes,
+            [
+                r#"VT100("?2004h")"#,
+                r#"Sgr("31m")"#,
+                r#"Sgr("1;2m")"#,
+                r#"Csi(">4;2m")"#,
+                r#"Csi("2J")"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Command tracking through the semantic prompt marks (OSC 133) emitted by shell integration
+//! scripts: `A` prompt start, `B` command input start, `C` command executed, `D;<status>`
+//! command finished.
+
+use std::{collections::VecDeque, ops::Range};
+
+use crate::term::terminal_buffer::TerminalBuffer;
+
+/// Listings kept at most, the oldest go first
+const MAX_LISTINGS: usize = 64;
+
+/// Programs whose output is a listing of files
+const LISTING_COMMANDS: &[&str] = &["ls", "ll", "la", "l", "dir", "vdir", "exa", "eza", "lsd"];
+
+/// Something the shell reported about the command lifecycle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandEvent {
+    /// The user's command line started running (`133;C`)
+    Started { command: String },
+    /// The running command finished (`133;D`), with its exit status if the shell reported one
+    Finished { exit_code: Option<i32> },
+}
+
+/// The output of a command listing files (`ls` and the like), whose names can be clicked
+#[derive(Debug, Clone)]
+pub struct Listing {
+    /// The command line, whose arguments may name the directories listed
+    pub command: String,
+    /// The lines of the output, counted from the first line ever on the primary screen
+    pub lines: Range<u64>,
+}
+
+#[derive(Default)]
+pub struct ShellIntegration {
+    /// Where the command input began (`133;B`), used to read the command line at `133;C`
+    pub input_start: Option<(usize, usize)>,
+    /// Events since the last `Term::take_command_events`
+    pub events: Vec<CommandEvent>,
+    /// The listing being printed by the running command, until it finishes
+    pub running_listing: Option<Listing>,
+    /// The listings finished, oldest first
+    pub listings: VecDeque<Listing>,
+}
+
+impl ShellIntegration {
+    /// What was typed after the prompt so far, read back from the screen. `None` when the
+    /// shell didn't mark where the input begins
+    pub fn command_line(&self, buffer: &TerminalBuffer) -> Option<String> {
+        let (x, y) = self.input_start?;
+        let row = buffer.cells.get(y)?;
+        Some(
+            row.iter()
+                .skip(x)
+                .filter(|cell| !cell.wide_tail)
+                .map(|cell| cell.character)
+                .collect::<String>()
+                .trim()
+                .to_string(),
+        )
+    }
+
+    /// Start a listing at `line` when `command` lists files
+    pub fn command_started(&mut self, command: &str, line: u64) {
+        let program = command
+            .split_whitespace()
+            .next()
+            .and_then(|program| program.rsplit('/').next())
+            .unwrap_or_default();
+        self.running_listing = LISTING_COMMANDS.contains(&program).then(|| Listing {
+            command: command.to_string(),
+            lines: line..line,
+        });
+    }
+
+    /// End the running listing before `line`
+    pub fn command_finished(&mut self, line: u64) {
+        let Some(mut listing) = self.running_listing.take() else {
+            return;
+        };
+        listing.lines.end = line;
+        if listing.lines.is_empty() {
+            return;
+        }
+        if self.listings.len() == MAX_LISTINGS {
+            self.listings.pop_front();
+        }
+        self.listings.push_back(listing);
+    }
+
+    /// Drop the listings that scrolled out of the history, which starts at `first_line`
+    pub fn collect_garbage(&mut self, first_line: u64) {
+        self.listings
+            .retain(|listing| listing.lines.end > first_line);
+    }
+}
@@ -0,0 +1,940 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    vec,
+};
+
+use crate::term::color::Rgba;
+use unicode_width::UnicodeWidthChar;
+
+use crate::term::terminal_cell::TerminalCell;
+
+/// Receives the lines that scroll off the top of the screen
+pub trait ScrollbackSink {
+    fn push_line(&mut self, line: Vec<TerminalCell>);
+}
+
+/// A sink that drops every line, for screens without history (e.g. the alternate screen)
+pub struct DiscardScrollback;
+
+impl ScrollbackSink for DiscardScrollback {
+    fn push_line(&mut self, _line: Vec<TerminalCell>) {}
+}
+
+/// Where the generations of all buffers come from, so a buffer swapped in for another never
+/// has generations that look older than ones already rendered
+static GENERATIONS: AtomicU64 = AtomicU64::new(1);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Cursor state saved by DECSC / CSI s and restored by DECRC / CSI u
+#[derive(Clone, Debug, Default)]
+pub struct SavedCursor {
+    pub x: usize,
+    pub y: usize,
+    /// The graphic rendition in effect when the cursor was saved
+    pub attributes: TerminalCell,
+}
+
+/// A single screen (primary or alternate). Everything that belongs to a screen lives here -
+/// cells, cursor, scroll region, current attributes and the saved cursor slot - so switching
+/// screens is a matter of swapping whole buffers.
+pub struct TerminalBuffer {
+    pub cells: Vec<Vec<TerminalCell>>,
+    pub width: usize,
+    pub height: usize,
+    pub cursor_x: usize,
+    pub cursor_y: usize,
+    pub scroll_region_top: usize,
+    pub scroll_region_bottom: usize,
+    pub current_fg_color: Rgba,
+    pub current_bg_color: Rgba,
+    pub current_bold: bool,
+    pub current_underline: bool,
+    pub current_italic: bool,
+    pub current_blink: bool,
+    pub current_strikethrough: bool,
+    pub current_hidden: bool,
+    pub saved_cursor: SavedCursor,
+    /// Columns with a tab stop
+    tab_stops: Vec<bool>,
+    /// Interval of the default tab stops
+    tab_width: usize,
+    /// Bumped by every change of the cells
+    pub generation: u64,
+    /// The generation each row was last changed in
+    row_generation: Vec<u64>,
+}
+
+/// Columns between the default tab stops unless configured otherwise
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+impl TerminalBuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut cells = Vec::with_capacity(height);
+        for _ in 0..height {
+            cells.push(vec![TerminalCell::default(); width]);
+        }
+        let generation = next_generation();
+
+        Self {
+            cells,
+            width,
+            height,
+            cursor_x: 0,
+            cursor_y: 0,
+            scroll_region_top: 0,
+            scroll_region_bottom: height - 1,
+            current_fg_color: Rgba::WHITE,
+            current_bg_color: Rgba::TRANSPARENT,
+            current_bold: false,
+            current_underline: false,
+            current_italic: false,
+            current_blink: false,
+            current_strikethrough: false,
+            current_hidden: false,
+            saved_cursor: SavedCursor::default(),
+            tab_stops: default_tab_stops(width, DEFAULT_TAB_WIDTH),
+            tab_width: DEFAULT_TAB_WIDTH,
+            generation,
+            row_generation: vec![generation; height],
+        }
+    }
+
+    /// Note a change of the cells of `rows`
+    fn touch(&mut self, rows: impl IntoIterator<Item = usize>) {
+        self.generation = next_generation();
+        for row in rows {
+            if let Some(row_generation) = self.row_generation.get_mut(row) {
+                *row_generation = self.generation;
+            }
+        }
+    }
+
+    /// Note a change of every row, e.g. after the cells were changed directly or the buffer
+    /// was swapped back in
+    pub fn touch_all(&mut self) {
+        self.touch(0..self.height);
+    }
+
+    /// The rows changed after `generation`
+    pub fn changed_rows_since(&self, generation: u64) -> impl Iterator<Item = usize> + '_ {
+        self.row_generation
+            .iter()
+            .enumerate()
+            .filter(move |&(_, &row_generation)| row_generation > generation)
+            .map(|(row, _)| row)
+    }
+
+    pub fn make_cell(&self, ch: char) -> TerminalCell {
+        TerminalCell {
+            character: ch,
+            fg_color: self.current_fg_color,
+            bg_color: self.current_bg_color,
+            bold: self.current_bold,
+            underline: self.current_underline,
+            italic: self.current_italic,
+            blink: self.current_blink,
+            strikethrough: self.current_strikethrough,
+            hidden: self.current_hidden,
+            wide_tail: false,
+        }
+    }
+
+    pub fn resize(&mut self, new_width: usize, new_height: usize) {
+        self.width = new_width;
+        self.height = new_height;
+
+        // Adjust the cells
+        if self.cells.len() < new_height {
+            while self.cells.len() < new_height {
+                self.cells.push(vec![TerminalCell::default(); new_width]);
+            }
+        } else if self.cells.len() > new_height {
+            self.cells.truncate(new_height);
+        }
+
+        // Adjust each row to the new width
+        for row in &mut self.cells {
+            if row.len() < new_width {
+                row.resize(new_width, TerminalCell::default());
+            } else if row.len() > new_width {
+                row.truncate(new_width);
+            }
+        }
+
+        // Stops set by the application are kept, new columns get the default ones
+        let old_width = self.tab_stops.len();
+        self.tab_stops.resize(new_width, false);
+        for col in old_width..new_width {
+            self.tab_stops[col] = col != 0 && col % self.tab_width == 0;
+        }
+
+        // Adjust cursor position
+        self.cursor_x = self.cursor_x.min(new_width.saturating_sub(1));
+        self.cursor_y = self.cursor_y.min(new_height.saturating_sub(1));
+        self.scroll_region_top = 0;
+        self.scroll_region_bottom = new_height - 1;
+
+        self.row_generation.resize(new_height, 0);
+        self.touch_all();
+    }
+
+    /// The column the cursor is visually on.
+    /// `cursor_x` may be equal to `width` right after the last column has been written (the
+    /// "pending wrap" state); that phantom column is reported as the last real column.
+    pub fn cursor_col(&self) -> usize {
+        self.cursor_x.min(self.width.saturating_sub(1))
+    }
+
+    pub fn put_char(&mut self, ch: char, auto_wrap: bool, scrollback: &mut dyn ScrollbackSink) {
+        let display_width = UnicodeWidthChar::width(ch).unwrap_or(1);
+        if display_width == 0 || self.width == 0 {
+            // Skip zero-width characters
+            return;
+        }
+
+        self.resolve_pending_wrap(display_width, auto_wrap, scrollback);
+
+        // Insert the character at the current cursor position
+        if self.cursor_y < self.height {
+            self.touch([self.cursor_y]);
+            self.cells[self.cursor_y][self.cursor_x] = self.make_cell(ch);
+            if display_width > 1 && self.cursor_x + 1 < self.width {
+                self.cells[self.cursor_y][self.cursor_x + 1] = {
+                    let mut cell = self.make_cell(ch);
+                    cell.wide_tail = true;
+                    cell
+                };
+            }
+            // May leave the cursor in the phantom column (cursor_x == width)
+            self.cursor_x = (self.cursor_x + display_width).min(self.width);
+        }
+    }
+
+    /// Make sure a character `display_width` cells wide fits at the cursor: from the pending
+    /// wrap state (or when it doesn't fit on the line) wrap to the next one, or without
+    /// `auto_wrap` move back so it ends in the last column
+    pub fn resolve_pending_wrap(
+        &mut self,
+        display_width: usize,
+        auto_wrap: bool,
+        scrollback: &mut dyn ScrollbackSink,
+    ) {
+        if self.cursor_x + display_width > self.width {
+            if auto_wrap {
+                self.new_line(true, scrollback);
+            } else {
+                self.cursor_x = self.width.saturating_sub(display_width);
+            }
+        }
+    }
+
+    /// Put the characters of `text` one after the other with the same result as `put_char`,
+    /// but a row at a time: the wrap is checked once per row and the row touched once
+    pub fn put_str(&mut self, text: &str, auto_wrap: bool, scrollback: &mut dyn ScrollbackSink) {
+        if self.width == 0 {
+            return;
+        }
+        let template = self.make_cell(' ');
+        let mut chars = text
+            .chars()
+            .map(|ch| (ch, UnicodeWidthChar::width(ch).unwrap_or(1)))
+            // Zero-width characters are skipped, as by `put_char`
+            .filter(|&(_, display_width)| display_width > 0)
+            .peekable();
+
+        while let Some(&(_, display_width)) = chars.peek() {
+            self.resolve_pending_wrap(display_width, auto_wrap, scrollback);
+            let Some(row) = self.cells.get_mut(self.cursor_y) else {
+                return;
+            };
+
+            // The first character goes in whether it fits or not (a wide one on a line of one
+            // cell), the following ones as long as they fit
+            let mut x = self.cursor_x;
+            while let Some(&(ch, display_width)) = chars.peek() {
+                if x != self.cursor_x && x + display_width > self.width {
+                    break;
+                }
+                chars.next();
+                row[x] = TerminalCell {
+                    character: ch,
+                    ..template.clone()
+                };
+                if display_width > 1 && x + 1 < self.width {
+                    row[x + 1] = TerminalCell {
+                        character: ch,
+                        wide_tail: true,
+                        ..template.clone()
+                    };
+                }
+                // May leave the cursor in the phantom column (cursor_x == width)
+                x = (x + display_width).min(self.width);
+            }
+            self.cursor_x = x;
+            self.touch([self.cursor_y]);
+        }
+    }
+
+    pub fn new_line(&mut self, lmn_mode: bool, scrollback: &mut dyn ScrollbackSink) {
+        if lmn_mode {
+            self.cursor_x = 0;
+        }
+        self.index(scrollback);
+    }
+
+    /// Move the cursor down one line, scrolling the scroll region up at its bottom margin (IND)
+    pub fn index(&mut self, scrollback: &mut dyn ScrollbackSink) {
+        if self.cursor_y == self.scroll_region_bottom {
+            self.scroll_up(1, scrollback);
+        } else if self.cursor_y + 1 < self.height {
+            self.cursor_y += 1;
+        }
+    }
+
+    /// Move the cursor up one line, scrolling the scroll region down at its top margin (RI)
+    pub fn reverse_index(&mut self) {
+        if self.cursor_y == self.scroll_region_top {
+            self.scroll_down(1);
+        } else if self.cursor_y > 0 {
+            self.cursor_y -= 1;
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        // Backspace from the pending wrap position acts from the last column
+        self.cursor_x = self.cursor_col();
+        if self.cursor_x > 0 {
+            self.cursor_x -= 1;
+            self.cells[self.cursor_y][self.cursor_x] = TerminalCell::default();
+            self.touch([self.cursor_y]);
+        }
+    }
+
+    /// Scroll the scroll region up by `count` lines.
+    /// Lines leaving the top of the screen are handed to `scrollback`; lines leaving a scroll
+    /// region that doesn't start at the top of the screen are discarded, as in xterm.
+    pub fn scroll_up(&mut self, count: usize, scrollback: &mut dyn ScrollbackSink) {
+        let (top, bottom) = (self.scroll_region_top, self.scroll_region_bottom);
+        let count = count.min(bottom + 1 - top);
+        let blank = self.blank_cell();
+
+        for line in self
+            .cells
+            .splice(top..top + count, std::iter::empty())
+            .collect::<Vec<_>>()
+        {
+            if top == 0 {
+                scrollback.push_line(line);
+            }
+        }
+        let insert_at = bottom + 1 - count;
+        self.cells.splice(
+            insert_at..insert_at,
+            std::iter::repeat_n(vec![blank; self.width], count),
+        );
+        self.touch(top..=bottom);
+    }
+
+    /// Scroll the scroll region down by `count` lines, inserting blank lines at the top
+    pub fn scroll_down(&mut self, count: usize) {
+        let (top, bottom) = (self.scroll_region_top, self.scroll_region_bottom);
+        let count = count.min(bottom + 1 - top);
+        let blank = self.blank_cell();
+
+        self.cells.drain(bottom + 1 - count..=bottom);
+        self.cells.splice(
+            top..top,
+            std::iter::repeat_n(vec![blank; self.width], count),
+        );
+        self.touch(top..=bottom);
+    }
+
+    /// Insert `count` blank lines at the cursor row, pushing the lines below down (IL)
+    pub fn insert_lines(&mut self, count: usize) {
+        if !(self.scroll_region_top..=self.scroll_region_bottom).contains(&self.cursor_y) {
+            return;
+        }
+
+        let top = self.scroll_region_top;
+        self.scroll_region_top = self.cursor_y;
+        self.scroll_down(count);
+        self.scroll_region_top = top;
+        self.cursor_x = 0;
+    }
+
+    /// Delete `count` lines at the cursor row, pulling the lines below up (DL)
+    pub fn delete_lines(&mut self, count: usize) {
+        if !(self.scroll_region_top..=self.scroll_region_bottom).contains(&self.cursor_y) {
+            return;
+        }
+
+        let top = self.scroll_region_top;
+        self.scroll_region_top = self.cursor_y;
+        // Lines deleted inside the screen never go to the scrollback
+        self.scroll_up(count, &mut DiscardScrollback);
+        self.scroll_region_top = top;
+        self.cursor_x = 0;
+    }
+
+    /// Set the scroll region (DECSTBM). `top` and `bottom` are 0-based and inclusive.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.height.saturating_sub(1));
+        if top < bottom {
+            self.scroll_region_top = top;
+            self.scroll_region_bottom = bottom;
+        } else {
+            self.scroll_region_top = 0;
+            self.scroll_region_bottom = self.height.saturating_sub(1);
+        }
+    }
+
+    /// A blank cell carrying the current background color (BCE - Background Color Erase)
+    pub fn blank_cell(&self) -> TerminalCell {
+        TerminalCell {
+            bg_color: self.current_bg_color,
+            ..Default::default()
+        }
+    }
+
+    pub fn clear_range(
+        &mut self,
+        start_pos: Option<(usize, usize)>,
+        end_pos: Option<(usize, usize)>,
+    ) {
+        let start_x = start_pos.map_or(0, |(x, _)| x);
+        let start_y = start_pos.map_or(0, |(_, y)| y);
+        let end_x = end_pos.map_or(self.width.saturating_sub(1), |(x, _)| x);
+        let end_y = end_pos.map_or(self.height.saturating_sub(1), |(_, y)| y);
+        let blank = self.blank_cell();
+
+        // y range within the height of the buffer
+        let y_start = start_y.min(self.height);
+        let y_end = end_y.min(self.height.saturating_sub(1));
+
+        for y in y_start..=y_end {
+            // x range within the width of the buffer
+            let x_start = start_x.min(self.width);
+            let x_end = end_x.min(self.width.saturating_sub(1));
+
+            if x_start <= x_end {
+                self.cells[y][x_start..=x_end].fill(blank.clone());
+                self.touch([y]);
+                // A boundary may have landed in the middle of a wide character
+                self.sanitize_wide_pairs(y);
+            }
+        }
+    }
+
+    /// Erase in Display (ED). The cursor is never moved.
+    /// - 0: from the cursor to the end of the screen
+    /// - 1: from the beginning of the screen to the cursor
+    /// - 2: the entire screen
+    pub fn erase_display(&mut self, mode: u16) {
+        let (cx, cy) = (self.cursor_col(), self.cursor_y);
+        let last_col = self.width.saturating_sub(1);
+        match mode {
+            0 => {
+                self.clear_range(Some((cx, cy)), Some((last_col, cy)));
+                if cy + 1 < self.height {
+                    self.clear_range(Some((0, cy + 1)), None);
+                }
+            }
+            1 => {
+                if cy > 0 {
+                    self.clear_range(None, Some((last_col, cy - 1)));
+                }
+                self.clear_range(Some((0, cy)), Some((cx, cy)));
+            }
+            2 => self.clear_range(None, None),
+            _ => {}
+        }
+    }
+
+    /// Erase in Line (EL). The cursor is never moved.
+    /// - 0: from the cursor to the end of the line
+    /// - 1: from the beginning of the line to the cursor
+    /// - 2: the entire line
+    pub fn erase_line(&mut self, mode: u16) {
+        let (cx, cy) = (self.cursor_col(), self.cursor_y);
+        let last_col = self.width.saturating_sub(1);
+        match mode {
+            0 => self.clear_range(Some((cx, cy)), Some((last_col, cy))),
+            1 => self.clear_range(Some((0, cy)), Some((cx, cy))),
+            2 => self.clear_range(Some((0, cy)), Some((last_col, cy))),
+            _ => {}
+        }
+    }
+
+    /// Delete `count` characters at the cursor, shifting the rest of the line left (DCH)
+    pub fn delete_chars(&mut self, count: usize) {
+        self.cursor_x = self.cursor_col();
+        if self.cursor_y >= self.height || self.cursor_x >= self.width {
+            return;
+        }
+
+        let blank = self.blank_cell();
+        let row = &mut self.cells[self.cursor_y];
+        let count = count.min(self.width - self.cursor_x);
+        row.drain(self.cursor_x..self.cursor_x + count);
+        row.resize(self.width, blank);
+        self.touch([self.cursor_y]);
+        self.sanitize_wide_pairs(self.cursor_y);
+    }
+
+    /// Insert `count` blank characters at the cursor, shifting the rest of the line right (ICH)
+    pub fn insert_chars(&mut self, count: usize) {
+        self.cursor_x = self.cursor_col();
+        if self.cursor_y >= self.height || self.cursor_x >= self.width {
+            return;
+        }
+
+        let blank = self.blank_cell();
+        let row = &mut self.cells[self.cursor_y];
+        let count = count.min(self.width - self.cursor_x);
+        row.splice(self.cursor_x..self.cursor_x, vec![blank; count]);
+        row.truncate(self.width);
+        self.touch([self.cursor_y]);
+        self.sanitize_wide_pairs(self.cursor_y);
+    }
+
+    /// Erase `count` characters starting at the cursor without shifting the line or moving the
+    /// cursor (ECH)
+    pub fn erase_chars(&mut self, count: usize) {
+        self.cursor_x = self.cursor_col();
+        if self.cursor_y >= self.height || self.cursor_x >= self.width {
+            return;
+        }
+
+        let end = (self.cursor_x + count.max(1)).min(self.width);
+        let blank = self.blank_cell();
+        self.cells[self.cursor_y][self.cursor_x..end].fill(blank);
+        self.touch([self.cursor_y]);
+        self.sanitize_wide_pairs(self.cursor_y);
+    }
+
+    /// Replace any wide character head without its tail (or tail without its head) with a blank
+    /// cell in the current background, as erasing does
+    pub fn sanitize_wide_pairs(&mut self, row: usize) {
+        let blank = self.blank_cell();
+        let Some(cells) = self.cells.get_mut(row) else {
+            return;
+        };
+
+        let is_wide_head = |cell: &TerminalCell| {
+            !cell.wide_tail && UnicodeWidthChar::width(cell.character).unwrap_or(1) > 1
+        };
+        // The tail of a character holds the same character, a shifted line can bring the tail
+        // of another one next to a head
+        let is_pair = |head: &TerminalCell, tail: &TerminalCell| {
+            is_wide_head(head) && tail.wide_tail && tail.character == head.character
+        };
+
+        for x in 0..cells.len() {
+            if cells[x].wide_tail {
+                if x == 0 || !is_pair(&cells[x - 1], &cells[x]) {
+                    cells[x] = blank.clone();
+                }
+            } else if is_wide_head(&cells[x])
+                && !cells
+                    .get(x + 1)
+                    .is_some_and(|tail| is_pair(&cells[x], tail))
+            {
+                cells[x] = blank.clone();
+            }
+        }
+    }
+
+    pub fn move_cursor(&mut self, x: usize, y: usize) {
+        self.cursor_x = x.min(self.width.saturating_sub(1));
+        self.cursor_y = y.min(self.height.saturating_sub(1));
+    }
+
+    /// Save the cursor position and current attributes (DECSC)
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor = SavedCursor {
+            x: self.cursor_col(),
+            y: self.cursor_y,
+            attributes: self.make_cell(' '),
+        };
+    }
+
+    /// Restore the cursor position and attributes saved by `save_cursor` (DECRC)
+    pub fn restore_cursor(&mut self) {
+        let saved = self.saved_cursor.clone();
+        self.move_cursor(saved.x, saved.y);
+        self.copy_attributes_from(&saved.attributes);
+    }
+
+    /// Set the current attributes from the attributes of `cell`
+    pub fn copy_attributes_from(&mut self, cell: &TerminalCell) {
+        self.current_fg_color = cell.fg_color;
+        self.current_bg_color = cell.bg_color;
+        self.current_bold = cell.bold;
+        self.current_underline = cell.underline;
+        self.current_italic = cell.italic;
+        self.current_blink = cell.blink;
+        self.current_strikethrough = cell.strikethrough;
+        self.current_hidden = cell.hidden;
+    }
+
+    pub fn carriage_return(&mut self) {
+        self.cursor_x = 0;
+    }
+
+    /// Use tab stops every `tab_width` columns, dropping those set by the application
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+        self.reset_tab_stops();
+    }
+
+    /// Go back to the default tab stops
+    pub fn reset_tab_stops(&mut self) {
+        self.tab_stops = default_tab_stops(self.width, self.tab_width);
+    }
+
+    /// Take over the tab stops of `other`, which are shared between the screens
+    pub fn copy_tab_stops_from(&mut self, other: &TerminalBuffer) {
+        self.tab_width = other.tab_width;
+        self.tab_stops = other.tab_stops.clone();
+        self.tab_stops.resize(self.width, false);
+    }
+
+    /// Set a tab stop at the cursor (HTS)
+    pub fn set_tab_stop(&mut self) {
+        let col = self.cursor_col();
+        if let Some(stop) = self.tab_stops.get_mut(col) {
+            *stop = true;
+        }
+    }
+
+    /// Clear the tab stop at the cursor (TBC 0), or all of them (TBC 3)
+    pub fn clear_tab_stops(&mut self, all: bool) {
+        if all {
+            self.tab_stops.fill(false);
+        } else {
+            let col = self.cursor_col();
+            if let Some(stop) = self.tab_stops.get_mut(col) {
+                *stop = false;
+            }
+        }
+    }
+
+    /// The column of the `count`th tab stop right of the cursor, the last column if there
+    /// are fewer
+    pub fn next_tab_stop(&self, count: usize) -> usize {
+        let last = self.width.saturating_sub(1);
+        let mut col = self.cursor_col();
+        for _ in 0..count {
+            col = (col + 1..self.width)
+                .find(|&col| self.tab_stops[col])
+                .unwrap_or(last);
+        }
+        col
+    }
+
+    /// Move the cursor to the `count`th tab stop left of it, or the first column (CBT)
+    pub fn tab_backward(&mut self, count: usize) {
+        let mut col = self.cursor_col();
+        for _ in 0..count {
+            col = (0..col).rev().find(|&col| self.tab_stops[col]).unwrap_or(0);
+        }
+        self.cursor_x = col;
+    }
+
+    /// Move the cursor to the `count`th tab stop right of it (HT, CHT)
+    pub fn tab_forward(&mut self, count: usize) {
+        self.cursor_x = self.next_tab_stop(count);
+    }
+}
+
+fn default_tab_stops(width: usize, tab_width: usize) -> Vec<bool> {
+    (0..width)
+        .map(|col| col != 0 && col % tab_width == 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A change made to a buffer in a test case
+    type Change = fn(&mut TerminalBuffer);
+    /// Cells as `(x, y)`
+    type Cells = &'static [(usize, usize)];
+
+    /// The rows `change` marks as changed in a 10×5 buffer with the cursor at `cursor`
+    fn touched(cursor: (usize, usize), change: impl FnOnce(&mut TerminalBuffer)) -> Vec<usize> {
+        let mut buffer = TerminalBuffer::new(10, 5);
+        buffer.move_cursor(cursor.0, cursor.1);
+        let generation = buffer.generation;
+        change(&mut buffer);
+        buffer.changed_rows_since(generation).collect()
+    }
+
+    #[test]
+    fn mutators_mark_the_rows_they_change() {
+        let cases: [(&str, Change, &[usize]); 9] = [
+            (
+                "put_char",
+                |b| b.put_char('a', true, &mut DiscardScrollback),
+                &[2],
+            ),
+            ("backspace", |b| b.backspace(), &[2]),
+            ("erase_line", |b| b.erase_line(2), &[2]),
+            ("erase_display below", |b| b.erase_display(0), &[2, 3, 4]),
+            ("erase_display above", |b| b.erase_display(1), &[0, 1, 2]),
+            ("delete_chars", |b| b.delete_chars(1), &[2]),
+            ("insert_chars", |b| b.insert_chars(1), &[2]),
+            ("erase_chars", |b| b.erase_chars(1), &[2]),
+            ("insert_lines", |b| b.insert_lines(1), &[2, 3, 4]),
+        ];
+        for (name, change, rows) in cases {
+            assert_eq!(touched((3, 2), change), rows, "{name}");
+        }
+    }
+
+    #[test]
+    fn moving_the_cursor_changes_nothing() {
+        let cases: [Change; 5] = [
+            |b| b.move_cursor(0, 0),
+            |b| b.carriage_return(),
+            |b| b.tab_forward(1),
+            |b| b.save_cursor(),
+            |b| b.new_line(true, &mut DiscardScrollback),
+        ];
+        for change in cases {
+            assert_eq!(touched((3, 2), change), Vec::<usize>::new());
+        }
+    }
+
+    #[test]
+    fn scrolling_marks_the_whole_region() {
+        assert_eq!(
+            touched((0, 4), |b| b.new_line(true, &mut DiscardScrollback)),
+            [0, 1, 2, 3, 4]
+        );
+        assert_eq!(touched((0, 0), |b| b.reverse_index()), [0, 1, 2, 3, 4]);
+        assert_eq!(
+            touched((0, 3), |b| {
+                b.set_scroll_region(1, 3);
+                b.delete_lines(1);
+            }),
+            [3]
+        );
+        assert_eq!(
+            touched((0, 1), |b| {
+                b.set_scroll_region(1, 3);
+                b.scroll_up(1, &mut DiscardScrollback);
+            }),
+            [1, 2, 3]
+        );
+        assert_eq!(
+            touched((0, 1), |b| {
+                b.set_scroll_region(1, 3);
+                b.scroll_down(2);
+            }),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn resizing_marks_every_row() {
+        assert_eq!(touched((0, 0), |b| b.resize(12, 3)), [0, 1, 2]);
+        assert_eq!(touched((0, 0), |b| b.resize(8, 6)), [0, 1, 2, 3, 4, 5]);
+    }
+
+    /// A 10×3 buffer with `text` put at the start of the first row
+    fn with_line(text: &str) -> TerminalBuffer {
+        let mut buffer = TerminalBuffer::new(10, 3);
+        buffer.put_str(text, true, &mut DiscardScrollback);
+        buffer
+    }
+
+    /// Panic if a wide character in `row` lost its head or its tail
+    fn assert_whole_pairs(buffer: &TerminalBuffer, row: usize, what: &str) {
+        let cells = &buffer.cells[row];
+        for (x, cell) in cells.iter().enumerate() {
+            let is_head = !cell.wide_tail && UnicodeWidthChar::width(cell.character) == Some(2);
+            if cell.wide_tail {
+                assert!(
+                    x > 0 && cells[x - 1].character == cell.character && !cells[x - 1].wide_tail,
+                    "{what}: tail at {x} without its head"
+                );
+            } else if is_head {
+                assert!(
+                    cells.get(x + 1).is_some_and(|next| next.wide_tail),
+                    "{what}: head at {x} without its tail"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn deleting_through_wide_characters_keeps_whole_pairs() {
+        for col in 0..10 {
+            for count in 1..4 {
+                let mut buffer = with_line("日本語日本");
+                buffer.move_cursor(col, 0);
+                buffer.delete_chars(count);
+                assert_whole_pairs(&buffer, 0, &format!("DCH {count} at {col}"));
+            }
+        }
+
+        // Deleting the head of 本 takes the whole character, its tail becomes a blank
+        let mut buffer = with_line("日本語");
+        buffer.move_cursor(2, 0);
+        buffer.delete_chars(1);
+        let text: String = buffer.cells[0]
+            .iter()
+            .filter(|cell| !cell.wide_tail)
+            .map(|cell| cell.character)
+            .collect();
+        assert_eq!(text, "日 語     ");
+    }
+
+    #[test]
+    fn orphaned_halves_take_the_current_background() {
+        let background = Rgba([1, 2, 3, 255]);
+        let mut buffer = with_line("日本");
+        buffer.current_bg_color = background;
+        buffer.move_cursor(0, 0);
+        buffer.delete_chars(1);
+        // The tail of 日 lost its head
+        assert_eq!(buffer.cells[0][0].character, ' ');
+        assert!(!buffer.cells[0][0].wide_tail);
+        assert_eq!(buffer.cells[0][0].bg_color, background);
+    }
+
+    #[test]
+    fn inserting_and_erasing_in_wide_characters_keeps_whole_pairs() {
+        for col in 0..10 {
+            let mut buffer = with_line("日本語日本");
+            buffer.move_cursor(col, 0);
+            buffer.insert_chars(1);
+            assert_whole_pairs(&buffer, 0, &format!("ICH at {col}"));
+
+            let mut buffer = with_line("日本語日本");
+            buffer.move_cursor(col, 0);
+            buffer.erase_chars(1);
+            assert_whole_pairs(&buffer, 0, &format!("ECH at {col}"));
+
+            let mut buffer = with_line("日本語日本");
+            buffer.clear_range(Some((col, 0)), Some((col, 0)));
+            assert_whole_pairs(&buffer, 0, &format!("clear_range at {col}"));
+        }
+    }
+
+    #[test]
+    fn a_full_line_leaves_the_cursor_in_the_phantom_column() {
+        let mut buffer = with_line("0123456789");
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (10, 0));
+        assert_eq!(buffer.cursor_col(), 9);
+
+        // Backspace acts from the last column
+        buffer.backspace();
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (8, 0));
+
+        let mut buffer = with_line("0123456789");
+        buffer.carriage_return();
+        buffer.new_line(false, &mut DiscardScrollback);
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (0, 1));
+
+        // The next character wraps instead of overwriting the last cell
+        let mut buffer = with_line("0123456789");
+        buffer.put_char('x', true, &mut DiscardScrollback);
+        assert_eq!(buffer.cells[0][9].character, '9');
+        assert_eq!(buffer.cells[1][0].character, 'x');
+        assert_eq!((buffer.cursor_x, buffer.cursor_y), (1, 1));
+
+        // Erasing from the phantom column erases the last cell
+        let mut buffer = with_line("0123456789");
+        buffer.erase_line(0);
+        assert_eq!(buffer.cells[0][8].character, '8');
+        assert_eq!(buffer.cells[0][9].character, ' ');
+    }
+
+    #[test]
+    fn erasing_keeps_the_cursor_and_uses_the_background() {
+        let background = Rgba([1, 2, 3, 255]);
+        let cases: [(&str, Change, Cells); 6] = [
+            ("erase_line right", |b| b.erase_line(0), &[(4, 1), (9, 1)]),
+            ("erase_line left", |b| b.erase_line(1), &[(0, 1), (4, 1)]),
+            ("erase_line all", |b| b.erase_line(2), &[(0, 1), (9, 1)]),
+            (
+                "erase_display below",
+                |b| b.erase_display(0),
+                &[(4, 1), (0, 2)],
+            ),
+            (
+                "erase_display above",
+                |b| b.erase_display(1),
+                &[(0, 0), (4, 1)],
+            ),
+            ("erase_chars", |b| b.erase_chars(2), &[(4, 1), (5, 1)]),
+        ];
+        for (name, change, erased) in cases {
+            let mut buffer = TerminalBuffer::new(10, 3);
+            for _ in 0..3 {
+                buffer.put_str("abcdefghij", true, &mut DiscardScrollback);
+            }
+            buffer.move_cursor(4, 1);
+            buffer.current_bg_color = background;
+            change(&mut buffer);
+
+            assert_eq!((buffer.cursor_x, buffer.cursor_y), (4, 1), "{name}");
+            for &(x, y) in erased {
+                assert_eq!(buffer.cells[y][x].character, ' ', "{name} at {x},{y}");
+                assert_eq!(buffer.cells[y][x].bg_color, background, "{name} at {x},{y}");
+            }
+        }
+    }
+
+    #[test]
+    fn put_str_matches_put_char() {
+        let texts = [
+            "plain text that wraps around the line",
+            "日本語のテキストも折り返す",
+            "a日b本c語d",
+            "zero\u{200b}width\u{301} marks",
+            "",
+        ];
+        for text in texts {
+            for (width, auto_wrap) in [(7, true), (7, false), (1, true), (1, false)] {
+                let mut by_str = TerminalBuffer::new(width, 4);
+                let mut by_char = TerminalBuffer::new(width, 4);
+                by_str.move_cursor(width / 2, 1);
+                by_char.move_cursor(width / 2, 1);
+                by_str.put_str(text, auto_wrap, &mut DiscardScrollback);
+                for ch in text.chars() {
+                    by_char.put_char(ch, auto_wrap, &mut DiscardScrollback);
+                }
+
+                let what = format!("{text:?} on {width} cells, auto wrap {auto_wrap}");
+                assert_eq!(
+                    format!("{:?}", by_str.cells),
+                    format!("{:?}", by_char.cells),
+                    "{what}"
+                );
+                assert_eq!(
+                    (by_str.cursor_x, by_str.cursor_y),
+                    (by_char.cursor_x, by_char.cursor_y),
+                    "{what}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn put_str_touches_the_rows_it_writes() {
+        let mut buffer = TerminalBuffer::new(10, 5);
+        let generation = buffer.generation;
+        buffer.put_str(&"x".repeat(25), true, &mut DiscardScrollback);
+        assert_eq!(
+            buffer.changed_rows_since(generation).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+    }
+}
@@ -1,10 +1,10 @@
-use eframe::egui::Color32;
+use crate::term::color::Rgba;
 
 #[derive(Clone, Debug)]
 pub struct TerminalCell {
     pub character: char,
-    pub fg_color: Color32,
-    pub bg_color: Color32,
+    pub fg_color: Rgba,
+    pub bg_color: Rgba,
     pub bold: bool,
     pub underline: bool,
     pub italic: bool,
@@ -18,8 +18,8 @@ impl Default for TerminalCell {
     fn default() -> Self {
         Self {
             character: ' ',
-            fg_color: Color32::WHITE,
-            bg_color: Color32::TRANSPARENT,
+            fg_color: Rgba::WHITE,
+            bg_color: Rgba::TRANSPARENT,
             bold: false,
             underline: false,
             italic: false,
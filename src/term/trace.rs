@@ -0,0 +1,105 @@
+//! Records of the tokens the dispatcher applied, for the inspector (`inspector` feature)
+
+use std::{collections::VecDeque, ops::RangeInclusive};
+
+use crate::term::{parser::sequence_token::SequenceToken, terminal_buffer::TerminalBuffer};
+
+/// Events kept until the UI takes them; older ones are dropped
+const MAX_PENDING_EVENTS: usize = 1000;
+
+/// A sequence or control character as the dispatcher applied it
+#[derive(Clone, Debug)]
+pub struct TraceEvent {
+    /// CSI, OSC, SGR, ...
+    pub class: &'static str,
+    /// The bytes of the sequence, ESC and control characters spelled out
+    pub raw: String,
+    /// The handler that took it, none if no handler did
+    pub handler: Option<&'static str>,
+    /// The screen rows the cursor was on before and after, as far as the sequence is known to
+    /// have affected the screen
+    pub rows: RangeInclusive<usize>,
+}
+
+/// An event waiting for the dispatcher to finish
+pub struct PendingTrace {
+    class: &'static str,
+    raw: String,
+    cursor_y: usize,
+}
+
+impl PendingTrace {
+    /// Start tracing `token`. Text isn't traced, it would drown everything else
+    pub fn new(token: &SequenceToken, buffer: &TerminalBuffer) -> Option<Self> {
+        let (class, raw) = match token {
+            SequenceToken::Csi(seq) => ("CSI", format!("ESC [ {seq}")),
+            SequenceToken::VT100(seq) => ("DEC", format!("ESC [ {seq}")),
+            SequenceToken::Sgr(seq) => ("SGR", format!("ESC [ {seq}")),
+            SequenceToken::Osc(seq) => ("OSC", format!("ESC ] {} ST", seq.escape_debug())),
+            SequenceToken::Dcs(seq) => ("DCS", format!("ESC P {} ST", seq.escape_debug())),
+            // Only the control data, the payload can be many kilobytes of base64
+            SequenceToken::Apc(seq) => {
+                let control = seq.split(';').next().unwrap_or_default();
+                ("APC", format!("ESC _ {} ST", control.escape_debug()))
+            }
+            SequenceToken::Esc(first, second) => (
+                "ESC",
+                format!(
+                    "ESC {first}{}",
+                    second.map(String::from).unwrap_or_default()
+                ),
+            ),
+            SequenceToken::ControlChar(code) => ("Control", control_name(*code)),
+            SequenceToken::Character(_) | SequenceToken::Text(_) => return None,
+        };
+        Some(Self {
+            class,
+            raw,
+            cursor_y: buffer.cursor_y,
+        })
+    }
+
+    pub fn finish(self, handler: Option<&'static str>, buffer: &TerminalBuffer) -> TraceEvent {
+        TraceEvent {
+            class: self.class,
+            raw: self.raw,
+            handler,
+            rows: self.cursor_y.min(buffer.cursor_y)..=self.cursor_y.max(buffer.cursor_y),
+        }
+    }
+}
+
+fn control_name(code: u8) -> String {
+    match code {
+        b'\r' => "CR".to_string(),
+        b'\n' => "LF".to_string(),
+        b'\t' => "TAB".to_string(),
+        b'\x08' => "BS".to_string(),
+        b'\x07' => "BEL".to_string(),
+        b'\x03' => "ETX".to_string(),
+        b'\x05' => "ENQ".to_string(),
+        b'\x00' => "NUL".to_string(),
+        b'\x0e' => "SO".to_string(),
+        b'\x0f' => "SI".to_string(),
+        other => format!("{other:#04x}"),
+    }
+}
+
+/// The events not taken by the UI yet
+#[derive(Default)]
+pub struct Trace {
+    events: VecDeque<TraceEvent>,
+}
+
+impl Trace {
+    pub fn push(&mut self, event: TraceEvent) {
+        if self.events.len() == MAX_PENDING_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn take(&mut self) -> VecDeque<TraceEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
@@ -0,0 +1,1433 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{
+        Arc, Condvar,
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use eframe::egui::{self, mutex::Mutex};
+use portable_pty::{Child, CommandBuilder, ExitStatus, PtyPair, PtySize, native_pty_system};
+
+use crate::{
+    CONFIG,
+    asciicast::{self, Player, PlayerEvent, Recorder},
+    config::{BellMode, Config, RuleAction, ScrollbackOnOutput},
+    session_log::{self, SessionLog},
+    term::shell_integration::CommandEvent,
+    terminal_widget::{TerminalWidget, listing::ListingClick},
+};
+
+/// A shell that exits sooner than this after starting counts as dying instantly
+const QUICK_EXIT: Duration = Duration::from_secs(5);
+/// Delay before restarting a shell that died instantly; doubled for every further quick exit
+const INITIAL_RESTART_DELAY: Duration = Duration::from_millis(500);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// Output is parsed in chunks of this size, checking the frame budget in between
+const CHUNK_SIZE: usize = 64 * 1024;
+/// How long parsing output may take per frame normally
+const FRAME_BUDGET: Duration = Duration::from_millis(8);
+const MAX_BYTES_PER_FRAME: usize = 2 * 1024 * 1024;
+/// Above this much unparsed output the session is throttled until it is all parsed: parsing
+/// gets a larger budget, the terminal isn't drawn anew and shows an indicator instead
+const HIGH_WATER_MARK: usize = 4 * 1024 * 1024;
+const THROTTLED_FRAME_BUDGET: Duration = Duration::from_millis(40);
+/// The output thread stops reading at this much unparsed output until the UI catches up, so
+/// the program writing it waits instead of the backlog growing without bound
+const MAX_BACKLOG: usize = 16 * 1024 * 1024;
+
+/// How often the directory and foreground program are looked up for `[[profile]]` matches
+const PROFILE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Output this soon after replying to a query may be the reply echoed back by the TTY, so it
+/// isn't counted as activity of the tab
+const REPLY_ECHO_WINDOW: Duration = Duration::from_millis(200);
+
+/// Id of the next session, so a session can be told apart from the one that replaced it
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Threads waiting out the grace period of shells that were sent SIGHUP, joined on exit
+static REAPERS: std::sync::Mutex<Vec<JoinHandle<()>>> = std::sync::Mutex::new(Vec::new());
+
+/// Output read from the PTY and not parsed yet, handed from the output thread to the UI
+#[derive(Default)]
+struct OutputBuffer {
+    backlog: std::sync::Mutex<Backlog>,
+    /// Signalled when output was taken or the buffer closed
+    drained: Condvar,
+}
+
+#[derive(Default)]
+struct Backlog {
+    data: VecDeque<u8>,
+    /// The session stopped, nothing is taken anymore
+    closed: bool,
+}
+
+impl OutputBuffer {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Backlog> {
+        self.backlog.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Append `bytes`, first waiting while `MAX_BACKLOG` is reached. Returns `false` once the
+    /// buffer is closed, for the output thread to stop
+    fn push(&self, bytes: &[u8]) -> bool {
+        let mut backlog = self.lock();
+        while backlog.data.len() >= MAX_BACKLOG && !backlog.closed {
+            backlog = self
+                .drained
+                .wait(backlog)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        if backlog.closed {
+            return false;
+        }
+        backlog.data.extend(bytes);
+        true
+    }
+
+    /// Move up to `max` bytes from the front into `chunk`. Returns `false` if there were none
+    fn take(&self, chunk: &mut Vec<u8>, max: usize) -> bool {
+        let mut backlog = self.lock();
+        let count = backlog.data.len().min(max);
+        chunk.clear();
+        chunk.extend(backlog.data.drain(..count));
+        if count > 0 {
+            self.drained.notify_all();
+        }
+        count > 0
+    }
+
+    fn len(&self) -> usize {
+        self.lock().data.len()
+    }
+
+    /// Drop the backlog and release the output thread, waiting or not
+    fn close(&self) {
+        let mut backlog = self.lock();
+        backlog.closed = true;
+        backlog.data = VecDeque::new();
+        self.drained.notify_all();
+    }
+}
+
+/// What the tab strip shows on a tab that isn't looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TabActivity {
+    /// Output arrived since the tab was last looked at
+    Output,
+    /// Output arrived, then nothing for the configured interval
+    Silence,
+}
+
+/// One shell running in its own PTY, together with the terminal that displays it
+pub struct TerminalSession {
+    /// Stays the same while the tabs around the session are opened and closed
+    pub id: u64,
+    pub terminal_widget: TerminalWidget,
+    pty_pair: Option<PtyPair>,
+    child: Option<Box<dyn Child + Send + Sync>>,
+    // Filled by the output thread of the current PTY; replaced on restart so a stale thread
+    // can't mix old output into the new terminal
+    output_buffer: Arc<OutputBuffer>,
+    // Dropping the sender ends the input thread
+    input_sender: Option<mpsc::Sender<Vec<u8>>>,
+    is_running: bool,
+    // Used by the output thread to wake up the UI when data arrives
+    egui_ctx: egui::Context,
+    /// Set when the shell exited and the user is asked what to do
+    pub exit_status: Option<ExitStatus>,
+    // The size last reported to the PTY, compared with the current one to detect resizes
+    last_size: PtySize,
+    pid: Option<u32>,
+    /// The directory the shell was started in, reused on restart
+    working_directory: Option<PathBuf>,
+    /// Title set by the application through OSC 0
+    title: Option<String>,
+    /// Set from a backlog above `HIGH_WATER_MARK` until it is all parsed
+    throttled: bool,
+    /// When the current shell was started
+    started_at: Instant,
+    /// Delay for the next restart after a quick exit (exit_behavior = "restart")
+    restart_delay: Duration,
+    /// When a scheduled restart is due
+    restart_at: Option<Instant>,
+    /// The command running in the shell and when it started, from the OSC 133 marks
+    running_command: Option<(String, Instant)>,
+    /// Commands that finished since the last `take_finished_commands`
+    finished_commands: Vec<FinishedCommand>,
+    /// The `[[profile]]` in effect, by its index in the configuration
+    profile: Option<usize>,
+    /// Title prefix of that profile
+    title_prefix: Option<String>,
+    /// When profiles were last matched against the directory and program
+    profile_checked_at: Option<Instant>,
+    /// The asciicast recording of the output, shared with the output thread
+    recorder: Arc<Mutex<Option<Recorder>>>,
+    /// The recording played back instead of running a shell (`--play`)
+    player: Option<Player>,
+    /// Bytes of output received from the PTY
+    pub bytes_received: u64,
+    /// When output last arrived, echoed replies left out
+    last_output_at: Option<Instant>,
+    /// Output arrived since the tab was last looked at
+    unseen_output: bool,
+    /// When replies to queries were last written to the PTY
+    replied_at: Option<Instant>,
+}
+
+/// Send `signal` to each of the process `groups`
+#[cfg(unix)]
+fn signal_process_groups(groups: &[libc::pid_t], signal: libc::c_int) {
+    for &group in groups {
+        // SAFETY: kill has no memory safety requirements
+        unsafe {
+            libc::kill(-group, signal);
+        }
+    }
+}
+
+/// The variables of the shell on top of explotty's own: terminal capabilities, then `[env]`
+/// and `env_remove` of `config`
+fn set_env_vars(cmd: &mut CommandBuilder, config: Option<&Config>) {
+    let term = config
+        .and_then(|config| config.term.clone())
+        .unwrap_or_else(|| "xterm-256color".to_string());
+    cmd.env("TERM", term);
+    cmd.env("COLORTERM", "truecolor");
+
+    // The size is reported through the PTY, stale values would override it
+    cmd.env_remove("LINES");
+    cmd.env_remove("COLUMNS");
+
+    // Merge order: explotty's own environment (inherited by CommandBuilder), the defaults
+    // above, then `[env]` from the config, which can override both; `env_remove` wins last
+    if let Some(env) = config.and_then(|config| config.env.as_ref()) {
+        for (key, value) in env {
+            cmd.env(key, crate::utils::expand_env_vars(value));
+        }
+    }
+    if let Some(env_remove) = config.and_then(|config| config.env_remove.as_ref()) {
+        for key in env_remove {
+            cmd.env_remove(key);
+        }
+    }
+}
+
+/// Wait until the shells of closed sessions have exited or were killed after their grace
+/// period, so none outlive the app
+pub fn join_reapers() {
+    let reapers = std::mem::take(&mut *REAPERS.lock().unwrap_or_else(|e| e.into_inner()));
+    for reaper in reapers {
+        let _ = reaper.join();
+    }
+}
+
+/// A command that ran in the shell, reported through shell integration (OSC 133)
+pub struct FinishedCommand {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub duration: Duration,
+}
+
+impl TerminalSession {
+    /// Create a session and start its shell, in `working_directory` if given.
+    /// A failure to start is shown in the terminal so the user can fix their config.
+    pub fn new(ctx: &egui::Context, working_directory: Option<PathBuf>) -> Self {
+        let mut session = Self::unstarted(ctx, working_directory);
+        session.start_or_report();
+        session
+    }
+
+    /// A session playing `player` back, without a shell
+    pub fn play(ctx: &egui::Context, player: Player) -> Self {
+        let mut session = Self::unstarted(ctx, None);
+        session
+            .terminal_widget
+            .set_fixed_size(player.width, player.height);
+        session.title = player
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string());
+        session.player = Some(player);
+        session
+    }
+
+    fn unstarted(ctx: &egui::Context, working_directory: Option<PathBuf>) -> Self {
+        Self {
+            id: NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            terminal_widget: TerminalWidget::new(80, 24),
+            pty_pair: None,
+            child: None,
+            output_buffer: Arc::default(),
+            input_sender: None,
+            is_running: false,
+            egui_ctx: ctx.clone(),
+            exit_status: None,
+            last_size: PtySize::default(),
+            pid: None,
+            working_directory,
+            title: None,
+            throttled: false,
+            started_at: Instant::now(),
+            restart_delay: INITIAL_RESTART_DELAY,
+            restart_at: None,
+            running_command: None,
+            finished_commands: Vec::new(),
+            profile: None,
+            title_prefix: None,
+            profile_checked_at: None,
+            recorder: Arc::new(Mutex::new(None)),
+            player: None,
+            bytes_received: 0,
+            last_output_at: None,
+            unseen_output: false,
+            replied_at: None,
+        }
+    }
+
+    fn start_or_report(&mut self) {
+        if let Err(e) = self.start_pty() {
+            error!("{e}");
+            self.terminal_widget
+                .term
+                .feed(format!("\x1b[31m{e}\x1b[0m\r\n").as_bytes());
+        }
+    }
+
+    /// The shells to try in order: the configured shell (with its arguments), `$SHELL`, then
+    /// `/bin/sh`. A command given with `-e` is the only candidate.
+    fn shell_candidates() -> Vec<(String, Vec<String>)> {
+        let mut candidates = Vec::new();
+
+        if let Some(config) = CONFIG.get()
+            && let Some((program, args)) = config.command.as_ref().and_then(|c| c.split_first())
+        {
+            candidates.push((program.clone(), args.to_vec()));
+            return candidates;
+        }
+
+        if let Some(config) = CONFIG.get()
+            && let Some(shell) = &config.shell
+        {
+            candidates.push((shell.clone(), config.shell_args.clone().unwrap_or_default()));
+        }
+        if let Ok(shell) = std::env::var("SHELL")
+            && !shell.is_empty()
+        {
+            candidates.push((shell, Vec::new()));
+        }
+        candidates.push(("/bin/sh".to_string(), Vec::new()));
+
+        candidates
+    }
+
+    /// Set up the environment the shell sees: terminal capabilities and the working directory
+    fn set_environment(&self, cmd: &mut CommandBuilder) {
+        let config = CONFIG.get();
+        let config = config.as_deref();
+
+        set_env_vars(cmd, config);
+
+        if let Some(dir) = &self.working_directory {
+            cmd.cwd(dir);
+        } else if let Some(dir) = config.and_then(|config| config.working_directory.as_ref()) {
+            cmd.cwd(dir);
+        }
+    }
+
+    fn start_pty(&mut self) -> anyhow::Result<()> {
+        let pty_system = native_pty_system();
+        let pty_pair = pty_system
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| anyhow::anyhow!("failed to create PTY: {e}"))?;
+
+        // Spawn a shell in the PTY, falling back to the next candidate when spawning fails
+        let mut child = None;
+        let mut errors = Vec::new();
+        for (shell, args) in Self::shell_candidates() {
+            let mut cmd = CommandBuilder::new(&shell);
+            for arg in &args {
+                cmd.arg(arg);
+            }
+            self.set_environment(&mut cmd);
+
+            match pty_pair.slave.spawn_command(cmd) {
+                Ok(spawned) => {
+                    child = Some(spawned);
+                    break;
+                }
+                Err(e) => {
+                    warn!("Failed to start {shell}: {e}");
+                    errors.push(format!("failed to start {shell}: {e}"));
+                }
+            }
+        }
+        let Some(child) = child else {
+            anyhow::bail!(errors.join("\r\n"));
+        };
+        self.pid = child.process_id();
+
+        self.pty_pair = Some(pty_pair);
+        self.child = Some(child);
+        self.is_running = true;
+        self.exit_status = None;
+        self.started_at = Instant::now();
+        self.restart_at = None;
+
+        // Initialize output thread. Reads block until the shell writes something; each chunk
+        // wakes up the UI, so frames are only produced when there is output to show
+        let output_buffer = Arc::new(OutputBuffer::default());
+        self.output_buffer = output_buffer.clone();
+        let egui_ctx = self.egui_ctx.clone();
+        if let Some(ref pty_pair) = self.pty_pair {
+            let mut reader = pty_pair
+                .master
+                .try_clone_reader()
+                .map_err(|e| anyhow::anyhow!("failed to clone PTY reader: {e}"))?;
+
+            let mut log = SessionLog::from_config();
+            let recorder = self.recorder.clone();
+
+            let reader_thread = thread::Builder::new().name(format!("pty-reader-{}", self.id));
+            reader_thread.spawn(move || {
+                let mut buffer = [0u8; 4096];
+                loop {
+                    match reader.read(&mut buffer) {
+                        Ok(0) => break, // EOF
+                        Ok(n) => {
+                            if let Some(log) = &mut log {
+                                log.write(&buffer[..n]);
+                            }
+                            if let Some(recorder) = &mut *recorder.lock() {
+                                recorder.output(&buffer[..n]);
+                            }
+                            // Blocks while the UI is behind; the session stopped if closed
+                            if !output_buffer.push(&buffer[..n]) {
+                                break;
+                            }
+                            egui_ctx.request_repaint();
+                        }
+                        Err(e) => {
+                            // EIO is how Linux reports that the shell side of the PTY was closed
+                            debug!("PTY reader stopped: {e}");
+                            break;
+                        }
+                    }
+                }
+                // Let the UI notice the exited shell right away
+                egui_ctx.request_repaint();
+            })?;
+        }
+
+        // Initialize input thread. It sleeps in `recv` until there is input and ends when the
+        // sender is dropped
+        let (input_sender, input_receiver) = mpsc::channel::<Vec<u8>>();
+        self.input_sender = Some(input_sender);
+        if let Some(ref pty_pair) = self.pty_pair {
+            let mut writer = pty_pair
+                .master
+                .take_writer()
+                .map_err(|e| anyhow::anyhow!("failed to take PTY writer: {e}"))?;
+
+            let writer_thread = thread::Builder::new().name(format!("pty-writer-{}", self.id));
+            writer_thread.spawn(move || {
+                while let Ok(data) = input_receiver.recv() {
+                    if let Err(e) = writer.write_all(&data) {
+                        error!("Error writing to PTY: {e}");
+                        break;
+                    }
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a job other than the shell itself is in the foreground
+    pub fn has_running_job(&self) -> bool {
+        let (Some(pty_pair), Some(pid)) = (&self.pty_pair, self.pid) else {
+            return false;
+        };
+        if self.child.is_none() {
+            return false;
+        }
+
+        #[cfg(unix)]
+        {
+            pty_pair
+                .master
+                .process_group_leader()
+                .is_some_and(|leader| leader as u32 != pid)
+        }
+
+        #[cfg(not(unix))]
+        {
+            false
+        }
+    }
+
+    /// The shell's process group and the foreground job's group
+    #[cfg(unix)]
+    fn process_groups(&self) -> Vec<libc::pid_t> {
+        let mut groups = Vec::new();
+        if let Some(pid) = self.pid {
+            // The shell is a session leader, so its process group id is its pid
+            groups.push(pid as libc::pid_t);
+        }
+        if let Some(leader) = self
+            .pty_pair
+            .as_ref()
+            .and_then(|pty_pair| pty_pair.master.process_group_leader())
+            && !groups.contains(&leader)
+        {
+            groups.push(leader);
+        }
+        groups
+    }
+
+    /// End the shell the way closing a terminal does: SIGHUP first, so jobs can clean up,
+    /// and SIGKILL for whatever is still running after the grace period. The grace period
+    /// is waited out on a thread of its own, `join_reapers` waits for those
+    fn terminate_child(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+
+        #[cfg(unix)]
+        {
+            let grace_period = Duration::from_millis(
+                CONFIG
+                    .get()
+                    .and_then(|config| config.close_grace_period_ms)
+                    .unwrap_or(1000),
+            );
+
+            // Looked up now, the PTY is gone once the grace period is over
+            let groups = self.process_groups();
+            signal_process_groups(&groups, libc::SIGHUP);
+
+            let reaper = thread::spawn(move || {
+                let deadline = Instant::now() + grace_period;
+                while Instant::now() < deadline {
+                    if let Ok(Some(_)) = child.try_wait() {
+                        return;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+
+                warn!("Shell did not exit after SIGHUP, killing it");
+                signal_process_groups(&groups, libc::SIGKILL);
+                let _ = child.kill();
+                let _ = child.wait();
+            });
+            let mut reapers = REAPERS.lock().unwrap_or_else(|e| e.into_inner());
+            reapers.retain(|reaper| !reaper.is_finished());
+            reapers.push(reaper);
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    /// Tear down the PTY: stop its I/O threads and end and reap the child
+    pub fn stop(&mut self) {
+        self.input_sender = None;
+        self.terminate_child();
+        // Dropping the master closes the PTY, which ends the blocking read in the output thread
+        self.pty_pair = None;
+        self.is_running = false;
+        self.pid = None;
+        self.output_buffer.close();
+        self.set_throttled(false);
+    }
+
+    /// Replace the exited shell with a new one in a fresh terminal
+    pub fn restart(&mut self) {
+        self.stop();
+
+        let (cols, rows) = (
+            self.terminal_widget.term.buffer.width,
+            self.terminal_widget.term.buffer.height,
+        );
+        self.terminal_widget = TerminalWidget::new(cols, rows);
+        self.title = None;
+        // Make sure the new PTY gets the current size
+        self.last_size = PtySize::default();
+
+        self.start_or_report();
+    }
+
+    /// Restart the exited shell, right away unless it keeps dying instantly: then each restart
+    /// waits twice as long as the previous one
+    pub fn schedule_restart(&mut self) {
+        let delay = if self.started_at.elapsed() < QUICK_EXIT {
+            let delay = self.restart_delay;
+            self.restart_delay = (delay * 2).min(MAX_RESTART_DELAY);
+            delay
+        } else {
+            self.restart_delay = INITIAL_RESTART_DELAY;
+            Duration::ZERO
+        };
+
+        if delay.is_zero() {
+            self.restart();
+            return;
+        }
+
+        warn!("Shell exited right after starting, restarting in {delay:?}");
+        self.terminal_widget.term.feed(
+            format!(
+                "\r\n\x1b[33mShell exited, restarting in {}\x1b[0m\r\n",
+                crate::utils::to_human_readable_duration(delay)
+            )
+            .as_bytes(),
+        );
+        self.restart_at = Some(Instant::now() + delay);
+        self.egui_ctx.request_repaint_after(delay);
+    }
+
+    /// Run a restart scheduled by `schedule_restart` once it's due
+    pub fn restart_if_due(&mut self) {
+        if self.restart_at.is_some_and(|at| Instant::now() >= at) {
+            self.restart();
+        }
+    }
+
+    /// Returns the exit status once when the shell has exited
+    pub fn poll_exit(&mut self) -> Option<ExitStatus> {
+        let child = self.child.as_mut()?;
+        let status = match child.try_wait() {
+            Ok(Some(status)) => status,
+            Ok(None) => return None,
+            Err(e) => {
+                error!("Failed to check the shell status: {e}");
+                return None;
+            }
+        };
+
+        if status.success() {
+            info!("{}", describe_exit_status(&status));
+        } else {
+            warn!("{}", describe_exit_status(&status));
+        }
+        self.child = None;
+        self.is_running = false;
+        self.input_sender = None;
+        Some(status)
+    }
+
+    /// The current working directory of the shell
+    pub fn current_dir(&self) -> Option<PathBuf> {
+        self.pid.and_then(crate::utils::get_current_dir_from_pty)
+    }
+
+    /// The file a click on a listing hit: the names looked up in the directories named by the
+    /// arguments of the command, then in the shell's directory
+    pub fn resolve_listing_click(&self, click: &ListingClick) -> Option<PathBuf> {
+        let cwd = self.current_dir()?;
+        let mut directories: Vec<PathBuf> = click
+            .command
+            .split_whitespace()
+            .skip(1)
+            .filter(|argument| !argument.starts_with('-'))
+            .map(|argument| cwd.join(crate::utils::expand_home(argument)))
+            .collect();
+        directories.push(cwd);
+        click
+            .names
+            .iter()
+            .flat_map(|name| {
+                directories
+                    .iter()
+                    .map(move |directory| directory.join(name))
+            })
+            .find(|path| path.symlink_metadata().is_ok())
+    }
+
+    /// Name of the program in the foreground of the terminal (the shell when it's idle)
+    pub fn foreground_command(&self) -> Option<String> {
+        let pty_pair = self.pty_pair.as_ref()?;
+        self.child.as_ref()?;
+
+        #[cfg(unix)]
+        {
+            let leader = pty_pair.master.process_group_leader()?;
+            let comm = std::fs::read_to_string(format!("/proc/{leader}/comm")).ok()?;
+            Some(comm.trim_end().to_string())
+        }
+
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    /// Command line of the program in the foreground, its arguments separated by spaces
+    pub fn foreground_command_line(&self) -> Option<String> {
+        let pty_pair = self.pty_pair.as_ref()?;
+        self.child.as_ref()?;
+        let leader = pty_pair.master.process_group_leader()?;
+        let cmdline = std::fs::read(format!("/proc/{leader}/cmdline")).ok()?;
+        Some(
+            cmdline
+                .split(|&byte| byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(String::from_utf8_lossy)
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    /// Take over the settings of `config`, with the tab's profile on top
+    pub fn apply_config(&mut self, config: &Config, previous: Option<&Config>) {
+        self.terminal_widget.apply_config(config, previous);
+        // Profiles may have changed, so the match starts over
+        self.profile = None;
+        self.title_prefix = None;
+        self.apply_profile(config);
+        self.profile_checked_at = Some(Instant::now());
+    }
+
+    /// Switch to the profile matching the directory and foreground program now, if it changed.
+    /// Looks them up every `PROFILE_CHECK_INTERVAL`
+    pub fn update_profile(&mut self) {
+        if self
+            .profile_checked_at
+            .is_some_and(|checked_at| checked_at.elapsed() < PROFILE_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.profile_checked_at = Some(Instant::now());
+        if let Some(config) = CONFIG.get() {
+            self.apply_profile(&config);
+        }
+    }
+
+    fn apply_profile(&mut self, config: &Config) {
+        if config.profiles.is_empty() && self.profile.is_none() {
+            return;
+        }
+
+        let profile = config.profile_for(
+            self.current_dir().as_deref(),
+            self.foreground_command_line().as_deref(),
+        );
+        if profile == self.profile {
+            return;
+        }
+
+        // The profile's colors replace the configured ones only in this terminal, so they
+        // never end up in the configuration itself
+        match profile.and_then(|index| config.profiles.get(index).map(|p| (index, p))) {
+            Some((index, matched)) => {
+                info!("Profile {} applies", matched.label(index));
+                self.terminal_widget
+                    .term
+                    .set_palette(matched.palette().clone());
+                self.title_prefix = matched.title_prefix.clone();
+            }
+            None => {
+                self.terminal_widget
+                    .term
+                    .set_palette(config.palette().clone());
+                self.title_prefix = None;
+            }
+        }
+        self.profile = profile;
+    }
+
+    /// Put in front of the window title by the tab's profile
+    pub fn title_prefix(&self) -> Option<&str> {
+        self.title_prefix.as_deref()
+    }
+
+    /// Window title from `template`, where `{cwd}`, `{command}` and `{title}` are replaced
+    /// with the shell's directory, the foreground program and the application's title
+    pub fn format_title(&self, template: &str) -> String {
+        let cwd = self
+            .current_dir()
+            .map(|dir| crate::utils::to_display_path(&dir))
+            .unwrap_or_default();
+        let command = self.foreground_command().unwrap_or_default();
+
+        template
+            .replace("{cwd}", &cwd)
+            .replace("{command}", &command)
+            .replace("{title}", self.title().unwrap_or_default())
+    }
+
+    /// The title of the application, if it set one
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn take_finished_commands(&mut self) -> Vec<FinishedCommand> {
+        std::mem::take(&mut self.finished_commands)
+    }
+
+    /// Label of the tab: the OSC title, or else the name of the current directory
+    pub fn label(&self) -> String {
+        if let Some(title) = self.title() {
+            return title.to_string();
+        }
+        match self.current_dir() {
+            Some(dir) => dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| dir.to_string_lossy().to_string()),
+            None => "shell".to_string(),
+        }
+    }
+
+    /// Parse the output that arrived since the last frame, within a time and size budget so
+    /// a flood of output can't stall the UI. What doesn't fit is left for the next frames.
+    pub fn handle_pty_output(&mut self) {
+        if self.player.is_some() {
+            self.play_due_events();
+            return;
+        }
+        let backlog = self.output_buffer.len();
+        if backlog == 0 {
+            self.set_throttled(false);
+            return;
+        }
+
+        // With a large backlog, spend more time per frame on parsing and none on drawing
+        // intermediate screens nobody can read anyway, until it is all parsed
+        if backlog > HIGH_WATER_MARK {
+            self.set_throttled(true);
+        }
+        let (budget, max_bytes) = if self.throttled {
+            (THROTTLED_FRAME_BUDGET, usize::MAX)
+        } else {
+            (FRAME_BUDGET, MAX_BYTES_PER_FRAME)
+        };
+        let deadline = Instant::now() + budget;
+        // Checked before the output is parsed, which may reply again
+        let replied_before = self
+            .replied_at
+            .is_some_and(|replied_at| replied_at.elapsed() < REPLY_ECHO_WINDOW);
+
+        let mut processed = 0;
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        while processed < max_bytes && self.output_buffer.take(&mut chunk, CHUNK_SIZE) {
+            self.process_output(&chunk);
+            processed += chunk.len();
+            if Instant::now() >= deadline {
+                break;
+            }
+        }
+
+        self.note_output(processed, replied_before);
+
+        if self.output_buffer.len() == 0 {
+            self.set_throttled(false);
+        } else {
+            self.egui_ctx.request_repaint();
+        }
+    }
+
+    /// Throttle the session or end it, the terminal keeping the screen as last drawn meanwhile
+    fn set_throttled(&mut self, throttled: bool) {
+        self.throttled = throttled;
+        self.terminal_widget.frozen = throttled;
+    }
+
+    /// Count `bytes` of output, which are activity unless they may be the echo of a reply
+    fn note_output(&mut self, bytes: usize, replied_before: bool) {
+        self.bytes_received += bytes as u64;
+        if !replied_before {
+            self.last_output_at = Some(Instant::now());
+            self.unseen_output = true;
+        }
+    }
+
+    /// The tab is being looked at, which clears its activity
+    pub fn mark_seen(&mut self) {
+        self.unseen_output = false;
+    }
+
+    /// What to show on the tab while it isn't looked at; silence only with `silence_after`
+    pub fn activity(&self, silence_after: Option<Duration>) -> Option<TabActivity> {
+        if !self.unseen_output {
+            return None;
+        }
+        let silent = self.last_output_at.zip(silence_after).is_some_and(
+            |(last_output_at, silence_after)| last_output_at.elapsed() >= silence_after,
+        );
+        Some(match silent {
+            true => TabActivity::Silence,
+            false => TabActivity::Output,
+        })
+    }
+
+    /// Apply the events of the recording that are due, and wake up for the next one
+    fn play_due_events(&mut self) {
+        let Some(player) = &mut self.player else {
+            return;
+        };
+        let events = player.due_events();
+        if let Some(delay) = player.until_next() {
+            self.egui_ctx.request_repaint_after(delay);
+        }
+        for event in events {
+            match event {
+                PlayerEvent::Output(data) => self.process_output(data.as_bytes()),
+                PlayerEvent::Resize(cols, rows) => self.terminal_widget.set_fixed_size(cols, rows),
+            }
+        }
+    }
+
+    /// Play the recording again from the start, on a fresh terminal
+    fn rewind_player(&mut self) {
+        let Some(player) = &mut self.player else {
+            return;
+        };
+        player.rewind();
+        let font_size = self.terminal_widget.font_size;
+        self.terminal_widget = TerminalWidget::new(player.width, player.height);
+        self.terminal_widget.set_font_size(font_size);
+        self.terminal_widget
+            .set_fixed_size(player.width, player.height);
+        self.egui_ctx.request_repaint();
+    }
+
+    /// Pause, rewind and speed buttons over the bottom right corner of the terminal
+    fn show_player_controls(&mut self, ctx: &egui::Context, rect: egui::Rect) {
+        let Some(player) = &mut self.player else {
+            return;
+        };
+        let mut rewind = false;
+        egui::Area::new(egui::Id::new(("player_controls", &player.path)))
+            .fixed_pos(rect.right_bottom() + egui::vec2(-8.0, -8.0))
+            .pivot(egui::Align2::RIGHT_BOTTOM)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        rewind = ui
+                            .button("⏮")
+                            .on_hover_text("Play from the start")
+                            .clicked();
+                        let paused = player.is_paused();
+                        if ui
+                            .button(if paused { "▶" } else { "⏸" })
+                            .on_hover_text(if paused { "Play" } else { "Pause" })
+                            .clicked()
+                        {
+                            player.set_paused(!paused);
+                        }
+                        for speed in asciicast::SPEEDS {
+                            if ui
+                                .selectable_label(player.speed == speed, format!("{speed}×"))
+                                .clicked()
+                            {
+                                player.set_speed(speed);
+                            }
+                        }
+                        let (position, length) = player.progress();
+                        ui.label(format!("{position:.1} / {length:.1} s"));
+                        if player.is_finished() {
+                            ui.weak("ended");
+                        }
+                    });
+                });
+            });
+        if !player.is_paused() && !player.is_finished() {
+            // Keeps the time shown running between events
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if rewind {
+            self.rewind_player();
+        }
+    }
+
+    /// Record the output from now on to the asciicast file `path`
+    pub fn start_recording(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let recorder = Recorder::create(
+            path,
+            self.terminal_widget.term.buffer.width as u16,
+            self.terminal_widget.term.buffer.height as u16,
+        )?;
+        info!("Recording the terminal to {}", recorder.path.display());
+        *self.recorder.lock() = Some(recorder);
+        Ok(())
+    }
+
+    /// Stop the recording, returning the file it went to
+    pub fn stop_recording(&mut self) -> Option<PathBuf> {
+        let recorder = self.recorder.lock().take()?;
+        info!("Recorded the terminal to {}", recorder.path.display());
+        Some(recorder.path.clone())
+    }
+
+    fn process_output(&mut self, data: &[u8]) {
+        self.terminal_widget.term.feed(data);
+
+        // An empty title hands the title back to the template
+        if let Some(title) = self.terminal_widget.term.take_title() {
+            self.title = Some(title).filter(|title| !title.is_empty());
+        }
+
+        for event in self.terminal_widget.term.take_command_events() {
+            match event {
+                CommandEvent::Started { command } => {
+                    self.running_command = Some((command, Instant::now()));
+                }
+                CommandEvent::Finished { exit_code } => {
+                    if let Some((command, started_at)) = self.running_command.take() {
+                        self.finished_commands.push(FinishedCommand {
+                            command,
+                            exit_code,
+                            duration: started_at.elapsed(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if self.terminal_widget.term.take_bell() {
+            self.ring_bell();
+        }
+        for event in self.terminal_widget.term.take_rule_events() {
+            match event.action {
+                RuleAction::Notify => {
+                    crate::utils::send_desktop_notification("explotty", &event.message);
+                }
+                // A rule asked for the bell, so it isn't silent even with `bell_mode = "none"`
+                RuleAction::Bell if self.terminal_widget.behavior.bell_mode == BellMode::None => {
+                    self.terminal_widget.flash();
+                    self.egui_ctx.request_repaint();
+                }
+                RuleAction::Bell => self.ring_bell(),
+                // Marks stay with the terminal, which draws them
+                RuleAction::Mark => {}
+            }
+        }
+        if self.terminal_widget.behavior.scrollback_on_output == ScrollbackOnOutput::Bottom {
+            self.terminal_widget.scroll_to_bottom();
+        }
+
+        // Answers to queries such as DSR go back to the shell of this session
+        let replies = self.terminal_widget.term.take_replies();
+        if !replies.is_empty() {
+            self.write_to_pty(replies);
+            self.replied_at = Some(Instant::now());
+        }
+    }
+
+    /// Answer the bell of the application as configured with `bell_mode`
+    fn ring_bell(&mut self) {
+        match self.terminal_widget.behavior.bell_mode {
+            BellMode::None => {}
+            BellMode::Visual => {
+                self.terminal_widget.flash();
+                self.egui_ctx.request_repaint();
+            }
+            BellMode::Urgent => {
+                if !self.egui_ctx.input(|i| i.focused) {
+                    self.egui_ctx
+                        .send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                            egui::UserAttentionType::Informational,
+                        ));
+                }
+            }
+        }
+    }
+
+    fn write_to_pty(&self, data: Vec<u8>) {
+        if let Some(sender) = &self.input_sender {
+            // Fails only when the input thread is gone, i.e. the shell already exited
+            let _ = sender.send(data);
+        }
+    }
+
+    pub fn send_input(&self, data: &[u8]) {
+        if !data.is_empty() {
+            self.write_to_pty(data.to_vec());
+        }
+
+        session_log::log_input_data(data);
+    }
+
+    /// Type a command line into the shell. Whatever was typed so far is erased with Ctrl+U
+    /// first, unless shell integration tells that nothing was
+    pub fn run_command(&self, command: &str) {
+        let typed = self.terminal_widget.term.command_line();
+        if typed.is_none_or(|typed| !typed.is_empty()) {
+            self.send_input(b"\x15");
+        }
+        self.send_input(format!("{command}\r").as_bytes());
+    }
+
+    /// Draw the terminal, keep the PTY size in sync and forward keyboard input
+    pub fn show(&mut self, ui: &mut egui::Ui) -> egui::Response {
+        let response = self.terminal_widget.show(ui);
+
+        // Applications drawing images read the pixel size, so it is reported in physical pixels
+        // and a change of it alone (e.g. zooming) counts as a resize as well
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        let cols = self.terminal_widget.term.buffer.width;
+        let rows = self.terminal_widget.term.buffer.height;
+        let size = PtySize {
+            rows: rows as u16,
+            cols: cols as u16,
+            pixel_width: (cols as f32 * self.terminal_widget.char_width * pixels_per_point).round()
+                as u16,
+            pixel_height: (rows as f32 * self.terminal_widget.line_height * pixels_per_point)
+                .round() as u16,
+        };
+
+        if self.last_size != size {
+            self.resize_pty(size);
+            self.last_size = size;
+            if let Some(recorder) = &mut *self.recorder.lock() {
+                recorder.resize(size.cols, size.rows);
+            }
+        }
+
+        if let Some(recorder) = &*self.recorder.lock() {
+            let name = recorder
+                .path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy();
+            ui.painter().text(
+                response.rect.left_top() + egui::vec2(8.0, 8.0),
+                egui::Align2::LEFT_TOP,
+                format!("● REC {name}"),
+                egui::FontId::proportional(12.0),
+                egui::Color32::RED,
+            );
+        }
+        self.show_player_controls(ui.ctx(), response.rect);
+
+        if self.throttled {
+            ui.painter().text(
+                response.rect.right_top() + egui::vec2(-8.0, 8.0),
+                egui::Align2::RIGHT_TOP,
+                "…output throttled…",
+                egui::FontId::proportional(12.0),
+                egui::Color32::YELLOW,
+            );
+        }
+
+        response
+    }
+
+    pub fn handle_input(&mut self, ctx: &egui::Context) {
+        let input_data = self.terminal_widget.handle_input(ctx);
+        self.send_input(&input_data);
+    }
+
+    fn resize_pty(&mut self, size: PtySize) {
+        if let Some(ref pty_pair) = self.pty_pair
+            && let Err(e) = pty_pair.master.resize(size)
+        {
+            error!("Failed to resize PTY: {e}");
+        }
+    }
+}
+
+/// Human readable account of how the shell ended, telling a normal exit from a crash
+pub fn describe_exit_status(status: &ExitStatus) -> String {
+    match status.signal() {
+        Some(description) => match signal_name(description) {
+            Some(name) => format!("Shell terminated by {name} ({description})"),
+            None => format!("Shell terminated by signal: {description}"),
+        },
+        None => format!("Shell exited with status {}", status.exit_code()),
+    }
+}
+
+/// portable-pty only keeps the `strsignal` description of a signal; find the signal's name
+/// by comparing it with the descriptions of the signals that usually end a shell
+fn signal_name(description: &str) -> Option<&'static str> {
+    #[cfg(unix)]
+    {
+        const SIGNALS: [(libc::c_int, &str); 13] = [
+            (libc::SIGHUP, "SIGHUP"),
+            (libc::SIGINT, "SIGINT"),
+            (libc::SIGQUIT, "SIGQUIT"),
+            (libc::SIGILL, "SIGILL"),
+            (libc::SIGTRAP, "SIGTRAP"),
+            (libc::SIGABRT, "SIGABRT"),
+            (libc::SIGBUS, "SIGBUS"),
+            (libc::SIGFPE, "SIGFPE"),
+            (libc::SIGKILL, "SIGKILL"),
+            (libc::SIGSEGV, "SIGSEGV"),
+            (libc::SIGPIPE, "SIGPIPE"),
+            (libc::SIGALRM, "SIGALRM"),
+            (libc::SIGTERM, "SIGTERM"),
+        ];
+
+        SIGNALS.iter().find_map(|&(signal, name)| {
+            // SAFETY: strsignal returns a valid C string (or null) for any signal number
+            let signal_description = unsafe { libc::strsignal(signal) };
+            if signal_description.is_null() {
+                return None;
+            }
+            let signal_description = unsafe { std::ffi::CStr::from_ptr(signal_description) };
+            (signal_description.to_string_lossy() == description).then_some(name)
+        })
+    }
+
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Closing a tab or the app without `stop` ends the shell and the I/O threads all the same
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, io::Read};
+
+    use super::*;
+
+    /// What `cmd` prints when run in a PTY of its own, until it exits
+    fn run_in_pty(cmd: CommandBuilder) -> String {
+        let pty_pair = native_pty_system()
+            .openpty(PtySize::default())
+            .expect("failed to open a PTY");
+        let mut child = pty_pair.slave.spawn_command(cmd).unwrap();
+        let mut reader = pty_pair.master.try_clone_reader().unwrap();
+        // Only the child holds the shell side now, so the reader ends when it exits
+        drop(pty_pair.slave);
+
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 4096];
+        // EIO once the child is gone
+        while let Ok(n @ 1..) = reader.read(&mut buffer) {
+            output.extend_from_slice(&buffer[..n]);
+        }
+        child.wait().unwrap();
+        String::from_utf8_lossy(&output).replace("\r\n", "\n")
+    }
+
+    /// The variables `env` sees in the PTY with `config`
+    fn env_in_pty(config: Option<&Config>) -> Vec<String> {
+        let mut cmd = CommandBuilder::new("env");
+        set_env_vars(&mut cmd, config);
+        run_in_pty(cmd).lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn the_shell_gets_the_terminal_capabilities() {
+        let vars = env_in_pty(None);
+        assert!(
+            vars.iter().any(|var| var == "TERM=xterm-256color"),
+            "{vars:?}"
+        );
+        assert!(
+            vars.iter().any(|var| var == "COLORTERM=truecolor"),
+            "{vars:?}"
+        );
+        assert!(
+            !vars
+                .iter()
+                .any(|var| var.starts_with("LINES=") || var.starts_with("COLUMNS=")),
+            "{vars:?}"
+        );
+
+        let mut config = Config::default();
+        config.term = Some("xterm-direct".to_string());
+        let vars = env_in_pty(Some(&config));
+        assert!(
+            vars.iter().any(|var| var == "TERM=xterm-direct"),
+            "{vars:?}"
+        );
+    }
+
+    #[test]
+    fn config_variables_override_the_parent_and_env_remove_wins() {
+        // Three variables of explotty's own environment
+        let parent: Vec<(String, String)> = std::env::vars()
+            .filter(|(key, value)| {
+                !["PATH", "TERM", "COLORTERM", "LINES", "COLUMNS"].contains(&key.as_str())
+                    && !value.is_empty()
+                    && !value.contains(['\n', '$'])
+            })
+            .take(3)
+            .collect();
+        let [(kept, kept_value), (overridden, _), (removed, _)] = parent.as_slice() else {
+            panic!("too few variables to test with: {parent:?}");
+        };
+
+        let mut config = Config::default();
+        config.env = Some(HashMap::from([
+            (overridden.clone(), "from-config".to_string()),
+            ("TERM".to_string(), "from-config".to_string()),
+            ("EXPLOTTY_ADDED".to_string(), "added".to_string()),
+            ("EXPLOTTY_REMOVED".to_string(), "removed".to_string()),
+            (
+                "EXPLOTTY_EXPANDED".to_string(),
+                format!("${{{kept}}}|${{EXPLOTTY_SURELY_UNSET}}|${kept}|${{{kept}"),
+            ),
+        ]));
+        config.env_remove = Some(vec![removed.clone(), "EXPLOTTY_REMOVED".to_string()]);
+        let vars = env_in_pty(Some(&config));
+
+        let value = |key: &str| {
+            vars.iter()
+                .find_map(|var| var.strip_prefix(key)?.strip_prefix('='))
+        };
+        assert_eq!(value(kept), Some(kept_value.as_str()), "{vars:?}");
+        assert_eq!(value(overridden), Some("from-config"), "{vars:?}");
+        assert_eq!(value("TERM"), Some("from-config"), "{vars:?}");
+        assert_eq!(value("EXPLOTTY_ADDED"), Some("added"), "{vars:?}");
+        assert_eq!(value(removed), None, "{vars:?}");
+        assert_eq!(value("EXPLOTTY_REMOVED"), None, "{vars:?}");
+        assert_eq!(
+            value("EXPLOTTY_EXPANDED"),
+            Some(format!("{kept_value}||${kept}|${{{kept}").as_str()),
+            "{vars:?}"
+        );
+    }
+
+    /// Names of the threads of this process still running
+    fn thread_names() -> Vec<String> {
+        std::fs::read_dir("/proc/self/task")
+            .unwrap()
+            .filter_map(|task| std::fs::read_to_string(task.ok()?.path().join("comm")).ok())
+            .map(|name| name.trim_end().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn dropping_a_session_ends_its_io_threads() {
+        let session = TerminalSession::new(&egui::Context::default(), None);
+        assert!(session.is_running);
+        let names = [
+            format!("pty-reader-{}", session.id),
+            format!("pty-writer-{}", session.id),
+        ];
+        let running = || {
+            let threads = thread_names();
+            names.iter().filter(|name| threads.contains(name)).count()
+        };
+        let wait_for = |count| {
+            let started = Instant::now();
+            while running() != count {
+                assert!(
+                    started.elapsed() < Duration::from_secs(10),
+                    "expected {count} of {names:?}, running: {:?}",
+                    thread_names()
+                );
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
+        // Threads name themselves once they run
+        wait_for(2);
+
+        drop(session);
+        // The shell gets SIGHUP and the reader sees the PTY closed once it exited
+        wait_for(0);
+        join_reapers();
+    }
+
+    #[test]
+    fn the_output_thread_waits_while_the_backlog_is_full() {
+        let buffer = Arc::new(OutputBuffer::default());
+        assert!(buffer.push(&vec![b'x'; MAX_BACKLOG]));
+
+        let (sender, receiver) = mpsc::channel();
+        let pusher = {
+            let buffer = buffer.clone();
+            thread::spawn(move || {
+                let pushed = buffer.push(b"late");
+                sender.send(()).unwrap();
+                pushed
+            })
+        };
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // Taking output makes room
+        let mut chunk = Vec::new();
+        assert!(buffer.take(&mut chunk, CHUNK_SIZE));
+        assert_eq!(chunk.len(), CHUNK_SIZE);
+        receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(pusher.join().unwrap());
+        assert_eq!(buffer.len(), MAX_BACKLOG - CHUNK_SIZE + 4);
+
+        // Closing releases a waiting thread, which then stops
+        assert!(buffer.push(&vec![b'x'; CHUNK_SIZE]));
+        let pusher = {
+            let buffer = buffer.clone();
+            thread::spawn(move || buffer.push(b"after"))
+        };
+        thread::sleep(Duration::from_millis(50));
+        buffer.close();
+        assert!(!pusher.join().unwrap());
+        assert_eq!(buffer.len(), 0);
+        assert!(!buffer.take(&mut chunk, CHUNK_SIZE));
+    }
+
+    #[test]
+    fn a_large_backlog_is_parsed_in_order_without_drawing_until_it_drains() {
+        let mut session = TerminalSession::unstarted(&egui::Context::default(), None);
+        let line = format!("{}\r\n", "x".repeat(78));
+        let flood = line.repeat(HIGH_WATER_MARK / line.len() + 1);
+        assert!(session.output_buffer.push(flood.as_bytes()));
+
+        session.handle_pty_output();
+        assert!(session.throttled);
+        assert!(session.terminal_widget.frozen);
+
+        // Output arriving meanwhile goes after the backlog
+        assert!(session.output_buffer.push(b"last line"));
+        let mut frames = 1;
+        while session.output_buffer.len() > 0 {
+            // Stays throttled until the backlog is gone, however small it got
+            assert!(session.throttled);
+            session.handle_pty_output();
+            frames += 1;
+            assert!(frames < 10_000, "the backlog doesn't drain");
+        }
+        assert!(!session.throttled);
+        assert!(!session.terminal_widget.frozen);
+        assert_eq!(
+            session.bytes_received,
+            (flood.len() + "last line".len()) as u64
+        );
+        let screen = session.terminal_widget.term.screen_text();
+        assert_eq!(screen.lines().last(), Some("last line"), "{screen}");
+        assert_eq!(screen.lines().nth(22), Some("x".repeat(78).as_str()));
+    }
+
+    #[test]
+    fn small_output_is_parsed_at_once_and_drawn() {
+        let mut session = TerminalSession::unstarted(&egui::Context::default(), None);
+        assert!(session.output_buffer.push(b"hello"));
+        session.handle_pty_output();
+        assert!(!session.throttled);
+        assert!(!session.terminal_widget.frozen);
+        assert_eq!(session.output_buffer.len(), 0);
+        assert!(
+            session
+                .terminal_widget
+                .term
+                .screen_text()
+                .starts_with("hello")
+        );
+    }
+}
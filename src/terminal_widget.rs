@@ -1,82 +1,175 @@
-pub mod color;
+mod accessibility;
+mod cell_info;
+mod color;
 mod input;
+pub mod listing;
 mod render;
 
+use std::{collections::HashMap, time::Instant};
+
 use eframe::egui::{self, Color32};
 
 use crate::{
-    parser::{
-        dispatcher::SequenceDispatcher, handler_context::HandlerContext,
-        sequence_tokenizer::SequenceTokenizer,
+    CONFIG,
+    config::{Behavior, Config},
+    term::{
+        DEFAULT_SCROLLBACK_LINES, Term, export, terminal_buffer::DEFAULT_TAB_WIDTH,
+        terminal_cell::TerminalCell,
     },
-    terminal_buffer::TerminalBuffer,
-    terminal_cell::TerminalCell,
+    terminal_widget::{accessibility::AccessibleLines, listing::ListingClick, render::RowCache},
 };
 
+pub const DEFAULT_FONT_SIZE: f32 = 14.0;
+
 pub struct TerminalWidget {
-    pub buffer: TerminalBuffer,
+    pub term: Term,
     pub font_size: f32,
     pub char_width: f32,
     pub line_height: f32,
-    pub show_cursor: bool,
-    tokenizer: SequenceTokenizer,
-    dispatcher: SequenceDispatcher,
     selection_start: Option<(usize, usize)>,
     selection_end: Option<(usize, usize)>,
-    bracket_paste_mode: bool,
-    // Storage location for current screen information used when Alternative Screen Buffer is used
-    saved_screen_buffer: Option<TerminalBuffer>,
-    // DEC Private Mode states
-    decckm_mode: bool,        // DECCKM - Cursor Key Application Mode (?1h/l)
-    decom_mode: bool,         // DECOM - Origin Mode (?6h/l)
-    decawm_mode: bool,        // DECAWM - Auto Wrap Mode (?7h/l)
-    reverse_video_mode: bool, // DECSCNM - Screen Reverse Video (?5h/l)
     scroll_offset: usize,
-    max_scroll_lines: usize,
-    scrollback_buffer: Vec<Vec<TerminalCell>>,
-    new_line_mode: bool,
     empty_line: Vec<TerminalCell>,
+    /// The `[behavior]` settings
+    pub behavior: Behavior,
+    /// When the visual bell started
+    bell_flash: Option<Instant>,
+    /// Several lines pasted while paste protection is on, waiting for a confirmation
+    pending_paste: Option<String>,
+    /// Input produced while drawing (mouse reports, confirmed pastes), sent with the keyboard's
+    pending_input: Vec<u8>,
+    /// The button held down while the application gets the mouse, as reported
+    mouse_button: Option<u8>,
+    /// The cell the mouse was last reported in, so motion is only reported between cells
+    mouse_cell: Option<(usize, usize)>,
+    /// The visible lines as screen readers were last given them
+    accessible_lines: AccessibleLines,
+    /// The generation of the buffer and the scroll offset last drawn
+    rendered: Option<(u64, usize)>,
+    /// The shapes of the rows last drawn
+    row_cache: RowCache,
+    /// Show the rows as last drawn instead of laying out the ones that changed, while output
+    /// comes in faster than anyone can read it
+    pub frozen: bool,
+    /// Opened for the first copy with HTML and kept, so the data stays offered
+    clipboard: Option<arboard::Clipboard>,
+    /// Show the state of the hovered cell in a tooltip
+    pub inspect_cells: bool,
+    /// Opacity of the default background, for a window the desktop shows through
+    pub background_opacity: f32,
+    /// Cells kept whatever the space, e.g. those of a recording played back
+    pub fixed_size: Option<(usize, usize)>,
+    /// A click on a file name in the output of `ls`, taken with `take_listing_click`
+    listing_click: Option<ListingClick>,
+    /// "Save as PDF…" was picked in the context menu, taken with `take_pdf_request`
+    pdf_requested: bool,
+    /// The images of the terminal uploaded for drawing, by their serials
+    image_textures: HashMap<u64, egui::TextureHandle>,
+    /// Screen rows marked by the inspector
+    #[cfg(feature = "inspector")]
+    pub highlighted_rows: Option<std::ops::RangeInclusive<usize>>,
 }
 
 impl TerminalWidget {
     pub fn new(width: usize, height: usize) -> Self {
-        let font_size = 14.0;
-        Self {
-            buffer: TerminalBuffer::new(width, height),
+        let font_size = DEFAULT_FONT_SIZE;
+        let mut widget = Self {
+            term: Term::new(width, height),
             font_size,
             char_width: font_size * 0.6,
             line_height: font_size * 1.2,
-            show_cursor: true,
-            tokenizer: SequenceTokenizer::new(),
-            dispatcher: SequenceDispatcher::new(),
             selection_start: None,
             selection_end: None,
-            bracket_paste_mode: false,
-            saved_screen_buffer: None,
-            // Initialize DEC Private Mode states to their default values
-            decckm_mode: false,        // Cursor key normal mode
-            decom_mode: false,         // Absolute origin mode
-            decawm_mode: true,         // Auto wrap mode enabled by default
-            reverse_video_mode: false, // Normal video mode
             scroll_offset: 0,
-            max_scroll_lines: 1000,
-            scrollback_buffer: Vec::new(),
-            new_line_mode: true,
             empty_line: vec![TerminalCell::default(); width],
+            behavior: Behavior::default(),
+            bell_flash: None,
+            pending_paste: None,
+            pending_input: Vec::new(),
+            mouse_button: None,
+            mouse_cell: None,
+            accessible_lines: AccessibleLines::default(),
+            rendered: None,
+            row_cache: RowCache::default(),
+            frozen: false,
+            clipboard: None,
+            inspect_cells: cfg!(feature = "debug-outline"),
+            background_opacity: 1.0,
+            fixed_size: None,
+            listing_click: None,
+            pdf_requested: false,
+            image_textures: HashMap::new(),
+            #[cfg(feature = "inspector")]
+            highlighted_rows: None,
+        };
+        if let Some(config) = CONFIG.get() {
+            widget.apply_config(&config, None);
         }
+        widget
+    }
+
+    /// Take over the settings of `config`, replacing `previous`. The font size only changes
+    /// with `font_size`, so a terminal zoomed otherwise stays as it is
+    pub fn apply_config(&mut self, config: &Config, previous: Option<&Config>) {
+        self.row_cache.clear();
+        self.term.set_palette(config.palette().clone());
+        self.term.max_scroll_lines = config.scrollback_lines.unwrap_or(DEFAULT_SCROLLBACK_LINES);
+        self.term.set_default_cursor(
+            config.cursor_style.unwrap_or_default(),
+            config.cursor_blink.unwrap_or(false),
+        );
+        self.behavior = config.behavior.clone();
+        // Setting the tab width drops the stops set by the application, so only on a change
+        if previous.is_none_or(|previous| previous.tab_width != config.tab_width) {
+            self.term
+                .set_tab_width(config.tab_width.map_or(DEFAULT_TAB_WIDTH, usize::from));
+        }
+        self.term.tab_insert_spaces = config.tab_insert_spaces.unwrap_or(false);
+        self.term
+            .set_answerback(config.answerback.as_deref().unwrap_or_default());
+        self.term.set_highlight_rules(&config.highlights);
+        if config.font_size != previous.and_then(|previous| previous.font_size) {
+            self.set_font_size(config.font_size.unwrap_or(DEFAULT_FONT_SIZE));
+        }
+    }
+
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.row_cache.clear();
+        self.font_size = font_size;
+        self.char_width = font_size * 0.6;
+        self.line_height = font_size * 1.2;
+    }
+
+    /// Keep the terminal at `cols`×`rows` cells whatever the space
+    pub fn set_fixed_size(&mut self, cols: usize, rows: usize) {
+        self.fixed_size = Some((cols, rows));
+        self.term.resize(cols, rows);
+        self.empty_line.resize(cols, TerminalCell::default());
+    }
+
+    /// Flash the terminal for the visual bell
+    pub fn flash(&mut self) {
+        self.bell_flash = Some(Instant::now());
+    }
+
+    /// Go back to the bottom of the scrollback
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_offset = 0;
     }
 
     pub fn show(&mut self, ui: &mut egui::Ui) -> egui::Response {
         let available_size = ui.available_size();
 
         // Calculate terminal size
-        let cols = (available_size.x / self.char_width) as usize;
-        let rows = (available_size.y / self.line_height) as usize;
+        let (cols, rows) = self.fixed_size.unwrap_or((
+            (available_size.x / self.char_width) as usize,
+            (available_size.y / self.line_height) as usize,
+        ));
 
         // Adjust buffer size
-        if cols != self.buffer.width || rows != self.buffer.height {
-            self.buffer.resize(cols, rows);
-            self.adjust_scrollback_buffer_width(cols);
+        if cols != self.term.buffer.width || rows != self.term.buffer.height {
+            self.term.resize(cols, rows);
+            self.empty_line.resize(cols, TerminalCell::default());
         }
 
         let response = ui.allocate_response(available_size, egui::Sense::click_and_drag());
@@ -84,78 +177,335 @@ impl TerminalWidget {
         // Handle scrolling with mouse wheel and keyboard
         self.handle_scroll(ui);
 
-        // Selection logic
+        // The application gets the mouse if it asked for it, otherwise it selects text
+        let rect = response.rect;
+        if self.reports_mouse(ui) {
+            self.report_mouse(ui, rect);
+        } else {
+            self.handle_selection(ui, &response);
+            response.context_menu(|ui| self.show_context_menu(ui));
+        }
+
+        // Draw background
+        ui.painter().rect_filled(
+            response.rect,
+            0.0,
+            Color32::from(self.term.palette().background).gamma_multiply(self.background_opacity),
+        );
+
+        // Draw the terminal cells (characters) with scrolling consideration, and the selection
+        self.draw_terminal_content(ui, &rect);
+        self.draw_images(ui, &rect);
+        self.draw_marks(ui, &rect);
+
+        // Draw cursor (only when at the bottom of scroll)
+        if self.scroll_offset == 0 {
+            self.draw_cursor(ui, &rect);
+        }
+
+        // Draw scroll indicator if scrolled
+        if self.scroll_offset > 0 {
+            self.draw_scroll_indicator(ui, &rect);
+        }
+
+        #[cfg(feature = "inspector")]
+        if let Some(rows) = self.highlighted_rows.clone()
+            && self.scroll_offset == 0
+        {
+            let top = rect.top() + *rows.start() as f32 * self.line_height;
+            let bottom = rect.top() + (*rows.end() + 1) as f32 * self.line_height;
+            ui.painter().rect_filled(
+                egui::Rect::from_x_y_ranges(rect.x_range(), top..=bottom),
+                0.0,
+                Color32::from_rgba_unmultiplied(255, 200, 0, 40),
+            );
+        }
+
+        if self.behavior.accessibility {
+            let cursor = (self.scroll_offset == 0 && self.term.show_cursor)
+                .then_some((self.term.buffer.cursor_x, self.term.buffer.cursor_y));
+            let mut accessible_lines = std::mem::take(&mut self.accessible_lines);
+            let lines = self.get_visible_lines();
+            let changed = self.changed_rows(lines.len());
+            let lines: Vec<_> = lines.into_iter().zip(changed).collect();
+            accessible_lines.publish(
+                ui.ctx(),
+                response.id,
+                rect,
+                self.line_height,
+                &lines,
+                cursor,
+            );
+            self.accessible_lines = accessible_lines;
+        } else {
+            self.accessible_lines = AccessibleLines::default();
+        }
+        // Frozen, the rows changed since stay due for the first frame after
+        if !self.frozen {
+            self.rendered = Some((self.term.buffer.generation, self.scroll_offset));
+        }
+
+        if self.inspect_cells {
+            self.show_cell_tooltip(ui, &response);
+        }
+
+        self.draw_bell_flash(ui, &rect);
+        self.show_paste_confirmation(ui.ctx());
+
+        response
+    }
+
+    /// Whether each of the `rows` visible rows may differ from when it was last drawn: the
+    /// rows the buffer changed since, or all of them once the view scrolled
+    fn changed_rows(&self, rows: usize) -> Vec<bool> {
+        let Some((generation, scroll_offset)) = self.rendered else {
+            return vec![true; rows];
+        };
+        if scroll_offset != self.scroll_offset {
+            return vec![true; rows];
+        }
+        if self.scroll_offset > 0 {
+            // New lines move the history under the view
+            return vec![self.term.buffer.generation != generation; rows];
+        }
+        let mut changed = vec![false; rows];
+        for row in self.term.buffer.changed_rows_since(generation) {
+            if let Some(changed) = changed.get_mut(row) {
+                *changed = true;
+            }
+        }
+        changed
+    }
+
+    fn handle_selection(&mut self, ui: &egui::Ui, response: &egui::Response) {
         let rect = response.rect;
 
         if response.drag_started()
             && let Some(pos) = response.hover_pos()
         {
-            let col = ((pos.x - rect.left()) / self.char_width).floor() as usize;
-            let row = ((pos.y - rect.top()) / self.line_height).floor() as usize;
-            let clamped_col = col.min(self.buffer.width.saturating_sub(1));
-            let clamped_row = row.min(self.buffer.height.saturating_sub(1));
-            self.selection_start = Some((clamped_col, clamped_row));
-            self.selection_end = Some((clamped_col, clamped_row));
+            let cell = self.cell_at(rect, pos);
+            self.selection_start = Some(cell);
+            self.selection_end = Some(cell);
         }
 
         if response.dragged()
             && let Some(pos) = response.hover_pos()
         {
-            let col = ((pos.x - rect.left()) / self.char_width).floor() as usize;
-            let row = ((pos.y - rect.top()) / self.line_height).floor() as usize;
-            let clamped_col = col.min(self.buffer.width.saturating_sub(1));
-            let clamped_row = row.min(self.buffer.height.saturating_sub(1));
-            self.selection_end = Some((clamped_col, clamped_row));
+            self.selection_end = Some(self.cell_at(rect, pos));
         }
 
         if response.clicked() {
             self.selection_start = None;
             self.selection_end = None;
+            // Plain clicks only, the modifiers select or open other things
+            if ui.input(|i| i.modifiers.is_none())
+                && let Some(pos) = response.interact_pointer_pos()
+            {
+                let (col, row) = self.cell_at(rect, pos);
+                self.listing_click = self.listing_click_at(col, row);
+            }
         }
 
-        // Draw background
-        ui.painter().rect_filled(response.rect, 0.0, Color32::BLACK);
+        if response.double_clicked()
+            && let Some(pos) = response.hover_pos()
+        {
+            let (col, row) = self.cell_at(rect, pos);
+            self.select_word(col, row);
+        }
 
-        // Draw the terminal cells (characters) with scrolling consideration
-        self.draw_terminal_content(ui, &rect);
+        if (response.drag_stopped() || response.double_clicked())
+            && self.behavior.copy_on_select
+            && let Some(text) = self.selected_text()
+        {
+            ui.ctx().copy_text(text);
+        }
+    }
 
-        // Draw cursor (only when at the bottom of scroll)
-        if self.scroll_offset == 0 {
-            self.draw_cursor(ui, &rect);
+    /// The cell at `pos`, clamped to the screen
+    fn cell_at(&self, rect: egui::Rect, pos: egui::Pos2) -> (usize, usize) {
+        let col = ((pos.x - rect.left()) / self.char_width).floor().max(0.0) as usize;
+        let row = ((pos.y - rect.top()) / self.line_height).floor().max(0.0) as usize;
+        (
+            col.min(self.term.buffer.width.saturating_sub(1)),
+            row.min(self.term.buffer.height.saturating_sub(1)),
+        )
+    }
+
+    /// Select the word at a cell: letters, digits and the characters of `word_chars`
+    fn select_word(&mut self, col: usize, row: usize) {
+        let visible_lines = self.get_visible_lines();
+        let Some(line) = visible_lines.get(row) else {
+            return;
+        };
+        let is_word = |col: &usize| {
+            line.get(*col).is_some_and(|cell| {
+                cell.character.is_alphanumeric()
+                    || self.behavior.word_chars.contains(cell.character)
+            })
+        };
+        if !is_word(&col) {
+            return;
         }
 
-        // Draw selection
-        self.draw_selection(ui, &rect);
+        let start = (0..col).rev().take_while(is_word).last().unwrap_or(col);
+        let end = (col + 1..line.len())
+            .take_while(is_word)
+            .last()
+            .unwrap_or(col);
+        self.selection_start = Some((start, row));
+        self.selection_end = Some((end, row));
+    }
 
-        // Draw scroll indicator if scrolled
-        if self.scroll_offset > 0 {
-            self.draw_scroll_indicator(ui, &rect);
+    /// The text of the selected cells, one line per row
+    fn selected_text(&self) -> Option<String> {
+        let (start, end) = self.selection_start.zip(self.selection_end)?;
+        let mut selected_text = String::new();
+
+        let (start_row, end_row) = (start.1.min(end.1), start.1.max(end.1));
+        let (start_col, end_col) = (start.0.min(end.0), start.0.max(end.0));
+
+        let visible_lines = self.get_visible_lines();
+        for r in start_row..=end_row {
+            for c in start_col..=end_col {
+                if r < visible_lines.len() && c < visible_lines[r].len() {
+                    selected_text.push(visible_lines[r][c].character);
+                }
+            }
+            if r < end_row {
+                selected_text.push('\n');
+            }
         }
 
-        response
+        Some(selected_text)
+    }
+
+    /// Copying the selection, or the whole screen without one, with or without its colors
+    fn show_context_menu(&mut self, ui: &mut egui::Ui) {
+        if let Some(text) = self.selected_text()
+            && ui.button("Copy").clicked()
+        {
+            ui.ctx().copy_text(text);
+        }
+        if ui.button("Copy as HTML").clicked() {
+            let lines = self.export_lines();
+            let palette = self.term.palette();
+            let html = export::to_html(&lines, palette);
+            let text = export::to_text(&lines, palette);
+            self.copy_html(ui.ctx(), html, text);
+        }
+        if ui.button("Copy as ANSI").clicked() {
+            let ansi = export::to_ansi(&self.export_lines(), self.term.palette());
+            ui.ctx().copy_text(ansi);
+        }
+        if ui.button("Save as PDF…").clicked() {
+            self.pdf_requested = true;
+        }
+    }
+
+    /// Take the request to save as PDF made in the context menu since the last call
+    pub fn take_pdf_request(&mut self) -> bool {
+        std::mem::take(&mut self.pdf_requested)
+    }
+
+    /// The selected part of the visible lines, all of them without a selection
+    pub fn export_lines(&self) -> Vec<&[TerminalCell]> {
+        let visible_lines = self.get_visible_lines();
+        let Some((start, end)) = self.selection_start.zip(self.selection_end) else {
+            return visible_lines;
+        };
+
+        let (start_row, end_row) = (start.1.min(end.1), start.1.max(end.1));
+        let (start_col, end_col) = (start.0.min(end.0), start.0.max(end.0));
+        visible_lines
+            .into_iter()
+            .skip(start_row)
+            .take(end_row + 1 - start_row)
+            .map(|line| {
+                let end = (end_col + 1).min(line.len());
+                &line[start_col.min(end)..end]
+            })
+            .collect()
+    }
+
+    /// Put `html` on the clipboard, with `text` for applications that only take plain text
+    fn copy_html(&mut self, ctx: &egui::Context, html: String, text: String) {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => Ok(clipboard),
+            None => arboard::Clipboard::new().map(|clipboard| self.clipboard.insert(clipboard)),
+        };
+        if let Err(e) = clipboard.and_then(|clipboard| clipboard.set_html(html, Some(text.clone())))
+        {
+            warn!("Failed to copy HTML, copying plain text: {e}");
+            ctx.copy_text(text);
+        }
+    }
+
+    /// Ask whether to paste several lines, with paste protection
+    fn show_paste_confirmation(&mut self, ctx: &egui::Context) {
+        let Some(text) = &self.pending_paste else {
+            return;
+        };
+
+        let mut confirmed = None;
+        egui::Modal::new(egui::Id::new("paste_confirmation")).show(ctx, |ui| {
+            ui.heading("Paste several lines?");
+            ui.label("Each line may run as a command:");
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    ui.monospace(text);
+                });
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Paste").clicked() {
+                    confirmed = Some(true);
+                }
+                if ui.button("Cancel").clicked() || ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    confirmed = Some(false);
+                }
+            });
+        });
+
+        match confirmed {
+            Some(true) => {
+                if let Some(text) = self.pending_paste.take() {
+                    let bytes = self.paste_bytes(&text);
+                    self.pending_input.extend_from_slice(&bytes);
+                }
+            }
+            Some(false) => self.pending_paste = None,
+            None => {}
+        }
     }
 
     fn get_visible_lines(&self) -> Vec<&[TerminalCell]> {
         if self.scroll_offset == 0 {
             // At the bottom, show current buffer
-            return self.buffer.cells.iter().map(|l| l.as_slice()).collect();
+            return self
+                .term
+                .screen_cells()
+                .iter()
+                .map(|l| l.as_slice())
+                .collect();
         }
 
         let mut visible_lines = Vec::new();
 
-        for i in 0..self.buffer.height {
-            let line_index_from_bottom = self.scroll_offset + self.buffer.height - 1 - i;
+        for i in 0..self.term.buffer.height {
+            let line_index_from_bottom = self.scroll_offset + self.term.buffer.height - 1 - i;
 
-            if line_index_from_bottom < self.buffer.height {
+            if line_index_from_bottom < self.term.buffer.height {
                 // This line is in the current buffer
-                let buffer_line_index = self.buffer.height - 1 - line_index_from_bottom;
-                visible_lines.push(self.buffer.cells[buffer_line_index].as_slice());
+                let buffer_line_index = self.term.buffer.height - 1 - line_index_from_bottom;
+                visible_lines.push(self.term.buffer.cells[buffer_line_index].as_slice());
             } else {
                 // This line is in the scrollback buffer
-                let scrollback_index = line_index_from_bottom - self.buffer.height;
-                if scrollback_index < self.scrollback_buffer.len() {
-                    let scrollback_line_index = self.scrollback_buffer.len() - 1 - scrollback_index;
-                    visible_lines.push(self.scrollback_buffer[scrollback_line_index].as_slice());
+                let scrollback_index = line_index_from_bottom - self.term.buffer.height;
+                if scrollback_index < self.term.scrollback_buffer.len() {
+                    let scrollback_line_index =
+                        self.term.scrollback_buffer.len() - 1 - scrollback_index;
+                    visible_lines
+                        .push(self.term.scrollback_buffer[scrollback_line_index].as_slice());
                 } else {
                     // Empty line if we're beyond available history
                     visible_lines.push(self.empty_line.as_slice());
@@ -165,40 +515,4 @@ impl TerminalWidget {
 
         visible_lines
     }
-
-    fn adjust_scrollback_buffer_width(&mut self, new_width: usize) {
-        // Adjust existing scrollback lines to new width
-        for line in &mut self.scrollback_buffer {
-            if line.len() < new_width {
-                line.resize(new_width, TerminalCell::default());
-            } else if line.len() > new_width {
-                line.truncate(new_width);
-            }
-        }
-
-        self.empty_line.resize(new_width, TerminalCell::default());
-    }
-
-    pub fn process_output(&mut self, ctx: &egui::Context, data: &[u8]) {
-        let tokens = self.tokenizer.feed(data);
-
-        for token in tokens {
-            let mut handler_ctx = HandlerContext {
-                buffer: &mut self.buffer,
-                scrollback_buffer: &mut self.scrollback_buffer,
-                saved_screen_buffer: &mut self.saved_screen_buffer,
-                max_scroll_lines: &mut self.max_scroll_lines,
-                decckm_mode: &mut self.decckm_mode,
-                decom_mode: &mut self.decom_mode,
-                decawm_mode: &mut self.decawm_mode,
-                reverse_video_mode: &mut self.reverse_video_mode,
-                show_cursor: &mut self.show_cursor,
-                bracket_paste_mode: &mut self.bracket_paste_mode,
-                new_line_mode: &mut self.new_line_mode,
-                ctx,
-            };
-
-            self.dispatcher.dispatch(&mut handler_ctx, token);
-        }
-    }
 }
@@ -0,0 +1,106 @@
+//! The terminal's lines as AccessKit nodes, so screen readers get the text the painter draws
+
+use eframe::egui::{self, accesskit};
+
+use crate::term::terminal_cell::TerminalCell;
+
+/// The text of the visible rows as last published. egui builds the accessibility tree anew
+/// every frame, but the text of a row is only rebuilt when its cells changed, and the adapter
+/// only reports the nodes that differ from the last frame
+#[derive(Default)]
+pub struct AccessibleLines {
+    lines: Vec<String>,
+}
+
+impl AccessibleLines {
+    /// Publish `lines`, drawn from the top of `rect`, as the children of the node `id`. Each
+    /// line comes with whether it may have changed since the last frame; only those are
+    /// compared with their text. The caret goes to `cursor` (column, row) when it is on the
+    /// screen. Nothing is done while no assistive technology is listening
+    pub fn publish(
+        &mut self,
+        ctx: &egui::Context,
+        id: egui::Id,
+        rect: egui::Rect,
+        line_height: f32,
+        lines: &[(&[TerminalCell], bool)],
+        cursor: Option<(usize, usize)>,
+    ) {
+        let listening = ctx.accesskit_node_builder(id, |node| {
+            node.set_role(accesskit::Role::Terminal);
+            // New output is announced without interrupting
+            node.set_live(accesskit::Live::Polite);
+        });
+        if listening.is_none() {
+            // Changes go unseen meanwhile, so every row is compared again once published
+            self.lines.clear();
+            return;
+        }
+
+        // Rows that weren't published before have no text to compare with
+        let all = self.lines.len() != lines.len();
+        self.lines.resize_with(lines.len(), String::new);
+        for (text, &(cells, changed)) in self.lines.iter_mut().zip(lines) {
+            if (all || changed) && !text.chars().eq(line_chars(cells)) {
+                *text = line_chars(cells).collect();
+            }
+        }
+
+        ctx.with_accessibility_parent(id, || {
+            for (row, text) in self.lines.iter().enumerate() {
+                let top = rect.top() + row as f32 * line_height;
+                ctx.accesskit_node_builder(id.with(row), |node| {
+                    node.set_role(accesskit::Role::TextRun);
+                    node.set_bounds(accesskit::Rect {
+                        x0: rect.left().into(),
+                        y0: top.into(),
+                        x1: rect.right().into(),
+                        y1: (top + line_height).into(),
+                    });
+                    node.set_text_direction(accesskit::TextDirection::LeftToRight);
+                    node.set_character_lengths(
+                        text.chars()
+                            .map(|ch| ch.len_utf8() as u8)
+                            .collect::<Vec<_>>(),
+                    );
+                    node.set_value(text.as_str());
+                });
+            }
+        });
+
+        if let Some((col, row)) = cursor
+            && let (Some(text), Some(cells)) =
+                (self.lines.get(row), lines.get(row).map(|&(cells, _)| cells))
+        {
+            // Wide characters take two cells but are one character of the text
+            let index = cells
+                .iter()
+                .take(col)
+                .filter(|cell| !cell.wide_tail)
+                .count()
+                .min(text.chars().count());
+            let position = accesskit::TextPosition {
+                node: id.with(row).value().into(),
+                character_index: index,
+            };
+            ctx.accesskit_node_builder(id, |node| {
+                node.set_text_selection(accesskit::TextSelection {
+                    anchor: position,
+                    focus: position,
+                });
+            });
+        }
+    }
+}
+
+/// The characters of a row as text: the tails of wide characters skipped, trailing blanks cut
+fn line_chars(cells: &[TerminalCell]) -> impl Iterator<Item = char> {
+    let end = cells
+        .iter()
+        .rposition(|cell| !cell.wide_tail && cell.character != ' ')
+        .map_or(0, |last| last + 1);
+    cells[..end]
+        .iter()
+        .filter(|cell| !cell.wide_tail)
+        .map(|cell| cell.character)
+}
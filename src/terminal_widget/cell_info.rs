@@ -0,0 +1,94 @@
+//! A tooltip with everything known about the cell under the pointer, for diagnosing rendering
+//! issues (on with the `debug-outline` feature, or toggled in the inspector)
+
+use eframe::egui;
+
+use crate::{
+    term::{color::Rgba, terminal_cell::TerminalCell},
+    terminal_widget::TerminalWidget,
+};
+
+impl TerminalWidget {
+    /// Show the state of the hovered cell next to the pointer. Not while a button is held, so
+    /// selecting by dragging isn't covered
+    pub(super) fn show_cell_tooltip(&self, ui: &egui::Ui, response: &egui::Response) {
+        if ui.input(|i| i.pointer.any_down()) {
+            return;
+        }
+        let Some(pos) = response.hover_pos() else {
+            return;
+        };
+        let (col, row) = self.cell_at(response.rect, pos);
+        let visible_lines = self.get_visible_lines();
+        let Some(line) = visible_lines.get(row) else {
+            return;
+        };
+        let Some(cell) = line.get(col) else {
+            return;
+        };
+        let wide = line.get(col + 1).is_some_and(|next| next.wide_tail);
+
+        let location = if self.term.is_alternate_screen() {
+            format!("({col}, {row}), alternate screen")
+        } else {
+            let line =
+                (self.term.screen_line() + row as u64).saturating_sub(self.scroll_offset as u64);
+            format!("({col}, {row}), line {line} of the history")
+        };
+        response.clone().on_hover_ui_at_pointer(|ui| {
+            egui::Grid::new("cell_info").num_columns(2).show(ui, |ui| {
+                ui.label("Character");
+                ui.monospace(describe_character(cell.character));
+                ui.end_row();
+                ui.label("Foreground");
+                ui.monospace(hex(cell.fg_color));
+                ui.end_row();
+                ui.label("Background");
+                ui.monospace(hex(cell.bg_color));
+                ui.end_row();
+                ui.label("Attributes");
+                ui.monospace(attributes(cell));
+                ui.end_row();
+                ui.label("Width");
+                ui.monospace(match (cell.wide_tail, wide) {
+                    (true, _) => "tail of a wide character",
+                    (false, true) => "wide",
+                    (false, false) => "narrow",
+                });
+                ui.end_row();
+                ui.label("Cell");
+                ui.monospace(location);
+                ui.end_row();
+            });
+        });
+    }
+}
+
+/// `'a' U+0061`, with control characters escaped
+fn describe_character(character: char) -> String {
+    format!("'{}' U+{:04X}", character.escape_debug(), character as u32)
+}
+
+fn hex(color: Rgba) -> String {
+    let [r, g, b, a] = color.to_array();
+    format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+}
+
+/// The attribute flags set on `cell`, `none` without any
+fn attributes(cell: &TerminalCell) -> String {
+    let flags: Vec<&str> = [
+        (cell.bold, "bold"),
+        (cell.italic, "italic"),
+        (cell.underline, "underline"),
+        (cell.blink, "blink"),
+        (cell.strikethrough, "strikethrough"),
+        (cell.hidden, "hidden"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| set.then_some(name))
+    .collect();
+    match flags.is_empty() {
+        true => "none".to_string(),
+        false => flags.join(" "),
+    }
+}
@@ -1,10 +1,10 @@
 use eframe::egui;
 
-use crate::terminal_widget::TerminalWidget;
+use crate::{term::MouseTracking, terminal_widget::TerminalWidget};
 
 impl TerminalWidget {
     pub fn handle_input(&mut self, ctx: &egui::Context) -> Vec<u8> {
-        let mut output = Vec::new();
+        let mut output = std::mem::take(&mut self.pending_input);
         let mut text_to_copy = None;
 
         // If we're scrolled up, any input should bring us back to bottom
@@ -14,36 +14,18 @@ impl TerminalWidget {
             for event in &i.events {
                 match event {
                     egui::Event::Copy => {
-                        if let Some((start, end)) = self.selection_start.zip(self.selection_end) {
-                            let mut selected_text = String::new();
-
-                            let (start_row, end_row) = (start.1.min(end.1), start.1.max(end.1));
-                            let (start_col, end_col) = (start.0.min(end.0), start.0.max(end.0));
-
-                            let visible_lines = self.get_visible_lines();
-                            for r in start_row..=end_row {
-                                for c in start_col..=end_col {
-                                    if r < visible_lines.len() && c < visible_lines[r].len() {
-                                        selected_text.push(visible_lines[r][c].character);
-                                    }
-                                }
-                                if r < end_row {
-                                    selected_text.push('\n');
-                                }
-                            }
-
-                            text_to_copy = Some(selected_text);
-                        }
-
+                        text_to_copy = self.selected_text();
                         output.extend_from_slice(b"\x03");
                     }
                     egui::Event::Paste(paste) => {
-                        let mut paste_text = paste.clone();
-                        if self.bracket_paste_mode {
-                            paste_text = format!("\x1b[200~{paste_text}\x1b[201~");
+                        if self.behavior.paste_protection
+                            && !self.term.bracket_paste_mode
+                            && paste.contains(['\n', '\r'])
+                        {
+                            self.pending_paste = Some(paste.clone());
+                        } else {
+                            output.extend_from_slice(&self.paste_bytes(paste));
                         }
-
-                        output.extend_from_slice(paste_text.as_bytes());
                     }
                     egui::Event::Key {
                         key,
@@ -64,28 +46,28 @@ impl TerminalWidget {
 
                             // Arrow keys
                             egui::Key::ArrowUp => {
-                                output.extend_from_slice(if self.decckm_mode {
+                                output.extend_from_slice(if self.term.decckm_mode {
                                     b"\x1bOA"
                                 } else {
                                     b"\x1b[A"
                                 });
                             }
                             egui::Key::ArrowDown => {
-                                output.extend_from_slice(if self.decckm_mode {
+                                output.extend_from_slice(if self.term.decckm_mode {
                                     b"\x1bOB"
                                 } else {
                                     b"\x1b[B"
                                 });
                             }
                             egui::Key::ArrowLeft => {
-                                output.extend_from_slice(if self.decckm_mode {
+                                output.extend_from_slice(if self.term.decckm_mode {
                                     b"\x1bOD"
                                 } else {
                                     b"\x1b[D"
                                 });
                             }
                             egui::Key::ArrowRight => {
-                                output.extend_from_slice(if self.decckm_mode {
+                                output.extend_from_slice(if self.term.decckm_mode {
                                     b"\x1bOC"
                                 } else {
                                     b"\x1b[C"
@@ -93,56 +75,56 @@ impl TerminalWidget {
                             }
 
                             // Numpad keys (only special in DECCKM application mode)
-                            egui::Key::Num0 if self.decckm_mode => {
+                            egui::Key::Num0 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOp")
                             }
-                            egui::Key::Num1 if self.decckm_mode => {
+                            egui::Key::Num1 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOq")
                             }
-                            egui::Key::Num2 if self.decckm_mode => {
+                            egui::Key::Num2 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOr")
                             }
-                            egui::Key::Num3 if self.decckm_mode => {
+                            egui::Key::Num3 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOs")
                             }
-                            egui::Key::Num4 if self.decckm_mode => {
+                            egui::Key::Num4 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOt")
                             }
-                            egui::Key::Num5 if self.decckm_mode => {
+                            egui::Key::Num5 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOu")
                             }
-                            egui::Key::Num6 if self.decckm_mode => {
+                            egui::Key::Num6 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOv")
                             }
-                            egui::Key::Num7 if self.decckm_mode => {
+                            egui::Key::Num7 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOw")
                             }
-                            egui::Key::Num8 if self.decckm_mode => {
+                            egui::Key::Num8 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOx")
                             }
-                            egui::Key::Num9 if self.decckm_mode => {
+                            egui::Key::Num9 if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOy")
                             }
-                            egui::Key::Plus if self.decckm_mode => {
+                            egui::Key::Plus if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOl")
                             }
-                            egui::Key::Minus if self.decckm_mode => {
+                            egui::Key::Minus if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOm")
                             }
                             // Why no asterisks? Huh? Process in text input instead
-                            /*egui::Key::Asterisk if self.decckm_mode => {
+                            /*egui::Key::Asterisk if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOj")
                             }*/
-                            egui::Key::Slash if self.decckm_mode => {
+                            egui::Key::Slash if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOo")
                             }
-                            egui::Key::Period if self.decckm_mode => {
+                            egui::Key::Period if self.term.decckm_mode => {
                                 output.extend_from_slice(b"\x1bOn")
                             }
 
                             // Enter keys
                             egui::Key::Enter => {
-                                if self.decckm_mode {
+                                if self.term.decckm_mode {
                                     output.extend_from_slice(b"\x1bOM");
                                 } else {
                                     output.extend_from_slice(b"\r");
@@ -170,7 +152,7 @@ impl TerminalWidget {
                     }
                     egui::Event::Text(text) => {
                         for ch in text.chars() {
-                            if ch == '*' && self.decckm_mode {
+                            if ch == '*' && self.term.decckm_mode {
                                 output.extend_from_slice(b"\x1bOj");
                             } else {
                                 let mut buf = [0; 4];
@@ -198,15 +180,26 @@ impl TerminalWidget {
         output
     }
 
+    /// What pasting `text` sends, bracketed if the application asked for it
+    pub fn paste_bytes(&self, text: &str) -> Vec<u8> {
+        if self.term.bracket_paste_mode {
+            format!("\x1b[200~{text}\x1b[201~").into_bytes()
+        } else {
+            text.as_bytes().to_vec()
+        }
+    }
+
     pub fn handle_scroll(&mut self, ui: &mut egui::Ui) {
+        // The wheel goes to an application that gets the mouse
+        let reports_mouse = self.reports_mouse(ui);
         ui.input(|i| {
             let scroll_delta = i.smooth_scroll_delta.y;
-            if scroll_delta.abs() > 0.0 {
+            if scroll_delta.abs() > 0.0 && !reports_mouse {
                 let lines_to_scroll = (scroll_delta / self.line_height).round() as i32;
 
                 if lines_to_scroll > 0 {
                     // Scrolling down
-                    let max_scroll = self.scrollback_buffer.len();
+                    let max_scroll = self.term.scrollback_buffer.len();
                     self.scroll_offset =
                         (self.scroll_offset + lines_to_scroll as usize).min(max_scroll);
                 } else {
@@ -227,18 +220,18 @@ impl TerminalWidget {
                 {
                     match key {
                         egui::Key::PageUp => {
-                            let scroll_amount = self.buffer.height.saturating_sub(1);
-                            let max_scroll = self.scrollback_buffer.len();
+                            let scroll_amount = self.term.buffer.height.saturating_sub(1);
+                            let max_scroll = self.term.scrollback_buffer.len();
                             self.scroll_offset =
                                 (self.scroll_offset + scroll_amount).min(max_scroll);
                         }
                         egui::Key::PageDown => {
-                            let scroll_amount = self.buffer.height.saturating_sub(1);
+                            let scroll_amount = self.term.buffer.height.saturating_sub(1);
                             self.scroll_offset = self.scroll_offset.saturating_sub(scroll_amount);
                         }
                         egui::Key::Home if modifiers.ctrl => {
                             // Ctrl+Home: Go to top of history
-                            self.scroll_offset = self.scrollback_buffer.len();
+                            self.scroll_offset = self.term.scrollback_buffer.len();
                         }
                         egui::Key::End if modifiers.ctrl => {
                             // Ctrl+End: Go to bottom (current)
@@ -250,4 +243,121 @@ impl TerminalWidget {
             }
         });
     }
+
+    /// Whether the mouse goes to the application: it asked for it, reporting is enabled and
+    /// Shift isn't held, which selects text instead
+    pub fn reports_mouse(&self, ui: &egui::Ui) -> bool {
+        self.behavior.mouse_reporting_enabled
+            && self.term.mouse_tracking != MouseTracking::Off
+            && !ui.input(|i| i.modifiers.shift)
+    }
+
+    /// Report presses, releases, motion and the wheel over `rect` to the application, in the
+    /// encoding it asked for
+    pub fn report_mouse(&mut self, ui: &egui::Ui, rect: egui::Rect) {
+        let tracking = self.term.mouse_tracking;
+        ui.input(|i| {
+            for event in &i.events {
+                match event {
+                    egui::Event::PointerButton {
+                        pos,
+                        button,
+                        pressed,
+                        modifiers,
+                    } => {
+                        let code = match button {
+                            egui::PointerButton::Primary => 0,
+                            egui::PointerButton::Middle => 1,
+                            egui::PointerButton::Secondary => 2,
+                            _ => continue,
+                        };
+                        // A release is reported where it happens, also outside the terminal
+                        if *pressed && !rect.contains(*pos) {
+                            continue;
+                        }
+                        if !*pressed && self.mouse_button != Some(code) {
+                            continue;
+                        }
+                        let cell = self.cell_at(rect, *pos);
+                        self.mouse_button = pressed.then_some(code);
+                        self.mouse_cell = Some(cell);
+                        self.push_mouse_report(code, *modifiers, cell, *pressed);
+                    }
+                    egui::Event::PointerMoved(pos) if rect.contains(*pos) => {
+                        let cell = self.cell_at(rect, *pos);
+                        if self.mouse_cell == Some(cell) {
+                            continue;
+                        }
+                        self.mouse_cell = Some(cell);
+                        let code = match (tracking, self.mouse_button) {
+                            (MouseTracking::Drag | MouseTracking::Motion, Some(button)) => button,
+                            // Motion without a button is reported as a release
+                            (MouseTracking::Motion, None) => 3,
+                            _ => continue,
+                        };
+                        self.push_mouse_report(code + 32, i.modifiers, cell, true);
+                    }
+                    egui::Event::MouseWheel {
+                        unit,
+                        delta,
+                        modifiers,
+                    } => {
+                        let Some(pos) = i.pointer.hover_pos().filter(|pos| rect.contains(*pos))
+                        else {
+                            continue;
+                        };
+                        let lines = match unit {
+                            egui::MouseWheelUnit::Line => delta.y,
+                            egui::MouseWheelUnit::Point => delta.y / self.line_height,
+                            egui::MouseWheelUnit::Page => delta.y * self.term.buffer.height as f32,
+                        };
+                        // Buttons 4 and 5 are wheel up and down
+                        let code = if lines > 0.0 { 64 } else { 65 };
+                        let cell = self.cell_at(rect, pos);
+                        for _ in 0..(lines.abs().round() as usize).max(1) {
+                            self.push_mouse_report(code, *modifiers, cell, true);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// Encode one mouse event for the application: `ESC [ < b ; x ; y M/m` in SGR mode, or
+    /// the classic `ESC [ M b x y` with coordinates limited to 223
+    fn push_mouse_report(
+        &mut self,
+        code: u8,
+        modifiers: egui::Modifiers,
+        (col, row): (usize, usize),
+        pressed: bool,
+    ) {
+        let mut code = code;
+        if modifiers.alt {
+            code += 8;
+        }
+        if modifiers.ctrl {
+            code += 16;
+        }
+
+        if self.term.sgr_mouse_mode {
+            let final_byte = if pressed { 'M' } else { 'm' };
+            self.pending_input.extend_from_slice(
+                format!("\x1b[<{code};{};{}{final_byte}", col + 1, row + 1).as_bytes(),
+            );
+        } else {
+            // The classic encoding can't tell which button was released
+            let code = if pressed { code } else { code | 3 };
+            let coordinate = |n: usize| (n + 33).min(255) as u8;
+            self.pending_input.extend_from_slice(&[
+                0x1b,
+                b'[',
+                b'M',
+                code + 32,
+                coordinate(col),
+                coordinate(row),
+            ]);
+        }
+    }
 }
@@ -0,0 +1,96 @@
+//! Clicking a name in the output of `ls` and the like, to select the file in the explorer
+
+use crate::{term::terminal_cell::TerminalCell, terminal_widget::TerminalWidget};
+
+/// Words joined at most into one name, for names with spaces
+const MAX_JOINED_WORDS: usize = 4;
+
+/// Decorations `ls -F` and `--classify` append to names
+const CLASSIFY_SUFFIXES: &[char] = &['/', '*', '@', '|', '=', '>'];
+
+/// A click on a listing: the names it may have hit and the command that printed them
+pub struct ListingClick {
+    /// Longest first, each followed by itself without quotes and decorations
+    pub names: Vec<String>,
+    pub command: String,
+}
+
+impl TerminalWidget {
+    /// The names around `col` of the visible `row`, when the row is part of a listing
+    pub(super) fn listing_click_at(&self, col: usize, row: usize) -> Option<ListingClick> {
+        if self.term.is_alternate_screen() {
+            return None;
+        }
+        let line = (self.term.screen_line() + row as u64).checked_sub(self.scroll_offset as u64)?;
+        let listing = self.term.listing_at(line)?;
+        let names = candidate_names(self.get_visible_lines().get(row)?, col);
+        (!names.is_empty()).then(|| ListingClick {
+            names,
+            command: listing.command.clone(),
+        })
+    }
+
+    /// Take the click on a listing since the last call
+    pub fn take_listing_click(&mut self) -> Option<ListingClick> {
+        self.listing_click.take()
+    }
+}
+
+/// The words of `cells` at `col`, alone and joined with their neighbors
+fn candidate_names(cells: &[TerminalCell], col: usize) -> Vec<String> {
+    let chars: Vec<(usize, char)> = cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| !cell.wide_tail)
+        .map(|(col, cell)| (col, cell.character))
+        .collect();
+
+    // Runs of characters other than spaces, as ranges of `chars`
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, (_, character)) in chars.iter().enumerate() {
+        match (character.is_whitespace(), start) {
+            (false, None) => start = Some(index),
+            (true, Some(word_start)) => {
+                words.push(word_start..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push(word_start..chars.len());
+    }
+
+    let mut spans = Vec::new();
+    for first in 0..words.len() {
+        for last in first..words.len().min(first + MAX_JOINED_WORDS) {
+            let (start, end) = (words[first].start, words[last].end);
+            // A wide character at the end covers the cell after it too
+            let last_col = chars[end - 1].0;
+            let last_col = match cells.get(last_col + 1) {
+                Some(next) if next.wide_tail => last_col + 1,
+                _ => last_col,
+            };
+            if (chars[start].0..=last_col).contains(&col) {
+                spans.push(start..end);
+            }
+        }
+    }
+    spans.sort_by_key(|span| std::cmp::Reverse(span.len()));
+
+    let mut names = Vec::new();
+    for span in spans {
+        let name: String = chars[span].iter().map(|(_, character)| character).collect();
+        let bare = name
+            .trim_end_matches(CLASSIFY_SUFFIXES)
+            .trim_matches(|character| character == '\'' || character == '"')
+            .to_string();
+        for name in [name, bare] {
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
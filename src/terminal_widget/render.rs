@@ -1,171 +1,445 @@
-use eframe::egui::{self, Color32, FontId, Pos2, Rect, TextFormat, text::LayoutJob};
-use std::time::{SystemTime, UNIX_EPOCH};
+use eframe::egui::{
+    self, Color32, FontId, Pos2, Rect, Shape, TextFormat, epaint::TextureAtlas, mutex::Mutex,
+    text::LayoutJob,
+};
+use std::{
+    ops::{Range, RangeInclusive},
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::terminal_widget::TerminalWidget;
+use crate::{
+    config::HighlightRule,
+    term::{CursorStyle, terminal_cell::TerminalCell},
+    terminal_widget::TerminalWidget,
+};
+
+/// The shapes of the visible rows as last drawn, reused for rows that stayed the same
+#[derive(Default)]
+pub struct RowCache {
+    /// The font atlas the text was laid out in
+    atlas: Weak<Mutex<TextureAtlas>>,
+    rows: Vec<Option<CachedRow>>,
+}
+
+impl RowCache {
+    /// Forget every row, for a change of the font or the colors
+    pub fn clear(&mut self) {
+        self.rows.clear();
+    }
+}
+
+struct CachedRow {
+    key: RowKey,
+    shapes: Vec<Shape>,
+}
+
+/// What the shapes of a row depend on besides its cells
+#[derive(PartialEq)]
+struct RowKey {
+    origin: Pos2,
+    selected: Option<RangeInclusive<usize>>,
+    highlights: Vec<(Range<usize>, usize)>,
+}
 
 impl TerminalWidget {
-    pub fn draw_terminal_content(&self, ui: &mut egui::Ui, rect: &Rect) {
+    pub fn draw_terminal_content(&mut self, ui: &mut egui::Ui, rect: &Rect) {
+        let mut row_cache = std::mem::take(&mut self.row_cache);
+        // Galleys point into the font atlas, which egui replaces when it fills up or the
+        // scale changes
+        let atlas = ui.ctx().fonts(|fonts| fonts.texture_atlas());
+        if !row_cache.atlas.ptr_eq(&Arc::downgrade(&atlas)) {
+            row_cache.clear();
+            row_cache.atlas = Arc::downgrade(&atlas);
+        }
+
         let visible_lines = self.get_visible_lines();
+        let changed = if self.frozen {
+            vec![false; visible_lines.len()]
+        } else {
+            self.changed_rows(visible_lines.len())
+        };
+        row_cache
+            .rows
+            .resize_with(visible_lines.len(), Default::default);
 
         for (row_index, row) in visible_lines.iter().enumerate() {
-            for (col_index, cell) in row.iter().enumerate() {
-                let pos = Pos2::new(
-                    rect.left() + col_index as f32 * self.char_width,
+            let key = RowKey {
+                origin: Pos2::new(
+                    rect.left(),
                     rect.top() + row_index as f32 * self.line_height,
-                );
+                ),
+                selected: self.selected_cols(row_index),
+                highlights: self.highlight_spans(row_index),
+            };
+            let cached = &mut row_cache.rows[row_index];
+            match cached {
+                Some(cached) if !changed[row_index] && cached.key == key => {
+                    ui.painter().extend(cached.shapes.iter().cloned());
+                }
+                _ => {
+                    let shapes = self.row_shapes(ui, row_index, row, key.origin);
+                    ui.painter().extend(shapes.iter().cloned());
+                    // Blinking cells change with the time, not with the buffer
+                    *cached =
+                        (!row.iter().any(|cell| cell.blink)).then_some(CachedRow { key, shapes });
+                }
+            }
+        }
+
+        self.row_cache = row_cache;
+    }
+
+    /// The shapes of the visible `row`, with its top left corner at `origin`
+    fn row_shapes(
+        &self,
+        ui: &egui::Ui,
+        row_index: usize,
+        row: &[TerminalCell],
+        origin: Pos2,
+    ) -> Vec<Shape> {
+        let palette = self.term.palette();
+        let highlights = self.highlights_of_row(row_index, row.len());
+        let mut shapes = Vec::new();
+        for (col_index, cell) in row.iter().enumerate() {
+            let highlight = highlights.get(col_index).copied().flatten();
+            let pos = Pos2::new(origin.x + col_index as f32 * self.char_width, origin.y);
+
+            // Draw background color, the selection's for selected cells
+            let selected = self.is_selected(col_index, row_index);
+            let bg_color = if selected {
+                Color32::from(palette.selection_background)
+            } else if let Some(bg) = highlight.and_then(|rule| rule.bg) {
+                Color32::from(bg)
+            } else {
+                Color32::from(cell.bg_color)
+            };
+            if bg_color != Color32::TRANSPARENT {
+                shapes.push(Shape::rect_filled(
+                    egui::Rect::from_min_size(pos, egui::vec2(self.char_width, self.line_height)),
+                    0.0,
+                    bg_color,
+                ));
+            }
 
-                // Draw background color
-                if cell.bg_color != Color32::TRANSPARENT {
-                    ui.painter().rect_filled(
-                        egui::Rect::from_min_size(
-                            pos,
-                            egui::vec2(self.char_width, self.line_height),
-                        ),
+            // Draw character
+            if cell.character != ' ' && !cell.wide_tail {
+                // Skip rendering hidden text
+                if cell.hidden {
+                    continue;
+                }
+
+                // Handle blinking - show/hide based on time
+                let should_show_blink = if cell.blink {
+                    // Blink every 500ms (2Hz)
+                    let current_time = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis();
+                    (current_time / 500).is_multiple_of(2)
+                } else {
+                    true
+                };
+
+                if should_show_blink {
+                    // Draw debug outline if debug-outline feature is enabled
+                    #[cfg(feature = "debug-outline")]
+                    {
+                        use egui::Stroke;
+
+                        shapes.push(Shape::rect_stroke(
+                            Rect {
+                                min: pos,
+                                max: pos + egui::vec2(self.char_width, self.line_height),
+                            },
+                            0,
+                            Stroke::new(1.0, Color32::RED),
+                            egui::StrokeKind::Middle,
+                        ));
+                    }
+
+                    let mut fg_color = highlight.and_then(|rule| rule.fg).unwrap_or(cell.fg_color);
+                    let bold = cell.bold || highlight.is_some_and(|rule| rule.bold);
+                    let underline = cell.underline || highlight.is_some_and(|rule| rule.underline);
+                    if bold
+                        && palette.bold_is_bright
+                        && let Some(bright) = palette.brightened(fg_color)
+                    {
+                        fg_color = bright;
+                    }
+                    if selected && let Some(selection_fg) = palette.selection_foreground {
+                        fg_color = selection_fg;
+                    }
+                    let color = Color32::from(fg_color);
+                    let font_id = FontId::monospace(self.font_size);
+
+                    let mut job = LayoutJob::default();
+                    job.append(
+                        &cell.character.to_string(),
                         0.0,
-                        cell.bg_color,
+                        TextFormat {
+                            font_id,
+                            italics: cell.italic,
+                            color,
+                            ..Default::default()
+                        },
                     );
-                }
 
-                // Draw character
-                if cell.character != ' ' && !cell.wide_tail {
-                    // Skip rendering hidden text
-                    if cell.hidden {
-                        continue;
+                    let galley = ui.painter().layout_job(job);
+                    shapes.push(Shape::galley(pos, galley, color));
+
+                    // Draw underline
+                    if underline {
+                        let underline_y = pos.y + self.line_height - 2.0;
+                        shapes.push(Shape::line_segment(
+                            [
+                                Pos2::new(pos.x, underline_y),
+                                Pos2::new(pos.x + self.char_width, underline_y),
+                            ],
+                            egui::Stroke::new(1.0, color),
+                        ));
                     }
 
-                    // Handle blinking - show/hide based on time
-                    let should_show_blink = if cell.blink {
-                        // Blink every 500ms (2Hz)
-                        let current_time = SystemTime::now()
-                            .duration_since(UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_millis();
-                        (current_time / 500).is_multiple_of(2)
-                    } else {
-                        true
-                    };
-
-                    if should_show_blink {
-                        // Draw debug outline if debug-outline feature is enabled
-                        #[cfg(feature = "debug-outline")]
-                        {
-                            use egui::Stroke;
-
-                            ui.painter().rect(
-                                Rect {
-                                    min: pos,
-                                    max: pos + egui::vec2(self.char_width, self.line_height),
-                                },
-                                0,
-                                Color32::TRANSPARENT,
-                                Stroke::new(1.0, Color32::RED),
-                                egui::StrokeKind::Middle,
-                            );
-                        }
-
-                        let mut color = cell.fg_color;
-                        let font_id = FontId::monospace(self.font_size);
-
-                        if cell.bold {
-                            color = Color32::from_rgb(
-                                (color.r() as u16 * 3 / 2).min(255) as u8,
-                                (color.g() as u16 * 3 / 2).min(255) as u8,
-                                (color.b() as u16 * 3 / 2).min(255) as u8,
-                            );
-                        }
-
-                        let mut job = LayoutJob::default();
-                        job.append(
-                            &cell.character.to_string(),
-                            0.0,
-                            TextFormat {
-                                font_id,
-                                italics: cell.italic,
-                                color,
-                                ..Default::default()
-                            },
-                        );
-
-                        let galley = ui.painter().layout_job(job);
-                        ui.painter().galley(Pos2::new(pos.x, pos.y), galley, color);
-
-                        // Draw underline
-                        if cell.underline {
-                            let underline_y = pos.y + self.line_height - 2.0;
-                            ui.painter().line_segment(
-                                [
-                                    Pos2::new(pos.x, underline_y),
-                                    Pos2::new(pos.x + self.char_width, underline_y),
-                                ],
-                                egui::Stroke::new(1.0, color),
-                            );
-                        }
-
-                        // Draw strikethrough
-                        if cell.strikethrough {
-                            let strikethrough_y = pos.y + self.line_height / 2.0;
-
-                            // Check if this is a wide character (first cell of a double-width character)
-                            let is_wide_char = !cell.wide_tail
-                                && col_index + 1 < row.len()
-                                && row[col_index + 1].wide_tail;
-
-                            let strikethrough_width = if is_wide_char {
-                                self.char_width * 2.0 // Cover both cells for wide characters
-                            } else {
-                                self.char_width // Cover one cell for normal characters
-                            };
-
-                            ui.painter().line_segment(
-                                [
-                                    Pos2::new(pos.x, strikethrough_y),
-                                    Pos2::new(pos.x + strikethrough_width, strikethrough_y),
-                                ],
-                                egui::Stroke::new(1.0, color),
-                            );
-                        }
+                    // Draw strikethrough
+                    if cell.strikethrough {
+                        let strikethrough_y = pos.y + self.line_height / 2.0;
+
+                        // Check if this is a wide character (first cell of a double-width character)
+                        let is_wide_char = !cell.wide_tail
+                            && col_index + 1 < row.len()
+                            && row[col_index + 1].wide_tail;
+
+                        let strikethrough_width = if is_wide_char {
+                            self.char_width * 2.0 // Cover both cells for wide characters
+                        } else {
+                            self.char_width // Cover one cell for normal characters
+                        };
+
+                        shapes.push(Shape::line_segment(
+                            [
+                                Pos2::new(pos.x, strikethrough_y),
+                                Pos2::new(pos.x + strikethrough_width, strikethrough_y),
+                            ],
+                            egui::Stroke::new(1.0, color),
+                        ));
                     }
                 }
             }
         }
+        shapes
     }
 
-    pub fn draw_cursor(&mut self, ui: &mut egui::Ui, rect: &Rect) {
-        if self.show_cursor {
-            let cursor_pos = Pos2::new(
-                rect.left() + self.buffer.cursor_x as f32 * self.char_width,
-                rect.top() + self.buffer.cursor_y as f32 * self.line_height,
+    /// The line of the visible `row`, counted like `Term::screen_line`, while highlights are
+    /// drawn on it
+    fn highlighted_line(&self, row: usize) -> Option<u64> {
+        if self.term.is_alternate_screen() {
+            return None;
+        }
+        (self.term.screen_line() + row as u64).checked_sub(self.scroll_offset as u64)
+    }
+
+    /// The highlight rule drawn over each of the `len` cells of the visible `row`, if any
+    fn highlights_of_row(&self, row: usize, len: usize) -> Vec<Option<&HighlightRule>> {
+        let mut highlights = vec![None; len];
+        let Some(line) = self.highlighted_line(row) else {
+            return highlights;
+        };
+        for (span, rule) in self.term.highlights(line) {
+            for highlight in highlights
+                .iter_mut()
+                .take(span.cols.end)
+                .skip(span.cols.start)
+            {
+                *highlight = Some(rule);
+            }
+        }
+        highlights
+    }
+
+    /// The highlighted columns of the visible `row` and the indices of their rules
+    fn highlight_spans(&self, row: usize) -> Vec<(Range<usize>, usize)> {
+        self.highlighted_line(row)
+            .into_iter()
+            .flat_map(|line| self.term.highlights(line))
+            .map(|(span, _)| (span.cols.clone(), span.rule))
+            .collect()
+    }
+
+    /// Draw the images placed by the application over the cells they cover
+    pub fn draw_images(&mut self, ui: &mut egui::Ui, rect: &Rect) {
+        let pixels_per_point = ui.ctx().pixels_per_point();
+        self.term.graphics.cell_pixels = (
+            (self.char_width * pixels_per_point).round().max(1.0) as u32,
+            (self.line_height * pixels_per_point).round().max(1.0) as u32,
+        );
+
+        let graphics = &self.term.graphics;
+        self.image_textures.retain(|serial, _| {
+            graphics
+                .images
+                .values()
+                .any(|image| image.serial == *serial)
+        });
+        if graphics.placements.is_empty() {
+            return;
+        }
+
+        let alternate = self.term.is_alternate_screen();
+        // The line shown in the top row
+        let top_line = if alternate {
+            0
+        } else {
+            self.term.screen_line() as i64 - self.scroll_offset as i64
+        };
+        let painter = ui.painter().with_clip_rect(*rect);
+        for placement in &graphics.placements {
+            if placement.alternate != alternate || (alternate && self.scroll_offset > 0) {
+                continue;
+            }
+            let Some(image) = graphics.images.get(&placement.image_id) else {
+                continue;
+            };
+            let row = placement.line as i64 - top_line;
+            if row + placement.rows as i64 <= 0 || row >= self.term.buffer.height as i64 {
+                continue;
+            }
+
+            let texture = self.image_textures.entry(image.serial).or_insert_with(|| {
+                ui.ctx().load_texture(
+                    format!("terminal-image-{}", image.serial),
+                    egui::ColorImage::from_rgba_unmultiplied(
+                        [image.width as usize, image.height as usize],
+                        &image.pixels,
+                    ),
+                    egui::TextureOptions::LINEAR,
+                )
+            });
+            let size = if placement.stretch {
+                egui::vec2(
+                    placement.cols as f32 * self.char_width,
+                    placement.rows as f32 * self.line_height,
+                )
+            } else {
+                egui::vec2(image.width as f32, image.height as f32) / pixels_per_point
+            };
+            let image_rect = Rect::from_min_size(
+                Pos2::new(
+                    rect.left() + placement.col as f32 * self.char_width,
+                    rect.top() + row as f32 * self.line_height,
+                ),
+                size,
+            );
+            painter.image(
+                texture.id(),
+                image_rect,
+                Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                Color32::WHITE,
             );
+        }
+    }
 
-            ui.painter().rect_filled(
-                Rect::from_min_size(cursor_pos, egui::vec2(self.char_width, self.line_height)),
-                0.0,
-                Color32::from_rgba_premultiplied(255, 255, 255, 128),
+    pub fn draw_cursor(&mut self, ui: &mut egui::Ui, rect: &Rect) {
+        if !self.term.show_cursor {
+            return;
+        }
+
+        if self.term.cursor_blink {
+            // Blink every 500ms, like blinking text
+            let current_time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            ui.ctx()
+                .request_repaint_after(Duration::from_millis(500 - (current_time % 500) as u64));
+            if !(current_time / 500).is_multiple_of(2) {
+                return;
+            }
+        }
+
+        let cursor_col = self.term.buffer.cursor_col();
+        let cursor_y = self.term.buffer.cursor_y;
+        let cursor_pos = Pos2::new(
+            rect.left() + cursor_col as f32 * self.char_width,
+            rect.top() + cursor_y as f32 * self.line_height,
+        );
+        let palette = self.term.palette();
+        let color = Color32::from(palette.cursor);
+
+        let cursor_rect = match self.term.cursor_style {
+            CursorStyle::Block => {
+                Rect::from_min_size(cursor_pos, egui::vec2(self.char_width, self.line_height))
+            }
+            CursorStyle::Underline => Rect::from_min_size(
+                Pos2::new(cursor_pos.x, cursor_pos.y + self.line_height - 2.0),
+                egui::vec2(self.char_width, 2.0),
+            ),
+            CursorStyle::Bar => Rect::from_min_size(cursor_pos, egui::vec2(2.0, self.line_height)),
+        };
+        ui.painter().rect_filled(cursor_rect, 0.0, color);
+
+        // An opaque block hides the character, which is drawn again in the background color
+        if self.term.cursor_style == CursorStyle::Block
+            && palette.cursor.a() == 255
+            && let Some(cell) = self
+                .term
+                .buffer
+                .cells
+                .get(cursor_y)
+                .and_then(|row| row.get(cursor_col))
+            && cell.character != ' '
+            && !cell.hidden
+        {
+            ui.painter().text(
+                cursor_pos,
+                egui::Align2::LEFT_TOP,
+                cell.character,
+                FontId::monospace(self.font_size),
+                Color32::from(palette.background),
             );
         }
     }
 
-    pub fn draw_selection(&self, ui: &mut egui::Ui, rect: &Rect) {
-        if let (Some(start), Some(end)) = (self.selection_start, self.selection_end) {
-            let (start_row, end_row) = (start.1.min(end.1), start.1.max(end.1));
-            let (start_col, end_col) = (start.0.min(end.0), start.0.max(end.0));
+    /// The columns of the visible `row` in the selected block
+    fn selected_cols(&self, row: usize) -> Option<RangeInclusive<usize>> {
+        let (Some(start), Some(end)) = (self.selection_start, self.selection_end) else {
+            return None;
+        };
+        (start.1.min(end.1)..=start.1.max(end.1))
+            .contains(&row)
+            .then(|| start.0.min(end.0)..=start.0.max(end.0))
+    }
 
-            for r in start_row..=end_row {
-                for c in start_col..=end_col {
-                    let pos = Pos2::new(
-                        rect.left() + c as f32 * self.char_width,
-                        rect.top() + r as f32 * self.line_height,
-                    );
-                    let selection_rect = egui::Rect::from_min_size(
-                        pos,
-                        egui::vec2(self.char_width, self.line_height),
-                    );
-                    ui.painter().rect_filled(
-                        selection_rect,
-                        0.0,
-                        Color32::from_rgba_premultiplied(100, 100, 100, 100),
-                    );
-                }
+    /// Whether the cell at `col`, `row` of the screen is in the selected block
+    fn is_selected(&self, col: usize, row: usize) -> bool {
+        let (Some(start), Some(end)) = (self.selection_start, self.selection_end) else {
+            return false;
+        };
+        (start.0.min(end.0)..=start.0.max(end.0)).contains(&col)
+            && (start.1.min(end.1)..=start.1.max(end.1)).contains(&row)
+    }
+
+    /// A bar at the left edge of the rows a rule with `action = "mark"` marked
+    pub fn draw_marks(&self, ui: &mut egui::Ui, rect: &Rect) {
+        if self.term.is_alternate_screen() {
+            return;
+        }
+        let color = ui.visuals().warn_fg_color;
+        for row in 0..self.get_visible_lines().len() {
+            let Some(line) =
+                (self.term.screen_line() + row as u64).checked_sub(self.scroll_offset as u64)
+            else {
+                continue;
+            };
+            if self.term.is_marked(line) {
+                let top = rect.top() + row as f32 * self.line_height;
+                ui.painter().rect_filled(
+                    Rect::from_min_size(
+                        Pos2::new(rect.left(), top),
+                        egui::vec2(2.0, self.line_height),
+                    ),
+                    0.0,
+                    color,
+                );
             }
         }
     }
@@ -182,4 +456,22 @@ impl TerminalWidget {
             Color32::YELLOW,
         );
     }
+
+    /// Lighten the terminal for a moment after the bell, with the visual bell
+    pub fn draw_bell_flash(&mut self, ui: &mut egui::Ui, rect: &Rect) {
+        const FLASH_DURATION: Duration = Duration::from_millis(150);
+
+        let Some(started) = self.bell_flash else {
+            return;
+        };
+        let elapsed = started.elapsed();
+        if elapsed >= FLASH_DURATION {
+            self.bell_flash = None;
+            return;
+        }
+
+        ui.painter()
+            .rect_filled(*rect, 0.0, Color32::from_white_alpha(40));
+        ui.ctx().request_repaint_after(FLASH_DURATION - elapsed);
+    }
 }
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+/// How long a toast stays on screen
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+
+/// Short messages shown in the corner of the window without blocking it
+#[derive(Default)]
+pub struct Toasts {
+    /// The messages with the time they were added
+    toasts: Vec<(String, Instant)>,
+}
+
+impl Toasts {
+    pub fn error(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        error!("{message}");
+        self.toasts.push((message, Instant::now()));
+    }
+
+    /// Draw the toasts in the bottom right corner, dropping expired ones
+    pub fn show(&mut self, ctx: &egui::Context) {
+        self.toasts
+            .retain(|(_, added)| added.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-12.0, -12.0])
+            .order(egui::Order::Foreground)
+            .interactable(false)
+            .show(ctx, |ui| {
+                for (message, _) in &self.toasts {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.colored_label(ui.visuals().error_fg_color, message);
+                    });
+                }
+            });
+
+        // Wake up again to remove the oldest one
+        if let Some(remaining) = self
+            .toasts
+            .iter()
+            .map(|(_, added)| TOAST_DURATION.saturating_sub(added.elapsed()))
+            .min()
+        {
+            ctx.request_repaint_after(remaining);
+        }
+    }
+}
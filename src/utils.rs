@@ -1,14 +1,11 @@
 use std::{
     path::{Path, PathBuf},
     process::Command,
+    time::Duration,
 };
 
-use eframe::egui::{Context, FontData, FontDefinitions, FontFamily};
-use font_kit::{
-    family_name::FamilyName, handle::Handle, properties::Properties, source::SystemSource,
-};
-use gio::glib::object::Cast;
-use gtk::traits::IconThemeExt;
+use eframe::egui::Context;
+use font_kit::source::SystemSource;
 
 use crate::CONFIG;
 
@@ -27,89 +24,51 @@ pub fn get_current_dir_from_pty(pid: u32) -> Option<PathBuf> {
     }
 }
 
-pub fn load_system_font(ctx: &Context) -> anyhow::Result<()> {
-    let mut fonts = FontDefinitions::default();
-
-    let (sans_serif_family, monospace_family) = if let Some(config) = CONFIG.get() {
-        (
-            FamilyName::Title(config.ui_font_family.clone().unwrap_or_default()),
-            FamilyName::Title(config.terminal_font_family.clone().unwrap_or_default()),
-        )
-    } else {
-        (FamilyName::SansSerif, FamilyName::Monospace)
-    };
+/// Quote `value` as a single word for POSIX shells: wrapped in single quotes, with embedded
+/// single quotes written as `'\''`
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
 
-    let terminal_fallback_fonts = if let Some(config) = CONFIG.get() {
-        config
-            .terminal_fallback_font_families
-            .clone()
-            .unwrap_or_default()
-    } else {
-        Vec::new()
-    };
+/// Replace `${VAR}` with the value of `VAR` in explotty's environment (empty if unset).
+/// `$` not followed by `{` is kept as is.
+pub fn expand_env_vars(value: &str) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
 
-    let sans_serif_handle =
-        SystemSource::new().select_best_match(&[sans_serif_family], &Properties::new())?;
-    let sans_serif_buf: Vec<u8> = match sans_serif_handle {
-        Handle::Memory { bytes, .. } => bytes.to_vec(),
-        Handle::Path { path, .. } => std::fs::read(path)?,
-    };
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                expanded.push_str(&std::env::var(&after[..end]).unwrap_or_default());
+                rest = &after[end + 1..];
+            }
+            None => {
+                // Unterminated, keep it literally
+                expanded.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    expanded.push_str(rest);
 
-    let monospace_handle =
-        SystemSource::new().select_best_match(&[monospace_family], &Properties::new())?;
-    let monospace_buf: Vec<u8> = match monospace_handle {
-        Handle::Memory { bytes, .. } => bytes.to_vec(),
-        Handle::Path { path, .. } => std::fs::read(path)?,
-    };
+    expanded
+}
 
-    let terminal_fallback_buffers = terminal_fallback_fonts
-        .iter()
-        .map(|family| {
-            let handle = SystemSource::new()
-                .select_best_match(&[FamilyName::Title(family.clone())], &Properties::new())?;
-            match handle {
-                Handle::Memory { bytes, .. } => Ok(bytes.to_vec()),
-                Handle::Path { path, .. } => Ok(std::fs::read(path)?),
-            }
-        })
-        .collect::<anyhow::Result<Vec<_>>>()?;
-
-    const SANS_SERIF_FONT_ID: &str = "System Sans Serif";
-    const MONOSPACE_FONT_ID: &str = "System Monospace";
-
-    fonts.font_data.insert(
-        SANS_SERIF_FONT_ID.to_string(),
-        FontData::from_owned(sans_serif_buf).into(),
-    );
-    fonts.font_data.insert(
-        MONOSPACE_FONT_ID.to_string(),
-        FontData::from_owned(monospace_buf).into(),
-    );
-    fonts.font_data.extend(
-        terminal_fallback_buffers
-            .into_iter()
-            .enumerate()
-            .map(|(i, buf)| {
-                (
-                    terminal_fallback_fonts[i].clone(),
-                    FontData::from_owned(buf).into(),
-                )
-            }),
-    );
-
-    if let Some(vec) = fonts.families.get_mut(&FontFamily::Proportional) {
-        vec.insert(0, SANS_SERIF_FONT_ID.to_string());
-        info!("Proportional font family: {vec:?}");
-    }
-    if let Some(vec) = fonts.families.get_mut(&FontFamily::Monospace) {
-        let mut fonts = vec![MONOSPACE_FONT_ID.to_string()];
-        fonts.extend(terminal_fallback_fonts.iter().cloned());
-        vec.splice(0..0, fonts);
-        info!("Monospace font family: {vec:?}");
+/// The path with the home directory shortened to `~`
+pub fn to_display_path(path: &Path) -> String {
+    match path.strip_prefix(gio::glib::home_dir()) {
+        Ok(relative) if relative.as_os_str().is_empty() => "~".to_string(),
+        Ok(relative) => format!("~/{}", relative.display()),
+        Err(_) => path.display().to_string(),
     }
+}
 
+/// Give egui the configured fonts
+pub fn load_system_font(ctx: &Context) -> anyhow::Result<()> {
+    let fonts = crate::fonts::resolve(CONFIG.get().as_deref(), &SystemSource::new())?;
     ctx.set_fonts(fonts);
-
     Ok(())
 }
 
@@ -130,63 +89,335 @@ pub fn to_human_readable_size(size: u64) -> String {
     }
 }
 
-pub fn get_mime_type_from_path(path: &Path) -> String {
-    match path.is_dir() {
-        true => "inode/directory".to_string(),
-        false => {
-            let (content_type, _) = gio::content_type_guess(Some(path), None);
-            content_type.to_string()
-        }
+/// Format a duration like `3m12s`
+pub fn to_human_readable_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 60 {
+        format!("{seconds}s")
+    } else if seconds < 60 * 60 {
+        format!("{}m{}s", seconds / 60, seconds % 60)
+    } else {
+        format!(
+            "{}h{}m{}s",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
     }
 }
 
-fn find_icon(mime_type: &str, size: i32) -> Option<String> {
-    let icon = gio::content_type_get_icon(mime_type);
+pub fn get_desc_from_mime_type(mime_type: &str) -> String {
+    let desc = gio::content_type_get_description(mime_type);
+    desc.to_string()
+}
+
+pub fn copy_file_uris_to_clipboard(paths: &[PathBuf]) {
+    let uri_list: String = paths
+        .iter()
+        .map(|path| path_to_file_uri(path) + "\r\n")
+        .collect();
 
-    if let Some(themed_icon) = icon.downcast_ref::<gio::ThemedIcon>() {
-        let icon_names = themed_icon.names();
-        let icon_theme = gtk::IconTheme::default();
+    let mut command = if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        let mut command = Command::new("wl-copy");
+        command.args(["--type", "text/uri-list"]);
+        command
+    } else {
+        let mut command = Command::new("xclip");
+        command.args(["-selection", "clipboard", "-t", "text/uri-list"]);
+        command
+    };
 
-        for name in icon_names {
-            if let Some(icon_info) =
-                icon_theme
-                    .clone()?
-                    .lookup_icon(&name, size, gtk::IconLookupFlags::empty())
-                && let Some(filename) = icon_info.filename()
-            {
-                return Some(filename.to_string_lossy().to_string());
+    // The list goes through stdin, so names need no quoting
+    let result = command
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .and_then(|mut child| {
+            if let Some(mut stdin) = child.stdin.take() {
+                std::io::Write::write_all(&mut stdin, uri_list.as_bytes())?;
             }
+            child.wait()
+        });
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            error!("Clipboard copy failed. Command exited with status: {status}");
+        }
+        Err(e) => {
+            error!("Failed to spawn clipboard command: {e}")
         }
     }
+}
 
-    None
+/// Whether `name` matches `pattern`, where `*` is any run of characters and `?` any one
+pub fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and the name position it is tried to cover up to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
-pub fn get_formatted_icon_path(mime_type: &str, size: i32) -> String {
-    format!("file://{}", find_icon(mime_type, size).unwrap_or_default())
+/// Replace a leading `~` with the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some("") => gio::glib::home_dir(),
+        Some(rest) if rest.starts_with('/') => gio::glib::home_dir().join(&rest[1..]),
+        _ => PathBuf::from(path),
+    }
 }
 
-pub fn get_desc_from_mime_type(mime_type: &str) -> String {
-    let desc = gio::content_type_get_description(mime_type);
-    desc.to_string()
+/// Read the files of a `text/uri-list` on the clipboard, as put there by file managers
+pub fn read_file_uris_from_clipboard() -> Option<Vec<PathBuf>> {
+    let output = if std::env::var("WAYLAND_DISPLAY").is_ok() {
+        Command::new("wl-paste")
+            .args(["--no-newline", "--type", "text/uri-list"])
+            .output()
+    } else {
+        Command::new("xclip")
+            .args(["-selection", "clipboard", "-t", "text/uri-list", "-o"])
+            .output()
+    }
+    .inspect_err(|e| error!("Failed to spawn clipboard command: {e}"))
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let paths: Vec<PathBuf> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(file_uri_to_path)
+        .collect();
+    (!paths.is_empty()).then_some(paths)
 }
 
-pub fn copy_file_uri_to_clipboard(path: &str) {
-    let uri = format!("file://{path}").replace("\'", "'\\''");
+/// The path of a `file://` URI
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    uri.strip_prefix("file://")
+        .map(|path| PathBuf::from(percent_decode(path)))
+}
 
-    let commandline = if std::env::var("WAYLAND_DISPLAY").is_ok() {
-        format!("echo {uri} | wl-copy --type text/uri-list")
-    } else {
-        format!("echo {uri} | xclip -selection clipboard -t text/uri-list")
-    };
+/// A `file://` URI for `path`, with everything but unreserved characters and `/` escaped
+pub fn path_to_file_uri(path: &Path) -> String {
+    let mut uri = "file://".to_string();
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
 
-    match Command::new("sh").arg("-c").arg(&commandline).status() {
-        Ok(status) if status.success() => {}
-        Ok(status) => {
-            error!("Clipboard copy failed. Command exited with status: {status}");
+/// Decode `%XX` escapes of a URI
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Show a desktop notification through `notify-send`
+pub fn send_desktop_notification(summary: &str, body: &str) {
+    match Command::new("notify-send")
+        .arg("--app-name=explotty")
+        .arg(summary)
+        .arg(body)
+        .spawn()
+    {
+        Ok(mut child) => {
+            // Reaped in the background so a slow notification daemon doesn't block the UI
+            std::thread::spawn(move || child.wait());
         }
         Err(e) => {
-            error!("Failed to spawn clipboard command: {e}")
+            error!("Failed to spawn notify-send: {e}");
+        }
+    }
+}
+
+/// The permission bits of `mode` the way `ls -l` shows them, e.g. `rwxr-xr-x`
+pub fn format_mode(mode: u32) -> String {
+    let mut formatted = String::with_capacity(9);
+    for shift in [6, 3, 0] {
+        let bits = mode >> shift;
+        formatted.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        formatted.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        formatted.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    formatted
+}
+
+/// The name of the user with this ID, from the password database
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let status = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// The name of the group with this ID, from the group database
+#[cfg(unix)]
+pub fn group_name(gid: u32) -> Option<String> {
+    let mut buffer = vec![0 as libc::c_char; 4096];
+    let mut group: libc::group = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let status = unsafe {
+        libc::getgrgid_r(
+            gid,
+            &mut group,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(group.gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// Available and total bytes of the filesystem containing `path`
+#[cfg(unix)]
+pub fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    let block_size = stats.f_frsize as u64;
+    Some((
+        stats.f_bavail as u64 * block_size,
+        stats.f_blocks as u64 * block_size,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADVERSARIAL_NAMES: &[&str] = &[
+        "plain",
+        "with space",
+        "it's",
+        "''",
+        "\"double\"",
+        "line\nbreak",
+        "-rf",
+        "--",
+        "$(touch pwned)",
+        "`touch pwned`",
+        "${HOME}",
+        "a;b|c&d>e<f",
+        "(sub)shell",
+        "back\\slash",
+        "*?[glob]",
+        "~user",
+        "!event",
+        "日本語",
+        "",
+    ];
+
+    #[test]
+    fn shell_quote_escapes_single_quotes() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("$(touch pwned)"), "'$(touch pwned)'");
+    }
+
+    /// The shell reads every name back as the single word it was
+    #[cfg(unix)]
+    #[test]
+    fn shell_quote_survives_the_shell() {
+        for name in ADVERSARIAL_NAMES {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!(
+                    "set -- {}; printf '%s|%s' \"$#\" \"$1\"",
+                    shell_quote(name)
+                ))
+                .output()
+                .unwrap();
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                format!("1|{name}"),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn expand_env_vars_replaces_braced_variables() {
+        // Set for every process, whatever the platform or test runner
+        let path = std::env::var("PATH").unwrap();
+        let cases = [
+            ("plain", "plain".to_string()),
+            ("${PATH}", path.clone()),
+            ("a${PATH}b${PATH}", format!("a{path}b{path}")),
+            // Unset variables are empty
+            ("a${EXPLOTTY_SURELY_UNSET}b", "ab".to_string()),
+            ("${}", String::new()),
+            // Only the braced form
+            ("$PATH", "$PATH".to_string()),
+            ("costs $5", "costs $5".to_string()),
+            // Unterminated, kept as is after what was expanded before it
+            ("${PATH", "${PATH".to_string()),
+            ("${PATH}/${PATH", format!("{path}/${{PATH")),
+            ("${", "${".to_string()),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(expand_env_vars(value), expected, "{value:?}");
         }
     }
 }